@@ -1,16 +1,30 @@
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
 use std::process;
 use std::fs;
+use std::io::Read;
+use std::sync::OnceLock;
+use zeroize::{Zeroize, Zeroizing};
 
 // Re-use library code from the main app
-use app_lib::crypto::{self, verify_password};
-use app_lib::database::{Database, operations};
-use app_lib::database::operations::{Project, Environment, Variable};
-use app_lib::vault;
+use app_lib::crypto::{self, KdfParams};
+use app_lib::database::{self, Database, DatabaseError, migrations, operations};
+use app_lib::database::operations::{Project, Environment, ValueType};
+use app_lib::database::operations::merge::MergeConflictStrategy;
+use app_lib::database::operations::records::{EnvironmentRecords, VariableRecord};
+use app_lib::vault::{self, VaultManager};
 
-// Session file name (stored in temp directory with process ID)
-const SESSION_FILE_PREFIX: &str = ".clerk_session";
+/// Where the master password comes from when not typed at the prompt
+enum PasswordSource {
+    File(PathBuf),
+    Fd(i32),
+}
+
+/// Set once in `main` from `--password-file` / `--password-fd`
+static PASSWORD_SOURCE: OnceLock<PasswordSource> = OnceLock::new();
+
+/// Set once in `main` from `--remote`
+static REMOTE_VAULT: OnceLock<bool> = OnceLock::new();
 
 #[derive(Parser)]
 #[command(name = "clerk")]
@@ -18,31 +32,170 @@ const SESSION_FILE_PREFIX: &str = ".clerk_session";
 #[command(version)]
 struct Cli {
     /// Skip session cache (always prompt for password)
-    #[arg(short = 'S', long, global = true)]
+    #[arg(short = 'S', long, visible_alias = "no-keychain", global = true)]
     no_session: bool,
     
     /// Custom vault directory
     #[arg(short = 'D', long, global = true)]
     vault_dir: Option<PathBuf>,
     
+    /// Read the master password from the first line of a file
+    #[arg(long, global = true, value_name = "PATH", conflicts_with = "password_fd")]
+    password_file: Option<PathBuf>,
+    
+    /// Read the master password from an open file descriptor (Unix), e.g. a pipe
+    #[arg(long, global = true, value_name = "FD")]
+    password_fd: Option<i32>,
+    
+    /// Work on a local copy of the vault database and copy it back after changes
+    /// (for network shares and synced folders; also enabled by a .clerk-remote file in the vault directory)
+    #[arg(long, global = true)]
+    remote: bool,
+    
+    /// Print machine-readable JSON output where a command has it; errors go to stderr as one JSON line
+    #[arg(long, global = true)]
+    json: bool,
+    
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Unlock the vault with master password
+    /// Unlock the vault with master password and start a session (skip with -S)
+    #[command(visible_alias = "login")]
     Unlock {
         /// Custom vault directory (optional)
         #[arg(short, long)]
         vault_dir: Option<PathBuf>,
     },
     
+    /// Create a new vault
+    VaultCreate {
+        /// Argon2id memory cost in KiB (default: 65536)
+        #[arg(long)]
+        kdf_memory: Option<u32>,
+        
+        /// Argon2id iteration count (default: 3)
+        #[arg(long)]
+        kdf_iterations: Option<u32>,
+        
+        /// Encrypt the whole database file with SQLCipher (needs a build with the sqlcipher feature)
+        #[arg(long)]
+        encrypt_database: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Time key derivation for several parameter sets and recommend one
+    BenchmarkKdf {
+        /// Target unlock time in milliseconds
+        #[arg(long, default_value_t = 250)]
+        target_ms: u64,
+    },
+    
+    /// Merge all projects, environments and variables from one vault into another
+    VaultMerge {
+        /// Source vault directory
+        #[arg(long)]
+        from: PathBuf,
+        
+        /// Target vault directory
+        #[arg(long)]
+        into: PathBuf,
+        
+        /// What to do when a project name exists in both vaults
+        #[arg(long, value_enum, default_value_t = MergeConflict::Skip)]
+        on_conflict: MergeConflict,
+    },
+    
+    /// Recover a vault whose vault.db or vault.clerk is missing
+    Repair {
+        /// Don't ask for confirmation before repairing
+        #[arg(short, long)]
+        yes: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Rotate the vault's salt and key, keeping the password, and re-encrypt every value
+    VaultRekey {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Change the master password, re-encrypting every value under the new key
+    ///
+    /// With --password-file or --password-fd the current password is the first line and the new one the second.
+    RotatePassword {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Shrink vault.db after large deletions (runs VACUUM and truncates the WAL)
+    VaultCompact {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Show the password hint, or change it with --set / --clear (needs an unlocked vault)
+    PasswordHint {
+        /// New hint; stored in plain text, so it must not reveal the password
+        #[arg(long, value_name = "HINT", conflicts_with = "clear")]
+        set: Option<String>,
+        
+        /// Remove the hint
+        #[arg(long)]
+        clear: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Show whether the whole database file is encrypted, or change it with --enable / --disable
+    DatabaseEncryption {
+        /// Encrypt vault.db with SQLCipher, hiding key names, projects and the audit log too
+        #[arg(long, conflicts_with = "disable")]
+        enable: bool,
+        
+        /// Decrypt vault.db back to plain SQLite (values stay encrypted)
+        #[arg(long)]
+        disable: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Delete the vault, its database, stored keychain key and sessions
+    ResetVault {
+        /// Required; you will also be asked to type a confirmation phrase
+        #[arg(long)]
+        confirm: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
     /// Get a variable value
     #[command(visible_alias = "g")]
     Get {
         /// Variable key name
-        key: String,
+        #[arg(required_unless_present = "all")]
+        key: Option<String>,
+        
+        /// Print every variable of the environment as KEY=value lines instead (for `source`)
+        #[arg(long, conflicts_with_all = ["key", "default", "raw", "escaped", "format", "equals", "slot"])]
+        all: bool,
         
         /// Project name
         #[arg(short, long)]
@@ -52,6 +205,35 @@ enum Commands {
         #[arg(short, long)]
         env: String,
         
+        /// Value to print when the key does not exist (exits 0)
+        #[arg(long)]
+        default: Option<String>,
+        
+        /// Print the value exactly, without a trailing newline
+        #[arg(long, conflicts_with = "escaped")]
+        raw: bool,
+        
+        /// Print newlines, tabs and backslashes as escape sequences
+        #[arg(long)]
+        escaped: bool,
+        
+        /// Output format: value, or export for an eval-safe `export KEY='value'` line
+        #[arg(long, value_enum, default_value_t = GetFormat::Value, conflicts_with_all = ["raw", "escaped"])]
+        format: GetFormat,
+        
+        /// Also read variables whose expiry has passed (with a warning)
+        #[arg(long)]
+        include_expired: bool,
+        
+        /// Print nothing; exit 0 if the value equals EXPECTED, 4 if it differs, 3 if the key
+        /// is missing. Use - to read EXPECTED from stdin and keep it out of the process list
+        #[arg(long, value_name = "EXPECTED", conflicts_with_all = ["default", "raw", "escaped", "format"])]
+        equals: Option<String>,
+        
+        /// Which value to read: current, or the pending one staged for rotation
+        #[arg(long, value_enum, default_value_t = Slot::Current)]
+        slot: Slot,
+        
         /// Custom vault directory (optional)
         #[arg(short, long)]
         vault_dir: Option<PathBuf>,
@@ -78,6 +260,99 @@ enum Commands {
         #[arg(short, long)]
         description: Option<String>,
         
+        /// Value type checked on every write: text, url, json, bool or number
+        /// (optional, keeps the current type when updating)
+        #[arg(long = "type", value_name = "TYPE")]
+        value_type: Option<ValueType>,
+        
+        /// Allow changing a protected variable
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Treat the variable as inactive after this long (e.g. 12h, 7d); it is kept, not deleted
+        #[arg(long, value_name = "DURATION")]
+        expires_in: Option<String>,
+        
+        /// Remove an expiry set earlier (updates keep the expiry otherwise)
+        #[arg(long, conflicts_with = "expires_in")]
+        no_expiry: bool,
+        
+        /// Create the project and environment if they don't exist (default: error)
+        #[arg(long)]
+        create: bool,
+        
+        /// Which value to write: current, or pending to stage the next value of an
+        /// existing variable while the current one stays live (see `clerk promote`)
+        #[arg(long, value_enum, default_value_t = Slot::Current)]
+        slot: Slot,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Create or update every entry of a JSON object read from stdin, in one transaction
+    SetJson {
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        
+        /// Store numbers, booleans, arrays and objects as their JSON text (default: only strings are accepted)
+        #[arg(long)]
+        stringify: bool,
+        
+        /// Create the project and environment if they don't exist (default: error)
+        #[arg(long)]
+        create: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Make a variable's pending value current, discarding the old current value
+    Promote {
+        /// Variable key name
+        key: String,
+        
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        
+        /// Allow changing a protected variable
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Edit a variable's value in $VISUAL / $EDITOR
+    Edit {
+        /// Variable key name
+        key: String,
+        
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        
+        /// Allow changing a protected variable
+        #[arg(short, long)]
+        force: bool,
+        
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
@@ -98,6 +373,14 @@ enum Commands {
         #[arg(short, long)]
         show_values: bool,
         
+        /// Tag each value with its detected format (e.g. [jwt], [url]) without revealing it
+        #[arg(long)]
+        classify: bool,
+        
+        /// Also list variables whose expiry has passed, tagged [expired]
+        #[arg(long)]
+        include_expired: bool,
+        
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
@@ -110,13 +393,90 @@ enum Commands {
         project: String,
         
         /// Environment name
-        #[arg(short, long)]
-        env: String,
+        #[arg(short, long, required_unless_present = "all_envs")]
+        env: Option<String>,
+        
+        /// Export every environment of the project
+        #[arg(long, conflicts_with_all = ["env", "shared", "output"])]
+        all_envs: bool,
         
         /// Output file (optional, defaults to stdout)
         #[arg(short, long)]
         output: Option<PathBuf>,
         
+        /// Output path for each environment with --all-envs; {env} and {project} are substituted
+        #[arg(long, value_name = "TEMPLATE", conflicts_with = "env")]
+        output_template: Option<String>,
+        
+        /// Emit only keys with blank values (descriptions become comments)
+        #[arg(long, conflicts_with = "shared")]
+        template_only: bool,
+        
+        /// Variable order in the output (key keeps diffs of exported files clean)
+        #[arg(long, value_enum, default_value_t = ExportSort::Key, conflicts_with = "shared")]
+        sort: ExportSort,
+        
+        /// Omit the comment header (project, environment, variable count)
+        #[arg(long, conflicts_with = "shared")]
+        no_header: bool,
+        
+        /// Output format: env (dotenv) or env-json (flat {"KEY": "value"} object)
+        #[arg(long, value_enum, default_value_t = ExportFormat::Env, conflicts_with = "shared")]
+        format: ExportFormat,
+        
+        /// Nest the env-json object under this key
+        #[arg(long, value_name = "KEY", conflicts_with = "shared")]
+        wrap: Option<String>,
+        
+        /// Exit with an error if the environment has no variables
+        #[arg(long, conflicts_with = "shared")]
+        fail_on_empty: bool,
+        
+        /// Also read variables whose expiry has passed (with a warning)
+        #[arg(long)]
+        include_expired: bool,
+        
+        /// Print a keyed checksum of the key/value pairs instead of the values; equal
+        /// environments in this vault print the same checksum (one line per env with --all-envs)
+        #[arg(long, conflicts_with_all = ["shared", "output", "output_template", "template_only", "format", "wrap", "no_header", "sort"])]
+        checksum: bool,
+        
+        /// Write a JSON document with each variable's description, type, tags,
+        /// protection, expiry and timestamps; `import` restores all of it
+        #[arg(long, conflicts_with_all = ["shared", "checksum", "template_only", "format", "wrap", "no_header"])]
+        include_metadata: bool,
+        
+        /// Write a passphrase-encrypted, time-limited bundle for sharing
+        #[arg(long)]
+        shared: bool,
+        
+        /// Only include these keys in a shared bundle (comma-separated)
+        #[arg(long, value_delimiter = ',', requires = "shared")]
+        keys: Vec<String>,
+        
+        /// How long a shared bundle stays importable (e.g. 30m, 24h, 7d)
+        #[arg(long, default_value = "24h", requires = "shared")]
+        expires_in: String,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Export every project and environment to {output-dir}/{project}/{env}.env
+    ExportTree {
+        /// Directory to write the tree into (created if missing)
+        #[arg(short, long)]
+        output_dir: PathBuf,
+        
+        /// Write real values instead of ******** placeholders
+        #[arg(long)]
+        show_values: bool,
+        
+        /// Also read variables whose expiry has passed (with a warning)
+        #[arg(long)]
+        include_expired: bool,
+        
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
@@ -146,6 +506,34 @@ enum Commands {
         #[arg(short, long)]
         env: String,
         
+        /// Print the variables that would be injected and exit without running
+        #[arg(long)]
+        print_env: bool,
+        
+        /// Show values with --print-env (default: masked)
+        #[arg(short, long, requires = "print_env")]
+        show_values: bool,
+        
+        /// Also read variables whose expiry has passed (with a warning)
+        #[arg(long)]
+        include_expired: bool,
+        
+        /// Print the names (not values) of the injected variables to stderr before running
+        #[arg(long)]
+        list_injected: bool,
+        
+        /// Banner printed to stderr before running; {count}, {project} and {env} are filled in
+        #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_RUN_BANNER)]
+        banner: String,
+        
+        /// Don't print the banner, the command line or the separators
+        #[arg(short, long, conflicts_with = "banner")]
+        quiet: bool,
+        
+        /// Run this script instead; trailing arguments are passed to it
+        #[arg(long, value_name = "FILE")]
+        script: Option<PathBuf>,
+        
         /// Command to run (e.g., "npm start", "python app.py")
         #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
         command: Vec<String>,
@@ -155,86 +543,221 @@ enum Commands {
         vault_dir: Option<PathBuf>,
     },
     
-    /// Lock the vault (clear session)
-    Lock,
-    
-    /// Check session status
-    Status {
-        /// Custom vault directory (optional)
-        #[arg(short = 'V', long)]
-        vault_dir: Option<PathBuf>,
-    },
-    
-    /// Create a new project
-    #[command(visible_alias = "pc")]
-    ProjectCreate {
+    /// Print `export KEY='value'` lines for `eval "$(clerk direnv ...)"` or a direnv .envrc
+    Direnv {
         /// Project name
-        name: String,
+        #[arg(short, long)]
+        project: String,
         
-        /// Project description (optional)
+        /// Environment name
         #[arg(short, long)]
-        description: Option<String>,
+        env: String,
+        
+        /// Also read variables whose expiry has passed (with a warning)
+        #[arg(long)]
+        include_expired: bool,
         
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
     
-    /// List all projects
-    #[command(visible_alias = "pl")]
-    ProjectList {
-        /// Custom vault directory (optional)
-        #[arg(short = 'V', long)]
-        vault_dir: Option<PathBuf>,
-    },
+    /// Print shell integration snippets (use `clerk hook ...`)
+    #[command(subcommand)]
+    Hook(HookCommands),
     
-    /// Delete a project
-    #[command(visible_alias = "pd")]
-    ProjectDelete {
-        /// Project name
-        name: String,
-        
-        /// Skip confirmation prompt
-        #[arg(short, long)]
-        force: bool,
-        
-        /// Custom vault directory (optional)
-        #[arg(short = 'V', long)]
-        vault_dir: Option<PathBuf>,
+    /// Print a shell completion script that also completes variable keys from the vault
+    Completions {
+        /// Shell to generate the script for
+        #[arg(value_enum)]
+        shell: CompletionShell,
     },
     
-    /// Create a new environment in a project
-    #[command(visible_alias = "ec")]
-    EnvCreate {
-        /// Environment name
-        name: String,
-        
-        /// Project name
+    /// List keys for shell completion (used by the `completions` scripts)
+    #[command(name = "__complete-keys", hide = true)]
+    CompleteKeys {
         #[arg(short, long)]
         project: String,
         
-        /// Environment description (optional)
         #[arg(short, long)]
-        description: Option<String>,
+        env: String,
+        
+        /// Only keys starting with this (case-insensitive)
+        #[arg(default_value = "")]
+        prefix: String,
         
-        /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
     
-    /// List all environments in a project
-    #[command(visible_alias = "el")]
-    EnvList {
-        /// Project name
-        #[arg(short, long)]
-        project: String,
+    /// Lock the vault (clear session)
+    #[command(visible_alias = "logout")]
+    Lock {
+        /// Also remove the GUI's "Remember Me" key from the OS keychain
+        #[arg(long)]
+        forget_keychain: bool,
         
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
     
-    /// Delete an environment
+    /// Check session status
+    Status {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Show which vault and session files this invocation resolves to, and why
+    Paths {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Write a redacted JSON description of the vault for bug reports (no values)
+    DebugDump {
+        /// Output file (optional, defaults to stdout)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Show or apply pending database schema migrations
+    Migrate {
+        /// Report current vs target schema version and pending migrations without applying them
+        #[arg(long, conflicts_with = "apply", required_unless_present = "apply")]
+        check: bool,
+        
+        /// Apply pending migrations (requires the master password)
+        #[arg(long)]
+        apply: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Recount projects, environments and variables and cross-check the totals
+    Stats {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// List variables created or updated since a point in time
+    Changes {
+        /// How far back to look: "1 day ago", "24h", "today", or a date such as 2026-10-01
+        #[arg(long)]
+        since: String,
+        /// Only report this project
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Poll an environment and run a command whenever its variables change
+    Watch {
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        /// Shell command to run on each change; CLERK_CHANGED_KEYS holds the changed keys
+        #[arg(long, value_name = "COMMAND")]
+        exec: String,
+        /// Poll interval in milliseconds
+        #[arg(long, default_value_t = 1000)]
+        interval: u64,
+        /// Wait until no further change for this many milliseconds, so a burst runs the command once
+        #[arg(long, default_value_t = 500)]
+        debounce: u64,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Create a new project
+    #[command(visible_alias = "pc")]
+    ProjectCreate {
+        /// Project name
+        name: String,
+        
+        /// Project description (optional)
+        #[arg(short, long)]
+        description: Option<String>,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// List all projects
+    #[command(visible_alias = "pl")]
+    ProjectList {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Delete a project
+    #[command(visible_alias = "pd")]
+    ProjectDelete {
+        /// Project name
+        name: String,
+        
+        /// Skip confirmation prompt
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Re-type the project name; delete only if it matches exactly (implies --force)
+        #[arg(long, value_name = "NAME")]
+        confirm_with_name: Option<String>,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Create a new environment in a project
+    #[command(visible_alias = "ec")]
+    EnvCreate {
+        /// Environment name
+        name: String,
+        
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Environment description (optional)
+        #[arg(short, long)]
+        description: Option<String>,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// List all environments in a project
+    #[command(visible_alias = "el")]
+    EnvList {
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Delete an environment
     #[command(visible_alias = "ed")]
     EnvDelete {
         /// Environment name
@@ -248,6 +771,84 @@ enum Commands {
         #[arg(short, long)]
         force: bool,
         
+        /// Re-type the environment name; delete only if it matches exactly (implies --force)
+        #[arg(long, value_name = "NAME")]
+        confirm_with_name: Option<String>,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Delete every variable of an environment but keep the environment (e.g. before a fresh import)
+    EnvClear {
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        
+        /// Required; also deletes protected variables
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Lock an environment so others' CLI writes to it are refused (advisory, expires after a day)
+    EnvLock {
+        /// Environment name
+        name: String,
+        
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Take the lock even if someone else holds it
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Release an environment lock
+    EnvUnlock {
+        /// Environment name
+        name: String,
+        
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Break a lock held by someone else
+        #[arg(short, long)]
+        force: bool,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Move an environment (with all its variables) to another project
+    #[command(visible_alias = "env-move")]
+    ProjectMove {
+        /// Environment name
+        env: String,
+        
+        /// Project the environment is in now
+        #[arg(long)]
+        from: String,
+        
+        /// Project to move it to
+        #[arg(long)]
+        to: String,
+        
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
@@ -276,6 +877,42 @@ enum Commands {
         vault_dir: Option<PathBuf>,
     },
     
+    /// Protect a variable so it can't be changed without --force or deleted until unprotected
+    Protect {
+        /// Variable key name
+        key: String,
+        
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
+    /// Remove protection from a variable
+    Unprotect {
+        /// Variable key name
+        key: String,
+        
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    
     /// Copy a variable to another environment
     #[command(visible_alias = "cp")]
     Copy {
@@ -310,7 +947,7 @@ enum Commands {
     /// Import variables from a .env file
     #[command(visible_alias = "imp")]
     Import {
-        /// Path to .env file
+        /// Path to a .env file, or a JSON file from `export --include-metadata`
         file: PathBuf,
         
         /// Project name
@@ -325,43 +962,309 @@ enum Commands {
         #[arg(long)]
         overwrite: bool,
         
+        /// Import a shared bundle created with `clerk export --shared`
+        #[arg(long)]
+        shared: bool,
+        
+        /// Create the project and environment if they don't exist (default: error)
+        #[arg(long)]
+        create: bool,
+        
+        /// Show what would be created, updated or skipped without writing anything
+        #[arg(long)]
+        dry_run: bool,
+        
+        /// With --dry-run, show old -> new for existing keys (values masked)
+        #[arg(long, requires = "dry_run")]
+        diff: bool,
+        
+        /// With --diff, print the actual old and new values
+        #[arg(long, requires = "diff")]
+        show_values: bool,
+        
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
-    /// Variable operations (use `clerk var ...`)
-    #[command(subcommand)]
-    Var(VarCommands),
-}
-
-#[derive(Subcommand)]
-enum VarCommands {
-    /// Get a variable value
-    #[command(visible_alias = "g")]
-    Get {
-        /// Variable key name
-        key: String,
+    /// Import layered dotenv files (.env, .env.local, .env.{mode}, ...) from a directory
+    ImportDir {
+        /// Directory containing the dotenv files
+        dir: PathBuf,
+        
         /// Project name
         #[arg(short, long)]
         project: String,
+        
         /// Environment name
         #[arg(short, long)]
         env: String,
+        
+        /// Mode used for .env.{mode} files (defaults to the environment name)
+        #[arg(short, long)]
+        mode: Option<String>,
+        
+        /// Overwrite existing variables
+        #[arg(long)]
+        overwrite: bool,
+        
+        /// Also report keys a later file sets to the same value again, and keys whose value
+        /// is identical in every environment of the project (candidates for a shared base env)
+        #[arg(long)]
+        dedupe: bool,
+        
         /// Custom vault directory (optional)
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
-    /// Set a variable value
-    #[command(visible_alias = "s")]
-    Set {
-        key: String,
-        value: String,
-        #[arg(short, long)]
-        project: String,
-        #[arg(short, long)]
-        env: String,
+    
+    /// Create the projects and environments described in a clerk.toml
+    /// and report expected keys that are not set yet
+    Apply {
+        /// Config file (follows its `include` list)
+        #[arg(default_value = app_lib::config::CONFIG_FILE_NAME)]
+        file: PathBuf,
+        
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Variable operations (use `clerk var ...`)
+    #[command(subcommand)]
+    Var(VarCommands),
+    /// Audit log operations (use `clerk audit ...`)
+    #[command(subcommand)]
+    Audit(AuditCommands),
+    /// Add or remove a tag on many variables at once (use `clerk tag ...`)
+    #[command(subcommand)]
+    Tag(TagCommands),
+}
+
+#[derive(Subcommand)]
+enum HookCommands {
+    /// Print an .envrc snippet that loads an environment through `clerk direnv`
+    Direnv {
+        /// Project name (placeholder if omitted)
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Environment name (placeholder if omitted)
+        #[arg(short, long)]
+        env: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AuditCommands {
+    /// Print the most recent audit entries, optionally following new ones
+    Tail {
+        /// Number of recent entries to print
+        #[arg(short = 'n', long, default_value_t = 10)]
+        lines: i64,
+        /// Keep polling and print new entries as they appear
+        #[arg(short, long)]
+        follow: bool,
+        /// Poll interval in milliseconds (with --follow)
+        #[arg(long, default_value_t = 1000)]
+        interval: u64,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Export audit entries as CSV or JSON (all matching entries, never a single page)
+    Export {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = AuditExportFormat::Csv)]
+        output: AuditExportFormat,
+        /// File to write (default: stdout)
+        #[arg(short, long)]
+        file: Option<PathBuf>,
+        /// Only entries about this entity type (project, environment, variable, ...)
+        #[arg(long)]
+        entity_type: Option<String>,
+        /// Only entries of this operation (create, update, delete, ...)
+        #[arg(long)]
+        operation: Option<String>,
+        /// Only entries since then ("7d", "1 day ago", 2026-10-01)
+        #[arg(long)]
+        since: Option<String>,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Show which keys exist in which environments of a project, highlighting gaps
+    Coverage {
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Export audit entries to a JSON archive, optionally removing them from the vault
+    Archive {
+        /// Archive file to write
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Only archive entries older than this (e.g. 90d)
+        #[arg(long)]
+        older_than: Option<String>,
+        /// Remove the archived entries from the vault after writing the file
+        #[arg(long)]
+        prune: bool,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// List variables whose expiry has passed (they are inactive but kept)
+    Expired {
+        /// Only report this project
+        #[arg(short, long)]
+        project: Option<String>,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Delete every audit entry (one "audit_cleared" entry records the wipe)
+    Clear {
+        /// Required; the master password is also asked for again, even with a session
+        #[arg(long)]
+        confirm: bool,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Stop recording operations in the audit log (existing entries are kept)
+    Disable {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Record operations in the audit log again (the default)
+    Enable {
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Merge an audit archive back into the vault, skipping entries already present
+    Import {
+        /// Archive file to read
+        file: PathBuf,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagCommands {
+    /// Tag variables by key or wildcard pattern (e.g. 'DB_*')
+    Add {
+        /// Tag to add (letters, digits, '-', '_' or '.')
+        tag: String,
+        /// Keys or patterns; `*` matches any run of characters, `?` one character
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Remove a tag from variables by key or wildcard pattern
+    Remove {
+        /// Tag to remove
+        tag: String,
+        /// Keys or patterns; `*` matches any run of characters, `?` one character
+        #[arg(required = true)]
+        keys: Vec<String>,
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum VarCommands {
+    /// Get a variable value
+    #[command(visible_alias = "g")]
+    Get {
+        /// Variable key name
+        key: String,
+        /// Project name
+        #[arg(short, long)]
+        project: String,
+        /// Environment name
+        #[arg(short, long)]
+        env: String,
+        /// Value to print when the key does not exist (exits 0)
+        #[arg(long)]
+        default: Option<String>,
+        /// Print the value exactly, without a trailing newline
+        #[arg(long, conflicts_with = "escaped")]
+        raw: bool,
+        /// Print newlines, tabs and backslashes as escape sequences
+        #[arg(long)]
+        escaped: bool,
+        /// Output format: value, or export for an eval-safe `export KEY='value'` line
+        #[arg(long, value_enum, default_value_t = GetFormat::Value, conflicts_with_all = ["raw", "escaped"])]
+        format: GetFormat,
+        /// Also read the variable if its expiry has passed (with a warning)
+        #[arg(long)]
+        include_expired: bool,
+        /// Compare with EXPECTED instead of printing (exit 0 equal, 4 different, 3 missing)
+        #[arg(long, value_name = "EXPECTED", conflicts_with_all = ["default", "raw", "escaped", "format"])]
+        equals: Option<String>,
+        /// Which value to read: current or pending
+        #[arg(long, value_enum, default_value_t = Slot::Current)]
+        slot: Slot,
+        /// Custom vault directory (optional)
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Set a variable value
+    #[command(visible_alias = "s")]
+    Set {
+        key: String,
+        value: String,
+        #[arg(short, long)]
+        project: String,
+        #[arg(short, long)]
+        env: String,
         #[arg(short, long)]
         description: Option<String>,
+        #[arg(long = "type", value_name = "TYPE")]
+        value_type: Option<ValueType>,
+        #[arg(short, long)]
+        force: bool,
+        #[arg(long, value_name = "DURATION")]
+        expires_in: Option<String>,
+        #[arg(long, conflicts_with = "expires_in")]
+        no_expiry: bool,
+        #[arg(long)]
+        create: bool,
+        #[arg(long, value_enum, default_value_t = Slot::Current)]
+        slot: Slot,
+        #[arg(short = 'V', long)]
+        vault_dir: Option<PathBuf>,
+    },
+    /// Edit a variable value in $VISUAL / $EDITOR
+    Edit {
+        key: String,
+        #[arg(short, long)]
+        project: String,
+        #[arg(short, long)]
+        env: String,
+        #[arg(short, long)]
+        force: bool,
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
@@ -374,6 +1277,10 @@ enum VarCommands {
         env: Option<String>,
         #[arg(short, long)]
         show_values: bool,
+        #[arg(long)]
+        classify: bool,
+        #[arg(long)]
+        include_expired: bool,
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
@@ -429,6 +1336,8 @@ enum VarCommands {
         env: String,
         #[arg(short, long)]
         output: Option<PathBuf>,
+        #[arg(long)]
+        include_expired: bool,
         #[arg(short = 'V', long)]
         vault_dir: Option<PathBuf>,
     },
@@ -455,19 +1364,175 @@ enum VarCommands {
     },
 }
 
+/// Conflict strategy for duplicate project names in `vault-merge`
+#[derive(Clone, Copy, ValueEnum)]
+enum CompletionShell {
+    Bash,
+    /// Uses zsh's bash completion compatibility
+    Zsh,
+    Fish,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum MergeConflict {
+    /// Keep the target project and skip the source one
+    Skip,
+    /// Add missing environments and variables, keep existing values
+    Merge,
+    /// Add missing environments and variables, replace existing values
+    Overwrite,
+    /// Import the source project under a new name
+    Rename,
+}
+
+/// Variable order for `export`
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportSort {
+    /// Alphabetical by key (byte order)
+    Key,
+    /// Oldest variable first
+    Created,
+    /// Order the variables were stored in
+    None,
+}
+
+/// Output format for `export`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum AuditExportFormat {
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum ExportFormat {
+    /// Dotenv KEY=value lines
+    Env,
+    /// Flat JSON object, as accepted by AWS Secrets Manager and GCP Secret Manager
+    #[value(alias = "json")]
+    EnvJson,
+}
+
+const DEFAULT_RUN_BANNER: &str = "Injecting {count} variables into process...";
+
+/// What `run` reports on stderr around the child process
+struct RunOutput {
+    list_injected: bool,
+    /// Banner template, or `None` for `--quiet`
+    banner: Option<String>,
+}
+
+impl RunOutput {
+    fn banner(&self, count: usize, project: &str, env: &str) -> Option<String> {
+        self.banner.as_ref().map(|template| {
+            template
+                .replace("{count}", &count.to_string())
+                .replace("{project}", project)
+                .replace("{env}", env)
+        })
+    }
+}
+
+/// Formatting options for `export`
+struct ExportOptions {
+    template_only: bool,
+    sort: ExportSort,
+    header: bool,
+    format: ExportFormat,
+    wrap: Option<String>,
+    fail_on_empty: bool,
+    include_expired: bool,
+    /// Write `********` instead of values (values are not decrypted)
+    mask_values: bool,
+    /// Write a records document (see `operations::records`) instead of `format`
+    include_metadata: bool,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        Self {
+            template_only: false,
+            sort: ExportSort::Key,
+            header: true,
+            format: ExportFormat::Env,
+            wrap: None,
+            fail_on_empty: false,
+            include_expired: false,
+            mask_values: false,
+            include_metadata: false,
+        }
+    }
+}
+
+/// What `import --dry-run` reports instead of writing
+#[derive(Debug, Clone, Copy)]
+enum ImportPreview {
+    /// Only the created/updated/skipped counts
+    Counts,
+    /// Counts plus old -> new for every key that already exists
+    Diff { show_values: bool },
+}
+
+/// Options for `import`
+#[derive(Default)]
+struct ImportOptions {
+    overwrite: bool,
+    shared: bool,
+    /// Create the project and environment if they are missing
+    create: bool,
+    preview: Option<ImportPreview>,
+}
+
+/// Sort export rows; every order ends with the row id so ties stay stable
+fn sort_for_export<T>(rows: &mut [T], sort: ExportSort, fields: impl Fn(&T) -> (&str, i64, i64)) {
+    match sort {
+        ExportSort::Key => rows.sort_by(|a, b| {
+            let (a, b) = (fields(a), fields(b));
+            a.0.cmp(b.0).then(a.2.cmp(&b.2))
+        }),
+        ExportSort::Created => rows.sort_by_key(|row| {
+            let (_, created_at, id) = fields(row);
+            (created_at, id)
+        }),
+        ExportSort::None => rows.sort_by_key(|row| fields(row).2),
+    }
+}
+
+impl From<MergeConflict> for MergeConflictStrategy {
+    fn from(value: MergeConflict) -> Self {
+        match value {
+            MergeConflict::Skip => MergeConflictStrategy::Skip,
+            MergeConflict::Merge => MergeConflictStrategy::Merge,
+            MergeConflict::Overwrite => MergeConflictStrategy::Overwrite,
+            MergeConflict::Rename => MergeConflictStrategy::Rename,
+        }
+    }
+}
+
 impl Commands {
     fn vault_dir(&self) -> Option<PathBuf> {
         match self {
             Commands::Unlock { vault_dir } => vault_dir.clone(),
+            Commands::VaultCreate { vault_dir, .. } => vault_dir.clone(),
+            Commands::BenchmarkKdf { .. } => None,
+            Commands::VaultMerge { .. } => None,
+            Commands::ResetVault { vault_dir, .. } => vault_dir.clone(),
+            Commands::VaultRekey { vault_dir } => vault_dir.clone(),
+            Commands::RotatePassword { vault_dir } => vault_dir.clone(),
+            Commands::VaultCompact { vault_dir } => vault_dir.clone(),
+            Commands::PasswordHint { vault_dir, .. } => vault_dir.clone(),
+            Commands::DatabaseEncryption { vault_dir, .. } => vault_dir.clone(),
+            Commands::Repair { vault_dir, .. } => vault_dir.clone(),
             Commands::Get { vault_dir, .. } => vault_dir.clone(),
             Commands::Set { vault_dir, .. } => vault_dir.clone(),
             Commands::List { vault_dir, .. } => vault_dir.clone(),
             Commands::Export { vault_dir, .. } => vault_dir.clone(),
+            Commands::ExportTree { vault_dir, .. } => vault_dir.clone(),
             Commands::Init { vault_dir, .. } => vault_dir.clone(),
             Commands::Var(cmd) => {
                 match cmd {
                     VarCommands::Get { vault_dir, .. } => vault_dir.clone(),
                     VarCommands::Set { vault_dir, .. } => vault_dir.clone(),
+                    VarCommands::Edit { vault_dir, .. } => vault_dir.clone(),
                     VarCommands::List { vault_dir, .. } => vault_dir.clone(),
                     VarCommands::Delete { vault_dir, .. } => vault_dir.clone(),
                     VarCommands::Copy { vault_dir, .. } => vault_dir.clone(),
@@ -477,213 +1542,624 @@ impl Commands {
                     VarCommands::BulkSet { vault_dir, .. } => vault_dir.clone(),
                 }
             }
+            Commands::Audit(AuditCommands::Tail { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Export { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Clear { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Disable { vault_dir }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Enable { vault_dir }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Coverage { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Archive { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Expired { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Tag(TagCommands::Add { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Tag(TagCommands::Remove { vault_dir, .. }) => vault_dir.clone(),
+            Commands::Audit(AuditCommands::Import { vault_dir, .. }) => vault_dir.clone(),
             Commands::Run { vault_dir, .. } => vault_dir.clone(),
-            Commands::Lock => None,
+            Commands::Direnv { vault_dir, .. } => vault_dir.clone(),
+            Commands::Hook(_) => None,
+            Commands::Completions { .. } => None,
+            Commands::CompleteKeys { vault_dir, .. } => vault_dir.clone(),
+            Commands::Lock { vault_dir, .. } => vault_dir.clone(),
             Commands::Status { vault_dir } => vault_dir.clone(),
+            Commands::Paths { vault_dir } => vault_dir.clone(),
+            Commands::DebugDump { vault_dir, .. } => vault_dir.clone(),
+            Commands::Stats { vault_dir } => vault_dir.clone(),
+            Commands::Changes { vault_dir, .. } => vault_dir.clone(),
+            Commands::Watch { vault_dir, .. } => vault_dir.clone(),
+            Commands::Migrate { vault_dir, .. } => vault_dir.clone(),
             Commands::ProjectCreate { vault_dir, .. } => vault_dir.clone(),
             Commands::ProjectList { vault_dir } => vault_dir.clone(),
             Commands::ProjectDelete { vault_dir, .. } => vault_dir.clone(),
             Commands::EnvCreate { vault_dir, .. } => vault_dir.clone(),
             Commands::EnvList { vault_dir, .. } => vault_dir.clone(),
             Commands::EnvDelete { vault_dir, .. } => vault_dir.clone(),
+            Commands::EnvClear { vault_dir, .. } => vault_dir.clone(),
+            Commands::EnvLock { vault_dir, .. } => vault_dir.clone(),
+            Commands::EnvUnlock { vault_dir, .. } => vault_dir.clone(),
+            Commands::ProjectMove { vault_dir, .. } => vault_dir.clone(),
             Commands::Delete { vault_dir, .. } => vault_dir.clone(),
+            Commands::Edit { vault_dir, .. } => vault_dir.clone(),
+            Commands::Promote { vault_dir, .. } => vault_dir.clone(),
+            Commands::SetJson { vault_dir, .. } => vault_dir.clone(),
+            Commands::Protect { vault_dir, .. } => vault_dir.clone(),
+            Commands::Unprotect { vault_dir, .. } => vault_dir.clone(),
             Commands::Copy { vault_dir, .. } => vault_dir.clone(),
             Commands::Import { vault_dir, .. } => vault_dir.clone(),
+            Commands::ImportDir { vault_dir, .. } => vault_dir.clone(),
+            Commands::Apply { vault_dir, .. } => vault_dir.clone(),
         }
     }
 }
 
 fn main() {
     let cli = Cli::parse();
+    if let Some(path) = &cli.password_file {
+        let _ = PASSWORD_SOURCE.set(PasswordSource::File(path.clone()));
+    } else if let Some(fd) = cli.password_fd {
+        let _ = PASSWORD_SOURCE.set(PasswordSource::Fd(fd));
+    }
+    let _ = REMOTE_VAULT.set(cli.remote);
     let use_session = !cli.no_session;
     let vault_dir = cli.vault_dir.or_else(|| cli.command.vault_dir());
     
     match &cli.command {
         Commands::Unlock { .. } => {
             if let Err(e) = cmd_unlock(vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::VaultCreate { kdf_memory, kdf_iterations, encrypt_database, .. } => {
+            if let Err(e) = cmd_vault_create(*kdf_memory, *kdf_iterations, *encrypt_database, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::BenchmarkKdf { target_ms } => {
+            if let Err(e) = cmd_benchmark_kdf(*target_ms) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::VaultMerge { from, into, on_conflict } => {
+            if let Err(e) = cmd_vault_merge(from, into, (*on_conflict).into(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Repair { yes, .. } => {
+            if let Err(e) = cmd_repair(*yes, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::PasswordHint { set, clear, .. } => {
+            if let Err(e) = cmd_password_hint(set.as_deref(), *clear, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::DatabaseEncryption { enable, disable, .. } => {
+            if let Err(e) = cmd_database_encryption(*enable, *disable, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::VaultRekey { .. } => {
+            if let Err(e) = cmd_vault_rekey(vault_dir.clone()) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::RotatePassword { .. } => {
+            if let Err(e) = cmd_rotate_password(vault_dir.clone()) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::VaultCompact { .. } => {
+            if let Err(e) = cmd_vault_compact(cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::ResetVault { confirm, .. } => {
+            if let Err(e) = cmd_reset_vault(*confirm, vault_dir.clone()) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Get { key: None, project, env, include_expired, .. } => {
+            if let Err(e) = cmd_get_all(project, env, cli.json, *include_expired, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Get { key: Some(key), project, env, equals: Some(expected), include_expired, slot, .. } => {
+            match cmd_get_equals(key, project, env, expected, *include_expired, *slot, vault_dir.clone(), use_session) {
+                Ok(code) => process::exit(code),
+                Err(e) => exit_with_error(cli.json, e),
+            }
+        }
+        Commands::Get { key: Some(key), project, env, default, raw, escaped, format, include_expired, slot, .. } => {
+            let output = GetOutput::from_flags(cli.json, *raw, *escaped, *format);
+            if let Err(e) = cmd_get(key, project, env, default.as_deref(), output, *include_expired, *slot, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Set { key, value, project, env, description, value_type, force, expires_in, no_expiry, create, slot, .. } => {
+            if let Err(e) = cmd_set(key, value, project, env, description.as_deref(), *value_type, *force, expires_in.as_deref(), *no_expiry, *create, *slot, cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::List { project, env, show_values, classify, include_expired, .. } => {
+            if let Err(e) = cmd_list(project.as_deref(), env.as_deref(), *show_values, *classify, *include_expired, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::Get { key, project, env, .. } => {
-            if let Err(e) = cmd_get(key, project, env, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::Export { project, env: Some(env), output, shared: true, keys, expires_in, include_expired, .. } => {
+            if let Err(e) = cmd_export_shared(project, env, keys, expires_in, *include_expired, output.clone(), vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::Set { key, value, project, env, description, .. } => {
-            if let Err(e) = cmd_set(key, value, project, env, description.as_deref(), vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::Export { project, env, all_envs, checksum: true, fail_on_empty, include_expired, .. } => {
+            if let Err(e) = cmd_export_checksum(project, env.as_deref(), *all_envs, *fail_on_empty, *include_expired, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::List { project, env, show_values, .. } => {
-            if let Err(e) = cmd_list(project.as_deref(), env.as_deref(), *show_values, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::Export { project, env, output, output_template, template_only, sort, no_header, format, wrap, fail_on_empty, include_expired, include_metadata, .. } => {
+            let options = ExportOptions {
+                template_only: *template_only,
+                sort: *sort,
+                header: !no_header,
+                format: *format,
+                wrap: wrap.clone(),
+                fail_on_empty: *fail_on_empty,
+                include_expired: *include_expired,
+                mask_values: false,
+                include_metadata: *include_metadata,
+            };
+            // clap requires --env unless --all-envs is given, and the two conflict
+            let result = match env {
+                Some(env) => cmd_export(project, env, output.clone(), options, vault_dir.clone(), use_session),
+                None => cmd_export_all_envs(project, output_template.as_deref(), options, vault_dir.clone(), use_session),
+            };
+            if let Err(e) = result {
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::Export { project, env, output, .. } => {
-            if let Err(e) = cmd_export(project, env, output.clone(), vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::ExportTree { output_dir, show_values, include_expired, .. } => {
+            if let Err(e) = cmd_export_tree(output_dir, *show_values, *include_expired, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::Init { project, description, .. } => {
             if let Err(e) = cmd_init(project, description.as_deref(), vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Direnv { project, env, include_expired, .. } => {
+            if let Err(e) = cmd_direnv(project, env, *include_expired, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::Run { project, env, command, .. } => {
-            if command.is_empty() {
-                eprintln!("Error: No command specified");
-                process::exit(1);
+        Commands::Hook(HookCommands::Direnv { project, env }) => {
+            print!("{}", direnv_hook_snippet(project.as_deref(), env.as_deref()));
+        }
+        Commands::Completions { shell } => {
+            print!("{}", completion_script(*shell));
+        }
+        Commands::CompleteKeys { project, env, prefix, .. } => {
+            // Completion must stay quiet: no candidates is the only failure mode
+            let _ = cmd_complete_keys(project, env, prefix, vault_dir.clone());
+        }
+        Commands::Run { project, env, script, command, print_env, show_values, include_expired, list_injected, banner, quiet, .. } => {
+            if command.is_empty() && script.is_none() && !print_env {
+                exit_with_error(cli.json, "No command specified");
             }
-            if let Err(e) = cmd_run(project, env, command, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+            let command = match script {
+                Some(script) => script_command(script, command).unwrap_or_else(|e| exit_with_error(cli.json, e)),
+                None => command.clone(),
+            };
+            let print_mode = print_env.then_some(*show_values);
+            let output = RunOutput {
+                list_injected: *list_injected,
+                banner: (!quiet).then(|| banner.clone()),
+            };
+            if let Err(e) = cmd_run(project, env, &command, print_mode, *include_expired, &output, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::Lock => {
-            if let Err(e) = cmd_lock(vault_dir.clone()) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::Lock { forget_keychain, .. } => {
+            if let Err(e) = cmd_lock(*forget_keychain, vault_dir.clone()) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::Status { .. } => {
             if let Err(e) = cmd_status(vault_dir.clone()) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Paths { .. } => {
+            // -D and the per-command -V share the `vault_dir` id, so clap merges them
+            let source = if vault_dir.is_some() { "flag (--vault-dir)" } else { "default" };
+            if let Err(e) = cmd_paths(cli.json, vault_dir.clone(), source) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::DebugDump { output, .. } => {
+            if let Err(e) = cmd_debug_dump(output.clone(), vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Stats { .. } => {
+            if let Err(e) = cmd_stats(cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Changes { since, project, .. } => {
+            if let Err(e) = cmd_changes(since, project.as_deref(), cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Watch { project, env, exec, interval, debounce, .. } => {
+            if let Err(e) = cmd_watch(project, env, exec, *interval, *debounce, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Migrate { apply, .. } => {
+            if let Err(e) = cmd_migrate(*apply, cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::ProjectCreate { name, description, .. } => {
-            if let Err(e) = cmd_project_create(name, description.as_deref(), vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+            if let Err(e) = cmd_project_create(name, description.as_deref(), cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::ProjectList { .. } => {
             if let Err(e) = cmd_project_list(vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::ProjectDelete { name, force, .. } => {
-            if let Err(e) = cmd_project_delete(name, *force, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::ProjectDelete { name, force, confirm_with_name, .. } => {
+            if let Err(e) = cmd_project_delete(name, *force, confirm_with_name.as_deref(), cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::EnvCreate { name, project, description, .. } => {
-            if let Err(e) = cmd_env_create(name, project, description.as_deref(), vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+            if let Err(e) = cmd_env_create(name, project, description.as_deref(), cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::ProjectMove { env, from, to, .. } => {
+            if let Err(e) = cmd_project_move(env, from, to, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::EnvList { project, .. } => {
             if let Err(e) = cmd_env_list(project, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::EnvDelete { name, project, force, confirm_with_name, .. } => {
+            if let Err(e) = cmd_env_delete(name, project, *force, confirm_with_name.as_deref(), cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::EnvClear { project, env, force, .. } => {
+            if let Err(e) = cmd_env_clear(project, env, *force, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::EnvLock { name, project, force, .. } => {
+            if let Err(e) = cmd_env_lock(name, project, *force, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::EnvDelete { name, project, force, .. } => {
-            if let Err(e) = cmd_env_delete(name, project, *force, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::EnvUnlock { name, project, force, .. } => {
+            if let Err(e) = cmd_env_unlock(name, project, *force, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::SetJson { project, env, stringify, create, .. } => {
+            if let Err(e) = cmd_set_json(project, env, *stringify, *create, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Promote { key, project, env, force, .. } => {
+            if let Err(e) = cmd_promote(key, project, env, *force, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Edit { key, project, env, force, .. } => {
+            if let Err(e) = cmd_edit(key, project, env, *force, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Protect { key, project, env, .. } => {
+            if let Err(e) = cmd_protect(key, project, env, true, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Unprotect { key, project, env, .. } => {
+            if let Err(e) = cmd_protect(key, project, env, false, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::Delete { key, project, env, force, .. } => {
-            if let Err(e) = cmd_delete(key, project, env, *force, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+            if let Err(e) = cmd_delete(key, project, env, *force, cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
         Commands::Copy { key, from_project, from_env, to_project, to_env, overwrite, .. } => {
             if let Err(e) = cmd_copy(key, from_project, from_env, to_project, to_env, *overwrite, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+                exit_with_error(cli.json, e);
             }
         }
-        Commands::Import { file, project, env, overwrite, .. } => {
-            if let Err(e) = cmd_import(file, project, env, *overwrite, vault_dir.clone(), use_session) {
-                eprintln!("Error: {}", e);
-                process::exit(1);
+        Commands::Import { file, project, env, overwrite, shared, create, dry_run, diff, show_values, .. } => {
+            let options = ImportOptions {
+                overwrite: *overwrite,
+                shared: *shared,
+                create: *create,
+                preview: dry_run.then_some(if *diff {
+                    ImportPreview::Diff { show_values: *show_values }
+                } else {
+                    ImportPreview::Counts
+                }),
+            };
+            if let Err(e) = cmd_import(file, project, env, options, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
             }
         }
-    Commands::Var(command) => {
-            // helper to choose per-command vault_dir or global one
-            let choose_vault = |cmd_vault: &Option<PathBuf>| -> Option<PathBuf> {
-                if let Some(v) = cmd_vault { Some(v.clone()) } else { vault_dir.clone() }
-            };
+        Commands::ImportDir { dir, project, env, mode, overwrite, dedupe, .. } => {
+            if let Err(e) = cmd_import_dir(dir, mode.as_deref(), project, env, *overwrite, *dedupe, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Apply { file, .. } => {
+            if let Err(e) = cmd_apply(file, cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Export { output, file, entity_type, operation, since, .. }) => {
+            let filter = operations::audit::AuditLogFilter {
+                entity_type: entity_type.clone(),
+                operation_type: operation.clone(),
+                ..Default::default()
+            };
+            if let Err(e) = cmd_audit_export(*output, file.as_deref(), filter, since.as_deref(), vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Disable { .. }) => {
+            if let Err(e) = cmd_audit_set_enabled(false, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Enable { .. }) => {
+            if let Err(e) = cmd_audit_set_enabled(true, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Clear { confirm, .. }) => {
+            if let Err(e) = cmd_audit_clear(*confirm, vault_dir.clone()) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Tail { lines, follow, interval, .. }) => {
+            if let Err(e) = cmd_audit_tail(*lines, *follow, *interval, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Coverage { project, .. }) => {
+            if let Err(e) = cmd_audit_coverage(project, cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Archive { output, older_than, prune, .. }) => {
+            if let Err(e) = cmd_audit_archive(output, older_than.as_deref(), *prune, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Expired { project, .. }) => {
+            if let Err(e) = cmd_audit_expired(project.as_deref(), cli.json, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Tag(TagCommands::Add { tag, keys, project, env, .. }) => {
+            if let Err(e) = cmd_tag(tag, keys, project, env, true, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Tag(TagCommands::Remove { tag, keys, project, env, .. }) => {
+            if let Err(e) = cmd_tag(tag, keys, project, env, false, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+        Commands::Audit(AuditCommands::Import { file, .. }) => {
+            if let Err(e) = cmd_audit_import(file, vault_dir.clone(), use_session) {
+                exit_with_error(cli.json, e);
+            }
+        }
+    Commands::Var(command) => {
+            // helper to choose per-command vault_dir or global one
+            let choose_vault = |cmd_vault: &Option<PathBuf>| -> Option<PathBuf> {
+                if let Some(v) = cmd_vault { Some(v.clone()) } else { vault_dir.clone() }
+            };
 
             match command {
-                VarCommands::Get { key, project, env, vault_dir: cmd_vault, .. } => {
+                VarCommands::Get { key, project, env, equals: Some(expected), include_expired, slot, vault_dir: cmd_vault, .. } => {
                     let vd = choose_vault(cmd_vault);
-                    if let Err(e) = cmd_get(key, project, env, vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                    match cmd_get_equals(key, project, env, expected, *include_expired, *slot, vd, use_session) {
+                        Ok(code) => process::exit(code),
+                        Err(e) => exit_with_error(cli.json, e),
                     }
                 }
-                VarCommands::Set { key, value, project, env, description, vault_dir: cmd_vault, .. } => {
+                VarCommands::Get { key, project, env, default, raw, escaped, format, include_expired, slot, vault_dir: cmd_vault, .. } => {
                     let vd = choose_vault(cmd_vault);
-                    if let Err(e) = cmd_set(key, value, project, env, description.as_deref(), vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                    let output = GetOutput::from_flags(cli.json, *raw, *escaped, *format);
+                    if let Err(e) = cmd_get(key, project, env, default.as_deref(), output, *include_expired, *slot, vd, use_session) {
+                        exit_with_error(cli.json, e);
                     }
                 }
-                VarCommands::List { project, env, show_values, vault_dir: cmd_vault, .. } => {
+                VarCommands::Set { key, value, project, env, description, value_type, force, expires_in, no_expiry, create, slot, vault_dir: cmd_vault } => {
                     let vd = choose_vault(cmd_vault);
-                    if let Err(e) = cmd_list(project.as_deref(), env.as_deref(), *show_values, vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                    if let Err(e) = cmd_set(key, value, project, env, description.as_deref(), *value_type, *force, expires_in.as_deref(), *no_expiry, *create, *slot, cli.json, vd, use_session) {
+                        exit_with_error(cli.json, e);
+                    }
+                }
+                VarCommands::List { project, env, show_values, classify, include_expired, vault_dir: cmd_vault } => {
+                    let vd = choose_vault(cmd_vault);
+                    if let Err(e) = cmd_list(project.as_deref(), env.as_deref(), *show_values, *classify, *include_expired, vd, use_session) {
+                        exit_with_error(cli.json, e);
+                    }
+                }
+                VarCommands::Edit { key, project, env, force, vault_dir: cmd_vault } => {
+                    let vd = choose_vault(cmd_vault);
+                    if let Err(e) = cmd_edit(key, project, env, *force, vd, use_session) {
+                        exit_with_error(cli.json, e);
                     }
                 }
                 VarCommands::Delete { key, project, env, force, vault_dir: cmd_vault, .. } => {
                     let vd = choose_vault(cmd_vault);
-                    if let Err(e) = cmd_delete(key, project, env, *force, vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                    if let Err(e) = cmd_delete(key, project, env, *force, cli.json, vd, use_session) {
+                        exit_with_error(cli.json, e);
                     }
                 }
                 VarCommands::Copy { key, from_project, from_env, to_project, to_env, overwrite, vault_dir: cmd_vault, .. } => {
                     let vd = choose_vault(cmd_vault);
                     if let Err(e) = cmd_copy(key, from_project, from_env, to_project, to_env, *overwrite, vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                        exit_with_error(cli.json, e);
                     }
                 }
                 VarCommands::Import { file, project, env, overwrite, vault_dir: cmd_vault, .. } => {
                     let vd = choose_vault(cmd_vault);
-                    if let Err(e) = cmd_import(file, project, env, *overwrite, vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                    let options = ImportOptions { overwrite: *overwrite, ..ImportOptions::default() };
+                    if let Err(e) = cmd_import(file, project, env, options, vd, use_session) {
+                        exit_with_error(cli.json, e);
                     }
                 }
-                VarCommands::Export { project, env, output, vault_dir: cmd_vault, .. } => {
+                VarCommands::Export { project, env, output, include_expired, vault_dir: cmd_vault } => {
                     let vd = choose_vault(cmd_vault);
-                    if let Err(e) = cmd_export(project, env, output.clone(), vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                    let options = ExportOptions { include_expired: *include_expired, ..ExportOptions::default() };
+                    if let Err(e) = cmd_export(project, env, output.clone(), options, vd, use_session) {
+                        exit_with_error(cli.json, e);
                     }
                 }
                 VarCommands::Keys { project, env, vault_dir: cmd_vault, .. } => {
                     let vd = choose_vault(cmd_vault);
                     if let Err(e) = cmd_var_keys(project, env, vd) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                        exit_with_error(cli.json, e);
                     }
                 }
                 VarCommands::BulkSet { file, project, env, overwrite, vault_dir: cmd_vault, .. } => {
                     let vd = choose_vault(cmd_vault);
                     if let Err(e) = cmd_var_bulk_set(file, project, env, *overwrite, vd, use_session) {
-                        eprintln!("Error: {}", e);
-                        process::exit(1);
+                        exit_with_error(cli.json, e);
                     }
                 }
             }
         }
     }
+    
+    // A remote vault is copied back when the command's database closes; if that
+    // failed the command's changes never reached the share
+    if let Some(e) = database::take_write_back_error() {
+        exit_with_error(cli.json, e.to_string());
+    }
+}
+
+/// A failed command; the variant picks its `--json` code and exit status
+#[derive(Debug)]
+enum CliError {
+    /// A project, environment or variable that doesn't exist
+    NotFound(String),
+    /// A project, environment or variable that would be created twice
+    AlreadyExists(String),
+    /// A protected variable changed without `--force`
+    Protected(String),
+    /// An environment locked by someone else
+    Locked(String),
+    InvalidPassword(String),
+    /// A vault whose schema is newer than this build
+    UnsupportedVersion(String),
+    Other(String),
+}
+
+impl CliError {
+    /// The `code` of the `--json` error object
+    fn code(&self) -> &'static str {
+        match self {
+            CliError::NotFound(_) => "NOT_FOUND",
+            CliError::AlreadyExists(_) => "ALREADY_EXISTS",
+            CliError::Protected(_) => "PROTECTED",
+            CliError::Locked(_) => "LOCKED",
+            CliError::InvalidPassword(_) => "INVALID_PASSWORD",
+            CliError::UnsupportedVersion(_) => "UNSUPPORTED_VERSION",
+            CliError::Other(_) => "ERROR",
+        }
+    }
+    
+    /// Process exit status; 2 is clap's usage errors and 3 and 4 are `get --equals` results
+    fn exit_status(&self) -> i32 {
+        match self {
+            CliError::Other(_) => 1,
+            CliError::NotFound(_) => 10,
+            CliError::AlreadyExists(_) => 11,
+            CliError::Protected(_) => 12,
+            CliError::Locked(_) => 13,
+            CliError::InvalidPassword(_) => 14,
+            CliError::UnsupportedVersion(_) => 15,
+        }
+    }
+    
+    fn message(&self) -> &str {
+        match self {
+            CliError::NotFound(message)
+            | CliError::AlreadyExists(message)
+            | CliError::Protected(message)
+            | CliError::Locked(message)
+            | CliError::InvalidPassword(message)
+            | CliError::UnsupportedVersion(message)
+            | CliError::Other(message) => message,
+        }
+    }
+}
+
+impl From<String> for CliError {
+    fn from(message: String) -> Self {
+        CliError::Other(message)
+    }
+}
+
+impl From<&str> for CliError {
+    fn from(message: &str) -> Self {
+        CliError::Other(message.to_string())
+    }
+}
+
+impl std::fmt::Display for CliError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+/// For helpers that report plain messages; the kind is dropped
+impl From<CliError> for String {
+    fn from(error: CliError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Report a failed command on stderr and exit with its status
+///
+/// With `--json` the error is a single `{"code": ..., "error": ...}` line, so
+/// stdout only ever carries a command's successful output. A remote vault's
+/// failed write-back goes into the same object as `write_back_error`.
+fn exit_with_error(json: bool, error: impl Into<CliError>) -> ! {
+    let error = error.into();
+    let write_back_error = database::take_write_back_error();
+    if json {
+        let mut object = serde_json::json!({ "error": error.message(), "code": error.code() });
+        if let Some(e) = &write_back_error {
+            object["write_back_error"] = e.to_string().into();
+        }
+        eprintln!("{}", object);
+    } else {
+        eprintln!("Error: {}", error.message());
+        if let Some(e) = &write_back_error {
+            eprintln!("Error: {}", e);
+        }
+    }
+    process::exit(error.exit_status());
 }
 
 fn get_vault_dir(custom_dir: Option<PathBuf>) -> Result<PathBuf, String> {
@@ -697,15 +2173,7 @@ fn get_vault_dir(custom_dir: Option<PathBuf>) -> Result<PathBuf, String> {
 // ========== SESSION MANAGEMENT ==========
 
 fn get_session_file(vault_dir: &PathBuf) -> PathBuf {
-    // Use vault directory hash to create unique session file
-    use std::collections::hash_map::DefaultHasher;
-    use std::hash::{Hash, Hasher};
-    
-    let mut hasher = DefaultHasher::new();
-    vault_dir.to_string_lossy().hash(&mut hasher);
-    let hash = hasher.finish();
-    
-    std::env::temp_dir().join(format!("{}-{:x}", SESSION_FILE_PREFIX, hash))
+    vault::session_file_path(vault_dir)
 }
 
 use base64::{engine::general_purpose, Engine as _};
@@ -760,30 +2228,108 @@ fn delete_session(vault_dir: &PathBuf) {
 
 // ========== VAULT OPERATIONS ==========
 
-fn unlock_vault(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(Database, [u8; 32]), String> {
-    let vault_path = get_vault_dir(vault_dir)?;
-    let metadata_path = vault_path.join("vault.clerk");
+/// The vault in `vault_path`, treated as remote with `--remote` or a remote marker
+///
+/// SQLite over a network filesystem can corrupt the file, so a remote vault's
+/// database is copied to the temp directory and copied back when `Database` is
+/// dropped, unless the remote file changed in the meantime.
+fn vault_manager(vault_path: &Path) -> VaultManager {
+    VaultManager::new(vault_path).with_remote(REMOTE_VAULT.get().copied().unwrap_or(false))
+}
+
+/// Open `vault.db`, through a local working copy when the vault is remote
+fn open_database_file(vault_path: &Path, key: &[u8; 32]) -> Result<Database, DatabaseError> {
+    vault_manager(vault_path).open_database(key)
+}
+
+/// Read the master password from `--password-file`, `--password-fd` or the terminal
+///
+/// Only the first line of a file or fd is used; the raw buffer is zeroized.
+fn read_master_password(prompt: &str) -> Result<Zeroizing<String>, String> {
+    read_master_password_line(prompt, 0)
+}
+
+/// Like `read_master_password`, but take line `file_line` (0-based) of a `--password-file`
+///
+/// An fd already yields its next line on each read, so the index only applies to a file.
+fn read_master_password_line(prompt: &str, file_line: usize) -> Result<Zeroizing<String>, String> {
+    let (mut raw, line_index) = match PASSWORD_SOURCE.get() {
+        None => {
+            // Prompt on stderr so stdout stays clean for `$(clerk ...)` and `eval`
+            eprintln!("{}", prompt);
+            return rpassword::read_password()
+                .map(Zeroizing::new)
+                .map_err(|e| format!("Failed to read password: {}", e));
+        }
+        Some(PasswordSource::File(path)) => (fs::read(path)
+            .map_err(|e| format!("Failed to read password file {}: {}", path.display(), e))?, file_line),
+        Some(PasswordSource::Fd(fd)) => (read_line_from_fd(*fd)?, 0),
+    };
     
-    if !metadata_path.exists() {
-        return Err("Vault does not exist. Please create one using the GUI first.".to_string());
+    let line = std::str::from_utf8(&raw)
+        .map_err(|_| "Password is not valid UTF-8".to_string())
+        .and_then(|text| match text.lines().nth(line_index) {
+            Some(line) => Ok(Zeroizing::new(line.to_string())),
+            None if line_index == 0 => Ok(Zeroizing::new(String::new())),
+            None => Err(format!("The password file has no line {} for this command", line_index + 1)),
+        });
+    raw.zeroize();
+    line
+}
+
+/// Read bytes from `fd` up to the first newline, leaving the fd open
+///
+/// Reads one byte at a time so nothing past the line is consumed, which lets
+/// a second password (e.g. for `vault-merge`) be read from the same pipe.
+#[cfg(unix)]
+fn read_line_from_fd(fd: i32) -> Result<Vec<u8>, String> {
+    use std::os::unix::io::FromRawFd;
+    
+    // ManuallyDrop keeps the File from closing an fd this process does not own
+    let mut file = std::mem::ManuallyDrop::new(unsafe { fs::File::from_raw_fd(fd) });
+    let mut line = Vec::new();
+    let mut byte = [0u8; 1];
+    
+    loop {
+        match file.read(&mut byte) {
+            Ok(0) => break,
+            Ok(_) if byte[0] == b'\n' => break,
+            Ok(_) => line.push(byte[0]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                line.zeroize();
+                return Err(format!("Failed to read password from fd {}: {}", fd, e));
+            }
+        }
     }
     
-    // Read vault metadata
-    let metadata_content = std::fs::read_to_string(&metadata_path)
-        .map_err(|e| format!("Failed to read vault metadata: {}", e))?;
+    Ok(line)
+}
+
+#[cfg(not(unix))]
+fn read_line_from_fd(_fd: i32) -> Result<Vec<u8>, String> {
+    Err("--password-fd is only supported on Unix".to_string())
+}
+
+fn unlock_vault(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(Database, [u8; 32]), CliError> {
+    let vault_path = get_vault_dir(vault_dir)?;
+    let manager = vault_manager(&vault_path);
+    
+    if !manager.exists() {
+        return Err("Vault does not exist. Please create one using the GUI first.".into());
+    }
     
-    let metadata: vault::VaultMetadata = serde_json::from_str(&metadata_content)
-        .map_err(|e| format!("Failed to parse vault metadata: {}", e))?;
+    let metadata = manager.metadata()?;
     
     // If session caching is enabled, try to use the cached derived key and stored password hash
     if use_session {
         if let Some((cached_key, stored_hash)) = load_session_key(&vault_path) {
             // If the stored password hash matches the vault metadata, we can reuse the key
             if stored_hash == metadata.password_hash {
-                // Open database and return cached key without noisy prints
-                let db_path = vault_path.join("vault.db");
-                let db = Database::new(&db_path)
-                    .map_err(|e| format!("Failed to open database: {}", e))?;
+                // Open database and return cached key without noisy prints.
+                // A cached key the database rejects is stale, so drop the session.
+                let db = open_vault_database(&vault_path, &cached_key, false)
+                    .inspect_err(|_| delete_session(&vault_path))?;
                 return Ok((db, cached_key));
             } else {
                 // Stored hash mismatch (possibly password changed); remove session
@@ -792,307 +2338,1666 @@ fn unlock_vault(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(Databa
         }
     }
 
-    // Prompt for password and verify
-    println!("Enter master password:");
-    let password = rpassword::read_password()
-        .map_err(|e| format!("Failed to read password: {}", e))?;
-
-    // Verify password against stored hash
-    if !verify_password(&password, &metadata.password_hash)
-        .map_err(|e| format!("Password verification failed: {}", e))? {
+    // Prompt for password (unless a file or fd was given) and verify
+    let password = read_master_password("Enter master password:")?;
+    
+    let (mut metadata, key) = manager.derive_key(&password)
+        .map_err(|e| match manager.password_hint() {
+            Some(hint) if e == vault::INVALID_PASSWORD_ERROR => CliError::InvalidPassword(format!("{} (hint: {})", e, hint)),
+            _ => password_error(e),
+        })
         // Delete invalid session if exists
-        if use_session {
-            delete_session(&vault_path);
-        }
-        return Err("Invalid password".to_string());
-    }
-
-    // Derive encryption key
-    let salt: [u8; 16] = metadata.salt.as_slice()
-        .try_into()
-        .map_err(|_| "Invalid salt length")?;
-
-    let key = crypto::key_derivation::derive_key(&password, &salt)
-        .map_err(|e| format!("Key derivation failed: {}", e))?;
-
+        .inspect_err(|_| if use_session { delete_session(&vault_path) })?;
+    
+    // Open database
+    let db = open_vault_database(&vault_path, &key, true)?;
+    manager.check_metadata_mac(&db, &mut metadata, &key)?;
+    
     // Save session if enabled and not already cached
     if use_session && load_session_key(&vault_path).is_none() {
         save_session_key(&key, &metadata.password_hash, &vault_path)?;
-        println!("Session saved for this terminal");
+        eprintln!("Session saved for this terminal");
     }
     
-    // Open database
-    let db_path = vault_path.join("vault.db");
-    let db = Database::new(&db_path)
-        .map_err(|e| format!("Failed to open database: {}", e))?;
-    
     // Do not print unlock confirmation here to avoid noisy per-command messages.
     Ok((db, key))
 }
 
-fn cmd_unlock(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+/// Open the vault database, bring its schema up to date and record the access
+///
+/// The key is checked against the vault's canary first. `password_verified` marks
+/// a key freshly derived from the master password, which may seed a missing canary.
+fn open_vault_database(vault_path: &Path, key: &[u8; 32], password_verified: bool) -> Result<Database, CliError> {
+    // Opening would silently create an empty database in its place
+    if !vault_path.join("vault.db").exists() {
+        return Err("Vault database (vault.db) is missing. Run 'clerk repair' to recreate an empty one.".into());
+    }
+    
+    vault_manager(vault_path).open_with_key(key, password_verified)
+        .map_err(|e| match e {
+            DatabaseError::NewerSchema { .. } => CliError::UnsupportedVersion(format!("Cannot open vault: {}", e)),
+            e => CliError::Other(format!("Cannot open vault: {}", e)),
+        })
+}
+
+/// Class an error from a `VaultManager` call that checks the master password
+fn password_error(message: String) -> CliError {
+    if message == vault::INVALID_PASSWORD_ERROR {
+        CliError::InvalidPassword(message)
+    } else {
+        CliError::Other(message)
+    }
+}
+
+fn cmd_unlock(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     unlock_vault(vault_dir, use_session)?;
-    println!("Vault is ready. You can now run other commands.");
+    if use_session {
+        println!("Vault is ready. You can now run other commands.");
+    } else {
+        println!("Password is correct. No session was started (-S), so other commands will ask again.");
+    }
     Ok(())
 }
 
-fn cmd_get(key: &str, project_name: &str, env_name: &str, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
-    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
-    
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
-    
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
-    
-    // Find environment
-    let environments = operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
-        .map_err(|e| format!("Failed to get environments: {}", e))?;
+fn cmd_vault_create(
+    kdf_memory: Option<u32>,
+    kdf_iterations: Option<u32>,
+    encrypt_database: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    // Validate KDF overrides before prompting for anything
+    let kdf_params = KdfParams::with_overrides(kdf_memory, kdf_iterations)?;
+    if encrypt_database && !database::sqlcipher_available() {
+        return Err(format!("{}; rebuild with `--features sqlcipher` to use --encrypt-database", database::SQLCIPHER_MISSING_ERROR).into());
+    }
     
-    let environment = environments.iter()
-        .find(|e| e.name == env_name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", env_name, project_name))?;
+    let vault_path = get_vault_dir(vault_dir)?;
+    let manager = vault_manager(&vault_path);
+    if manager.exists() {
+        return Err(CliError::AlreadyExists(format!("Vault already exists at {}", vault_path.display())));
+    }
     
-    // Get variables
-    let variables = operations::variables::get_variables_by_environment_decrypted(
-        db.connection(),
-        environment.id.unwrap(),
-        &encryption_key,
-    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    let password = read_new_master_password(0)?;
+    let (db, metadata, key) = manager.create(&password, kdf_params)?;
+    drop(db);
+    if encrypt_database {
+        manager.set_database_encryption(&key, true)?;
+    }
     
-    // Find the specific variable
-    let variable = variables.iter()
-        .find(|v| v.key == key)
-        .ok_or_else(|| format!("Variable '{}' not found", key))?;
+    if use_session {
+        save_session_key(&key, &metadata.password_hash, &vault_path)?;
+    }
     
-    // Output just the value (perfect for shell scripts)
-    println!("{}", variable.value);
+    println!("Vault created at {}", vault_path.display());
+    println!("   KDF: {} KiB memory, {} iterations", kdf_params.memory_kib, kdf_params.iterations);
+    if encrypt_database {
+        println!("   Database: encrypted with SQLCipher");
+    }
     Ok(())
 }
 
-fn cmd_set(
-    key: &str,
-    value: &str,
-    project_name: &str,
-    env_name: &str,
-    description: Option<&str>,
-    vault_dir: Option<PathBuf>,
-    use_session: bool,
-) -> Result<(), String> {
-    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
-    
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
-    
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
+/// Prompt for a new master password, asking twice when it is typed
+///
+/// `file_line` picks the line of a `--password-file` that holds it.
+fn read_new_master_password(file_line: usize) -> Result<Zeroizing<String>, String> {
+    let password = read_master_password_line("Enter new master password:", file_line)?;
+    
+    if password.len() < 8 {
+        return Err("Password must be at least 8 characters long".to_string());
+    }
     
-    // Find environment
-    let environments = operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
-        .map_err(|e| format!("Failed to get environments: {}", e))?;
+    // A password from a file or fd was not typed, so there is nothing to confirm
+    if PASSWORD_SOURCE.get().is_none() {
+        println!("Confirm master password:");
+        let confirmation = Zeroizing::new(rpassword::read_password()
+            .map_err(|e| format!("Failed to read password: {}", e))?);
+        
+        if *password != *confirmation {
+            return Err("Passwords do not match".to_string());
+        }
+    }
     
-    let environment = environments.iter()
-        .find(|e| e.name == env_name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", env_name, project_name))?;
+    Ok(password)
+}
+
+/// Ask a yes/no question on stdin (default no)
+fn confirm_prompt(question: &str) -> Result<bool, String> {
+    println!("{} [y/N]", question);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)
+        .map_err(|e| format!("Failed to read confirmation: {}", e))?;
+    
+    Ok(matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Recover a vault that lost one of its two files
+///
+/// * `vault.clerk` present, `vault.db` missing: after checking the password,
+///   create an empty database for the existing key.
+/// * `vault.db` present, `vault.clerk` missing: write new metadata for a new
+///   password. Values were encrypted under a key derived from the lost salt, so
+///   this is only possible while the database holds no variables.
+fn cmd_repair(yes: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir)?;
+    let manager = vault_manager(&vault_path);
+    let metadata_path = manager.metadata_path();
+    let db_path = manager.database_path();
+    
+    match (metadata_path.exists(), db_path.exists()) {
+        (true, true) => {
+            println!("Vault at {} has both vault.clerk and vault.db; nothing to repair.", vault_path.display());
+            Ok(())
+        }
+        (false, false) => Err(format!("No vault found at {}. Create one with: clerk vault-create", vault_path.display()).into()),
+        (true, false) => {
+            println!("vault.clerk exists but vault.db is missing at {}.", vault_path.display());
+            println!("Repair creates an EMPTY database: projects, environments and variables in the lost file are not recovered.");
+            if !yes && !confirm_prompt("Recreate an empty database?")? {
+                return Err("Repair cancelled. Nothing was changed.".into());
+            }
+            
+            let password = read_master_password("Enter master password:")?;
+            let (mut metadata, key) = manager.derive_key(&password).map_err(password_error)?;
+            
+            let db = manager.open_database(&key)
+                .map_err(|e| format!("Failed to create database: {}", e))?;
+            db.initialize()
+                .map_err(|e| format!("Failed to initialize database: {}", e))?;
+            operations::canary::store_key_canary(db.connection(), &key)
+                .map_err(|e| format!("Failed to store key canary: {}", e))?;
+            manager.check_metadata_mac(&db, &mut metadata, &key)?;
+            
+            if use_session {
+                save_session_key(&key, &metadata.password_hash, &vault_path)?;
+            }
+            println!("Recreated an empty database at {}", db_path.display());
+            Ok(())
+        }
+        (false, true) => {
+            println!("vault.db exists but vault.clerk is missing at {}.", vault_path.display());
+            
+            let counts = {
+                // An encrypted database is unreadable without the salt in the lost metadata
+                let db = Database::open_no_migrate(&db_path, None)
+                    .map_err(|e| format!("Failed to open database: {}", e))?;
+                operations::stats::vault_counts(db.connection())
+                    .map_err(|e| format!("Failed to read database (if it was encrypted, restore vault.clerk from a backup): {}", e))?
+            };
+            if counts.variable_count > 0 {
+                return Err(format!(
+                    "vault.db holds {} encrypted variable(s) whose key depended on the salt in the missing vault.clerk; \
+                     new metadata could not decrypt them. Restore vault.clerk from a backup instead (vault.db was left untouched).",
+                    counts.variable_count
+                ).into());
+            }
+            
+            println!(
+                "The database holds {} project(s) and {} environment(s) but no variables, so new metadata can be written for a new master password.",
+                counts.project_count, counts.environment_count
+            );
+            if !yes && !confirm_prompt("Reconstruct vault.clerk with a new master password?")? {
+                return Err("Repair cancelled. Nothing was changed.".into());
+            }
+            
+            let password = read_new_master_password(0)?;
+            let (metadata, key) = manager.write_metadata(&password, KdfParams::default())?;
+            
+            // The old canary belongs to the lost key; replace it so the new key is accepted
+            let db = manager.open_database(&key)
+                .map_err(|e| format!("Failed to open database: {}", e))?;
+            db.initialize()
+                .map_err(|e| format!("Failed to initialize database: {}", e))?;
+            operations::canary::store_key_canary(db.connection(), &key)
+                .map_err(|e| format!("Failed to store key canary: {}", e))?;
+            
+            if use_session {
+                delete_session(&vault_path);
+                save_session_key(&key, &metadata.password_hash, &vault_path)?;
+            }
+            println!("Reconstructed {}", metadata_path.display());
+            Ok(())
+        }
+    }
+}
+
+/// Rebuild vault.db without the space left by deleted rows and report the sizes
+fn cmd_vault_compact(json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
-    // Check if variable exists
-    let variables = operations::variables::get_variables_by_environment_decrypted(
-        db.connection(),
-        environment.id.unwrap(),
-        &encryption_key,
-    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    // SQLite refuses with "database is locked" while anyone else is using the file
+    let report = db.compact()
+        .map_err(|e| format!("Failed to compact the database (close the app and other clerk commands, then retry): {}", e))?;
     
-    if let Some(existing) = variables.iter().find(|v| v.key == key) {
-        // Update existing variable
-        operations::variables::update_variable_encrypted(
-            db.connection(),
-            existing.id,
-            key.to_string(),
-            value.to_string(),
-            description.map(String::from),
-            &encryption_key,
-        ).map_err(|e| format!("Failed to update variable: {}", e))?;
-        
-    println!("Updated variable '{}'", key);
+    if json {
+        let out = serde_json::to_string_pretty(&report)
+            .map_err(|e| format!("Failed to serialize result: {}", e))?;
+        println!("{}", out);
     } else {
-        // Create new variable
-        operations::variables::create_variable_encrypted(
-            db.connection(),
-            environment.id.unwrap(),
-            key.to_string(),
-            value.to_string(),
-            description.map(String::from),
-            &encryption_key,
-        ).map_err(|e| format!("Failed to create variable: {}", e))?;
-        
-    println!("Created variable '{}'", key);
+        println!("Compacted vault.db: {} -> {} bytes ({} reclaimed)",
+            report.size_before, report.size_after, report.size_before.saturating_sub(report.size_after));
     }
     
     Ok(())
 }
 
-fn cmd_list(
-    project_filter: Option<&str>,
-    env_filter: Option<&str>,
-    show_values: bool,
-    vault_dir: Option<PathBuf>,
-    use_session: bool,
-) -> Result<(), String> {
-    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+/// Give the vault a new salt and key for the same password, then drop every cached old key
+fn cmd_vault_rekey(vault_dir: Option<PathBuf>) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir)?;
+    let manager = vault_manager(&vault_path);
+    if !manager.exists() {
+        return Err(format!("No vault found at {}. Create one with: clerk vault-create", vault_path.display()).into());
+    }
     
-    // Get all projects
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    // Always the password: a cached session key can't re-derive anything
+    let password = read_master_password("Enter master password:")?;
+    let (_, _, _, count) = manager.rekey(&password).map_err(password_error)?;
+    println!("Rekeyed the vault at {}: new salt, {} value(s) re-encrypted", vault_path.display(), count);
     
-    let filtered_projects: Vec<_> = if let Some(filter) = project_filter {
-        projects.iter().filter(|p| p.name == filter).collect()
-    } else {
-        projects.iter().collect()
-    };
+    // Only the GUI's vault (the default directory) has a keychain entry
+    let is_default = vault::get_vault_directory().is_ok_and(|default_dir| default_dir == vault_path);
+    match manager.lock(true, is_default) {
+        Ok(()) => println!("   Sessions{} holding the old key were cleared; unlock again", if is_default { " and the keychain entry" } else { "" }),
+        Err(e) => eprintln!("Warning: {}. Remember Me will fail until you unlock with the password.", e),
+    }
+    Ok(())
+}
+
+/// Switch the vault to a new master password, then drop every cached old key
+fn cmd_rotate_password(vault_dir: Option<PathBuf>) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir)?;
+    let manager = vault_manager(&vault_path);
+    if !manager.exists() {
+        return Err(format!("No vault found at {}. Create one with: clerk vault-create", vault_path.display()).into());
+    }
     
-        if filtered_projects.is_empty() {
-        if let Some(filter) = project_filter {
-            println!("No project found matching '{}'", filter);
-        } else {
-            println!("No projects found. Create one using the GUI or 'clerk init'");
+    // A password file holds the current password on its first line and the new one on its second
+    let old_password = read_master_password("Enter current master password:")?;
+    let new_password = read_new_master_password(1)?;
+    if *new_password == *old_password {
+        return Err("The new password is the same as the current one; use vault-rekey to only rotate the key".into());
+    }
+    
+    let (_, metadata, new_key, count) = manager.change_password(&old_password, &new_password).map_err(password_error)?;
+    println!("Changed the master password of the vault at {}: {} value(s) re-encrypted", vault_path.display(), count);
+    if metadata.hint.is_some() {
+        println!("   The password hint was kept; update it with `clerk password-hint --set` if it describes the old password");
+    }
+    
+    if let Err(e) = manager.lock(true, false) {
+        eprintln!("Warning: {}", e);
+    }
+    println!("   Sessions holding the old key were cleared; unlock again");
+    
+    // Only the GUI's vault (the default directory) has a keychain entry; give it the new key as the GUI does
+    let is_default = vault::get_vault_directory().is_ok_and(|default_dir| default_dir == vault_path);
+    let keychain = app_lib::keychain::KeychainManager::new();
+    if is_default && matches!(keychain.get_key(), Ok(Some(_))) {
+        // Remove the old entry first; some backends refuse to overwrite a corrupt one
+        match keychain.delete_key().and_then(|_| keychain.save_key(&new_key)) {
+            Ok(()) => println!("   The keychain entry now holds the new key"),
+            Err(e) => eprintln!("Warning: failed to update the keychain entry ({}). Remember Me will fail until you unlock with the password.", e),
+        }
+    }
+    Ok(())
+}
+
+fn cmd_password_hint(set: Option<&str>, clear: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir.clone())?;
+    let manager = vault_manager(&vault_path);
+    if !manager.exists() {
+        return Err(format!("No vault found at {}. Create one with: clerk vault-create", vault_path.display()).into());
+    }
+    
+    if set.is_none() && !clear {
+        match manager.password_hint() {
+            Some(hint) => println!("{}", hint),
+            None => println!("No password hint is set"),
         }
         return Ok(());
     }
     
-    for project in filtered_projects {
-    println!("\nProject: {}", project.name);
-        if let Some(desc) = &project.description {
-            println!("   Description: {}", desc);
+    // The hint is part of the metadata MAC, which needs the vault key
+    let (_db, key) = unlock_vault(vault_dir, use_session)?;
+    manager.set_password_hint(&key, set)?;
+    if manager.password_hint().is_some() {
+        println!("Password hint saved in {}", manager.metadata_path().display());
+        println!("   Anyone who can read that file can read the hint; make sure it doesn't give the password away");
+    } else {
+        println!("Password hint removed");
+    }
+    Ok(())
+}
+
+fn cmd_database_encryption(enable: bool, disable: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir.clone())?;
+    let manager = vault_manager(&vault_path);
+    if !manager.exists() {
+        return Err(format!("No vault found at {}. Create one with: clerk vault-create", vault_path.display()).into());
+    }
+    
+    if !enable && !disable {
+        if manager.metadata()?.database_encrypted {
+            println!("Database encryption: on (vault.db is encrypted with SQLCipher)");
+        } else {
+            println!("Database encryption: off (only values are encrypted; names and the audit log are readable)");
+        }
+        if !database::sqlcipher_available() {
+            println!("   This build has no SQLCipher support; rebuild with --features sqlcipher to change it");
         }
+        return Ok(());
+    }
+    
+    // The file is swapped out, so the unlocking connection must be closed first
+    let (db, key) = unlock_vault(vault_dir, use_session)?;
+    drop(db);
+    if !manager.set_database_encryption(&key, enable)? {
+        println!("Database encryption is already {}", if enable { "on" } else { "off" });
+    } else if enable {
+        println!("Encrypted {} with SQLCipher; key names, projects and the audit log are no longer readable without the password", manager.database_path().display());
+    } else {
+        println!("Decrypted {} back to plain SQLite; values are still encrypted", manager.database_path().display());
+    }
+    Ok(())
+}
+
+fn cmd_reset_vault(confirm: bool, vault_dir: Option<PathBuf>) -> Result<(), CliError> {
+    if !confirm {
+        return Err("Refusing to reset the vault without --confirm".into());
+    }
+    
+    let vault_path = get_vault_dir(vault_dir)?;
+    if !vault_path.join("vault.clerk").exists() && !vault_path.join("vault.db").exists() {
+        println!("No vault found at {}", vault_path.display());
+        return Ok(());
+    }
+    
+    println!("This permanently deletes the vault at {} and every secret in it.", vault_path.display());
+    println!("Type '{}' to confirm:", vault::RESET_CONFIRMATION);
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)
+        .map_err(|e| format!("Failed to read confirmation: {}", e))?;
+    
+    if answer.trim_end_matches(['\r', '\n']) != vault::RESET_CONFIRMATION {
+        return Err("Confirmation did not match. Nothing was deleted.".into());
+    }
+    
+    let removed = vault::delete_vault_files(&vault_path)?;
+    
+    // Only the GUI's vault (the default directory) has a keychain entry
+    if vault::get_vault_directory().is_ok_and(|default_dir| default_dir == vault_path) {
+        app_lib::keychain::KeychainManager::new().delete_key()?;
+    }
+    
+    for path in &removed {
+        println!("   Deleted {}", path.display());
+    }
+    println!("Vault reset. Create a new one with: clerk vault-create");
+    Ok(())
+}
+
+fn cmd_benchmark_kdf(target_ms: u64) -> Result<(), CliError> {
+    use std::time::Instant;
+    
+    // Candidate (memory KiB, iterations) pairs, ordered from cheapest to most expensive
+    const CANDIDATES: [(u32, u32); 6] = [
+        (16384, 2),
+        (32768, 2),
+        (32768, 4),
+        (65536, 3),
+        (131072, 3),
+        (262144, 3),
+    ];
+    
+    let salt = crypto::generate_salt()
+        .map_err(|_| "Failed to generate salt".to_string())?;
+    
+    println!("Benchmarking Argon2id key derivation (target ~{} ms)", target_ms);
+    println!("--------------------------------------------------");
+    
+    let mut recommended: Option<KdfParams> = None;
+    for (memory_kib, iterations) in CANDIDATES {
+        let params = KdfParams::with_overrides(Some(memory_kib), Some(iterations))?;
         
-        // Get environments
-        let environments = operations::environments::get_environments_by_project(
-            db.connection(),
-            project.id.unwrap(),
-        ).map_err(|e| format!("Failed to get environments: {}", e))?;
+        let start = Instant::now();
+        crypto::derive_key_with_params("clerk-benchmark", &salt, &params)
+            .map_err(|e| format!("Key derivation failed: {}", e))?;
+        let elapsed_ms = start.elapsed().as_millis() as u64;
         
-        let filtered_envs: Vec<_> = if let Some(filter) = env_filter {
-            environments.iter().filter(|e| e.name == filter).collect()
-        } else {
-            environments.iter().collect()
-        };
+        println!("  {:>7} KiB, {} iterations: {:>6} ms", memory_kib, iterations, elapsed_ms);
         
-        for env in filtered_envs {
-            println!("   Environment: {}", env.name);
-            
-            // Get variables
-            let variables = operations::variables::get_variables_by_environment_decrypted(
-                db.connection(),
-                env.id.unwrap(),
-                &encryption_key,
-            ).map_err(|e| format!("Failed to get variables: {}", e))?;
-            
-            if variables.is_empty() {
-                println!("      (no variables)");
-            } else {
-                for var in variables {
-                    if show_values {
-                        println!("      {}={}", var.key, var.value);
-                    } else {
-                        println!("      {}=********", var.key);
-                    }
-                }
-            }
+        // Keep the strongest set that still fits within the target
+        if elapsed_ms <= target_ms || recommended.is_none() {
+            recommended = Some(params);
         }
+        if elapsed_ms > target_ms {
+            break;
+        }
+    }
+    
+    println!("--------------------------------------------------");
+    if let Some(params) = recommended {
+        println!(
+            "Recommended: clerk vault-create --kdf-memory {} --kdf-iterations {}",
+            params.memory_kib, params.iterations
+        );
     }
     
     Ok(())
 }
 
-fn cmd_export(
+fn cmd_vault_merge(
+    from: &PathBuf,
+    into: &PathBuf,
+    strategy: MergeConflictStrategy,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let same_vault = match (from.canonicalize(), into.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => from == into,
+    };
+    if same_vault {
+        return Err("Source and target must be different vaults".into());
+    }
+    
+    // Each vault is unlocked with its own password
+    println!("Source vault: {}", from.display());
+    let (source_db, source_key) = unlock_vault(Some(from.clone()), use_session)?;
+    
+    println!("Target vault: {}", into.display());
+    let (target_db, target_key) = unlock_vault(Some(into.clone()), use_session)?;
+    
+    let summary = operations::merge::merge_vaults(
+        source_db.connection(),
+        &source_key,
+        target_db.connection(),
+        &target_key,
+        strategy,
+    ).map_err(|e| format!("Merge failed (target vault unchanged): {}", e))?;
+    
+    println!("Merge completed:");
+    println!("   Projects created: {}", summary.projects_created);
+    println!("   Projects merged: {}", summary.projects_merged);
+    if summary.projects_skipped > 0 {
+        println!("   Projects skipped: {} (use --on-conflict to merge or rename)", summary.projects_skipped);
+    }
+    println!("   Environments created: {}", summary.environments_created);
+    println!("   Variables created: {}", summary.variables_created);
+    println!("   Variables updated: {}", summary.variables_updated);
+    if summary.variables_skipped > 0 {
+        println!("   Variables skipped: {} (use --on-conflict overwrite to replace)", summary.variables_skipped);
+    }
+    
+    Ok(())
+}
+
+/// Output format for `get`
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum GetFormat {
+    /// The value alone
+    Value,
+    /// `export KEY='value'`, safe to `eval`
+    Export,
+}
+
+/// Which of a variable's two values a command reads or writes
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Slot {
+    /// The live value, read by every other command
+    Current,
+    /// The next value, staged for rotation until `clerk promote`
+    Pending,
+}
+
+/// How `clerk get` prints a value
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum GetOutput {
+    /// Value followed by a newline
+    Plain,
+    /// Exact value bytes, no trailing newline (for piping into files)
+    Raw,
+    /// Single line with `\n`, `\r`, `\t` and `\\` escapes (for inspecting multi-line values)
+    Escaped,
+    /// JSON object with key, value and source
+    Json,
+    /// Single-quoted shell assignment
+    Export,
+}
+
+impl GetOutput {
+    fn from_flags(json: bool, raw: bool, escaped: bool, format: GetFormat) -> Self {
+        if json {
+            GetOutput::Json
+        } else if format == GetFormat::Export {
+            GetOutput::Export
+        } else if raw {
+            GetOutput::Raw
+        } else if escaped {
+            GetOutput::Escaped
+        } else {
+            GetOutput::Plain
+        }
+    }
+}
+
+/// Escape a value so multi-line content prints on one line
+fn escape_value(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_get(
+    key: &str,
     project_name: &str,
     env_name: &str,
-    output: Option<PathBuf>,
+    default: Option<&str>,
+    output: GetOutput,
+    include_expired: bool,
+    slot: Slot,
     vault_dir: Option<PathBuf>,
     use_session: bool,
-) -> Result<(), String> {
+) -> Result<(), CliError> {
+    if output == GetOutput::Export && !is_shell_identifier(key) {
+        return Err(format!("'{}' is not a valid shell variable name; it cannot be printed with --format export", key).into());
+    }
+    
+    // Unlock errors are never replaced by the default value
     let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let variable = find_variable(&db, &encryption_key, key, project_name, env_name, include_expired, slot)?;
+    
+    // Fall back to the default only when the key is missing
+    let (value, source) = match (&variable, default) {
+        (Some(value), _) => (value.expose(), "vault"),
+        (None, Some(default)) => (default, "default"),
+        (None, None) if slot == Slot::Pending => return Err(CliError::NotFound(format!("Variable '{}' not found or has no pending value", key))),
+        (None, None) => return Err(CliError::NotFound(format!("Variable '{}' not found", key))),
+    };
     
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    match output {
+        GetOutput::Json => println!("{}", serde_json::json!({ "key": key, "value": value, "source": source })),
+        GetOutput::Raw => {
+            use std::io::Write;
+            let mut stdout = std::io::stdout();
+            stdout.write_all(value.as_bytes())
+                .and_then(|_| stdout.flush())
+                .map_err(|e| format!("Failed to write value: {}", e))?;
+        }
+        GetOutput::Escaped => println!("{}", escape_value(value)),
+        GetOutput::Export => println!("export {}={}", key, shell_quote(value)),
+        // Output just the value (perfect for shell scripts)
+        GetOutput::Plain => println!("{}", value),
+    }
+    Ok(())
+}
+
+/// Print a whole environment as `KEY=value` lines (`clerk get --all`)
+///
+/// Only the lines go to stdout, with no header, so the output can be
+/// `source`d. Values are shell-quoted only when they need it, and keys that
+/// are not valid shell names are skipped with a warning on stderr.
+fn cmd_get_all(project_name: &str, env_name: &str, json: bool, include_expired: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, env_name)?;
     
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
+    let mut variables = operations::variables::get_variables_by_environment_decrypted(
+        db.connection(),
+        environment_id,
+        &encryption_key,
+    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    skip_expired(&mut variables, include_expired, |v| (&v.key, v.expires_at));
+    
+    if json {
+        let object: serde_json::Map<String, serde_json::Value> = variables.iter()
+            .map(|v| (v.key.clone(), serde_json::Value::String(v.value.expose().to_string())))
+            .collect();
+        let mut out = serde_json::Value::Object(object).to_string();
+        println!("{}", out);
+        out.zeroize();
+        return Ok(());
+    }
     
-    // Find environment
-    let environments = operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
-        .map_err(|e| format!("Failed to get environments: {}", e))?;
+    let mut out = String::new();
+    for var in &variables {
+        if !is_shell_identifier(&var.key) {
+            eprintln!("Warning: skipping '{}', not a valid shell variable name", var.key);
+            continue;
+        }
+        let value = var.value.expose();
+        if is_shell_safe_word(value) {
+            out.push_str(&format!("{}={}\n", var.key, value));
+        } else {
+            out.push_str(&format!("{}={}\n", var.key, shell_quote(value)));
+        }
+    }
+    
+    print!("{}", out);
+    out.zeroize();
+    Ok(())
+}
+
+/// True if `value` reads back unchanged in a shell without quoting
+fn is_shell_safe_word(value: &str) -> bool {
+    value.chars().all(|c| c.is_ascii_alphanumeric() || "_-.,:/@%+=".contains(c))
+}
+
+/// Exit status of `clerk get --equals` when the stored value differs
+///
+/// No `CliError` exits with it, so a failed unlock never reads as a mismatch.
+const EQUALS_MISMATCH_EXIT: i32 = 4;
+/// Exit status of `clerk get --equals` when the key doesn't exist (or has expired)
+///
+/// Not 2, which clap uses for usage errors.
+const EQUALS_MISSING_EXIT: i32 = 3;
+
+/// Compare a stored value with `expected` without printing either
+///
+/// Returns the process exit status; `expected` of "-" is read from stdin, minus
+/// one trailing newline.
+#[allow(clippy::too_many_arguments)]
+fn cmd_get_equals(
+    key: &str,
+    project_name: &str,
+    env_name: &str,
+    expected: &str,
+    include_expired: bool,
+    slot: Slot,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<i32, CliError> {
+    let expected = if expected == "-" {
+        let mut input = Zeroizing::new(String::new());
+        std::io::stdin().read_to_string(&mut input)
+            .map_err(|e| format!("Failed to read expected value from stdin: {}", e))?;
+        let trimmed = input.strip_suffix('\n').map(|v| v.strip_suffix('\r').unwrap_or(v)).unwrap_or(&input);
+        Zeroizing::new(trimmed.to_string())
+    } else {
+        Zeroizing::new(expected.to_string())
+    };
     
-    let environment = environments.iter()
-        .find(|e| e.name == env_name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", env_name, project_name))?;
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let code = match find_variable(&db, &encryption_key, key, project_name, env_name, include_expired, slot)? {
+        Some(value) if value.matches(&expected) => 0,
+        Some(_) => EQUALS_MISMATCH_EXIT,
+        None => EQUALS_MISSING_EXIT,
+    };
+    Ok(code)
+}
+
+/// Decrypt one variable's value in `slot`, or `None` if the key (or its pending value) is missing
+///
+/// An expired variable counts as missing (with a warning) unless `include_expired`.
+fn find_variable(
+    db: &Database,
+    encryption_key: &[u8; 32],
+    key: &str,
+    project_name: &str,
+    env_name: &str,
+    include_expired: bool,
+    slot: Slot,
+) -> Result<Option<crypto::SecretString>, CliError> {
+    let environment_id = find_environment_id(db, project_name, env_name)?;
+    let variables = operations::variables::get_variables_by_environment_decrypted(
+        db.connection(),
+        environment_id,
+        encryption_key,
+    ).map_err(|e| format!("Failed to get variables: {}", e))?;
     
-    // Get variables
+    let now = chrono::Utc::now().timestamp();
+    let Some(variable) = variables.into_iter().find(|v| v.key == key) else {
+        return Ok(None);
+    };
+    if let Some(expires_at) = variable.expires_at.filter(|_| variable.is_expired(now)) {
+        warn_expired(key, expires_at, include_expired);
+        if !include_expired {
+            return Ok(None);
+        }
+    }
+    if slot == Slot::Pending {
+        let stored = operations::variables::get_variable(db.connection(), variable.id)
+            .map_err(|e| format!("Failed to get variable: {}", e))?;
+        return Ok(operations::variables::decrypt_pending_value(db.connection(), &stored, encryption_key)
+            .map_err(|e| format!("Failed to decrypt pending value: {}", e))?);
+    }
+    Ok(Some(variable.value))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_set(
+    key: &str,
+    value: &str,
+    project_name: &str,
+    env_name: &str,
+    description: Option<&str>,
+    value_type: Option<ValueType>,
+    force: bool,
+    expires_in: Option<&str>,
+    no_expiry: bool,
+    create: bool,
+    slot: Slot,
+    json: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    if slot == Slot::Pending {
+        if description.is_some() || value_type.is_some() || expires_in.is_some() || no_expiry || create {
+            return Err("--slot pending only stages a value; change the description, type, expiry or create the variable with the current slot".into());
+        }
+        return cmd_set_pending(key, value, project_name, env_name, force, json, vault_dir, use_session);
+    }
+    
+    // None keeps the current expiry; Some(None) clears it
+    let expiry = match expires_in {
+        Some(duration) => Some(Some(chrono::Utc::now().timestamp() + parse_duration_secs(duration)?)),
+        None if no_expiry => Some(None),
+        None => None,
+    };
+    
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    // Anything created with --create is rolled back if the write fails
+    let tx = db.connection().unchecked_transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    
+    let environment_id = match find_environment_id(&db, project_name, env_name) {
+        Ok(environment_id) => {
+            check_environment_lock(&db, project_name, env_name, environment_id, force)?;
+            environment_id
+        }
+        Err(_) if create => {
+            let (environment_id, created) = operations::environments::ensure_project_environment(db.connection(), project_name, env_name)
+                .map_err(|e| format!("Failed to create project or environment: {}", e))?;
+            if !json {
+                print_created(project_name, env_name, created);
+            }
+            environment_id
+        }
+        Err(e) => return Err(e),
+    };
+    
+    // Check if variable exists
     let variables = operations::variables::get_variables_by_environment_decrypted(
         db.connection(),
-        environment.id.unwrap(),
+        environment_id,
         &encryption_key,
     ).map_err(|e| format!("Failed to get variables: {}", e))?;
     
-    // Generate .env content
-    let mut content = String::new();
-    content.push_str("# Generated by Clerk CLI\n");
-    content.push_str(&format!("# Project: {}\n", project_name));
-    content.push_str(&format!("# Environment: {}\n", env_name));
-    content.push_str(&format!("# Total variables: {}\n\n", variables.len()));
+    let (var_id, action) = if let Some(existing) = variables.iter().find(|v| v.key == key) {
+        // Update existing variable
+        operations::variables::update_variable_encrypted_with_type(
+            db.connection(),
+            existing.id,
+            key.to_string(),
+            value.to_string(),
+            description.map(String::from),
+            value_type,
+            force,
+            &encryption_key,
+        ).map_err(protected_error)?;
+        
+        (existing.id, "updated")
+    } else {
+        // Create new variable
+        let var_id = operations::variables::create_variable_encrypted_with_type(
+            db.connection(),
+            environment_id,
+            key.to_string(),
+            value.to_string(),
+            description.map(String::from),
+            value_type.unwrap_or_default(),
+            &encryption_key,
+        ).map_err(|e| format!("Failed to create variable: {}", e))?;
+        
+        (var_id, "created")
+    };
     
-    for var in variables {
-        let value = if var.value.contains(' ') || var.value.contains('"') {
-            format!("\"{}\"", var.value.replace('"', "\\\""))
-        } else {
-            var.value.clone()
-        };
-        content.push_str(&format!("{}={}\n", var.key, value));
+    if let Some(expires_at) = expiry {
+        operations::variables::set_variable_expiry(db.connection(), var_id, expires_at)
+            .map_err(|e| format!("Failed to set expiry: {}", e))?;
     }
     
-    // Output to file or stdout
-    if let Some(path) = output {
-        std::fs::write(&path, content)
-            .map_err(|e| format!("Failed to write file: {}", e))?;
-        println!("Exported to {}", path.display());
-    } else {
-        print!("{}", content);
+    tx.commit().map_err(|e| format!("Failed to save changes: {}", e))?;
+    
+    if json {
+        print_write_result(action, "variable", Some(key), project_name, Some(env_name));
+        return Ok(());
     }
+    println!("{} variable '{}'", if action == "created" { "Created" } else { "Updated" }, key);
+    match expiry {
+        Some(Some(expires_at)) => println!("   Expires {}", format_expiry(expires_at)),
+        Some(None) => println!("   Expiry removed"),
+        None => {}
+    }
+    Ok(())
+}
+
+/// Upsert the entries of a `{"KEY": "value"}` object read from stdin
+///
+/// The counterpart of `export --format env-json`: every entry is written in one
+/// transaction, so a bad value or a protected key leaves the environment unchanged.
+fn cmd_set_json(
+    project_name: &str,
+    env_name: &str,
+    stringify: bool,
+    create: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let mut input = Zeroizing::new(String::new());
+    std::io::stdin().read_to_string(&mut input)
+        .map_err(|e| format!("Failed to read JSON from stdin: {}", e))?;
+    let entries = parse_json_entries(&input, stringify)?;
     
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let (created, updated, _) = import_entries(&db, &encryption_key, project_name, env_name, entries, true, create)?;
+    
+    println!("Wrote {}/{} from JSON:", project_name, env_name);
+    println!("   Created: {}", created);
+    println!("   Updated: {}", updated);
     Ok(())
 }
 
-/// Print only variable keys for machine-friendly output
-fn cmd_var_keys(
+/// Turn a JSON object into (key, value, description) entries
+///
+/// String values are taken as-is. Other values are refused unless `stringify`,
+/// which stores them as compact JSON text (`3000`, `true`, `["a"]`); `null` is
+/// always refused since it has no obvious string form.
+fn parse_json_entries(input: &str, stringify: bool) -> Result<Vec<(String, String, Option<String>)>, String> {
+    let object: serde_json::Map<String, serde_json::Value> = serde_json::from_str(input)
+        .map_err(|e| format!("Expected a JSON object of {{\"KEY\": \"value\"}} pairs: {}", e))?;
+    
+    object.into_iter()
+        .map(|(key, value)| {
+            let value = match value {
+                serde_json::Value::String(value) => value,
+                serde_json::Value::Null => return Err(format!("'{}' is null; leave the key out instead", key)),
+                other if stringify => other.to_string(),
+                _ => return Err(format!("'{}' is not a string; quote it or pass --stringify", key)),
+            };
+            Ok((key, value, None))
+        })
+        .collect()
+}
+
+/// Stage `value` as the pending value of an existing variable
+#[allow(clippy::too_many_arguments)]
+fn cmd_set_pending(
+    key: &str,
+    value: &str,
     project_name: &str,
     env_name: &str,
+    force: bool,
+    json: bool,
     vault_dir: Option<PathBuf>,
-) -> Result<(), String> {
-    let (db, encryption_key) = unlock_vault(vault_dir, true)?;
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, env_name)?;
+    check_environment_lock(&db, project_name, env_name, environment_id, force)?;
+    
+    let variable = operations::variables::get_variables_by_environment(db.connection(), environment_id)
+        .map_err(|e| format!("Failed to get variables: {}", e))?
+        .into_iter()
+        .find(|v| v.key == key)
+        .ok_or_else(|| CliError::NotFound(format!("Variable '{}' not found; set its current value first", key)))?;
+    let variable_id = variable.id.ok_or("Variable ID is missing")?;
+    
+    operations::variables::set_pending_value(db.connection(), variable_id, value, &encryption_key)
+        .map_err(|e| format!("Failed to stage value: {}", e))?;
+    
+    if json {
+        print_write_result("staged", "variable", Some(key), project_name, Some(env_name));
+        return Ok(());
+    }
+    println!("Staged a pending value for '{}'", key);
+    println!("   Reads still return the current value; run 'clerk promote {} -p {} -e {}' to switch", key, project_name, env_name);
+    Ok(())
+}
 
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+/// Make the pending value of a variable its current value
+fn cmd_promote(
+    key: &str,
+    project_name: &str,
+    env_name: &str,
+    force: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, env_name)?;
+    check_environment_lock(&db, project_name, env_name, environment_id, force)?;
+    
+    let variable = operations::variables::get_variables_by_environment(db.connection(), environment_id)
+        .map_err(|e| format!("Failed to get variables: {}", e))?
+        .into_iter()
+        .find(|v| v.key == key)
+        .ok_or_else(|| CliError::NotFound(format!("Variable '{}' not found", key)))?;
+    if variable.pending_value.is_none() {
+        return Err(format!("Variable '{}' has no pending value; stage one with 'clerk set {} <value> -p {} -e {} --slot pending'", key, key, project_name, env_name).into());
+    }
+    let variable_id = variable.id.ok_or("Variable ID is missing")?;
+    
+    operations::variables::promote_pending_value(db.connection(), variable_id, force, &encryption_key)
+        .map_err(protected_error)?;
+    
+    println!("Promoted the pending value of '{}' in {}/{}", key, project_name, env_name);
+    Ok(())
+}
 
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
+/// Report the project and environment a `--create` write had to create
+/// Print what a write command did as one JSON object, for `--json` instead of the confirmation text
+///
+/// `key` and `environment` are left out for entities that don't have them.
+fn print_write_result(action: &str, entity: &str, key: Option<&str>, project_name: &str, env_name: Option<&str>) {
+    let mut result = serde_json::json!({ "action": action, "entity": entity, "project": project_name });
+    if let Some(key) = key {
+        result["key"] = key.into();
+    }
+    if let Some(env_name) = env_name {
+        result["environment"] = env_name.into();
+    }
+    println!("{}", result);
+}
 
-    // Find environment
-    let environments = operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
-        .map_err(|e| format!("Failed to get environments: {}", e))?;
+fn print_created(project_name: &str, env_name: &str, created: operations::environments::Created) {
+    if created.project {
+        println!("Created project '{}'", project_name);
+    }
+    if created.environment {
+        println!("Created environment '{}' in project '{}'", env_name, project_name);
+    }
+}
 
-    let environment = environments.iter()
-        .find(|e| e.name == env_name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", env_name, project_name))?;
+fn format_expiry(expires_at: i64) -> String {
+    chrono::DateTime::from_timestamp(expires_at, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_else(|| expires_at.to_string())
+}
 
-    let variables = operations::variables::get_variables_by_environment_decrypted(
+fn warn_expired(key: &str, expires_at: i64, included: bool) {
+    if included {
+        eprintln!("Warning: '{}' expired {} (included because of --include-expired)", key, format_expiry(expires_at));
+    } else {
+        eprintln!("Warning: skipping '{}', expired {} (use --include-expired to read it)", key, format_expiry(expires_at));
+    }
+}
+
+/// Drop expired variables unless `include_expired`, warning about each one on stderr
+///
+/// Expired variables stay in the vault for the audit trail; reads treat them as
+/// inactive. `entry` returns a variable's key and expiry.
+fn skip_expired<T>(variables: &mut Vec<T>, include_expired: bool, entry: impl Fn(&T) -> (&str, Option<i64>)) {
+    let now = chrono::Utc::now().timestamp();
+    variables.retain(|var| match entry(var) {
+        (key, Some(expires_at)) if expires_at <= now => {
+            warn_expired(key, expires_at, include_expired);
+            include_expired
+        }
+        _ => true,
+    });
+}
+
+fn cmd_edit(
+    key: &str,
+    project_name: &str,
+    env_name: &str,
+    force: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
+    check_environment_lock(&db, project_name, env_name, environment.id.unwrap(), force)?;
+    
+    let variables = operations::variables::get_variables_by_environment_decrypted(
+        db.connection(),
+        environment.id.unwrap(),
+        &encryption_key,
+    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    
+    let variable = variables.iter()
+        .find(|v| v.key == key)
+        .ok_or_else(|| CliError::NotFound(format!("Variable '{}' not found", key)))?;
+    let original = variable.value.expose();
+    
+    let extension = if variable.value_type == ValueType::Json { "json" } else { "txt" };
+    let path = write_edit_file(original, extension)?;
+    let edited = run_editor(&path).and_then(|_| {
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read edited value: {}", e))
+    });
+    remove_edit_file(&path);
+    let mut edited = Zeroizing::new(edited?);
+    
+    // Editors usually append a newline on save; drop it unless the value had one
+    if !original.ends_with('\n') && edited.ends_with('\n') {
+        edited.pop();
+        if edited.ends_with('\r') {
+            edited.pop();
+        }
+    }
+    
+    if edited.as_str() == original {
+        println!("No changes to '{}'", key);
+        return Ok(());
+    }
+    
+    operations::variables::update_variable_encrypted(
+        db.connection(),
+        variable.id,
+        key.to_string(),
+        std::mem::take(&mut *edited),
+        variable.description.clone(),
+        force,
+        &encryption_key,
+    ).map_err(protected_error)?;
+    
+    println!("Updated variable '{}'", key);
+    Ok(())
+}
+
+/// Write a value to a fresh temp file only the current user can read
+fn write_edit_file(value: &str, extension: &str) -> Result<PathBuf, String> {
+    use std::io::Write;
+    
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let path = std::env::temp_dir().join(format!("clerk-edit-{}-{:x}.{}", process::id(), nanos, extension));
+    
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    
+    let mut file = options.open(&path)
+        .map_err(|e| format!("Failed to create temp file: {}", e))?;
+    if let Err(e) = file.write_all(value.as_bytes()).and_then(|_| file.sync_all()) {
+        drop(file);
+        remove_edit_file(&path);
+        return Err(format!("Failed to write temp file: {}", e));
+    }
+    
+    Ok(path)
+}
+
+/// Open a file in $VISUAL / $EDITOR and wait; a non-zero exit aborts the edit
+fn run_editor(path: &Path) -> Result<(), String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|e| !e.trim().is_empty())
+        .unwrap_or_else(|| if cfg!(windows) { "notepad".to_string() } else { "vi".to_string() });
+    
+    // Allow editors with arguments, e.g. EDITOR="code --wait"
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or("No editor configured")?;
+    
+    let status = process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("Failed to start editor '{}': {}", program, e))?;
+    
+    if !status.success() {
+        return Err(format!("Editor exited with {}; value left unchanged", status));
+    }
+    Ok(())
+}
+
+/// Overwrite a temp file with zeros before deleting it
+fn remove_edit_file(path: &Path) {
+    if let Ok(len) = fs::metadata(path).map(|m| m.len()) {
+        if let Ok(mut file) = fs::OpenOptions::new().write(true).open(path) {
+            use std::io::Write;
+            let _ = file.write_all(&vec![0u8; len as usize]).and_then(|_| file.sync_all());
+        }
+    }
+    let _ = fs::remove_file(path);
+}
+
+fn cmd_list(
+    project_filter: Option<&str>,
+    env_filter: Option<&str>,
+    show_values: bool,
+    classify: bool,
+    include_expired: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    // Get the filtered project, or all projects
+    let filtered_projects = match project_filter {
+        Some(filter) => operations::projects::get_project_by_name(db.connection(), filter)
+            .map(|project| project.into_iter().collect()),
+        None => operations::projects::get_all_projects(db.connection()),
+    }.map_err(|e| format!("Failed to get projects: {}", e))?;
+    
+        if filtered_projects.is_empty() {
+        if let Some(filter) = project_filter {
+            println!("No project found matching '{}'", filter);
+        } else {
+            println!("No projects found. Create one using the GUI or 'clerk init'");
+        }
+        return Ok(());
+    }
+    
+    for project in filtered_projects {
+    println!("\nProject: {}", project.name);
+        if let Some(desc) = &project.description {
+            println!("   Description: {}", desc);
+        }
+        
+        // Get the filtered environment, or all environments
+        let filtered_envs = match env_filter {
+            Some(filter) => operations::environments::get_environment_by_name(db.connection(), project.id.unwrap(), filter)
+                .map(|env| env.into_iter().collect()),
+            None => operations::environments::get_environments_by_project(db.connection(), project.id.unwrap()),
+        }.map_err(|e| format!("Failed to get environments: {}", e))?;
+        
+        for env in filtered_envs {
+            println!("   Environment: {}", env.name);
+            
+            // Get variables
+            let mut variables = operations::variables::get_variables_by_environment_decrypted(
+                db.connection(),
+                env.id.unwrap(),
+                &encryption_key,
+            ).map_err(|e| format!("Failed to get variables: {}", e))?;
+            
+            let now = chrono::Utc::now().timestamp();
+            let total = variables.len();
+            if !include_expired {
+                variables.retain(|var| !var.is_expired(now));
+            }
+            let hidden = total - variables.len();
+            
+            if variables.is_empty() && hidden == 0 {
+                println!("      (no variables)");
+            } else {
+                for var in variables {
+                    let mut tags = String::new();
+                    if classify {
+                        if let Some(kind) = crypto::classify::detect_secret_type(var.value.expose()) {
+                            tags.push_str(&format!("  [{}]", kind.tag()));
+                        }
+                    }
+                    if var.is_protected {
+                        tags.push_str("  [protected]");
+                    }
+                    if var.is_expired(now) {
+                        tags.push_str("  [expired]");
+                    }
+                    if var.has_pending {
+                        tags.push_str("  [pending]");
+                    }
+                    for tag in &var.tags {
+                        tags.push_str(&format!("  #{}", tag));
+                    }
+                    
+                    if show_values {
+                        println!("      {}={}{}", var.key, var.value.expose(), tags);
+                    } else {
+                        println!("      {}=********{}", var.key, tags);
+                    }
+                }
+                if hidden > 0 {
+                    println!("      ({} expired hidden, use --include-expired)", hidden);
+                }
+            }
+        }
+    }
+    
+    Ok(())
+}
+
+fn cmd_export(
+    project_name: &str,
+    env_name: &str,
+    output: Option<PathBuf>,
+    options: ExportOptions,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    if options.wrap.is_some() && options.format != ExportFormat::EnvJson {
+        return Err("--wrap is only supported with --format env-json".into());
+    }
+    
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
+    
+    Ok(export_environment(&db, &encryption_key, project_name, &environment, output, &options)?)
+}
+
+/// Print the checksum of one environment, or `checksum  env` for each environment with `all_envs`
+///
+/// Values are decrypted only to be hashed; nothing but the checksum is printed.
+fn cmd_export_checksum(
+    project_name: &str,
+    env_name: Option<&str>,
+    all_envs: bool,
+    fail_on_empty: bool,
+    include_expired: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let environments = match env_name {
+        Some(env_name) => vec![resolve_project_env(&db, project_name, env_name)?.1],
+        None => {
+            let project = resolve_project(&db, project_name)?;
+            operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
+                .map_err(|e| format!("Failed to get environments: {}", e))?
+        }
+    };
+    if environments.is_empty() {
+        return Err(format!("Project '{}' has no environments", project_name).into());
+    }
+    
+    for environment in &environments {
+        let mut variables = operations::variables::get_variables_by_environment_decrypted(
+            db.connection(),
+            environment.id.unwrap(),
+            &encryption_key,
+        ).map_err(|e| format!("Failed to get variables: {}", e))?;
+        skip_expired(&mut variables, include_expired, |v| (&v.key, v.expires_at));
+        
+        if variables.is_empty() && fail_on_empty {
+            return Err(format!("Environment '{}/{}' has no variables", project_name, environment.name).into());
+        }
+        
+        let pairs: Vec<(&str, &str)> = variables.iter().map(|v| (v.key.as_str(), v.value.expose())).collect();
+        let checksum = crypto::checksum::environment_checksum(&encryption_key, &pairs)?;
+        if all_envs {
+            println!("{}  {}", checksum, environment.name);
+        } else {
+            println!("{}", checksum);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Export every environment of a project, unlocking the vault once
+///
+/// Each environment goes to the path built from `output_template`, or to
+/// stdout one after another when no template is given.
+fn cmd_export_all_envs(
+    project_name: &str,
+    output_template: Option<&str>,
+    options: ExportOptions,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    if options.wrap.is_some() && options.format != ExportFormat::EnvJson {
+        return Err("--wrap is only supported with --format env-json".into());
+    }
+    match output_template {
+        Some(template) if !template.contains("{env}") => {
+            return Err("--output-template must contain {env}, or every environment would write the same file".into());
+        }
+        None if options.format == ExportFormat::EnvJson || options.include_metadata => {
+            return Err("--all-envs with --format env-json or --include-metadata needs --output-template; concatenated JSON objects are not valid JSON".into());
+        }
+        _ => {}
+    }
+    
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let project = resolve_project(&db, project_name)?;
+    let environments = operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
+        .map_err(|e| format!("Failed to get environments: {}", e))?;
+    
+    if environments.is_empty() {
+        return Err(format!("Project '{}' has no environments", project_name).into());
+    }
+    
+    for environment in &environments {
+        let output = output_template.map(|template| {
+            PathBuf::from(template.replace("{project}", project_name).replace("{env}", &environment.name))
+        });
+        export_environment(&db, &encryption_key, project_name, environment, output, &options)?;
+    }
+    
+    Ok(())
+}
+
+/// Export every environment of every project into `{output_dir}/{project}/{env}.env`
+fn cmd_export_tree(
+    output_dir: &Path,
+    show_values: bool,
+    include_expired: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let options = ExportOptions {
+        include_expired,
+        mask_values: !show_values,
+        ..ExportOptions::default()
+    };
+    
+    let projects = operations::projects::get_all_projects(db.connection())
+        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    
+    // Distinct names can sanitize to the same path; refuse rather than overwrite
+    let mut written: std::collections::HashMap<PathBuf, String> = std::collections::HashMap::new();
+    for project in &projects {
+        let environments = operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
+            .map_err(|e| format!("Failed to get environments: {}", e))?;
+        
+        let project_dir = output_dir.join(sanitize_path_component(&project.name));
+        for environment in &environments {
+            let path = project_dir.join(format!("{}.env", sanitize_path_component(&environment.name)));
+            let label = format!("{}/{}", project.name, environment.name);
+            if let Some(previous) = written.insert(path.clone(), label.clone()) {
+                return Err(format!("'{}' and '{}' would both be written to {}; rename one of them", previous, label, path.display()).into());
+            }
+            
+            fs::create_dir_all(&project_dir)
+                .map_err(|e| format!("Failed to create {}: {}", project_dir.display(), e))?;
+            export_environment(&db, &encryption_key, &project.name, environment, Some(path), &options)?;
+        }
+    }
+    
+    println!("Exported {} environment(s) from {} project(s) to {}", written.len(), projects.len(), output_dir.display());
+    Ok(())
+}
+
+/// Make a project or environment name safe to use as a single path component
+///
+/// Anything but ASCII letters, digits, `-`, `_` and `.` becomes `_`, and names
+/// made only of dots (`.`, `..`) are replaced so they can't escape the tree.
+fn sanitize_path_component(name: &str) -> String {
+    let sanitized: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') { c } else { '_' })
+        .collect();
+    
+    if sanitized.chars().all(|c| c == '.') {
+        sanitized.replace('.', "_")
+    } else {
+        sanitized
+    }
+}
+
+/// Render and write one environment's export
+fn export_environment(
+    db: &Database,
+    encryption_key: &[u8; 32],
+    project_name: &str,
+    environment: &Environment,
+    output: Option<PathBuf>,
+    options: &ExportOptions,
+) -> Result<(), String> {
+    let env_name = environment.name.as_str();
+    
+    if options.include_metadata {
+        let mut variables = operations::variables::get_variables_by_environment_decrypted(
+            db.connection(),
+            environment.id.unwrap(),
+            encryption_key,
+        ).map_err(|e| format!("Failed to get variables: {}", e))?;
+        skip_expired(&mut variables, options.include_expired, |v| (&v.key, v.expires_at));
+        sort_for_export(&mut variables, options.sort, |v| (v.key.as_str(), v.created_at, v.id));
+        check_export_not_empty(variables.len(), project_name, env_name, options.fail_on_empty)?;
+        
+        let records = EnvironmentRecords::new(project_name, env_name, variables.iter().map(VariableRecord::from).collect());
+        let mut content = serde_json::to_string_pretty(&records)
+            .map_err(|e| format!("Failed to serialize variables: {}", e))?;
+        content.push('\n');
+        return write_export(content, output);
+    }
+    
+    // (key, value, description) rows; template and masked exports never decrypt values
+    let rows: Vec<(String, String, Option<String>)> = if options.template_only || options.mask_values {
+        let placeholder = if options.template_only { "" } else { "********" };
+        let mut variables = operations::variables::get_variables_by_environment(
+            db.connection(),
+            environment.id.unwrap(),
+        ).map_err(|e| format!("Failed to get variables: {}", e))?;
+        skip_expired(&mut variables, options.include_expired, |v| (&v.key, v.expires_at));
+        sort_for_export(&mut variables, options.sort, |v| (v.key.as_str(), v.created_at, v.id.unwrap_or(0)));
+        variables.into_iter()
+            .map(|v| {
+                let description = operations::variables::decrypt_description(db.connection(), &v, encryption_key)
+                    .map_err(|e| format!("Failed to read description of '{}': {}", v.key, e))?;
+                Ok((v.key, placeholder.to_string(), description))
+            })
+            .collect::<Result<_, String>>()?
+    } else {
+        let mut variables = operations::variables::get_variables_by_environment_decrypted(
+            db.connection(),
+            environment.id.unwrap(),
+            encryption_key,
+        ).map_err(|e| format!("Failed to get variables: {}", e))?;
+        skip_expired(&mut variables, options.include_expired, |v| (&v.key, v.expires_at));
+        sort_for_export(&mut variables, options.sort, |v| (v.key.as_str(), v.created_at, v.id));
+        variables.into_iter().map(|v| (v.key, v.value.into_exposed(), v.description)).collect()
+    };
+    
+    check_export_not_empty(rows.len(), project_name, env_name, options.fail_on_empty)?;
+    
+    let content = match options.format {
+        ExportFormat::Env => render_env_export(project_name, env_name, &rows, options),
+        ExportFormat::EnvJson => render_env_json_export(&rows, options.wrap.as_deref())?,
+    };
+    
+    write_export(content, output)
+}
+
+/// An empty environment usually means a typo or a missed setup step; say so
+/// on stderr so it is not mistaken for a successful export
+fn check_export_not_empty(count: usize, project_name: &str, env_name: &str, fail_on_empty: bool) -> Result<(), String> {
+    if count == 0 {
+        if fail_on_empty {
+            return Err(format!("Environment '{}/{}' has no variables", project_name, env_name));
+        }
+        eprintln!("Warning: environment '{}/{}' has no variables", project_name, env_name);
+    }
+    Ok(())
+}
+
+/// Render rows as a dotenv file
+///
+/// The output depends only on the stored data (no timestamps), so unchanged
+/// variables always export byte-identically.
+fn render_env_export(
+    project_name: &str,
+    env_name: &str,
+    rows: &[(String, String, Option<String>)],
+    options: &ExportOptions,
+) -> String {
+    let mut content = String::new();
+    if options.header {
+        content.push_str("# Generated by Clerk CLI\n");
+        content.push_str(&format!("# Project: {}\n", project_name));
+        content.push_str(&format!("# Environment: {}\n", env_name));
+        if options.template_only {
+            content.push_str("# Template: fill in a value for each key\n");
+        }
+        content.push_str(&format!("# Total variables: {}\n\n", rows.len()));
+    }
+    
+    for (key, value, description) in rows {
+        if options.template_only {
+            // Descriptions become comments so the template documents each key
+            if let Some(description) = description.as_deref().filter(|d| !d.trim().is_empty()) {
+                for line in description.lines() {
+                    content.push_str(&format!("# {}\n", line));
+                }
+            }
+            content.push_str(&format!("{}=\n", key));
+            continue;
+        }
+        
+        let value = if value.contains(' ') || value.contains('"') {
+            format!("\"{}\"", value.replace('"', "\\\""))
+        } else {
+            value.clone()
+        };
+        content.push_str(&format!("{}={}\n", key, value));
+    }
+    
+    content
+}
+
+/// Render rows as a flat `{"KEY": "value"}` JSON object, optionally nested under `wrap`
+///
+/// Keys are written in row order, so `--sort` applies here too.
+fn render_env_json_export(rows: &[(String, String, Option<String>)], wrap: Option<&str>) -> Result<String, String> {
+    let quote = |s: &str| serde_json::to_string(s).map_err(|e| format!("Failed to serialize value: {}", e));
+    let indent = if wrap.is_some() { "    " } else { "  " };
+    
+    let mut body = String::from("{\n");
+    for (i, (key, value, _)) in rows.iter().enumerate() {
+        let separator = if i + 1 < rows.len() { "," } else { "" };
+        body.push_str(&format!("{}{}: {}{}\n", indent, quote(key)?, quote(value)?, separator));
+    }
+    
+    let content = match wrap {
+        Some(wrap_key) => {
+            body.push_str("  }");
+            format!("{{\n  {}: {}\n}}\n", quote(wrap_key)?, body)
+        }
+        None => {
+            body.push_str("}\n");
+            body
+        }
+    };
+    
+    Ok(content)
+}
+
+/// Write export content to a file, or to stdout if no path is given
+fn write_export(content: String, output: Option<PathBuf>) -> Result<(), String> {
+    if let Some(path) = output {
+        std::fs::write(&path, content)
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        println!("Exported to {}", path.display());
+    } else {
+        print!("{}", content);
+    }
+    
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn cmd_export_shared(
+    project_name: &str,
+    env_name: &str,
+    keys: &[String],
+    expires_in: &str,
+    include_expired: bool,
+    output: Option<PathBuf>,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let lifetime_secs = parse_duration_secs(expires_in)?;
+    
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
+    
+    // Get variables
+    let mut variables = operations::variables::get_variables_by_environment_decrypted(
+        db.connection(),
+        environment.id.unwrap(),
+        &encryption_key,
+    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    skip_expired(&mut variables, include_expired, |v| (&v.key, v.expires_at));
+    
+    // Restrict to the selected keys, if any were given
+    if let Some(missing) = keys.iter().find(|k| !variables.iter().any(|v| &v.key == *k)) {
+        return Err(CliError::NotFound(format!("Variable '{}' not found", missing)));
+    }
+    
+    let selected: Vec<crypto::share::SharedVariable> = variables
+        .into_iter()
+        .filter(|v| keys.is_empty() || keys.contains(&v.key))
+        .map(|v| crypto::share::SharedVariable {
+            key: v.key,
+            value: v.value.into_exposed(),
+            description: v.description,
+        })
+        .collect();
+    
+    // A bundle with nothing in it is never what the sender meant
+    if selected.is_empty() {
+        return Err(format!("Environment '{}/{}' has no variables to share", project_name, env_name).into());
+    }
+    
+    // Prompt on stderr so a bundle printed to stdout can be redirected on its own
+    eprintln!("Enter share passphrase:");
+    let passphrase = Zeroizing::new(rpassword::read_password()
+        .map_err(|e| format!("Failed to read passphrase: {}", e))?);
+    
+    if passphrase.len() < 8 {
+        return Err("Share passphrase must be at least 8 characters long".into());
+    }
+    
+    eprintln!("Confirm share passphrase:");
+    let confirmation = Zeroizing::new(rpassword::read_password()
+        .map_err(|e| format!("Failed to read passphrase: {}", e))?);
+    
+    if *passphrase != *confirmation {
+        return Err("Passphrases do not match".into());
+    }
+    
+    let now = chrono::Utc::now().timestamp();
+    let payload = crypto::share::SharedPayload {
+        created_at: now,
+        expires_at: now + lifetime_secs,
+        project: project_name.to_string(),
+        environment: env_name.to_string(),
+        variables: selected,
+    };
+    
+    let bundle = crypto::share::seal_bundle(&payload, &passphrase)?;
+    let expires = chrono::DateTime::from_timestamp(payload.expires_at, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_default();
+    
+    // Output to file or stdout
+    if let Some(path) = output {
+        std::fs::write(&path, bundle)
+            .map_err(|e| format!("Failed to write file: {}", e))?;
+        println!("Shared {} variables to {} (expires {})", payload.variables.len(), path.display(), expires);
+    } else {
+        println!("{}", bundle);
+        eprintln!("Bundle expires {}", expires);
+    }
+    
+    Ok(())
+}
+
+/// Print only variable keys for machine-friendly output
+fn cmd_var_keys(
+    project_name: &str,
+    env_name: &str,
+    vault_dir: Option<PathBuf>,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, true)?;
+
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
+
+    let variables = operations::variables::get_variables_by_environment_decrypted(
         db.connection(),
         environment.id.unwrap(),
         &encryption_key,
@@ -1101,141 +4006,785 @@ fn cmd_var_keys(
     for v in variables {
         println!("{}", v.key);
     }
-
+
+    Ok(())
+}
+
+/// Bulk set simply delegates to import to reuse the parsing/encryption logic
+fn cmd_var_bulk_set(
+    file: &PathBuf,
+    project_name: &str,
+    env_name: &str,
+    overwrite: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    cmd_import(file, project_name, env_name, ImportOptions { overwrite, ..ImportOptions::default() }, vault_dir, use_session)
+}
+
+fn cmd_init(project_name: &str, description: Option<&str>, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    // Check if project already exists
+    let existing = operations::projects::get_project_by_name(db.connection(), project_name)
+        .map_err(|e| format!("Failed to get project: {}", e))?;
+    
+    if existing.is_some() {
+        return Err(CliError::AlreadyExists(format!("Project '{}' already exists", project_name)));
+    }
+    
+    // Create project
+    let project = operations::Project {
+        id: None,
+        name: project_name.to_string(),
+        description: description.map(String::from),
+        created_at: chrono::Utc::now().timestamp(),
+        updated_at: chrono::Utc::now().timestamp(),
+    };
+    
+    operations::projects::create_project(db.connection(), &project)
+        .map_err(|e| format!("Failed to create project: {}", e))?;
+    
+    println!("Created project '{}'", project_name);
+    println!("Next steps:");
+    println!("   1. Create an environment (using GUI or add to this CLI)");
+    println!("   2. Add variables with: clerk set KEY VALUE -p {} -e ENV_NAME", project_name);
+    
+    Ok(())
+}
+
+/// Quote a value for POSIX shells: wrap in single quotes, escaping embedded ones
+///
+/// Nothing is expanded inside single quotes, so `$`, backticks and newlines are safe.
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// True if `key` can be used as a shell variable name
+fn is_shell_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Print an environment as `export` lines meant to be `eval`'d by the current shell
+///
+/// Only export lines go to stdout. Keys that are not valid shell names are
+/// skipped with a warning on stderr rather than emitted unquoted.
+fn cmd_direnv(project_name: &str, env_name: &str, include_expired: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, env_name)?;
+    
+    let mut variables = operations::variables::get_variables_by_environment_decrypted(
+        db.connection(),
+        environment_id,
+        &encryption_key,
+    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    skip_expired(&mut variables, include_expired, |v| (&v.key, v.expires_at));
+    
+    let mut out = String::new();
+    for var in &variables {
+        if !is_shell_identifier(&var.key) {
+            eprintln!("Warning: skipping '{}', not a valid shell variable name", var.key);
+            continue;
+        }
+        out.push_str(&format!("export {}={}\n", var.key, shell_quote(var.value.expose())));
+    }
+    
+    print!("{}", out);
+    out.zeroize();
+    Ok(())
+}
+
+/// The .envrc lines that wire direnv up to `clerk direnv`
+fn direnv_hook_snippet(project: Option<&str>, env: Option<&str>) -> String {
+    let project = project.map(shell_quote).unwrap_or_else(|| "my-app".to_string());
+    let env = env.map(shell_quote).unwrap_or_else(|| "dev".to_string());
+    
+    format!(
+        "# Load variables from Clerk (needs an unlocked session: run `clerk unlock` first)\n\
+         eval \"$(clerk direnv -p {} -e {})\"\n",
+        project, env
+    )
+}
+
+/// Subcommand paths taking a positional `key` / `keys` argument, e.g. `get`, `var get`, `tag add`
+///
+/// Aliases are included, so `g` completes keys like `get` does.
+fn key_command_paths() -> Vec<String> {
+    fn names(command: &clap::Command) -> Vec<String> {
+        std::iter::once(command.get_name()).chain(command.get_all_aliases()).map(String::from).collect()
+    }
+    let takes_key = |command: &clap::Command| command.get_arguments()
+        .any(|arg| arg.is_positional() && matches!(arg.get_id().as_str(), "key" | "keys"));
+    
+    let mut paths = Vec::new();
+    for command in Cli::command().get_subcommands() {
+        if takes_key(command) {
+            paths.extend(names(command));
+        }
+        for nested in command.get_subcommands().filter(|nested| takes_key(nested)) {
+            for group in names(command) {
+                paths.extend(names(nested).into_iter().map(|name| format!("{} {}", group, name)));
+            }
+        }
+    }
+    paths
+}
+
+/// Shell completion script: subcommand names, plus keys from `clerk __complete-keys`
+/// once `-p` and `-e` are on the line of a command that takes a key
+fn completion_script(shell: CompletionShell) -> String {
+    let cli = Cli::command();
+    let subcommands: Vec<&str> = cli.get_subcommands()
+        .filter(|command| !command.is_hide_set())
+        .flat_map(|command| std::iter::once(command.get_name()).chain(command.get_visible_aliases()))
+        .collect();
+    let key_paths = key_command_paths();
+    
+    match shell {
+        CompletionShell::Bash | CompletionShell::Zsh => {
+            let groups: Vec<&str> = cli.get_subcommands()
+                .filter(|command| command.has_subcommands())
+                .flat_map(|command| std::iter::once(command.get_name()).chain(command.get_all_aliases()))
+                .collect();
+            let script = BASH_COMPLETION
+                .replace("@SUBCOMMANDS@", &subcommands.join(" "))
+                .replace("@GROUPS@", &groups.join(" "))
+                .replace("@KEY_COMMANDS@", &key_paths.join("|"));
+            if matches!(shell, CompletionShell::Zsh) {
+                format!("autoload -U +X bashcompinit && bashcompinit\n{}", script)
+            } else {
+                script
+            }
+        }
+        CompletionShell::Fish => {
+            // fish only checks whether any of these words was typed, so nested paths use their last word
+            let mut key_words: Vec<&str> = key_paths.iter().filter_map(|path| path.split(' ').next_back()).collect();
+            key_words.sort_unstable();
+            key_words.dedup();
+            FISH_COMPLETION
+                .replace("@SUBCOMMANDS@", &subcommands.join(" "))
+                .replace("@KEY_COMMANDS@", &key_words.join(" "))
+        }
+    }
+}
+
+const BASH_COMPLETION: &str = r#"_clerk() {
+    local cur="${COMP_WORDS[COMP_CWORD]}" project="" env="" vault="" words=() i
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            -p|--project) project="${COMP_WORDS[i+1]}"; ((i++)) ;;
+            -e|--env) env="${COMP_WORDS[i+1]}"; ((i++)) ;;
+            -D|--vault-dir|-V) vault="${COMP_WORDS[i+1]}"; ((i++)) ;;
+            --password-file|--password-fd) ((i++)) ;;
+            -*) ;;
+            *) words+=("${COMP_WORDS[i]}") ;;
+        esac
+    done
+
+    if [[ ${#words[@]} -eq 0 ]]; then
+        COMPREPLY=($(compgen -W "@SUBCOMMANDS@" -- "$cur"))
+        return
+    fi
+
+    local path="${words[0]}"
+    case " @GROUPS@ " in *" $path "*) path="$path ${words[1]}" ;; esac
+    case "|@KEY_COMMANDS@|" in
+        *"|$path|"*)
+            if [[ -n $project && -n $env && $cur != -* ]]; then
+                COMPREPLY=($(clerk ${vault:+-D "$vault"} __complete-keys -p "$project" -e "$env" "$cur" 2>/dev/null))
+            fi
+            ;;
+    esac
+}
+complete -o default -F _clerk clerk
+"#;
+
+const FISH_COMPLETION: &str = r#"function __clerk_keys
+    set -l tokens (commandline -opc)
+    set -l project
+    set -l env
+    set -l vault
+    for i in (seq (count $tokens))
+        set -l next (math $i + 1)
+        switch $tokens[$i]
+            case -p --project
+                set project $tokens[$next]
+            case -e --env
+                set env $tokens[$next]
+            case -D --vault-dir -V
+                set vault -D $tokens[$next]
+        end
+    end
+    if test -n "$project" -a -n "$env"
+        clerk $vault __complete-keys -p $project -e $env (commandline -ct) 2>/dev/null
+    end
+end
+
+complete -c clerk -f -n __fish_use_subcommand -a "@SUBCOMMANDS@"
+complete -c clerk -f -n "__fish_seen_subcommand_from @KEY_COMMANDS@" -a "(__clerk_keys)"
+"#;
+
+/// Print keys of an environment for shell completion
+///
+/// Only works while a session is cached: completion never prompts for the
+/// password, and a locked vault shows nothing. Only key names are read.
+fn cmd_complete_keys(project_name: &str, env_name: &str, prefix: &str, vault_dir: Option<PathBuf>) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir)?;
+    let metadata = vault_manager(&vault_path).metadata()?;
+    
+    let key = match load_session_key(&vault_path) {
+        Some((key, stored_hash)) if stored_hash == metadata.password_hash => key,
+        _ => return Ok(()),
+    };
+    
+    let db = open_database_file(&vault_path, &key)
+        .map_err(|e| format!("Failed to open database: {}", e))?;
+    let environment_id = find_environment_id(&db, project_name, env_name)?;
+    let keys = operations::variables::suggest_keys(db.connection(), environment_id, prefix, false, 1000)
+        .map_err(|e| format!("Failed to get keys: {}", e))?;
+    
+    for key in keys {
+        println!("{}", key);
+    }
+    Ok(())
+}
+
+/// Run a command with the environment's variables injected
+///
+/// When `print_env` is `Some(show_values)`, the variables that would be injected
+/// are printed (masked unless `show_values`) and no process is spawned.
+#[allow(clippy::too_many_arguments)]
+fn cmd_run(
+    project_name: &str,
+    env_name: &str,
+    command: &[String],
+    print_env: Option<bool>,
+    include_expired: bool,
+    output: &RunOutput,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    use std::process::Command;
+    use std::collections::HashMap;
+    
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
+    
+    // Get variables (encrypted)
+    let mut variables = operations::variables::get_variables_by_environment(
+        db.connection(),
+        environment.id.unwrap(),
+    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    skip_expired(&mut variables, include_expired, |v| (&v.key, v.expires_at));
+    
+    // Decrypt the variables to inject
+    let mut injected: Vec<(String, String)> = Vec::with_capacity(variables.len());
+    for var in variables {
+        // Decrypt with the AAD format the value was stored with
+        let value = operations::variables::decrypt_variable(db.connection(), &var, &encryption_key)
+            .map_err(|e| format!("Failed to decrypt variable '{}': {}", var.key, e))?;
+        
+        injected.push((var.key, value.into_exposed()));
+    }
+    
+    if let Some(show_values) = print_env {
+        println!("{} variables would be injected from {}/{}:", injected.len(), project_name, env_name);
+        for (key, value) in &injected {
+            if show_values {
+                println!("  {}={}", key, value);
+            } else {
+                println!("  {}=********", key);
+            }
+        }
+        return Ok(());
+    }
+    
+    // Build environment variable map
+    let mut env_vars: HashMap<String, String> = std::env::vars().collect();
+    
+    // Everything below goes to stderr so the child's stdout stays clean
+    let banner = output.banner(injected.len(), project_name, env_name);
+    if let Some(banner) = &banner {
+        eprintln!("{}", banner);
+    }
+    if output.list_injected {
+        for (key, _) in &injected {
+            eprintln!("  {}", key);
+        }
+    }
+    env_vars.extend(injected);
+    
+    // Parse command
+    let program = &command[0];
+    let args = &command[1..];
+    
+    if banner.is_some() {
+        eprintln!("Running: {} {}", program, args.join(" "));
+        eprintln!("--------------------------------------------------");
+    }
+    
+    // Run command with injected environment variables
+    let mut child = Command::new(program)
+        .args(args)
+        .envs(&env_vars)
+        .spawn()
+        .map_err(|e| format!("Failed to run command: {}", e))?;
+    
+    // Wait for command to complete
+    let status = child.wait()
+        .map_err(|e| format!("Failed to wait for command: {}", e))?;
+    
+    if banner.is_some() {
+        eprintln!("--------------------------------------------------");
+    }
+    
+    if status.success() {
+        if banner.is_some() {
+            eprintln!("Command completed successfully");
+        }
+        Ok(())
+    } else {
+        let code = status.code().unwrap_or(-1);
+        Err(format!("Command failed with exit code {}", code).into())
+    }
+}
+
+/// Command line that runs `script` with `args` (`clerk run --script`)
+///
+/// The script must be an executable file; it runs directly so its shebang
+/// picks the interpreter.
+#[cfg(unix)]
+fn script_command(script: &Path, args: &[String]) -> Result<Vec<String>, String> {
+    use std::os::unix::fs::PermissionsExt;
+    
+    let metadata = fs::metadata(script)
+        .map_err(|e| format!("Cannot read script '{}': {}", script.display(), e))?;
+    if !metadata.is_file() {
+        return Err(format!("Script '{}' is not a file", script.display()));
+    }
+    if metadata.permissions().mode() & 0o111 == 0 {
+        return Err(format!("Script '{}' is not executable (run: chmod +x {})", script.display(), script.display()));
+    }
+    
+    // A bare file name would be looked up on PATH instead of in the current directory
+    let program = if script.components().count() == 1 {
+        Path::new(".").join(script)
+    } else {
+        script.to_path_buf()
+    };
+    
+    let mut command = vec![program.to_string_lossy().into_owned()];
+    command.extend_from_slice(args);
+    Ok(command)
+}
+
+/// Command line that runs `script` with `args` (`clerk run --script`)
+///
+/// Windows picks the interpreter from the extension: `.bat`/`.cmd` go through
+/// `cmd /C`, `.ps1` through PowerShell, and `.exe`/`.com` run directly.
+#[cfg(not(unix))]
+fn script_command(script: &Path, args: &[String]) -> Result<Vec<String>, String> {
+    let metadata = fs::metadata(script)
+        .map_err(|e| format!("Cannot read script '{}': {}", script.display(), e))?;
+    if !metadata.is_file() {
+        return Err(format!("Script '{}' is not a file", script.display()));
+    }
+    
+    let path = script.to_string_lossy().into_owned();
+    let extension = script.extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    let mut command: Vec<String> = match extension.as_str() {
+        "bat" | "cmd" => vec!["cmd".into(), "/C".into(), path],
+        "ps1" => vec!["powershell".into(), "-NoProfile".into(), "-ExecutionPolicy".into(), "Bypass".into(), "-File".into(), path],
+        "exe" | "com" => vec![path],
+        _ => return Err(format!(
+            "Don't know how to run '{}'. Use a .bat, .cmd, .ps1 or .exe script, or pass the interpreter as the command",
+            script.display()
+        )),
+    };
+    command.extend_from_slice(args);
+    Ok(command)
+}
+
+fn print_audit_entry(entry: &operations::audit::AuditLogEntry) {
+    let timestamp = chrono::DateTime::from_timestamp(entry.timestamp, 0)
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_else(|| entry.timestamp.to_string());
+    
+    let mut line = format!("{}  {:<8} {:<12} {}", timestamp, entry.operation_type, entry.entity_type, entry.entity_name.as_deref().unwrap_or("-"));
+    if let Some(details) = &entry.details {
+        line.push_str("  ");
+        line.push_str(details);
+    }
+    println!("{}", line);
+}
+
+fn cmd_audit_export(
+    format: AuditExportFormat,
+    file: Option<&Path>,
+    mut filter: operations::audit::AuditLogFilter,
+    since: Option<&str>,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    filter.start_date = since.map(|s| parse_since(s, chrono::Utc::now())).transpose()?;
+    let (db, _key) = unlock_vault(vault_dir, use_session)?;
+    
+    let logs = operations::audit::export_audit_logs(db.connection(), Some(&filter))?;
+    let content = match format {
+        AuditExportFormat::Csv => operations::audit::audit_logs_to_csv(&logs),
+        AuditExportFormat::Json => serde_json::to_string_pretty(&logs)
+            .map_err(|e| format!("Failed to serialize audit log: {}", e))? + "\n",
+    };
+    
+    match file {
+        Some(path) => {
+            fs::write(path, content)
+                .map_err(|e| format!("Failed to write file: {}", e))?;
+            // stderr, so the count never mixes with exported data on stdout
+            eprintln!("Exported {} audit log entries to {}", logs.len(), path.display());
+        }
+        None => print!("{}", content),
+    }
+    
+    Ok(())
+}
+
+/// Wipe the audit log after `--confirm` and a fresh password check
+fn cmd_audit_clear(confirm: bool, vault_dir: Option<PathBuf>) -> Result<(), CliError> {
+    if !confirm {
+        return Err("Refusing to clear the audit log without --confirm".into());
+    }
+    
+    // A cached session is not enough to erase the history
+    let (db, _key) = unlock_vault(vault_dir, false)?;
+    
+    let removed = operations::audit::clear_audit_logs(db.connection())?;
+    println!("Cleared {} audit entries (an 'audit_cleared' entry records this)", removed);
+    Ok(())
+}
+
+fn cmd_audit_set_enabled(enabled: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _key) = unlock_vault(vault_dir, use_session)?;
+    
+    let current = migrations::get_audit_enabled(db.connection())
+        .map_err(|e| format!("Failed to read audit setting: {}", e))?;
+    if current == enabled {
+        println!("Audit logging is already {}", if enabled { "enabled" } else { "disabled" });
+        return Ok(());
+    }
+    
+    operations::audit::set_audit_enabled(db.connection(), enabled)?;
+    if enabled {
+        println!("Audit logging enabled");
+    } else {
+        println!("Audit logging disabled; operations are no longer recorded (existing entries are kept)");
+    }
+    Ok(())
+}
+
+fn cmd_audit_tail(
+    lines: i64,
+    follow: bool,
+    interval_ms: u64,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, _key) = unlock_vault(vault_dir, use_session)?;
+    
+    let filter = operations::audit::AuditLogFilter {
+        limit: Some(lines),
+        ..Default::default()
+    };
+    let mut recent = operations::audit::query_audit_logs(db.connection(), Some(&filter))?;
+    recent.sort_by_key(|e| e.id);
+    
+    // Entries are printed oldest first; the cursor is the highest id seen so far
+    let mut cursor = recent.last().map(|e| e.id).unwrap_or(0);
+    if lines > 0 {
+        recent.iter().for_each(print_audit_entry);
+    } else {
+        cursor = db.connection()
+            .query_row("SELECT COALESCE(MAX(id), 0) FROM audit_log", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read audit log: {}", e))?;
+    }
+    
+    if !follow {
+        return Ok(());
+    }
+    
+    let interval = std::time::Duration::from_millis(interval_ms.max(100));
+    loop {
+        std::thread::sleep(interval);
+        
+        let filter = operations::audit::AuditLogFilter {
+            after_id: Some(cursor),
+            ..Default::default()
+        };
+        let mut new_entries = operations::audit::query_audit_logs(db.connection(), Some(&filter))?;
+        new_entries.sort_by_key(|e| e.id);
+        
+        for entry in &new_entries {
+            print_audit_entry(entry);
+            cursor = entry.id;
+        }
+    }
+}
+
+fn cmd_audit_coverage(project_name: &str, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let project = resolve_project(&db, project_name)?;
+    let project_id = project.id.ok_or("Project ID is missing")?;
+    
+    let coverage = operations::coverage::key_coverage(db.connection(), project_id)
+        .map_err(|e| format!("Failed to build key coverage: {}", e))?;
+    
+    if json {
+        let out = serde_json::to_string_pretty(&coverage)
+            .map_err(|e| format!("Failed to serialize coverage: {}", e))?;
+        println!("{}", out);
+        return Ok(());
+    }
+    
+    if coverage.keys.is_empty() {
+        println!("No variables found in project '{}'", project_name);
+        return Ok(());
+    }
+    
+    let key_width = coverage.keys.iter().map(|row| row.key.len()).max().unwrap_or(0).max("KEY".len());
+    let widths: Vec<usize> = coverage.environments.iter().map(|name| name.len().max("MISSING".len())).collect();
+    
+    let mut header = format!("{:<width$}", "KEY", width = key_width);
+    for (name, width) in coverage.environments.iter().zip(&widths) {
+        header.push_str(&format!("  {:<width$}", name, width = *width));
+    }
+    println!("{}", header.trim_end());
+    
+    for row in &coverage.keys {
+        let mut line = format!("{:<width$}", row.key, width = key_width);
+        for (present, width) in row.present.iter().zip(&widths) {
+            let cell = if *present { "yes" } else { "MISSING" };
+            line.push_str(&format!("  {:<width$}", cell, width = *width));
+        }
+        println!("{}", line.trim_end());
+    }
+    
+    let gap_count = coverage.gaps().count();
+    println!();
+    if gap_count == 0 {
+        println!("All {} keys are defined in every environment", coverage.keys.len());
+    } else {
+        println!("{} of {} keys are missing from at least one environment", gap_count, coverage.keys.len());
+    }
+    
     Ok(())
 }
 
-/// Bulk set simply delegates to import to reuse the parsing/encryption logic
-fn cmd_var_bulk_set(
-    file: &PathBuf,
-    project_name: &str,
-    env_name: &str,
-    overwrite: bool,
-    vault_dir: Option<PathBuf>,
-    use_session: bool,
-) -> Result<(), String> {
-    cmd_import(file, project_name, env_name, overwrite, vault_dir, use_session)
-}
-
-fn cmd_init(project_name: &str, description: Option<&str>, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+/// Report variables whose expiry has passed, across the vault or one project
+fn cmd_audit_expired(project_filter: Option<&str>, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
-    // Check if project already exists
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
-    
-    if projects.iter().any(|p| p.name == project_name) {
-        return Err(format!("Project '{}' already exists", project_name));
+    let mut expired = operations::variables::get_expired_variables(db.connection(), chrono::Utc::now().timestamp())
+        .map_err(|e| format!("Failed to get expired variables: {}", e))?;
+    if let Some(project) = project_filter {
+        expired.retain(|v| v.project == project);
     }
     
-    // Create project
-    let project = operations::Project {
-        id: None,
-        name: project_name.to_string(),
-        description: description.map(String::from),
-        created_at: chrono::Utc::now().timestamp(),
-        updated_at: chrono::Utc::now().timestamp(),
-    };
+    if json {
+        let out = serde_json::to_string_pretty(&expired)
+            .map_err(|e| format!("Failed to serialize report: {}", e))?;
+        println!("{}", out);
+        return Ok(());
+    }
     
-    operations::projects::create_project(db.connection(), &project)
-        .map_err(|e| format!("Failed to create project: {}", e))?;
+    if expired.is_empty() {
+        println!("No expired variables");
+        return Ok(());
+    }
     
-    println!("Created project '{}'", project_name);
-    println!("Next steps:");
-    println!("   1. Create an environment (using GUI or add to this CLI)");
-    println!("   2. Add variables with: clerk set KEY VALUE -p {} -e ENV_NAME", project_name);
+    println!("{} expired variables (inactive, not deleted):", expired.len());
+    for var in &expired {
+        println!("  {}/{}  {}  expired {}", var.project, var.environment, var.key, format_expiry(var.expires_at));
+    }
+    println!();
+    println!("Set a new value with --expires-in, clear it with --no-expiry, or delete it");
     
     Ok(())
 }
 
-fn cmd_run(project_name: &str, env_name: &str, command: &[String], vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
-    use std::process::Command;
-    use std::collections::HashMap;
+/// Report variables created or updated since `since`, most recent first
+fn cmd_changes(since: &str, project_filter: Option<&str>, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let since = parse_since(since, chrono::Utc::now())?;
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
-    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let mut changed = operations::variables::get_variables_modified_since(db.connection(), since)
+        .map_err(|e| format!("Failed to get changed variables: {}", e))?;
+    if let Some(project) = project_filter {
+        changed.retain(|v| v.project == project);
+    }
     
-    // Get project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    if json {
+        let out = serde_json::to_string_pretty(&changed)
+            .map_err(|e| format!("Failed to serialize report: {}", e))?;
+        println!("{}", out);
+        return Ok(());
+    }
     
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
+    if changed.is_empty() {
+        println!("No variables changed since {}", format_expiry(since));
+        return Ok(());
+    }
     
-    // Get environment
-    let environments = operations::environments::get_environments_by_project(
-        db.connection(),
-        project.id.unwrap(),
-    ).map_err(|e| format!("Failed to get environments: {}", e))?;
+    println!("{} variables changed since {}:", changed.len(), format_expiry(since));
+    for var in &changed {
+        let change = if var.is_new(since) { "added  " } else { "updated" };
+        let changed_at = var.updated_at.max(var.created_at);
+        println!("  {}  {}  {}/{}  {}", format_expiry(changed_at), change, var.project, var.environment, var.key);
+    }
     
-    let environment = environments.iter()
-        .find(|e| e.name == env_name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", env_name, project_name))?;
+    Ok(())
+}
+
+/// Poll an environment's change markers and run `exec` after each settled burst of changes
+///
+/// The changed keys (added, updated or deleted) are passed in
+/// `CLERK_CHANGED_KEYS`, comma-separated; values are never put in the hook's
+/// environment. A failing hook is reported and watching continues.
+fn cmd_watch(
+    project_name: &str,
+    env_name: &str,
+    exec: &str,
+    interval_ms: u64,
+    debounce_ms: u64,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    use std::collections::BTreeSet;
+    use std::time::{Duration, Instant};
     
-    // Get variables (encrypted)
-    let variables = operations::variables::get_variables_by_environment(
-        db.connection(),
-        environment.id.unwrap(),
-    ).map_err(|e| format!("Failed to get variables: {}", e))?;
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
+    let environment_id = environment.id.ok_or("Environment ID is missing")?;
     
-    // Build environment variable map
-    let mut env_vars: HashMap<String, String> = std::env::vars().collect();
+    let read_markers = || operations::variables::environment_change_markers(db.connection(), environment_id)
+        .map_err(|e| format!("Failed to read variables: {}", e));
     
-    println!("Injecting {} variables into process...", variables.len());
-    for var in variables {
-        // Create AAD (Additional Authenticated Data) matching the format used during encryption
-        let aad = format!("env:{};key:{}", var.environment_id, var.key);
-        
-        // Decrypt the value
-        let decrypted = crypto::encryption::decrypt(
-            &encryption_key,
-            &var.encrypted_value,
-            aad.as_bytes(),
-        ).map_err(|e| format!("Failed to decrypt variable '{}': {:?}", var.key, e))?;
+    // The cursor is the last state seen; a change only runs the hook once it has settled
+    let mut cursor = read_markers()?;
+    let mut changed: BTreeSet<String> = BTreeSet::new();
+    let mut last_change: Option<Instant> = None;
+    
+    eprintln!("Watching {}/{} ({} variables); press Ctrl+C to stop", project_name, env_name, cursor.len());
+    let interval = Duration::from_millis(interval_ms.max(100));
+    let debounce = Duration::from_millis(debounce_ms);
+    loop {
+        std::thread::sleep(interval);
         
-        let value = String::from_utf8(decrypted.to_vec())
-            .map_err(|e| format!("Invalid UTF-8 in variable '{}': {}", var.key, e))?;
+        let markers = read_markers()?;
+        let keys = operations::variables::changed_keys(&cursor, &markers);
+        if !keys.is_empty() {
+            changed.extend(keys);
+            last_change = Some(Instant::now());
+            cursor = markers;
+        }
         
-        env_vars.insert(var.key.clone(), value);
+        if last_change.is_some_and(|at| at.elapsed() >= debounce) {
+            let keys: Vec<String> = std::mem::take(&mut changed).into_iter().collect();
+            last_change = None;
+            run_watch_hook(exec, project_name, env_name, &keys);
+        }
+    }
+}
+
+/// Run a `clerk watch` hook through the shell, reporting its outcome on stderr
+fn run_watch_hook(exec: &str, project_name: &str, env_name: &str, keys: &[String]) {
+    eprintln!("{} variable(s) changed: {}", keys.len(), keys.join(", "));
+    
+    let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+    let status = process::Command::new(shell)
+        .args([flag, exec])
+        .env("CLERK_CHANGED_KEYS", keys.join(","))
+        .env("CLERK_PROJECT", project_name)
+        .env("CLERK_ENV", env_name)
+        .status();
+    match status {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!("Warning: '{}' exited with {}", exec, status),
+        Err(e) => eprintln!("Warning: failed to run '{}': {}", exec, e),
     }
+}
+
+fn cmd_audit_archive(
+    output: &Path,
+    older_than: Option<&str>,
+    prune: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, _key) = unlock_vault(vault_dir, use_session)?;
     
-    // Parse command
-    let program = &command[0];
-    let args = &command[1..];
+    let before = older_than
+        .map(|d| parse_duration_secs(d).map(|secs| chrono::Utc::now().timestamp() - secs))
+        .transpose()?;
     
-    println!("Running: {} {}", program, args.join(" "));
-    println!("--------------------------------------------------");
+    let archive = operations::audit::archive_audit_logs(db.connection(), before)?;
+    if archive.entries.is_empty() {
+        println!("No audit entries to archive");
+        return Ok(());
+    }
     
-    // Run command with injected environment variables
-    let mut child = Command::new(program)
-        .args(args)
-        .envs(&env_vars)
-        .spawn()
-        .map_err(|e| format!("Failed to run command: {}", e))?;
+    let content = serde_json::to_string_pretty(&archive)
+        .map_err(|e| format!("Failed to serialize audit archive: {}", e))?;
+    std::fs::write(output, content)
+        .map_err(|e| format!("Failed to write file: {}", e))?;
+    println!("Archived {} audit entries to {}", archive.entries.len(), output.display());
     
-    // Wait for command to complete
-    let status = child.wait()
-        .map_err(|e| format!("Failed to wait for command: {}", e))?;
+    // Only prune once the archive is safely on disk
+    if prune {
+        let removed = operations::audit::prune_archived_logs(db.connection(), &archive)?;
+        println!("Removed {} entries from the vault", removed);
+    }
     
-    println!("--------------------------------------------------");
+    Ok(())
+}
+
+fn cmd_audit_import(file: &Path, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _key) = unlock_vault(vault_dir, use_session)?;
     
-    if status.success() {
-        println!("Command completed successfully");
-        Ok(())
-    } else {
-        let code = status.code().unwrap_or(-1);
-        Err(format!("Command failed with exit code {}", code))
-    }
+    let content = std::fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read file: {}", e))?;
+    let archive: operations::audit::AuditArchive = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid audit archive: {}", e))?;
+    
+    let report = operations::audit::import_audit_archive(db.connection(), &archive)?;
+    println!("Imported {} audit entries ({} already present)", report.imported, report.skipped);
+    
+    Ok(())
 }
 
-fn cmd_lock(vault_dir: Option<PathBuf>) -> Result<(), String> {
+fn cmd_lock(forget_keychain: bool, vault_dir: Option<PathBuf>) -> Result<(), CliError> {
     let vault_path = get_vault_dir(vault_dir)?;
-    delete_session(&vault_path);
+    let manager = vault_manager(&vault_path);
+    manager.lock(true, false)?;
     println!("Session cleared. You'll need to enter your password for the next command.");
+    
+    if forget_keychain {
+        // Only the GUI's vault (the default directory) has a keychain entry
+        if !vault::get_vault_directory().is_ok_and(|default_dir| default_dir == vault_path) {
+            return Err(format!("{} is not the GUI's vault, so it has no keychain entry", vault_path.display()).into());
+        }
+        manager.lock(false, true)?;
+        println!("Removed the stored key from the OS keychain. The app will ask for the master password on next launch.");
+    }
     Ok(())
 }
 
-fn cmd_status(vault_dir: Option<PathBuf>) -> Result<(), String> {
+fn cmd_status(vault_dir: Option<PathBuf>) -> Result<(), CliError> {
     let vault_path = get_vault_dir(vault_dir)?;
     let session_file = get_session_file(&vault_path);
     
@@ -1268,15 +4817,15 @@ fn cmd_status(vault_dir: Option<PathBuf>) -> Result<(), String> {
 
 // ========== PROJECT MANAGEMENT ==========
 
-fn cmd_project_create(name: &str, description: Option<&str>, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+fn cmd_project_create(name: &str, description: Option<&str>, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
     // Check if project already exists
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    let existing = operations::projects::get_project_by_name(db.connection(), name)
+        .map_err(|e| format!("Failed to get project: {}", e))?;
     
-    if projects.iter().any(|p| p.name == name) {
-        return Err(format!("Project '{}' already exists", name));
+    if existing.is_some() {
+        return Err(CliError::AlreadyExists(format!("Project '{}' already exists", name)));
     }
     
     // Create project
@@ -1284,11 +4833,15 @@ fn cmd_project_create(name: &str, description: Option<&str>, vault_dir: Option<P
     operations::projects::create_project(db.connection(), &project)
         .map_err(|e| format!("Failed to create project: {}", e))?;
     
+    if json {
+        print_write_result("created", "project", None, name, None);
+        return Ok(());
+    }
     println!("Project '{}' created successfully!", name);
     Ok(())
 }
 
-fn cmd_project_list(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+fn cmd_project_list(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
     let projects = operations::projects::get_all_projects(db.connection())
@@ -1320,61 +4873,224 @@ fn cmd_project_list(vault_dir: Option<PathBuf>, use_session: bool) -> Result<(),
     Ok(())
 }
 
-fn cmd_project_delete(name: &str, force: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+/// Print vault counts with a per-project breakdown; fails if the two disagree
+/// Print the resolved vault directory and the files derived from it
+///
+/// `source` says where the directory came from; every file path follows from it.
+fn cmd_paths(json: bool, vault_dir: Option<PathBuf>, source: &str) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir)?;
+    let paths = [
+        ("vault_dir", vault_path.clone(), source),
+        ("vault_file", vault_path.join("vault.clerk"), "vault directory"),
+        ("database_file", vault_path.join("vault.db"), "vault directory"),
+        ("session_file", get_session_file(&vault_path), "temp directory + vault directory hash"),
+    ];
+    
+    if json {
+        let entries: serde_json::Map<String, serde_json::Value> = paths.iter()
+            .map(|(name, path, source)| (name.to_string(), serde_json::json!({
+                "path": path.display().to_string(),
+                "source": source,
+                "exists": path.exists(),
+            })))
+            .collect();
+        println!("{}", serde_json::Value::Object(entries));
+    } else {
+        for (name, path, source) in &paths {
+            let missing = if path.exists() { "" } else { " (missing)" };
+            println!("{:<14} {}{}", name, path.display(), missing);
+            println!("{:<14} from {}", "", source);
+        }
+    }
+    
+    Ok(())
+}
+
+/// Write the redacted vault structure plus KDF parameters and the Clerk version
+///
+/// Only names, sizes and flags are included; nothing is decrypted.
+fn cmd_debug_dump(output: Option<PathBuf>, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir.clone())?;
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    let metadata = vault_manager(&vault_path).metadata()?;
+    
+    let dump = operations::dump::debug_dump(db.connection())
+        .map_err(|e| format!("Failed to read vault structure: {}", e))?;
+    
+    let document = serde_json::json!({
+        "clerk_version": env!("CARGO_PKG_VERSION"),
+        "vault_file_version": metadata.version,
+        "kdf_params": metadata.kdf_params,
+        "vault": dump,
+    });
+    let mut content = serde_json::to_string_pretty(&document)
+        .map_err(|e| format!("Failed to serialize dump: {}", e))?;
+    content.push('\n');
+    
+    Ok(write_export(content, output)?)
+}
+
+fn cmd_migrate(apply: bool, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let vault_path = get_vault_dir(vault_dir.clone())?;
+    let db_path = vault_path.join("vault.db");
+    if !db_path.exists() {
+        return Err(format!("Database not found: {}", db_path.display()).into());
+    }
+    
+    // An encrypted file needs the key even to read its schema version
+    let manager = vault_manager(&vault_path);
+    let cipher_key = match manager.metadata() {
+        Ok(metadata) if metadata.database_encrypted => {
+            let key = match load_session_key(&vault_path) {
+                Some((key, stored_hash)) if use_session && stored_hash == metadata.password_hash => key,
+                _ => manager.derive_key(&read_master_password("Enter master password:")?).map_err(password_error)?.1,
+            };
+            manager.database_cipher_key(&key)
+                .map_err(|e| format!("Failed to open database: {}", e))?
+        }
+        _ => None,
+    };
+    
+    // Read the status over a read-only connection so checking never migrates
+    let status = {
+        let db = Database::open_no_migrate(&db_path, cipher_key.as_deref())
+            .map_err(|e| format!("Failed to open database: {}", e))?;
+        migrations::migration_status(db.connection())
+            .map_err(|e| format!("Failed to read schema: {}", e))?
+    };
+    
+    // Unlocking runs every pending migration
+    if apply && !status.is_up_to_date() {
+        unlock_vault(vault_dir, use_session)?;
+    }
+    
+    if json {
+        let out = serde_json::to_string_pretty(&serde_json::json!({
+            "current_version": status.current_version,
+            "target_version": status.target_version,
+            "pending": status.pending,
+            "applied": apply,
+        }))
+        .map_err(|e| format!("Failed to serialize migration status: {}", e))?;
+        println!("{}", out);
+        return Ok(());
+    }
+    
+    println!("Schema version: {} (this build supports {})", status.current_version, status.target_version);
+    if status.is_up_to_date() {
+        println!("No pending migrations.");
+        return Ok(());
+    }
+    
+    println!("{} {} migration(s):", if apply { "Applied" } else { "Pending" }, status.pending.len());
+    for migration in &status.pending {
+        println!("  {:<28} {}", migration.name, migration.description);
+    }
+    if !apply {
+        println!("\nRun 'clerk migrate --apply' to apply them.");
+    }
+    Ok(())
+}
+
+fn cmd_stats(json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let stats = operations::stats::vault_stats(db.connection())
+        .map_err(|e| format!("Failed to compute stats: {}", e))?;
+    
+    if json {
+        let out = serde_json::to_string_pretty(&stats)
+            .map_err(|e| format!("Failed to serialize stats: {}", e))?;
+        println!("{}", out);
+    } else {
+        println!("Projects:     {}", stats.totals.project_count);
+        println!("Environments: {}", stats.totals.environment_count);
+        println!("Variables:    {}", stats.totals.variable_count);
+        
+        for project in &stats.projects {
+            println!();
+            println!("{} ({} environments, {} variables)", project.name, project.environments.len(), project.variable_count);
+            for environment in &project.environments {
+                println!("  {:<20} {}", environment.name, environment.variable_count);
+            }
+        }
+        println!();
+    }
+    
+    if !stats.is_consistent() {
+        return Err(format!("Counts do not add up: {}", stats.inconsistencies.join("; ")).into());
+    }
+    if !json {
+        println!("Consistency check passed: per-project counts match the totals");
+    }
     
-    let project = projects.iter()
-        .find(|p| p.name == name)
-        .ok_or_else(|| format!("Project '{}' not found", name))?;
+    Ok(())
+}
+
+/// Check a `--confirm-with-name` value against the name of what is being deleted
+///
+/// Returns whether a confirmation was given, so callers can treat it like `--force`.
+fn check_confirm_name(kind: &str, name: &str, confirm_with_name: Option<&str>) -> Result<bool, String> {
+    match confirm_with_name {
+        None => Ok(false),
+        Some(typed) if typed == name => Ok(true),
+        Some(typed) => Err(format!(
+            "Confirmation '{}' does not match {} name '{}'; nothing was deleted",
+            typed, kind, name
+        )),
+    }
+}
+
+fn cmd_project_delete(name: &str, force: bool, confirm_with_name: Option<&str>, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
+    let project = resolve_project(&db, name)?;
     let project_id = project.id.ok_or("Project ID is missing")?;
+    let force = check_confirm_name("project", name, confirm_with_name)? || force;
     
     // Check for environments
     let environments = operations::environments::get_environments_by_project(db.connection(), project_id)
         .map_err(|e| format!("Failed to get environments: {}", e))?;
     
+    if !environments.is_empty() && !force && json {
+        return Err(format!("Cannot delete project with {} environment(s); use --force", environments.len()).into());
+    }
     if !environments.is_empty() && !force {
     println!("Warning: Project '{}' has {} environment(s)", name, environments.len());
     println!("   Use --force to delete anyway, or delete environments first:");
         for env in &environments {
             println!("     - {}", env.name);
         }
-        return Err("Cannot delete project with environments".to_string());
+        return Err("Cannot delete project with environments".into());
     }
     
     // Delete project
     operations::projects::delete_project(db.connection(), project_id)
         .map_err(|e| format!("Failed to delete project: {}", e))?;
     
+    if json {
+        print_write_result("deleted", "project", None, name, None);
+        return Ok(());
+    }
     println!("Project '{}' deleted successfully!", name);
     Ok(())
 }
 
 // ========== ENVIRONMENT MANAGEMENT ==========
 
-fn cmd_env_create(name: &str, project_name: &str, description: Option<&str>, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+fn cmd_env_create(name: &str, project_name: &str, description: Option<&str>, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
-    
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
-    
+    let project = resolve_project(&db, project_name)?;
     let project_id = project.id.ok_or("Project ID is missing")?;
     
     // Check if environment already exists
-    let environments = operations::environments::get_environments_by_project(db.connection(), project_id)
-        .map_err(|e| format!("Failed to get environments: {}", e))?;
+    let existing = operations::environments::get_environment_by_name(db.connection(), project_id, name)
+        .map_err(|e| format!("Failed to get environment: {}", e))?;
     
-    if environments.iter().any(|e| e.name == name) {
-        return Err(format!("Environment '{}' already exists in project '{}'", name, project_name));
+    if existing.is_some() {
+        return Err(CliError::AlreadyExists(format!("Environment '{}' already exists in project '{}'", name, project_name)));
     }
     
     // Create environment
@@ -1382,21 +5098,155 @@ fn cmd_env_create(name: &str, project_name: &str, description: Option<&str>, vau
     operations::environments::create_environment(db.connection(), &environment)
         .map_err(|e| format!("Failed to create environment: {}", e))?;
     
+    if json {
+        print_write_result("created", "environment", None, project_name, Some(name));
+        return Ok(());
+    }
     println!("Environment '{}' created in project '{}'!", name, project_name);
     Ok(())
 }
 
-fn cmd_env_list(project_name: &str, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+fn cmd_env_lock(name: &str, project_name: &str, force: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, name)?;
+    let owner = lock_owner();
     
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    let previous = operations::environments::get_environment_lock(db.connection(), environment_id)
+        .map_err(|e| format!("Failed to read environment lock: {}", e))?;
+    operations::environments::lock_environment(db.connection(), environment_id, &owner, force)
+        .map_err(|e| lock_error(e, project_name, name))?;
+    
+    if let Some(previous) = previous.filter(|lock| lock.locked_by != owner) {
+        println!("Took over the lock held by {} since {}", previous.locked_by, format_expiry(previous.locked_at));
+    }
+    println!("Locked {}/{} as {}", project_name, name, owner);
+    println!("   Others' writes are refused until 'clerk env-unlock {} -p {}' or the lock goes stale after a day", name, project_name);
+    Ok(())
+}
+
+fn cmd_env_unlock(name: &str, project_name: &str, force: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, name)?;
+    
+    let released = operations::environments::unlock_environment(db.connection(), environment_id, &lock_owner(), force)
+        .map_err(|e| lock_error(e, project_name, name))?;
+    
+    match released {
+        Some(lock) => println!("Unlocked {}/{} (was locked by {})", project_name, name, lock.locked_by),
+        None => println!("{}/{} is not locked", project_name, name),
+    }
+    Ok(())
+}
+
+/// Who takes environment locks from this machine, e.g. `alice@laptop`
+fn lock_owner() -> String {
+    let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_else(|_| "unknown".to_string());
+    // Shells rarely export HOSTNAME, so fall back to the file most Linux systems keep it in
+    let host = std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|host| host.trim().to_string())
+        .unwrap_or_default();
+    if host.is_empty() { user } else { format!("{}@{}", user, host) }
+}
+
+fn lock_error(e: DatabaseError, project_name: &str, env_name: &str) -> CliError {
+    match e {
+        DatabaseError::EnvironmentLocked { locked_by, locked_at } => CliError::Locked(format!(
+            "Environment '{}/{}' is locked by {} since {}; use --force to override",
+            project_name, env_name, locked_by, format_expiry(locked_at)
+        )),
+        e => CliError::Other(format!("Failed to update environment lock: {}", e)),
+    }
+}
+
+/// Refuse to write to an environment someone else has locked with `env-lock`
+///
+/// With `force` the write goes ahead with a warning. Stale locks are ignored.
+fn check_environment_lock(db: &Database, project_name: &str, env_name: &str, environment_id: i64, force: bool) -> Result<(), CliError> {
+    let lock = operations::environments::get_environment_lock(db.connection(), environment_id)
+        .map_err(|e| format!("Failed to read environment lock: {}", e))?;
+    let Some(lock) = lock.filter(|lock| lock.blocks(&lock_owner(), chrono::Utc::now().timestamp())) else {
+        return Ok(());
+    };
+    
+    let held = format!("Environment '{}/{}' is locked by {} since {}", project_name, env_name, lock.locked_by, format_expiry(lock.locked_at));
+    if force {
+        eprintln!("Warning: {} (writing anyway because of --force)", held);
+        Ok(())
+    } else {
+        Err(CliError::Locked(format!("{}; ask them to unlock it, or break the lock with 'clerk env-unlock {} -p {} --force'", held, env_name, project_name)))
+    }
+}
+
+fn cmd_project_move(env_name: &str, from_project: &str, to_project: &str, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let (_, environment) = resolve_project_env(&db, from_project, env_name)?;
+    let environment_id = environment.id.ok_or("Environment ID is missing")?;
+    check_environment_lock(&db, from_project, env_name, environment_id, false)?;
+    
+    let dest_project_id = resolve_project(&db, to_project)?
+        .id.ok_or("Target project ID is missing")?;
+    
+    operations::environments::move_environment(db.connection(), environment_id, dest_project_id, &encryption_key)
+        .map_err(|e| format!("Failed to move environment: {}", e))?;
+    
+    println!("Environment '{}' moved from '{}' to '{}'", env_name, from_project, to_project);
+    Ok(())
+}
+
+fn cmd_apply(file: &Path, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    // Parse before unlocking so config mistakes don't cost a password prompt
+    let config = app_lib::config::load(file)?;
+    
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let report = app_lib::config::apply(db.connection(), &config)
+        .map_err(|e| format!("Failed to apply {}: {}", file.display(), e))?;
+    
+    if json {
+        let missing: Vec<_> = report.missing_keys.iter()
+            .map(|(project, env, keys)| serde_json::json!({ "project": project, "env": env, "keys": keys }))
+            .collect();
+        let created_envs: Vec<_> = report.created_environments.iter()
+            .map(|(project, env)| serde_json::json!({ "project": project, "env": env }))
+            .collect();
+        println!("{}", serde_json::json!({
+            "created_projects": report.created_projects,
+            "created_environments": created_envs,
+            "missing_keys": missing,
+        }));
+        return Ok(());
+    }
+    
+    for project in &report.created_projects {
+        println!("Created project '{}'", project);
+    }
+    for (project, env) in &report.created_environments {
+        println!("Created environment '{}' in project '{}'", env, project);
+    }
+    if report.created_projects.is_empty() && report.created_environments.is_empty() {
+        println!("Vault structure already matches {}", file.display());
+    }
     
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
+    if report.missing_keys.is_empty() {
+        println!("All expected keys are set");
+    } else {
+        println!();
+        println!("Expected keys not set:");
+        for (project, env, keys) in &report.missing_keys {
+            println!("  {}/{}: {}", project, env, keys.join(", "));
+        }
+    }
+    
+    Ok(())
+}
+
+fn cmd_env_list(project_name: &str, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
+    let project = resolve_project(&db, project_name)?;
     let project_id = project.id.ok_or("Project ID is missing")?;
     
     // Get environments
@@ -1424,38 +5274,30 @@ fn cmd_env_list(project_name: &str, vault_dir: Option<PathBuf>, use_session: boo
             }
         }
         println!("    Variables: {}", var_count);
+        if let Ok(Some(lock)) = operations::environments::get_environment_lock(db.connection(), env_id) {
+            let stale = if lock.is_stale(chrono::Utc::now().timestamp()) { " (stale)" } else { "" };
+            println!("    Locked by {} since {}{}", lock.locked_by, format_expiry(lock.locked_at), stale);
+        }
     }
     
     Ok(())
 }
 
-fn cmd_env_delete(name: &str, project_name: &str, force: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+fn cmd_env_delete(name: &str, project_name: &str, force: bool, confirm_with_name: Option<&str>, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
-    
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
-    
-    let project_id = project.id.ok_or("Project ID is missing")?;
-    
-    // Find environment
-    let environments = operations::environments::get_environments_by_project(db.connection(), project_id)
-        .map_err(|e| format!("Failed to get environments: {}", e))?;
-    
-    let environment = environments.iter()
-        .find(|e| e.name == name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", name, project_name))?;
-    
+    let (_, environment) = resolve_project_env(&db, project_name, name)?;
     let environment_id = environment.id.ok_or("Environment ID is missing")?;
+    let force = check_confirm_name("environment", name, confirm_with_name)? || force;
+    check_environment_lock(&db, project_name, name, environment_id, force)?;
     
     // Check for variables
     let variables = operations::variables::get_variables_by_environment(db.connection(), environment_id)
         .map_err(|e| format!("Failed to get variables: {}", e))?;
     
+    if !variables.is_empty() && !force && json {
+        return Err(format!("Cannot delete environment with {} variable(s); use --force", variables.len()).into());
+    }
     if !variables.is_empty() && !force {
     println!("Warning: Environment '{}' has {} variable(s)", name, variables.len());
     println!("   Use --force to delete anyway, or delete variables first:");
@@ -1465,41 +5307,51 @@ fn cmd_env_delete(name: &str, project_name: &str, force: bool, vault_dir: Option
         if variables.len() > 5 {
             println!("     ... and {} more", variables.len() - 5);
         }
-        return Err("Cannot delete environment with variables".to_string());
+        return Err("Cannot delete environment with variables".into());
     }
     
     // Delete environment (cascade will delete variables)
     operations::environments::delete_environment(db.connection(), environment_id)
         .map_err(|e| format!("Failed to delete environment: {}", e))?;
     
-    println!("Environment '{}' deleted from project '{}'!", name, project_name);
+    if json {
+        print_write_result("deleted", "environment", None, project_name, Some(name));
+        return Ok(());
+    }
+    println!("Environment '{}' deleted from project '{}'!", name, project_name);
+    Ok(())
+}
+
+fn cmd_env_clear(project_name: &str, env_name: &str, force: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
+    let environment_id = environment.id.ok_or("Environment ID is missing")?;
+    check_environment_lock(&db, project_name, env_name, environment_id, force)?;
+    
+    if !force {
+        let variables = operations::variables::get_variables_by_environment(db.connection(), environment_id)
+            .map_err(|e| format!("Failed to get variables: {}", e))?;
+        let protected = variables.iter().filter(|v| v.is_protected).count();
+        println!("Environment '{}' has {} variable(s), {} of them protected", env_name, variables.len(), protected);
+        return Err("Refusing to clear the environment without --force".into());
+    }
+    
+    let deleted = operations::variables::delete_all_variables(db.connection(), environment_id, true)
+        .map_err(|e| format!("Failed to clear environment, nothing was deleted: {}", e))?;
+    
+    println!("Cleared {}/{}: {} variable(s) deleted, environment kept", project_name, env_name, deleted.len());
     Ok(())
 }
 
 // ========== VARIABLE OPERATIONS ==========
 
-fn cmd_delete(key: &str, project_name: &str, env_name: &str, force: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), String> {
+fn cmd_delete(key: &str, project_name: &str, env_name: &str, force: bool, json: bool, vault_dir: Option<PathBuf>, use_session: bool) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
     
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
-    
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
-    
-    let project_id = project.id.ok_or("Project ID is missing")?;
-    
-    // Find environment
-    let environments = operations::environments::get_environments_by_project(db.connection(), project_id)
-        .map_err(|e| format!("Failed to get environments: {}", e))?;
-    
-    let environment = environments.iter()
-        .find(|e| e.name == env_name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", env_name, project_name))?;
-    
+    let (_, environment) = resolve_project_env(&db, project_name, env_name)?;
     let environment_id = environment.id.ok_or("Environment ID is missing")?;
+    check_environment_lock(&db, project_name, env_name, environment_id, force)?;
     
     // Find variable
     let variables = operations::variables::get_variables_by_environment(db.connection(), environment_id)
@@ -1507,138 +5359,155 @@ fn cmd_delete(key: &str, project_name: &str, env_name: &str, force: bool, vault_
     
     let variable = variables.iter()
         .find(|v| v.key == key)
-        .ok_or_else(|| format!("Variable '{}' not found", key))?;
+        .ok_or_else(|| CliError::NotFound(format!("Variable '{}' not found", key)))?;
     
     let variable_id = variable.id.ok_or("Variable ID is missing")?;
     
     // Confirm deletion if not forced
+    if !force && json {
+        return Err("Deletion cancelled. Use --force to confirm".into());
+    }
     if !force {
     println!("Are you sure you want to delete '{}'? (use --force to skip this prompt)", key);
         println!("   Project: {}", project_name);
         println!("   Environment: {}", env_name);
         
         // For CLI, we'll require --force flag instead of interactive prompt
-        return Err("Deletion cancelled. Use --force to confirm".to_string());
+        return Err("Deletion cancelled. Use --force to confirm".into());
+    }
+    
+    // Protected variables must be unprotected first; --force alone is not enough
+    if variable.is_protected {
+        return Err(CliError::Protected(format!(
+            "Variable '{}' is protected. Run 'clerk unprotect {} -p {} -e {}' first",
+            key, key, project_name, env_name
+        )));
     }
     
     // Delete variable
-    operations::variables::delete_variable(db.connection(), variable_id)
+    operations::variables::delete_variable(db.connection(), variable_id, false)
         .map_err(|e| format!("Failed to delete variable: {}", e))?;
     
+    if json {
+        print_write_result("deleted", "variable", Some(key), project_name, Some(env_name));
+        return Ok(());
+    }
     println!("Variable '{}' deleted from {}/{}", key, project_name, env_name);
     Ok(())
 }
 
-fn cmd_copy(
+/// Format an update error, pointing at `--force` when the variable is protected
+fn protected_error(err: DatabaseError) -> CliError {
+    match err {
+        DatabaseError::Protected(key) => CliError::Protected(format!("Variable '{}' is protected. Use --force to change it", key)),
+        e => CliError::Other(format!("Failed to update variable: {}", e)),
+    }
+}
+
+fn cmd_protect(
     key: &str,
-    from_project: &str,
-    from_env: &str,
-    to_project: &str,
-    to_env: &str,
-    overwrite: bool,
+    project_name: &str,
+    env_name: &str,
+    protected: bool,
     vault_dir: Option<PathBuf>,
     use_session: bool,
-) -> Result<(), String> {
+) -> Result<(), CliError> {
     let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, env_name)?;
+    check_environment_lock(&db, project_name, env_name, environment_id, false)?;
     
-    // Find source project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
-    
-    let src_project = projects.iter()
-        .find(|p| p.name == from_project)
-        .ok_or_else(|| format!("Source project '{}' not found", from_project))?;
+    let variable = operations::variables::get_variables_by_environment(db.connection(), environment_id)
+        .map_err(|e| format!("Failed to get variables: {}", e))?
+        .into_iter()
+        .find(|v| v.key == key)
+        .ok_or_else(|| CliError::NotFound(format!("Variable '{}' not found", key)))?;
     
-    let src_project_id = src_project.id.ok_or("Source project ID is missing")?;
+    if variable.is_protected == protected {
+        println!("Variable '{}' is already {}", key, if protected { "protected" } else { "unprotected" });
+        return Ok(());
+    }
     
-    // Find target project
-    let dest_project = projects.iter()
-        .find(|p| p.name == to_project)
-        .ok_or_else(|| format!("Target project '{}' not found", to_project))?;
+    let variable_id = variable.id.ok_or("Variable ID is missing")?;
+    operations::variables::set_variable_protected(db.connection(), variable_id, protected)
+        .map_err(|e| format!("Failed to update variable: {}", e))?;
     
-    let dest_project_id = dest_project.id.ok_or("Target project ID is missing")?;
+    if protected {
+        println!("Protected '{}' in {}/{}", key, project_name, env_name);
+    } else {
+        println!("Unprotected '{}' in {}/{}", key, project_name, env_name);
+    }
+    Ok(())
+}
+
+/// Add or remove a tag on every variable matching `keys` (exact keys or wildcards)
+fn cmd_tag(
+    tag: &str,
+    keys: &[String],
+    project_name: &str,
+    env_name: &str,
+    add: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, _encryption_key) = unlock_vault(vault_dir, use_session)?;
+    let environment_id = find_environment_id(&db, project_name, env_name)?;
+    check_environment_lock(&db, project_name, env_name, environment_id, false)?;
     
-    // Find source environment
-    let src_environments = operations::environments::get_environments_by_project(db.connection(), src_project_id)
-        .map_err(|e| format!("Failed to get source environments: {}", e))?;
+    let changed = operations::variables::tag_variables(db.connection(), environment_id, keys, tag, add)
+        .map_err(|e| e.to_string())?;
     
-    let src_environment = src_environments.iter()
-        .find(|e| e.name == from_env)
-        .ok_or_else(|| format!("Source environment '{}' not found", from_env))?;
+    if changed.is_empty() {
+        println!("No changes: every matching variable {} '{}'", if add { "already has" } else { "lacks" }, tag);
+    } else if add {
+        println!("Tagged {} variables with '{}' in {}/{}: {}", changed.len(), tag, project_name, env_name, changed.join(", "));
+    } else {
+        println!("Removed '{}' from {} variables in {}/{}: {}", tag, changed.len(), project_name, env_name, changed.join(", "));
+    }
+    Ok(())
+}
+
+fn cmd_copy(
+    key: &str,
+    from_project: &str,
+    from_env: &str,
+    to_project: &str,
+    to_env: &str,
+    overwrite: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
     
+    let (_, src_environment) = resolve_project_env(&db, from_project, from_env)
+        .map_err(|e| format!("Source: {}", e))?;
     let src_environment_id = src_environment.id.ok_or("Source environment ID is missing")?;
     
-    // Find target environment
-    let dest_environments = operations::environments::get_environments_by_project(db.connection(), dest_project_id)
-        .map_err(|e| format!("Failed to get target environments: {}", e))?;
-    
-    let dest_environment = dest_environments.iter()
-        .find(|e| e.name == to_env)
-        .ok_or_else(|| format!("Target environment '{}' not found", to_env))?;
-    
+    let (_, dest_environment) = resolve_project_env(&db, to_project, to_env)
+        .map_err(|e| format!("Target: {}", e))?;
     let dest_environment_id = dest_environment.id.ok_or("Target environment ID is missing")?;
+    check_environment_lock(&db, to_project, to_env, dest_environment_id, false)?;
     
-    // Find source variable
-    let src_variables = operations::variables::get_variables_by_environment(db.connection(), src_environment_id)
-        .map_err(|e| format!("Failed to get source variables: {}", e))?;
-    
-    let src_variable = src_variables.iter()
-        .find(|v| v.key == key)
-        .ok_or_else(|| format!("Variable '{}' not found in source environment", key))?;
-    
-    // Check if variable exists in target
-    let dest_variables = operations::variables::get_variables_by_environment(db.connection(), dest_environment_id)
-        .map_err(|e| format!("Failed to get target variables: {}", e))?;
-    
-    let exists_in_target = dest_variables.iter().any(|v| v.key == key);
-    
-    if exists_in_target && !overwrite {
-        return Err(format!(
+    // Check, re-encrypt for the target environment and write in one transaction
+    let replaced = operations::variables::copy_variable(
+        db.connection(),
+        src_environment_id,
+        dest_environment_id,
+        key,
+        overwrite,
+        &encryption_key,
+    )
+    .map_err(|e| match e {
+        DatabaseError::ConstraintViolation(_) => CliError::AlreadyExists(format!(
             "Variable '{}' already exists in {}/{}. Use --overwrite to replace it",
             key, to_project, to_env
-        ));
-    }
+        )),
+        e => CliError::Other(format!("Failed to copy variable: {}", e)),
+    })?;
     
-    // Create or update variable in target environment
-    if exists_in_target {
-        // Update existing
-        let target_var = dest_variables.iter()
-            .find(|v| v.key == key)
-            .unwrap();
-        
-        let target_var_id = target_var.id.ok_or("Target variable ID is missing")?;
-        
-        let updated_var = Variable::new(
-            dest_environment_id,
-            key.to_string(),
-            src_variable.encrypted_value.clone(),
-            src_variable.description.clone(),
-        );
-        
-        operations::variables::update_variable(
-            db.connection(),
-            target_var_id,
-            &updated_var,
-        )
-        .map_err(|e| format!("Failed to update variable: {}", e))?;
-        
-    println!("Variable '{}' updated in {}/{}", key, to_project, to_env);
+    if replaced {
+        println!("Variable '{}' updated in {}/{}", key, to_project, to_env);
     } else {
-        // Create new
-        let new_var = Variable::new(
-            dest_environment_id,
-            key.to_string(),
-            src_variable.encrypted_value.clone(),
-            src_variable.description.clone(),
-        );
-        
-        operations::variables::create_variable(
-            db.connection(),
-            &new_var,
-        )
-        .map_err(|e| format!("Failed to create variable: {}", e))?;
-        
-    println!("Variable '{}' copied to {}/{}", key, to_project, to_env);
+        println!("Variable '{}' copied to {}/{}", key, to_project, to_env);
     }
     
     Ok(())
@@ -1648,49 +5517,394 @@ fn cmd_import(
     file_path: &PathBuf,
     project_name: &str,
     env_name: &str,
-    overwrite: bool,
+    options: ImportOptions,
     vault_dir: Option<PathBuf>,
     use_session: bool,
-) -> Result<(), String> {
+) -> Result<(), CliError> {
     let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
     
     // Check if file exists
     if !file_path.exists() {
-        return Err(format!("File not found: {}", file_path.display()));
+        return Err(format!("File not found: {}", file_path.display()).into());
     }
     
     // Read .env file
     let content = std::fs::read_to_string(file_path)
         .map_err(|e| format!("Failed to read file: {}", e))?;
     
-    // Find project
-    let projects = operations::projects::get_all_projects(db.connection())
-        .map_err(|e| format!("Failed to get projects: {}", e))?;
+    // A metadata export (`export --include-metadata`) is restored with everything it carries
+    let records = if options.shared {
+        None
+    } else {
+        EnvironmentRecords::parse(&content).map_err(|e| e.to_string())?
+    };
+    
+    // Collect (key, value, description) entries from any format
+    let entries: Vec<(String, String, Option<String>)> = if let Some(records) = &records {
+        println!("Metadata export of {}/{} ({} variables)", records.project, records.environment, records.variables.len());
+        records.variables
+            .iter()
+            .map(|r| (r.key.clone(), r.value.clone(), r.description.clone()))
+            .collect()
+    } else if options.shared {
+        eprintln!("Enter share passphrase:");
+        let passphrase = Zeroizing::new(rpassword::read_password()
+            .map_err(|e| format!("Failed to read passphrase: {}", e))?);
+        
+        // Expiry is checked against the authenticated payload, not a plaintext field
+        let payload = crypto::share::open_bundle(&content, &passphrase, chrono::Utc::now().timestamp())?;
+        
+        println!("Shared bundle from {}/{} ({} variables)", payload.project, payload.environment, payload.variables.len());
+        payload.variables
+            .into_iter()
+            .map(|v| (v.key, v.value, v.description))
+            .collect()
+    } else {
+        parse_env_entries(&content)
+    };
     
-    let project = projects.iter()
-        .find(|p| p.name == project_name)
-        .ok_or_else(|| format!("Project '{}' not found", project_name))?;
+    if let Some(preview) = options.preview {
+        let environment_id = match find_environment_id(&db, project_name, env_name) {
+            Ok(environment_id) => Some(environment_id),
+            Err(_) if options.create => {
+                println!("Would create {}/{} (missing)", project_name, env_name);
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        return Ok(preview_import(&db, &encryption_key, environment_id, &entries, options.overwrite, preview)?);
+    }
     
-    let project_id = project.id.ok_or("Project ID is missing")?;
+    let (imported_count, updated_count, skipped_count) = match records {
+        Some(records) => import_records(&db, &encryption_key, project_name, env_name, records.variables, options.overwrite, options.create)?,
+        None => import_entries(&db, &encryption_key, project_name, env_name, entries, options.overwrite, options.create)?,
+    };
     
-    // Find environment
-    let environments = operations::environments::get_environments_by_project(db.connection(), project_id)
+    print_import_summary(imported_count, updated_count, skipped_count);
+    
+    Ok(())
+}
+
+/// Import the given directory's layered dotenv files into one environment
+///
+/// Files are applied in dotenv/Vite precedence order, later files winning:
+/// `.env`, `.env.local`, `.env.{mode}`, `.env.{mode}.local`.
+#[allow(clippy::too_many_arguments)]
+fn cmd_import_dir(
+    dir: &Path,
+    mode: Option<&str>,
+    project_name: &str,
+    env_name: &str,
+    overwrite: bool,
+    dedupe: bool,
+    vault_dir: Option<PathBuf>,
+    use_session: bool,
+) -> Result<(), CliError> {
+    if !dir.is_dir() {
+        return Err(format!("Directory not found: {}", dir.display()).into());
+    }
+    
+    let mode = mode.unwrap_or(env_name);
+    let candidates = [
+        ".env".to_string(),
+        ".env.local".to_string(),
+        format!(".env.{}", mode),
+        format!(".env.{}.local", mode),
+    ];
+    
+    // Resolve the final value of each key, remembering which file it came from
+    let mut resolved: Vec<(String, String, String)> = Vec::new();
+    // (key, earlier file, later file) where the later file repeats the same value
+    let mut repeated: Vec<(String, String, String)> = Vec::new();
+    let mut files_read = 0;
+    
+    for file_name in &candidates {
+        let path = dir.join(file_name);
+        if !path.is_file() {
+            continue;
+        }
+        
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        files_read += 1;
+        
+        for (key, value, _) in parse_env_entries(&content) {
+            match resolved.iter_mut().find(|(k, _, _)| *k == key) {
+                Some(entry) => {
+                    if entry.1 == value {
+                        repeated.push((key.clone(), entry.2.clone(), file_name.clone()));
+                    }
+                    entry.1 = value;
+                    entry.2 = file_name.clone();
+                }
+                None => resolved.push((key, value, file_name.clone())),
+            }
+        }
+    }
+    
+    if files_read == 0 {
+        return Err(format!(
+            "No dotenv files found in {} (looked for {})",
+            dir.display(),
+            candidates.join(", ")
+        ).into());
+    }
+    
+    let (db, encryption_key) = unlock_vault(vault_dir, use_session)?;
+    
+    println!("Resolved {} variables from {} files:", resolved.len(), files_read);
+    for (key, _, file_name) in &resolved {
+        println!("   {} <- {}", key, file_name);
+    }
+    
+    let entries = resolved
+        .into_iter()
+        .map(|(key, value, _)| (key, value, None))
+        .collect();
+    
+    let (imported_count, updated_count, skipped_count) =
+        import_entries(&db, &encryption_key, project_name, env_name, entries, overwrite, false)?;
+    
+    print_import_summary(imported_count, updated_count, skipped_count);
+    
+    if dedupe {
+        print_dedupe_report(&db, &encryption_key, project_name, &repeated)?;
+    }
+    
+    Ok(())
+}
+
+/// Report duplicated values found by `import-dir --dedupe` (read-only)
+///
+/// `repeated` lists keys a later dotenv file sets to the value they already
+/// had. Keys present with one identical value in every environment of the
+/// project (at least two) are listed as candidates for a shared base
+/// environment, e.g. with clerk.toml's `extends`.
+fn print_dedupe_report(
+    db: &Database,
+    encryption_key: &[u8; 32],
+    project_name: &str,
+    repeated: &[(String, String, String)],
+) -> Result<(), CliError> {
+    println!();
+    if repeated.is_empty() {
+        println!("No file repeats a value an earlier file already set");
+    } else {
+        println!("Repeated with the same value ({}), the later line can be removed:", repeated.len());
+        for (key, earlier, later) in repeated {
+            println!("   {}  {} -> {}", key, earlier, later);
+        }
+    }
+    
+    let project = resolve_project(db, project_name)?;
+    let environments = operations::environments::get_environments_by_project(db.connection(), project.id.unwrap())
         .map_err(|e| format!("Failed to get environments: {}", e))?;
+    if environments.len() < 2 {
+        println!("Project '{}' has one environment; nothing to compare across environments", project_name);
+        return Ok(());
+    }
     
-    let environment = environments.iter()
-        .find(|e| e.name == env_name)
-        .ok_or_else(|| format!("Environment '{}' not found in project '{}'", env_name, project_name))?;
+    // The key's value in the first environment, while every other one agrees
+    let mut shared: Option<Vec<operations::VariableDecrypted>> = None;
+    for environment in &environments {
+        let variables = operations::variables::get_variables_by_environment_decrypted(
+            db.connection(),
+            environment.id.unwrap(),
+            encryption_key,
+        ).map_err(|e| format!("Failed to get variables: {}", e))?;
+        shared = Some(match shared {
+            None => variables,
+            Some(mut candidates) => {
+                candidates.retain(|candidate| variables.iter().any(|v| v.key == candidate.key && v.value == candidate.value));
+                candidates
+            }
+        });
+    }
     
-    let environment_id = environment.id.ok_or("Environment ID is missing")?;
+    let shared = shared.unwrap_or_default();
+    if shared.is_empty() {
+        println!("No key has the same value in all {} environments of '{}'", environments.len(), project_name);
+    } else {
+        println!("Identical in all {} environments of '{}' ({}), candidates for a shared base environment:", environments.len(), project_name, shared.len());
+        for variable in &shared {
+            println!("   {}", variable.key);
+        }
+    }
+    Ok(())
+}
+
+/// Create or update parsed entries in an environment, returning (created, updated, skipped)
+///
+/// With `create`, a missing project and environment are created in the same
+/// transaction as the entries.
+fn import_entries(
+    db: &Database,
+    encryption_key: &[u8; 32],
+    project_name: &str,
+    env_name: &str,
+    entries: Vec<(String, String, Option<String>)>,
+    overwrite: bool,
+    create: bool,
+) -> Result<(usize, usize, usize), CliError> {
+    let environment_id = match find_environment_id(db, project_name, env_name) {
+        Ok(environment_id) => environment_id,
+        Err(_) if create => {
+            let (created, counts) = operations::variables::import_variables_creating(
+                db.connection(), project_name, env_name, entries, overwrite, encryption_key,
+            ).map_err(|e| format!("Import failed, nothing was created or changed: {}", e))?;
+            print_created(project_name, env_name, created);
+            return Ok(counts);
+        }
+        Err(e) => return Err(e),
+    };
+    check_environment_lock(db, project_name, env_name, environment_id, false)?;
     
-    // Get existing variables
-    let existing_variables = operations::variables::get_variables_by_environment(db.connection(), environment_id)
-        .map_err(|e| format!("Failed to get variables: {}", e))?;
+    // All entries are written in one transaction, so a failure leaves the environment unchanged
+    Ok(operations::variables::import_variables(db.connection(), environment_id, entries, overwrite, encryption_key)
+        .map_err(|e| format!("Import failed, no variables were changed: {}", e))?)
+}
+
+/// Like `import_entries`, also restoring tags, protection, expiry and timestamps
+fn import_records(
+    db: &Database,
+    encryption_key: &[u8; 32],
+    project_name: &str,
+    env_name: &str,
+    records: Vec<VariableRecord>,
+    overwrite: bool,
+    create: bool,
+) -> Result<(usize, usize, usize), CliError> {
+    let environment_id = match find_environment_id(db, project_name, env_name) {
+        Ok(environment_id) => environment_id,
+        Err(_) if create => {
+            let (created, counts) = operations::records::import_records_creating(
+                db.connection(), project_name, env_name, records, overwrite, encryption_key,
+            ).map_err(|e| format!("Import failed, nothing was created or changed: {}", e))?;
+            print_created(project_name, env_name, created);
+            return Ok(counts);
+        }
+        Err(e) => return Err(e),
+    };
+    check_environment_lock(db, project_name, env_name, environment_id, false)?;
+    
+    Ok(operations::records::import_records(db.connection(), environment_id, records, overwrite, encryption_key)
+        .map_err(|e| format!("Import failed, no variables were changed: {}", e))?)
+}
+
+/// Look up a project by name
+fn resolve_project(db: &Database, project_name: &str) -> Result<Project, CliError> {
+    operations::projects::get_project_by_name(db.connection(), project_name)
+        .map_err(|e| format!("Failed to get project: {}", e))?
+        .ok_or_else(|| CliError::NotFound(format!("Project '{}' not found", project_name)))
+}
+
+/// Look up a project and one of its environments by name (one query)
+fn resolve_project_env(db: &Database, project_name: &str, env_name: &str) -> Result<(Project, Environment), CliError> {
+    match operations::environments::find_project_environment(db.connection(), project_name, env_name)
+        .map_err(|e| format!("Failed to get environment: {}", e))?
+    {
+        Some((project, Some(environment))) => Ok((project, environment)),
+        Some((_, None)) => Err(CliError::NotFound(format!("Environment '{}' not found in project '{}'", env_name, project_name))),
+        None => Err(CliError::NotFound(format!("Project '{}' not found", project_name))),
+    }
+}
+
+/// Look up an environment's id by project and environment name
+fn find_environment_id(db: &Database, project_name: &str, env_name: &str) -> Result<i64, CliError> {
+    let (_, environment) = resolve_project_env(db, project_name, env_name)?;
+    environment.id.ok_or_else(|| "Environment ID is missing".into())
+}
+
+/// Report what an import would do without writing anything
+fn preview_import(
+    db: &Database,
+    encryption_key: &[u8; 32],
+    environment_id: Option<i64>,
+    entries: &[(String, String, Option<String>)],
+    overwrite: bool,
+    preview: ImportPreview,
+) -> Result<(), String> {
+    // Existing values are only decrypted when a diff was asked for; a missing environment has none
+    let existing: Vec<(String, Option<operations::VariableDecrypted>)> = match (environment_id, preview) {
+        (None, _) => Vec::new(),
+        (Some(environment_id), ImportPreview::Counts) => operations::variables::get_variables_by_environment(db.connection(), environment_id)
+            .map_err(|e| format!("Failed to get variables: {}", e))?
+            .into_iter()
+            .map(|v| (v.key, None))
+            .collect(),
+        (Some(environment_id), ImportPreview::Diff { .. }) => operations::variables::get_variables_by_environment_decrypted(db.connection(), environment_id, encryption_key)
+            .map_err(|e| format!("Failed to get variables: {}", e))?
+            .into_iter()
+            .map(|v| (v.key.clone(), Some(v)))
+            .collect(),
+    };
     
-    // Parse .env file
-    let mut imported_count = 0;
+    let mut created = Vec::new();
+    let mut changed = Vec::new();
     let mut skipped_count = 0;
     let mut updated_count = 0;
+    for (key, value, _) in entries {
+        match existing.iter().find(|(k, _)| k == key) {
+            None => created.push((key, value)),
+            Some(_) if !overwrite => {
+                skipped_count += 1;
+                changed.push((key, value, false));
+            }
+            Some(_) => {
+                updated_count += 1;
+                changed.push((key, value, true));
+            }
+        }
+    }
+    
+    println!("Dry run, nothing was written:");
+    println!("   Would create: {}", created.len());
+    println!("   Would update: {}", updated_count);
+    if skipped_count > 0 {
+        println!("   Would skip: {} (use --overwrite to update existing)", skipped_count);
+    }
+    
+    let ImportPreview::Diff { show_values } = preview else {
+        return Ok(());
+    };
+    let shown = |value: &str| if show_values { escape_value(value) } else { "********".to_string() };
+    
+    if !created.is_empty() || !changed.is_empty() {
+        println!();
+    }
+    for (key, value) in &created {
+        println!("  + {}: {}", key, shown(value));
+    }
+    for (key, value, applied) in &changed {
+        let old = existing.iter()
+            .find(|(k, _)| k == *key)
+            .and_then(|(_, v)| v.as_ref())
+            .map(|v| v.value.expose())
+            .unwrap_or_default();
+        let note = if *applied { "" } else { " (skipped without --overwrite)" };
+        
+        if old == value.as_str() {
+            println!("  = {}: unchanged{}", key, note);
+        } else {
+            println!("  ~ {}: {} -> {}{}", key, shown(old), shown(value), note);
+        }
+    }
+    
+    Ok(())
+}
+
+fn print_import_summary(imported_count: usize, updated_count: usize, skipped_count: usize) {
+    println!("Import completed:");
+    println!("   Created: {}", imported_count);
+    println!("   Updated: {}", updated_count);
+    if skipped_count > 0 {
+        println!("   Skipped: {} (use --overwrite to update existing)", skipped_count);
+    }
+}
+
+/// Parse KEY=VALUE lines from .env content, skipping blanks and comments
+fn parse_env_entries(content: &str) -> Vec<(String, String, Option<String>)> {
+    let mut entries = Vec::new();
     
     for line in content.lines() {
         let line = line.trim();
@@ -1707,56 +5921,147 @@ fn cmd_import(
                 .trim_matches('"')
                 .trim_matches('\'');
             
-            // Check if variable exists
-            let exists = existing_variables.iter().any(|v| v.key == key);
-            
-            if exists && !overwrite {
-                skipped_count += 1;
-                continue;
-            }
-            
-            if exists {
-                // Update existing using encrypted helper
-                let var = existing_variables.iter()
-                    .find(|v| v.key == key)
-                    .unwrap();
-                
-                let var_id = var.id.ok_or("Variable ID is missing")?;
-                
-                operations::variables::update_variable_encrypted(
-                    db.connection(),
-                    var_id,
-                    key.to_string(),
-                    value.to_string(),
-                    None,
-                    &encryption_key,
-                )
-                .map_err(|e| format!("Failed to update variable '{}': {}", key, e))?;
-                
-                updated_count += 1;
-            } else {
-                // Create new using encrypted helper
-                operations::variables::create_variable_encrypted(
-                    db.connection(),
-                    environment_id,
-                    key.to_string(),
-                    value.to_string(),
-                    None,
-                    &encryption_key,
-                )
-                .map_err(|e| format!("Failed to create variable '{}': {}", key, e))?;
-                
-                imported_count += 1;
+            entries.push((key.to_string(), value.to_string(), None));
+        }
+    }
+    
+    entries
+}
+
+/// Parse a `--since` value into a Unix timestamp
+///
+/// Accepts a duration back from `now` ("24h", "1 day ago", "2 weeks ago"),
+/// "today" (midnight UTC), a date (`2026-10-01`, midnight UTC) or an RFC 3339
+/// timestamp.
+fn parse_since(input: &str, now: chrono::DateTime<chrono::Utc>) -> Result<i64, String> {
+    let input = input.trim();
+    if input.eq_ignore_ascii_case("today") {
+        return Ok(now.date_naive().and_hms_opt(0, 0, 0).expect("midnight is valid").and_utc().timestamp());
+    }
+    if let Ok(date) = chrono::NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).expect("midnight is valid").and_utc().timestamp());
+    }
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(input) {
+        return Ok(time.timestamp());
+    }
+    
+    let invalid = || format!(
+        "Invalid --since '{}'. Use a duration (24h, \"1 day ago\"), \"today\", a date (2026-10-01) or an RFC 3339 time",
+        input
+    );
+    let duration = input.strip_suffix("ago").unwrap_or(input).trim();
+    let secs = match duration.split_once(char::is_whitespace) {
+        Some((amount, unit)) => {
+            let amount: i64 = amount.parse().map_err(|_| invalid())?;
+            let multiplier = match unit.trim().trim_end_matches('s') {
+                "second" | "sec" => 1,
+                "minute" | "min" => 60,
+                "hour" => 60 * 60,
+                "day" => 24 * 60 * 60,
+                "week" => 7 * 24 * 60 * 60,
+                _ => return Err(invalid()),
+            };
+            if amount <= 0 {
+                return Err(invalid());
             }
+            amount.checked_mul(multiplier).ok_or_else(invalid)?
         }
+        None => parse_duration_secs(duration).map_err(|_| invalid())?,
+    };
+    
+    now.timestamp().checked_sub(secs).ok_or_else(invalid)
+}
+
+/// Parse a short duration such as "45s", "30m", "24h" or "7d" into seconds
+fn parse_duration_secs(input: &str) -> Result<i64, String> {
+    let input = input.trim();
+    let invalid = || format!("Invalid duration '{}'. Use a number followed by s, m, h or d (e.g. 24h)", input);
+    
+    let split_at = input.len().checked_sub(1).ok_or_else(invalid)?;
+    let (amount, unit) = input.split_at(split_at);
+    let amount: i64 = amount.parse().map_err(|_| invalid())?;
+    
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    
+    if amount <= 0 {
+        return Err(invalid());
     }
     
-    println!("Import completed:");
-    println!("   Created: {}", imported_count);
-    println!("   Updated: {}", updated_count);
-    if skipped_count > 0 {
-        println!("   Skipped: {} (use --overwrite to update existing)", skipped_count);
+    amount.checked_mul(multiplier).ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Parse on a thread with a larger stack: the derived parser for this many
+    /// subcommands overflows the 2 MiB test threads in debug builds
+    fn parse(args: &'static [&'static str]) -> Cli {
+        std::thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(move || Cli::try_parse_from(args).unwrap())
+            .unwrap()
+            .join()
+            .unwrap()
     }
     
-    Ok(())
+    #[test]
+    fn test_subcommand_vault_dir_is_read_from_short_flag() {
+        let cli = parse(&["clerk", "lock", "-V", "/x"]);
+        assert_eq!(cli.command.vault_dir(), Some(PathBuf::from("/x")));
+    }
+    
+    #[test]
+    fn test_error_kinds_have_distinct_exit_statuses() {
+        let errors = [
+            CliError::NotFound(String::new()),
+            CliError::AlreadyExists(String::new()),
+            CliError::Protected(String::new()),
+            CliError::Locked(String::new()),
+            CliError::InvalidPassword(String::new()),
+            CliError::UnsupportedVersion(String::new()),
+            CliError::Other(String::new()),
+        ];
+        let statuses: std::collections::HashSet<i32> = errors.iter().map(CliError::exit_status).collect();
+        assert_eq!(statuses.len(), errors.len());
+        assert!(!statuses.contains(&0) && !statuses.contains(&2));
+        assert!(!statuses.contains(&EQUALS_MISMATCH_EXIT) && !statuses.contains(&EQUALS_MISSING_EXIT));
+        
+        // Plain messages are never classified by their wording
+        let error = CliError::from("Password file not found".to_string());
+        assert_eq!((error.code(), error.exit_status()), ("ERROR", 1));
+        assert_eq!(password_error(vault::INVALID_PASSWORD_ERROR.to_string()).code(), "INVALID_PASSWORD");
+    }
+    
+    #[test]
+    fn test_shell_quote_keeps_values_literal() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote("a\nb"), "'a\nb'");
+        assert_eq!(shell_quote("$(echo pwned)"), "'$(echo pwned)'");
+        
+        // The shell reads every quoted value back unchanged
+        #[cfg(unix)]
+        for value in ["", "it's", "a\nb", "$(echo pwned)", "`id` $HOME \\ \"x\" ''"] {
+            let output = process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf %s {}", shell_quote(value)))
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8(output.stdout).unwrap(), value);
+        }
+    }
+    
+    #[test]
+    fn test_direnv_hook_snippet_quotes_names() {
+        assert!(direnv_hook_snippet(None, None).contains("clerk direnv -p my-app -e dev"));
+        assert!(direnv_hook_snippet(Some("my app"), Some("it's $(x)"))
+            .contains("clerk direnv -p 'my app' -e 'it'\\''s $(x)'"));
+    }
 }
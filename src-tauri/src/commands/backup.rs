@@ -1,4 +1,5 @@
 use crate::commands::database::DatabaseState;
+use crate::commands::vault::vault_manager;
 use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
 use serde::{Deserialize, Serialize};
 use std::fs;
@@ -132,10 +133,17 @@ pub fn create_backup(
 }
 
 /// Restores a backup to the specified vault and database paths
+///
+/// The files are replaced under the open connection's feet, so this refuses
+/// while the vault is unlocked unless `force_lock` is set, in which case the
+/// vault is locked first. Either way the vault is locked afterwards (stored
+/// keys belong to the old vault) and has to be unlocked again.
 #[tauri::command]
 pub fn restore_backup(
     app: tauri::AppHandle,
+    state: State<DatabaseState>,
     backup_json: String,
+    force_lock: Option<bool>,
 ) -> Result<String, String> {
     // Get vault directory
     let vault_dir = app
@@ -164,6 +172,15 @@ pub fn restore_backup(
     let database_content = BASE64.decode(&backup.database_data)
         .map_err(|e| format!("Failed to decode database data: {}", e))?;
     
+    let unlocked = state.db.lock().map_err(|e| format!("Database lock error: {}", e))?.is_some();
+    if unlocked && !force_lock.unwrap_or(false) {
+        return Err("The vault is unlocked; lock it before restoring a backup".to_string());
+    }
+    
+    // Close the connection and drop the old vault's keys before its files change
+    state.clear()?;
+    vault_manager(&app)?.lock(true, true)?;
+    
     // Create backup of existing files if they exist
     if target_vault_path.exists() {
         let backup_vault = target_vault_path.with_extension("clerk.backup");
@@ -185,7 +202,7 @@ pub fn restore_backup(
         .map_err(|e| format!("Failed to write database file: {}", e))?;
     
     Ok(format!(
-        "Successfully restored backup. Projects: {}, Environments: {}, Variables: {}",
+        "Successfully restored backup. Projects: {}, Environments: {}, Variables: {}. Unlock the vault to continue.",
         backup.metadata.project_count,
         backup.metadata.environment_count,
         backup.metadata.variable_count
@@ -1,12 +1,21 @@
 use tauri::State;
 use serde::{Deserialize, Serialize};
 use std::sync::Mutex;
-use crate::database::{Database, operations};
+use std::sync::atomic::{AtomicBool, Ordering};
+use zeroize::Zeroize;
+use crate::database::{CompactReport, Database, migrations, operations};
+use super::value_cache::{ValueCache, VALUE_CACHE_CAPACITY};
 
 /// Shared database state
 pub struct DatabaseState {
     pub db: Mutex<Option<Database>>,
     pub encryption_key: Mutex<Option<[u8; 32]>>,
+    /// Ignore any keychain key for this run of the app (set by `--no-keychain`)
+    pub auto_unlock_disabled: AtomicBool,
+    /// The unlocked key is also stored in the OS keychain ("Remember Me")
+    pub keychain_backed: AtomicBool,
+    /// Decrypted variables of recently viewed environments
+    pub value_cache: Mutex<ValueCache>,
 }
 
 impl DatabaseState {
@@ -14,6 +23,56 @@ impl DatabaseState {
         Self {
             db: Mutex::new(None),
             encryption_key: Mutex::new(None),
+            auto_unlock_disabled: AtomicBool::new(false),
+            keychain_backed: AtomicBool::new(false),
+            value_cache: Mutex::new(ValueCache::new(VALUE_CACHE_CAPACITY)),
+        }
+    }
+    
+    /// Close the database and zero the in-memory encryption key and cached values
+    pub fn clear(&self) -> Result<(), String> {
+        self.keychain_backed.store(false, Ordering::SeqCst);
+        self.clear_value_cache()?;
+        
+        {
+            let mut db_guard = self.db.lock().map_err(|e| e.to_string())?;
+            *db_guard = None;
+        }
+        
+        let mut key_guard = self.encryption_key.lock().map_err(|e| e.to_string())?;
+        if let Some(key) = key_guard.as_mut() {
+            key.zeroize();
+        }
+        *key_guard = None;
+        
+        Ok(())
+    }
+    
+    /// Wipe cached values, e.g. before another vault's database is installed
+    pub fn clear_value_cache(&self) -> Result<(), String> {
+        self.value_cache.lock().map_err(|e| e.to_string())?.clear();
+        Ok(())
+    }
+}
+
+/// Copy a remote vault's working copy back to the share after a write
+///
+/// A no-op for local vaults. A conflict (the share changed since unlock) is
+/// returned so the GUI shows it; the change stays in the working copy.
+pub(crate) fn sync_working_copy(db: &mut Option<Database>) -> Result<(), String> {
+    match db.as_mut() {
+        Some(db) => db.sync()
+            .map(|_| ())
+            .map_err(|e| format!("Saved locally, but not written to the remote vault: {}", e)),
+        None => Ok(()),
+    }
+}
+
+impl Drop for DatabaseState {
+    /// Zero the key even if the state is dropped without an explicit lock
+    fn drop(&mut self) {
+        if let Ok(Some(key)) = self.encryption_key.get_mut().map(Option::as_mut) {
+            key.zeroize();
         }
     }
 }
@@ -40,12 +99,12 @@ pub async fn create_project(
     state: State<'_, DatabaseState>,
     request: CreateProjectRequest,
 ) -> Result<CreateProjectResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
     let project = operations::Project::new(request.name.clone(), request.description);
     
-    match operations::projects::create_project(db.connection(), &project) {
+    let response = match operations::projects::create_project(db.connection(), &project) {
         Ok(id) => Ok(CreateProjectResponse {
             success: true,
             project_id: Some(id),
@@ -56,7 +115,9 @@ pub async fn create_project(
             project_id: None,
             message: format!("Failed to create project: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -105,12 +166,12 @@ pub async fn update_project(
     state: State<'_, DatabaseState>,
     request: UpdateProjectRequest,
 ) -> Result<UpdateProjectResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
     let project = operations::Project::new(request.name.clone(), request.description);
     
-    match operations::projects::update_project(db.connection(), request.id, &project) {
+    let response = match operations::projects::update_project(db.connection(), request.id, &project) {
         Ok(_) => Ok(UpdateProjectResponse {
             success: true,
             message: "Project updated successfully".to_string(),
@@ -119,7 +180,9 @@ pub async fn update_project(
             success: false,
             message: format!("Failed to update project: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -138,10 +201,10 @@ pub async fn delete_project(
     state: State<'_, DatabaseState>,
     request: DeleteProjectRequest,
 ) -> Result<DeleteProjectResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
-    match operations::projects::delete_project(db.connection(), request.id) {
+    let response = match operations::projects::delete_project(db.connection(), request.id) {
         Ok(_) => Ok(DeleteProjectResponse {
             success: true,
             message: "Project deleted successfully".to_string(),
@@ -150,7 +213,9 @@ pub async fn delete_project(
             success: false,
             message: format!("Failed to delete project: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 // ============================================================================
@@ -176,7 +241,7 @@ pub async fn create_environment(
     state: State<'_, DatabaseState>,
     request: CreateEnvironmentRequest,
 ) -> Result<CreateEnvironmentResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
     let env = operations::Environment::new(
@@ -185,7 +250,7 @@ pub async fn create_environment(
         request.description,
     );
     
-    match operations::environments::create_environment(db.connection(), &env) {
+    let response = match operations::environments::create_environment(db.connection(), &env) {
         Ok(id) => Ok(CreateEnvironmentResponse {
             success: true,
             environment_id: Some(id),
@@ -196,7 +261,9 @@ pub async fn create_environment(
             environment_id: None,
             message: format!("Failed to create environment: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -252,7 +319,7 @@ pub async fn update_environment(
     state: State<'_, DatabaseState>,
     request: UpdateEnvironmentRequest,
 ) -> Result<UpdateEnvironmentResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
     let env = operations::Environment::new(
@@ -261,7 +328,7 @@ pub async fn update_environment(
         request.description,
     );
     
-    match operations::environments::update_environment(db.connection(), request.id, &env) {
+    let response = match operations::environments::update_environment(db.connection(), request.id, &env) {
         Ok(_) => Ok(UpdateEnvironmentResponse {
             success: true,
             message: "Environment updated successfully".to_string(),
@@ -270,7 +337,9 @@ pub async fn update_environment(
             success: false,
             message: format!("Failed to update environment: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -289,10 +358,10 @@ pub async fn delete_environment(
     state: State<'_, DatabaseState>,
     request: DeleteEnvironmentRequest,
 ) -> Result<DeleteEnvironmentResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
-    match operations::environments::delete_environment(db.connection(), request.id) {
+    let response = match operations::environments::delete_environment(db.connection(), request.id) {
         Ok(_) => Ok(DeleteEnvironmentResponse {
             success: true,
             message: "Environment deleted successfully".to_string(),
@@ -301,7 +370,84 @@ pub async fn delete_environment(
             success: false,
             message: format!("Failed to delete environment: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClearEnvironmentRequest {
+    pub environment_id: i64,
+    /// Required to delete protected variables
+    pub force: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ClearEnvironmentResponse {
+    pub success: bool,
+    pub deleted: usize,
+    pub message: String,
+}
+
+/// Delete every variable of an environment, keeping the environment
+#[tauri::command]
+pub async fn clear_environment(
+    state: State<'_, DatabaseState>,
+    request: ClearEnvironmentRequest,
+) -> Result<ClearEnvironmentResponse, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    let response = match operations::variables::delete_all_variables(db.connection(), request.environment_id, request.force.unwrap_or(false)) {
+        Ok(deleted) => Ok(ClearEnvironmentResponse {
+            success: true,
+            deleted: deleted.len(),
+            message: format!("{} variable(s) deleted", deleted.len()),
+        }),
+        Err(e) => Ok(ClearEnvironmentResponse {
+            success: false,
+            deleted: 0,
+            message: format!("Failed to clear environment: {}", e),
+        }),
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReorderEnvironmentsRequest {
+    pub project_id: i64,
+    /// Every environment of the project, first to last
+    pub environment_ids: Vec<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ReorderEnvironmentsResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Store a new environment order for a project (drag-and-drop in the tab bar)
+#[tauri::command]
+pub async fn reorder_environments(
+    state: State<'_, DatabaseState>,
+    request: ReorderEnvironmentsRequest,
+) -> Result<ReorderEnvironmentsResponse, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    let response = match operations::environments::set_environment_order(db.connection(), request.project_id, &request.environment_ids) {
+        Ok(()) => Ok(ReorderEnvironmentsResponse {
+            success: true,
+            message: "Environment order saved".to_string(),
+        }),
+        Err(e) => Ok(ReorderEnvironmentsResponse {
+            success: false,
+            message: format!("Failed to reorder environments: {}", e),
+        }),
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 // ============================================================================
@@ -314,6 +460,11 @@ pub struct CreateVariableRequest {
     pub key: String,
     pub value: String,
     pub description: Option<String>,
+    /// Defaults to text when omitted
+    pub value_type: Option<operations::ValueType>,
+    /// Update the variable instead of failing when the key already exists
+    #[serde(default)]
+    pub upsert: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -328,18 +479,44 @@ pub async fn create_variable(
     state: State<'_, DatabaseState>,
     request: CreateVariableRequest,
 ) -> Result<CreateVariableResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
     let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
     let encryption_key = key_guard.as_ref().ok_or("Encryption key not available")?;
     
-    match operations::variables::create_variable_encrypted(
+    if request.upsert {
+        let response = match operations::variables::upsert_variable_encrypted(
+            db.connection(),
+            request.environment_id,
+            request.key.clone(),
+            request.value,
+            request.description,
+            request.value_type,
+            encryption_key,
+        ) {
+            Ok((id, created)) => Ok(CreateVariableResponse {
+                success: true,
+                variable_id: Some(id),
+                message: format!("Variable '{}' {} successfully", request.key, if created { "created" } else { "updated" }),
+            }),
+            Err(e) => Ok(CreateVariableResponse {
+                success: false,
+                variable_id: None,
+                message: format!("Failed to save variable: {}", e),
+            }),
+        };
+        sync_working_copy(&mut db_guard)?;
+        return response;
+    }
+    
+    let response = match operations::variables::create_variable_encrypted_with_type(
         db.connection(),
         request.environment_id,
         request.key.clone(),
         request.value,
         request.description,
+        request.value_type.unwrap_or_default(),
         encryption_key,
     ) {
         Ok(id) => Ok(CreateVariableResponse {
@@ -352,12 +529,18 @@ pub async fn create_variable(
             variable_id: None,
             message: format!("Failed to create variable: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GetVariablesRequest {
     pub environment_id: i64,
+    /// Return the plaintext values; otherwise each one is `MASKED_VALUE` plus its length.
+    /// Only set for an explicit user action (show, copy, edit, export).
+    #[serde(default)]
+    pub reveal: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -378,16 +561,39 @@ pub async fn get_variables(
     let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
     let encryption_key = key_guard.as_ref().ok_or("Encryption key not available")?;
     
-    match operations::variables::get_variables_by_environment_decrypted(
+    let visible = |variables: Vec<operations::VariableDecrypted>| -> Vec<operations::VariableDecrypted> {
+        if request.reveal {
+            variables
+        } else {
+            variables.into_iter().map(operations::VariableDecrypted::masked).collect()
+        }
+    };
+    
+    let mut cache = state.value_cache.lock().map_err(|e| e.to_string())?;
+    let stamp = cache.watch(db.connection())?;
+    if let Some(variables) = cache.get(request.environment_id, stamp) {
+        let _ = migrations::update_last_accessed(db.connection());
+        return Ok(GetVariablesResponse {
+            success: true,
+            variables: visible(variables),
+            message: "Variables retrieved successfully".to_string(),
+        });
+    }
+    
+    // Lossy so one corrupted value doesn't hide the whole environment; it is flagged instead
+    match operations::variables::get_variables_by_environment_decrypted_lossy(
         db.connection(),
         request.environment_id,
         encryption_key,
     ) {
-        Ok(variables) => Ok(GetVariablesResponse {
-            success: true,
-            variables,
-            message: "Variables retrieved successfully".to_string(),
-        }),
+        Ok(variables) => {
+            cache.insert(request.environment_id, stamp, variables.clone());
+            Ok(GetVariablesResponse {
+                success: true,
+                variables: visible(variables),
+                message: "Variables retrieved successfully".to_string(),
+            })
+        }
         Err(e) => Ok(GetVariablesResponse {
             success: false,
             variables: vec![],
@@ -402,6 +608,10 @@ pub struct UpdateVariableRequest {
     pub key: String,
     pub value: String,
     pub description: Option<String>,
+    /// Keeps the stored type when omitted
+    pub value_type: Option<operations::ValueType>,
+    /// Required to change a protected variable
+    pub force: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -415,18 +625,20 @@ pub async fn update_variable(
     state: State<'_, DatabaseState>,
     request: UpdateVariableRequest,
 ) -> Result<UpdateVariableResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
     let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
     let encryption_key = key_guard.as_ref().ok_or("Encryption key not available")?;
     
-    match operations::variables::update_variable_encrypted(
+    let response = match operations::variables::update_variable_encrypted_with_type(
         db.connection(),
         request.id,
         request.key.clone(),
         request.value,
         request.description,
+        request.value_type,
+        request.force.unwrap_or(false),
         encryption_key,
     ) {
         Ok(_) => Ok(UpdateVariableResponse {
@@ -437,12 +649,16 @@ pub async fn update_variable(
             success: false,
             message: format!("Failed to update variable: {}", e),
         }),
-    }
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DeleteVariableRequest {
     pub id: i64,
+    /// Required to delete a protected variable
+    pub force: Option<bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -456,10 +672,10 @@ pub async fn delete_variable(
     state: State<'_, DatabaseState>,
     request: DeleteVariableRequest,
 ) -> Result<DeleteVariableResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
-    match operations::variables::delete_variable(db.connection(), request.id) {
+    let response = match operations::variables::delete_variable(db.connection(), request.id, request.force.unwrap_or(false)) {
         Ok(_) => Ok(DeleteVariableResponse {
             success: true,
             message: "Variable deleted successfully".to_string(),
@@ -468,6 +684,88 @@ pub async fn delete_variable(
             success: false,
             message: format!("Failed to delete variable: {}", e),
         }),
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetVariableProtectedRequest {
+    pub id: i64,
+    pub protected: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SetVariableProtectedResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+#[tauri::command]
+pub async fn set_variable_protected(
+    state: State<'_, DatabaseState>,
+    request: SetVariableProtectedRequest,
+) -> Result<SetVariableProtectedResponse, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    let response = match operations::variables::set_variable_protected(db.connection(), request.id, request.protected) {
+        Ok(_) => Ok(SetVariableProtectedResponse {
+            success: true,
+            message: if request.protected { "Variable protected" } else { "Variable unprotected" }.to_string(),
+        }),
+        Err(e) => Ok(SetVariableProtectedResponse {
+            success: false,
+            message: format!("Failed to update variable protection: {}", e),
+        }),
+    };
+    sync_working_copy(&mut db_guard)?;
+    response
+}
+
+/// Maximum number of key suggestions returned to the editor
+const MAX_KEY_SUGGESTIONS: usize = 20;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestKeysRequest {
+    pub environment_id: i64,
+    pub prefix: String,
+    /// Also suggest keys used in other environments
+    pub across_vault: Option<bool>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SuggestKeysResponse {
+    pub success: bool,
+    pub keys: Vec<String>,
+    pub message: String,
+}
+
+#[tauri::command]
+pub async fn suggest_keys(
+    state: State<'_, DatabaseState>,
+    request: SuggestKeysRequest,
+) -> Result<SuggestKeysResponse, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    match operations::variables::suggest_keys(
+        db.connection(),
+        request.environment_id,
+        &request.prefix,
+        request.across_vault.unwrap_or(false),
+        MAX_KEY_SUGGESTIONS,
+    ) {
+        Ok(keys) => Ok(SuggestKeysResponse {
+            success: true,
+            keys,
+            message: "Key suggestions retrieved successfully".to_string(),
+        }),
+        Err(e) => Ok(SuggestKeysResponse {
+            success: false,
+            keys: vec![],
+            message: format!("Failed to suggest keys: {}", e),
+        }),
     }
 }
 
@@ -480,6 +778,7 @@ pub struct DashboardStats {
     pub project_count: usize,
     pub environment_count: usize,
     pub variable_count: usize,
+    pub storage: operations::stats::StorageStats,
 }
 
 #[tauri::command]
@@ -491,21 +790,30 @@ pub async fn get_dashboard_stats(
     
     let conn = db.connection();
     
-    let project_count: usize = conn
-        .query_row("SELECT COUNT(*) FROM projects", [], |row| row.get(0))
-        .unwrap_or(0);
-    
-    let environment_count: usize = conn
-        .query_row("SELECT COUNT(*) FROM environments", [], |row| row.get(0))
-        .unwrap_or(0);
+    let counts = operations::stats::vault_counts(conn).unwrap_or_default();
     
-    let variable_count: usize = conn
-        .query_row("SELECT COUNT(*) FROM variables", [], |row| row.get(0))
-        .unwrap_or(0);
+    let storage = operations::stats::storage_stats(conn)
+        .map_err(|e| format!("Failed to compute storage stats: {}", e))?;
     
     Ok(DashboardStats {
-        project_count,
-        environment_count,
-        variable_count,
+        project_count: counts.project_count,
+        environment_count: counts.environment_count,
+        variable_count: counts.variable_count,
+        storage,
     })
 }
+
+/// Run `VACUUM` on the open vault to give back space freed by deletions
+///
+/// The database lock is held throughout, so no other command writes meanwhile.
+#[tauri::command]
+pub async fn compact_database(
+    state: State<'_, DatabaseState>,
+) -> Result<CompactReport, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    let report = db.compact().map_err(|e| format!("Failed to compact database: {}", e))?;
+    sync_working_copy(&mut db_guard)?;
+    Ok(report)
+}
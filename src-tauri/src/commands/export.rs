@@ -1,5 +1,6 @@
 use tauri::State;
-use crate::DatabaseState;
+use crate::commands::database::{sync_working_copy, DatabaseState};
+use crate::database::operations::MASKED_VALUE;
 use std::fs;
 use std::path::Path;
 
@@ -91,10 +92,11 @@ pub fn export_env(
 
     for variable in variables {
         // Escape value if it contains special characters
-        let value = if variable.value.contains(' ') || variable.value.contains('"') {
-            format!("\"{}\"", variable.value.replace('"', "\\\""))
+        let raw = variable.value.expose();
+        let value = if raw.contains(' ') || raw.contains('"') {
+            format!("\"{}\"", raw.replace('"', "\\\""))
         } else {
-            variable.value.clone()
+            raw.to_string()
         };
         
         content.push_str(&format!("{}={}\n", variable.key, value));
@@ -103,7 +105,11 @@ pub fn export_env(
     Ok(ExportEnvResponse {
         success: true,
         content: Some(content),
-        message: format!("Exported {} variables", var_count),
+        message: if var_count == 0 {
+            "Environment has no variables; the export is empty".to_string()
+        } else {
+            format!("Exported {} variables", var_count)
+        },
     })
 }
 
@@ -112,7 +118,7 @@ pub fn import_env(
     request: ImportEnvRequest,
     state: State<DatabaseState>,
 ) -> Result<ImportEnvResponse, String> {
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
     
     let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
@@ -172,6 +178,7 @@ pub fn import_env(
                                 key_str.to_string(),
                                 final_value,
                                 None,
+                                false,
                                 key,
                             ).is_ok() {
                                 imported_count += 1;
@@ -197,6 +204,8 @@ pub fn import_env(
             errors.join(", ")
         )
     };
+    
+    sync_working_copy(&mut db_guard)?;
 
     Ok(ImportEnvResponse {
         success: true,
@@ -237,10 +246,11 @@ pub fn export_env_to_file(
 
     for variable in variables {
         // Escape value if it contains special characters
-        let value = if variable.value.contains(' ') || variable.value.contains('"') {
-            format!("\"{}\"", variable.value.replace('"', "\\\""))
+        let raw = variable.value.expose();
+        let value = if raw.contains(' ') || raw.contains('"') {
+            format!("\"{}\"", raw.replace('"', "\\\""))
         } else {
-            variable.value.clone()
+            raw.to_string()
         };
         
         content.push_str(&format!("{}={}\n", variable.key, value));
@@ -251,5 +261,72 @@ pub fn export_env_to_file(
     fs::write(path, content)
         .map_err(|e| format!("Failed to write file: {}", e))?;
 
+    if var_count == 0 {
+        return Ok(format!("Environment has no variables; wrote an empty export to {}", file_path));
+    }
+    
     Ok(format!("Exported {} variables to {}", var_count, file_path))
 }
+
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct EnvironmentJsonRequest {
+    pub environment_id: i64,
+    /// Include decrypted values instead of the mask
+    pub reveal: Option<bool>,
+}
+
+#[derive(serde::Serialize)]
+pub struct EnvironmentJsonResponse {
+    pub success: bool,
+    /// Flat `{"KEY": "value"}` object, keys sorted
+    pub variables: Option<serde_json::Map<String, serde_json::Value>>,
+    pub message: String,
+}
+
+/// Return an environment as a single `{"KEY": "value"}` JSON object
+///
+/// Values are masked unless `reveal` is set, in which case the output matches
+/// `clerk export --format json`. Masked output never decrypts anything.
+#[tauri::command]
+pub fn get_environment_as_json(
+    request: EnvironmentJsonRequest,
+    state: State<DatabaseState>,
+) -> Result<EnvironmentJsonResponse, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    
+    let variables: Vec<(String, String)> = if request.reveal.unwrap_or(false) {
+        let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
+        let key = key_guard.as_ref().ok_or("Encryption key not available")?;
+        
+        crate::database::operations::variables::get_variables_by_environment_decrypted(
+            db.connection(),
+            request.environment_id,
+            key,
+        )
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|v| (v.key, v.value.into_exposed()))
+        .collect()
+    } else {
+        crate::database::operations::variables::get_variables_by_environment(
+            db.connection(),
+            request.environment_id,
+        )
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|v| (v.key, MASKED_VALUE.to_string()))
+        .collect()
+    };
+    
+    let count = variables.len();
+    let object = variables.into_iter()
+        .map(|(key, value)| (key, serde_json::Value::String(value)))
+        .collect();
+    
+    Ok(EnvironmentJsonResponse {
+        success: true,
+        variables: Some(object),
+        message: format!("Loaded {} variables", count),
+    })
+}
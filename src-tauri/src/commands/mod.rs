@@ -6,6 +6,7 @@ pub mod export;
 pub mod audit;
 pub mod backup;
 pub mod system;
+pub mod value_cache;
 
 /// Example command that will be callable from the frontend
 #[tauri::command]
@@ -0,0 +1,151 @@
+use rusqlite::Connection;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use zeroize::Zeroize;
+use crate::database::operations::VariableDecrypted;
+
+/// Number of environments whose decrypted variables are kept
+pub const VALUE_CACHE_CAPACITY: usize = 8;
+
+/// Tables whose rows end up in a `VariableDecrypted` (or its AAD)
+const WATCHED_TABLES: [&str; 3] = ["variables", "environments", "projects"];
+
+/// Point in the database's write history an entry was decrypted at
+///
+/// `generation` counts writes to the watched tables made through this
+/// connection and `data_version` changes whenever another connection (such as
+/// the CLI) commits, so any write that could change a variable makes older
+/// stamps unequal. Bookkeeping writes such as `last_accessed` or the audit log
+/// leave it alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChangeStamp {
+    generation: u64,
+    data_version: i64,
+}
+
+struct CachedEnvironment {
+    environment_id: i64,
+    stamp: ChangeStamp,
+    variables: Vec<VariableDecrypted>,
+}
+
+impl Drop for CachedEnvironment {
+    /// Values zeroize themselves; descriptions are wiped here
+    fn drop(&mut self) {
+        for variable in &mut self.variables {
+            if let Some(description) = variable.description.as_mut() {
+                description.zeroize();
+            }
+        }
+    }
+}
+
+/// Decrypted variables of recently viewed environments in the GUI session
+///
+/// Least recently used entries are evicted first. An entry is only returned
+/// while the database is unchanged since it was filled, so writes made
+/// anywhere (including through the CLI) invalidate it; `clear` drops
+/// everything when the vault is locked or another one is opened.
+pub struct ValueCache {
+    /// Most recently used last
+    entries: Vec<CachedEnvironment>,
+    capacity: usize,
+    /// Bumped by the update hook `watch` installs
+    generation: Arc<AtomicU64>,
+}
+
+impl ValueCache {
+    pub fn new(capacity: usize) -> Self {
+        ValueCache { entries: Vec::new(), capacity, generation: Arc::new(AtomicU64::new(0)) }
+    }
+    
+    /// Start counting writes on `conn` and return its current stamp
+    ///
+    /// Installing the hook again replaces the previous one, so this is called
+    /// on every read rather than tracking which connection already has it.
+    pub fn watch(&self, conn: &Connection) -> Result<ChangeStamp, String> {
+        let generation = Arc::clone(&self.generation);
+        conn.update_hook(Some(move |_, _: &str, table: &str, _| {
+            if WATCHED_TABLES.contains(&table) {
+                generation.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+        
+        let data_version = conn
+            .query_row("PRAGMA data_version", [], |row| row.get(0))
+            .map_err(|e| format!("Failed to read data version: {}", e))?;
+        Ok(ChangeStamp { generation: self.generation.load(Ordering::SeqCst), data_version })
+    }
+    
+    /// Cached variables of an environment, if still valid at `stamp`
+    pub fn get(&mut self, environment_id: i64, stamp: ChangeStamp) -> Option<Vec<VariableDecrypted>> {
+        let index = self.entries.iter().position(|entry| entry.environment_id == environment_id)?;
+        let entry = self.entries.remove(index);
+        if entry.stamp != stamp {
+            return None;
+        }
+        let variables = entry.variables.clone();
+        self.entries.push(entry);
+        Some(variables)
+    }
+    
+    pub fn insert(&mut self, environment_id: i64, stamp: ChangeStamp, variables: Vec<VariableDecrypted>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.entries.retain(|entry| entry.environment_id != environment_id);
+        if self.entries.len() >= self.capacity {
+            self.entries.remove(0);
+        }
+        self.entries.push(CachedEnvironment { environment_id, stamp, variables });
+    }
+    
+    /// Drop every entry, wiping the decrypted values
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, operations::{Environment, Project, environments, projects, variables}};
+
+    #[test]
+    fn test_value_cache_is_invalidated_by_writes_and_evicts_oldest() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        let key = [9u8; 32];
+        
+        let app = projects::create_project(conn, &Project::new("app".to_string(), None)).unwrap();
+        let dev = environments::create_environment(conn, &Environment::new(app, "dev".to_string(), None)).unwrap();
+        variables::create_variable_encrypted(conn, dev, "A".to_string(), "1".to_string(), None, &key).unwrap();
+        
+        let mut cache = ValueCache::new(2);
+        let stamp = cache.watch(conn).unwrap();
+        let decrypted = variables::get_variables_by_environment_decrypted_lossy(conn, dev, &key).unwrap();
+        cache.insert(dev, stamp, decrypted);
+        
+        // Reading touched last_accessed, which doesn't count as a change
+        let stamp = cache.watch(conn).unwrap();
+        assert_eq!(cache.get(dev, stamp).unwrap()[0].value.expose(), "1");
+        
+        // A write to the variables moves the stamp on, so the stale entry is dropped
+        variables::create_variable_encrypted(conn, dev, "B".to_string(), "2".to_string(), None, &key).unwrap();
+        let stamp = cache.watch(conn).unwrap();
+        assert!(cache.get(dev, stamp).is_none());
+        assert!(cache.get(dev, stamp).is_none());
+        
+        // The least recently used environment goes first
+        cache.insert(1, stamp, Vec::new());
+        cache.insert(2, stamp, Vec::new());
+        cache.get(1, stamp).unwrap();
+        cache.insert(3, stamp, Vec::new());
+        assert!(cache.get(2, stamp).is_none());
+        assert!(cache.get(1, stamp).is_some() && cache.get(3, stamp).is_some());
+        
+        cache.clear();
+        assert!(cache.get(1, stamp).is_none());
+    }
+}
@@ -1,9 +1,29 @@
-use crate::crypto::{derive_key, generate_salt, hash_password, verify_password};
-use crate::database::Database;
-use crate::commands::database::DatabaseState;
-use crate::keychain::KeychainManager;
-use serde::{Deserialize, Serialize};
-use tauri::{AppHandle, Manager, State};
+use crate::crypto::{verify_password, KdfParams};
+use crate::crypto::NonceMode;
+use crate::database::{Database, DatabaseError, migrations, operations::{quota::{self, Quota}, variables}};
+use crate::commands::database::{sync_working_copy, DatabaseState};
+use crate::keychain::{KeychainError, KeychainManager, KEYCHAIN_LOCKED_ERROR};
+use crate::vault::{self, VaultManager};
+use serde::Serialize;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use zeroize::{Zeroize, Zeroizing};
+
+/// Exit lock policy that only clears in-memory state
+const EXIT_LOCK_SOFT: &str = "soft";
+
+/// Exit lock policy that also removes the stored keychain entry
+const EXIT_LOCK_HARD: &str = "hard";
+
+/// Command-line flag that disables auto-unlock for the whole app session
+pub const NO_KEYCHAIN_FLAG: &str = "--no-keychain";
+
+/// Event emitted when the keychain entry behind "Remember Me" stops being usable
+pub const KEYCHAIN_UNAVAILABLE_EVENT: &str = "keychain-unavailable";
+
+/// How often the keychain entry is re-checked while the vault is unlocked
+const KEYCHAIN_CHECK_INTERVAL: Duration = Duration::from_secs(60);
 
 /// Response for vault creation
 #[derive(Serialize)]
@@ -17,78 +37,84 @@ pub struct CreateVaultResponse {
 pub struct UnlockVaultResponse {
     pub success: bool,
     pub message: String,
+    /// Unlocked, but "Remember Me" couldn't store the key in the OS keychain
+    pub remember_failed: bool,
+}
+
+/// Response for keychain repair
+#[derive(Serialize)]
+pub struct RepairKeychainResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Response for a master password change
+#[derive(Serialize)]
+pub struct RotatePasswordResponse {
+    pub success: bool,
+    pub message: String,
+    /// Number of values re-encrypted under the new key
+    pub reencrypted: usize,
+}
+
+/// Payload of `KEYCHAIN_UNAVAILABLE_EVENT`
+#[derive(Clone, Serialize)]
+pub struct KeychainUnavailablePayload {
+    pub message: String,
+}
+
+/// Response for vault metadata (timestamps are unix seconds)
+#[derive(Serialize)]
+pub struct VaultMetadataResponse {
+    pub version: i64,
+    pub created_at: i64,
+    pub last_accessed: i64,
+    pub last_modified: i64,
+    pub lock_timeout_minutes: i64,
+}
+
+/// The GUI's vault, in the app data directory
+pub(crate) fn vault_manager(app: &AppHandle) -> Result<VaultManager, String> {
+    let vault_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    
+    Ok(VaultManager::new(vault_dir))
 }
 
 /// Creates a new encrypted vault
 /// 
 /// # Arguments
 /// * `password` - Master password for the vault
-/// * `vault_path` - Optional custom path for vault file (defaults to app data dir)
+/// * `kdf_memory` - Optional Argon2id memory cost in KiB (defaults to 64 MiB)
+/// * `kdf_iterations` - Optional Argon2id iteration count (defaults to 3)
 #[tauri::command]
 pub async fn create_vault(
     app: AppHandle,
     state: State<'_, DatabaseState>,
     password: String,
+    kdf_memory: Option<u32>,
+    kdf_iterations: Option<u32>,
 ) -> Result<CreateVaultResponse, String> {
     // Validate password strength
     if password.len() < 8 {
         return Err("Password must be at least 8 characters long".to_string());
     }
 
-    // Get vault path in app data directory
-    let vault_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    
-    // Create directory if it doesn't exist
-    std::fs::create_dir_all(&vault_dir)
-        .map_err(|e| format!("Failed to create vault directory: {}", e))?;
-    
-    let vault_path = vault_dir.join("vault.clerk");
-    
-    // Check if vault already exists
-    if vault_path.exists() {
+    // Validate KDF overrides before touching the filesystem
+    let kdf_params = KdfParams::with_overrides(kdf_memory, kdf_iterations)?;
+
+    let manager = vault_manager(&app)?;
+    if manager.exists() {
         return Err("Vault already exists. Please unlock it instead.".to_string());
     }
 
-    // Generate salt for key derivation
-    let salt = generate_salt()
-        .map_err(|_| "Failed to generate salt".to_string())?;
-    
-    // Hash password for verification
-    let password_hash = hash_password(&password)
-        .map_err(|e| format!("Failed to hash password: {}", e))?;
-
-    // Create vault metadata
-    let metadata = VaultMetadata {
-        version: 1,
-        salt: salt.to_vec(),
-        password_hash,
-        created_at: chrono::Utc::now().timestamp(),
-    };
-
-    // Save metadata to file
-    let metadata_json = serde_json::to_string_pretty(&metadata)
-        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
-    
-    std::fs::write(&vault_path, metadata_json)
-        .map_err(|e| format!("Failed to write vault file: {}", e))?;
-
-    // Derive encryption key
-    let encryption_key = derive_key(&password, &salt)
-        .map_err(|e| format!("Failed to derive key: {}", e))?;
-
-    // Initialize database
-    let db_path = vault_dir.join("vault.db");
-    let db = Database::new(&db_path)
-        .map_err(|e| format!("Failed to create database: {}", e))?;
-    
-    // Run migrations
-    db.initialize()
-        .map_err(|e| format!("Failed to initialize database: {}", e))?;
+    // Writes the metadata, creates the database and stores its key canary
+    let (db, _, encryption_key) = manager.create(&password, kdf_params)?;
 
-    // Store database and encryption key in app state
+    // Store database and encryption key in app state; nothing cached may outlive the old one
+    state.clear_value_cache()?;
     {
         let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
         *db_guard = Some(db);
@@ -101,64 +127,35 @@ pub async fn create_vault(
 
     Ok(CreateVaultResponse {
         success: true,
-        message: format!("Vault created successfully at: {}", vault_path.display()),
+        message: format!("Vault created successfully at: {}", manager.metadata_path().display()),
     })
 }
 
 /// Unlocks an existing vault
+///
+/// If vault.db is missing, fails with `vault::DATABASE_MISSING_ERROR` unless
+/// `recreate_database` confirms that an empty database should be created.
 #[tauri::command]
 pub async fn unlock_vault(
     app: AppHandle,
     state: State<'_, DatabaseState>,
     password: String,
     remember_me: Option<bool>,
+    recreate_database: Option<bool>,
 ) -> Result<UnlockVaultResponse, String> {
-    // Get vault path
-    let vault_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    
-    let vault_path = vault_dir.join("vault.clerk");
+    let manager = vault_manager(&app)?;
     
     // Check if vault exists
-    if !vault_path.exists() {
+    if !manager.exists() {
         return Err("Vault does not exist. Please create one first.".to_string());
     }
 
-    // Read vault metadata
-    let metadata_json = std::fs::read_to_string(&vault_path)
-        .map_err(|e| format!("Failed to read vault file: {}", e))?;
-    
-    let metadata: VaultMetadata = serde_json::from_str(&metadata_json)
-        .map_err(|e| format!("Failed to parse vault metadata: {}", e))?;
-
-    // Verify password
-    let is_valid = verify_password(&password, &metadata.password_hash)
-        .map_err(|e| format!("Failed to verify password: {}", e))?;
-
-    if !is_valid {
-        return Err("Invalid password".to_string());
-    }
-
-    // Derive encryption key
-    let salt_array: [u8; 16] = metadata.salt
-        .try_into()
-        .map_err(|_| "Invalid salt length".to_string())?;
-    
-    let encryption_key = derive_key(&password, &salt_array)
-        .map_err(|e| format!("Failed to derive key: {}", e))?;
-
-    // Initialize database
-    let db_path = vault_dir.join("vault.db");
-    let db = Database::new(&db_path)
-        .map_err(|e| format!("Failed to open database: {}", e))?;
-    
-    // Run migrations
-    db.initialize()
-        .map_err(|e| format!("Failed to initialize database: {}", e))?;
+    // Verifies the password, checks the key against the database's canary,
+    // migrates it and upgrades legacy AAD values
+    let (db, _, encryption_key) = manager.unlock(&password, recreate_database.unwrap_or(false))?;
 
-    // Store database and encryption key in app state
+    // Store database and encryption key in app state; nothing cached may outlive the old one
+    state.clear_value_cache()?;
     {
         let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
         *db_guard = Some(db);
@@ -169,16 +166,26 @@ pub async fn unlock_vault(
         *key_guard = Some(encryption_key);
     }
 
-    // If remember_me is true, save key to OS keychain
+    // If remember_me is true, save key to OS keychain. The vault is already
+    // unlocked, so a missing keychain (e.g. no Secret Service on Linux) only
+    // costs the auto-unlock next time and must not fail the unlock.
     if remember_me.unwrap_or(false) {
         let keychain = KeychainManager::new();
-        keychain.save_key(&encryption_key)
-            .map_err(|e| format!("Failed to save key to keychain: {}", e))?;
+        if let Err(e) = keychain.save_key(&encryption_key) {
+            log::warn!("Remember Me failed, key not saved to keychain: {}", e);
+            return Ok(UnlockVaultResponse {
+                success: true,
+                message: format!("Vault unlocked, but the key could not be saved to the OS keychain ({}). You will need your password next time.", e),
+                remember_failed: true,
+            });
+        }
+        state.keychain_backed.store(true, Ordering::SeqCst);
     }
 
     Ok(UnlockVaultResponse {
         success: true,
         message: "Vault unlocked successfully".to_string(),
+        remember_failed: false,
     })
 }
 
@@ -190,19 +197,17 @@ pub async fn auto_unlock(
     app: AppHandle,
     state: State<'_, DatabaseState>,
 ) -> Result<UnlockVaultResponse, String> {
-    // Get vault path
-    let vault_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
-    
-    let vault_path = vault_dir.join("vault.clerk");
+    let manager = vault_manager(&app)?;
     
     // Check if vault exists
-    if !vault_path.exists() {
+    if !manager.exists() {
         return Err("Vault does not exist".to_string());
     }
 
+    if state.auto_unlock_disabled.load(Ordering::SeqCst) {
+        return Err("Auto-unlock is disabled for this session. Please unlock manually.".to_string());
+    }
+
     // Try to get encryption key from keychain
     let keychain = KeychainManager::new();
     let encryption_key = match keychain.get_key() {
@@ -210,21 +215,35 @@ pub async fn auto_unlock(
         Ok(None) => {
             return Err("No stored key found. Please unlock manually.".to_string());
         },
+        Err(KeychainError::Decode(e)) => {
+            return Err(format!("Stored key is unreadable ({}). Unlock manually and repair Remember Me.", e));
+        },
+        Err(KeychainError::Locked) => {
+            return Err(format!("{}. Unlock it and restart Clerk, or enter your password.", KEYCHAIN_LOCKED_ERROR));
+        },
+        Err(KeychainError::AccessDenied(e)) => {
+            return Err(format!("Access to the OS keychain was denied ({}). Please unlock manually.", e));
+        },
         Err(e) => {
             return Err(format!("Failed to access keychain: {}", e));
         }
     };
 
-    // Initialize database with stored key
-    let db_path = vault_dir.join("vault.db");
-    let db = Database::new(&db_path)
-        .map_err(|e| format!("Failed to open database: {}", e))?;
-    
-    // Run migrations
-    db.initialize()
-        .map_err(|e| format!("Failed to initialize database: {}", e))?;
+    // A stale keychain entry (e.g. saved before a password change) would open the
+    // database but fail on every read, so check the key before accepting it
+    let db = match manager.open_with_key(&encryption_key, false) {
+        Ok(db) => db,
+        Err(DatabaseError::KeyMismatch) => {
+            let _ = keychain.delete_key();
+            return Err("Stored key does not match this vault. Please unlock manually.".to_string());
+        },
+        Err(e) => {
+            return Err(format!("Failed to verify stored key: {}", e));
+        }
+    };
 
-    // Store database and encryption key in app state
+    // Store database and encryption key in app state; nothing cached may outlive the old one
+    state.clear_value_cache()?;
     {
         let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
         *db_guard = Some(db);
@@ -234,46 +253,278 @@ pub async fn auto_unlock(
         let mut key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
         *key_guard = Some(encryption_key);
     }
+    state.keychain_backed.store(true, Ordering::SeqCst);
 
     Ok(UnlockVaultResponse {
         success: true,
         message: "Vault auto-unlocked successfully".to_string(),
+        remember_failed: false,
     })
 }
 
+/// Replaces the keychain entry with the currently unlocked key
+///
+/// Used after a manual unlock when the OS keychain was reset or the stored
+/// entry became unreadable, so "Remember Me" works again.
+#[tauri::command]
+pub async fn repair_keychain(
+    state: State<'_, DatabaseState>,
+) -> Result<RepairKeychainResponse, String> {
+    let encryption_key = {
+        let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
+        *key_guard.as_ref()
+            .ok_or("Vault is locked. Please unlock it manually first.")?
+    };
+
+    let keychain = KeychainManager::new();
+
+    // Remove the stale entry first; some backends refuse to overwrite a corrupt one
+    keychain.delete_key()?;
+    keychain.save_key(&encryption_key)?;
+
+    // Read back to confirm the new entry is usable
+    match keychain.get_key() {
+        Ok(Some(stored)) if stored == encryption_key => {
+            state.keychain_backed.store(true, Ordering::SeqCst);
+            Ok(RepairKeychainResponse {
+                success: true,
+                message: "Keychain entry repaired".to_string(),
+            })
+        },
+        Ok(_) => Err("Keychain entry could not be verified after saving".to_string()),
+        Err(e) => Err(format!("Keychain entry could not be verified: {}", e)),
+    }
+}
+
+/// Why the keychain entry behind this session can no longer unlock the vault
+///
+/// Returns `None` when the session does not rely on the keychain, the vault is
+/// locked, or the stored key still matches the unlocked one.
+pub fn keychain_unavailable_reason(state: &DatabaseState) -> Option<String> {
+    if !state.keychain_backed.load(Ordering::SeqCst) {
+        return None;
+    }
+    
+    // Read the keychain before taking the key lock; the read may block on an OS prompt
+    let stored = KeychainManager::new().get_key();
+    
+    let key_guard = state.encryption_key.lock().ok()?;
+    let encryption_key = key_guard.as_ref()?;
+    
+    match stored {
+        Ok(Some(mut stored)) => {
+            let matches = stored == *encryption_key;
+            stored.zeroize();
+            (!matches).then(|| "The stored key no longer matches this vault".to_string())
+        },
+        Ok(None) => Some("The stored key was removed from the OS keychain".to_string()),
+        Err(e) => Some(e.to_string()),
+    }
+}
+
+/// Periodically re-check the keychain entry of a "Remember Me" session
+///
+/// The unlocked session keeps working from memory, but the next auto-unlock
+/// would fail. Emitting `KEYCHAIN_UNAVAILABLE_EVENT` right away lets the UI
+/// offer a repair instead. The event is sent once; a successful unlock with
+/// Remember Me or `repair_keychain` re-arms the check.
+pub fn spawn_keychain_monitor(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(KEYCHAIN_CHECK_INTERVAL);
+        
+        let state = app.state::<DatabaseState>();
+        if let Some(message) = keychain_unavailable_reason(&state) {
+            state.keychain_backed.store(false, Ordering::SeqCst);
+            let _ = app.emit(KEYCHAIN_UNAVAILABLE_EVENT, KeychainUnavailablePayload { message });
+        }
+    });
+}
+
 /// Checks if a vault exists
 #[tauri::command]
 pub async fn check_vault_exists(app: AppHandle) -> Result<bool, String> {
-    let vault_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+    Ok(vault_manager(&app)?.exists())
+}
+
+/// Password hint for the unlock screen (`None` if unset); readable while locked
+#[tauri::command]
+pub async fn get_password_hint(app: AppHandle) -> Result<Option<String>, String> {
+    Ok(vault_manager(&app)?.password_hint())
+}
+
+/// Set or remove (`None` or blank) the password hint of the unlocked vault
+#[tauri::command]
+pub async fn set_password_hint(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    hint: Option<String>,
+) -> Result<(), String> {
+    let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
+    let key = key_guard.as_ref().ok_or("Vault is locked. Please unlock it first.")?;
+    
+    vault_manager(&app)?.set_password_hint(key, hint.as_deref())
+}
+
+/// Checks a password against the vault without unlocking it
+///
+/// Only `vault.clerk` is read; the database and `DatabaseState` are left alone,
+/// so the GUI can use this for step-up confirmation before sensitive changes.
+#[tauri::command]
+pub async fn verify_current_password(app: AppHandle, password: String) -> Result<bool, String> {
+    let manager = vault_manager(&app)?;
+    if !manager.exists() {
+        return Err("Vault does not exist. Please create one first.".to_string());
+    }
+    
+    let metadata = manager.metadata()?;
     
-    let vault_path = vault_dir.join("vault.clerk");
-    Ok(vault_path.exists())
+    verify_password(&password, &metadata.password_hash)
+        .map_err(|e| format!("Failed to verify password: {}", e))
+}
+
+/// Changes the master password of the unlocked vault, re-encrypting every value
+///
+/// `old_password` is verified first and all values are re-encrypted in one
+/// transaction (see `VaultManager::change_password`). The GUI's connection is
+/// closed meanwhile and replaced by one on the new key (see `replace_open_vault`).
+/// CLI sessions still hold the old key and are removed; a "Remember Me" keychain
+/// entry gets the new key.
+#[tauri::command]
+pub async fn rotate_password(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    old_password: String,
+    new_password: String,
+) -> Result<RotatePasswordResponse, String> {
+    if new_password.len() < 8 {
+        return Err("Password must be at least 8 characters long".to_string());
+    }
+    if new_password == old_password {
+        return Err("The new password must differ from the current one".to_string());
+    }
+
+    let manager = vault_manager(&app)?;
+    let (new_key, count) = replace_open_vault(&state, &manager, &old_password, &new_password)?;
+
+    if let Err(e) = manager.lock(true, false) {
+        log::warn!("Failed to remove the CLI session after a password change: {}", e);
+    }
+
+    let mut message = format!("Master password changed, {} value(s) re-encrypted", count);
+    let keychain = KeychainManager::new();
+    if state.keychain_backed.load(Ordering::SeqCst) || matches!(keychain.get_key(), Ok(Some(_))) {
+        // Remove the old entry first; some backends refuse to overwrite a corrupt one
+        match keychain.delete_key().and_then(|_| keychain.save_key(&new_key)) {
+            Ok(()) => state.keychain_backed.store(true, Ordering::SeqCst),
+            Err(e) => {
+                log::warn!("Failed to refresh the keychain entry after a password change: {}", e);
+                state.keychain_backed.store(false, Ordering::SeqCst);
+                message.push_str(&format!(", but Remember Me could not be updated ({}). You will need your password next time.", e));
+            }
+        }
+    }
+
+    Ok(RotatePasswordResponse {
+        success: true,
+        message,
+        reencrypted: count,
+    })
+}
+
+/// Change the password of the vault open in `state` and swap in a connection on the new key
+///
+/// A remote vault's connections all share one working file, so the open one is
+/// written back and closed before the values are re-encrypted. If the change
+/// fails the vault is reopened with the old key, or left locked if that fails too.
+fn replace_open_vault(
+    state: &DatabaseState,
+    manager: &VaultManager,
+    old_password: &str,
+    new_password: &str,
+) -> Result<([u8; 32], usize), String> {
+    // Holding the lock keeps other commands out until the new connection is in place
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    if db_guard.is_none() {
+        return Err("Vault is locked. Please unlock it first.".to_string());
+    }
+    let old_key = Zeroizing::new(
+        state.encryption_key.lock().map_err(|e| e.to_string())?
+            .ok_or("Encryption key not available")?
+    );
+
+    sync_working_copy(&mut db_guard)?;
+    drop(db_guard.take());
+
+    let (db, _, new_key, count) = match manager.change_password(old_password, new_password) {
+        Ok(result) => result,
+        Err(e) => {
+            match manager.open_with_key(&old_key, false) {
+                Ok(db) => *db_guard = Some(db),
+                Err(reopen_error) => {
+                    drop(db_guard);
+                    state.clear()?;
+                    return Err(format!("{}. The vault could not be reopened ({}); unlock it again", e, reopen_error));
+                }
+            }
+            return Err(e);
+        }
+    };
+    *db_guard = Some(db);
+    drop(db_guard);
+
+    state.clear_value_cache()?;
+    let mut key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
+    if let Some(old_key) = key_guard.as_mut() {
+        old_key.zeroize();
+    }
+    *key_guard = Some(new_key);
+
+    Ok((new_key, count))
 }
 
 /// Locks the vault by clearing in-memory state and keychain
 #[tauri::command]
 pub async fn lock_vault(
+    app: AppHandle,
     state: State<'_, DatabaseState>,
 ) -> Result<(), String> {
+    // Write a remote vault's working copy back before it is closed
+    let synced = sync_working_copy(&mut *state.db.lock().map_err(|e| e.to_string())?);
+
     // Clear database and encryption key from app state
-    {
-        let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
-        *db_guard = None;
-    }
-    
-    {
-        let mut key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
-        *key_guard = None;
-    }
+    state.clear()?;
 
     // Delete stored key from OS keychain for security
-    let keychain = KeychainManager::new();
-    keychain.delete_key()
+    vault_manager(&app)?.lock(false, true)?;
+    synced
+}
+
+/// Delete the vault, its database, the keychain entry and any CLI session
+///
+/// Refuses unless `confirmation` is exactly `vault::RESET_CONFIRMATION`.
+#[tauri::command]
+pub async fn reset_vault(
+    app: AppHandle,
+    state: State<'_, DatabaseState>,
+    confirmation: String,
+) -> Result<(), String> {
+    if confirmation != vault::RESET_CONFIRMATION {
+        return Err(format!("Type '{}' to confirm the reset", vault::RESET_CONFIRMATION));
+    }
+
+    let vault_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get app data directory: {}", e))?;
+
+    // Close the database before its files are removed
+    state.clear()?;
+
+    KeychainManager::new().delete_key()
         .map_err(|e| format!("Failed to clear keychain: {}", e))?;
 
+    vault::delete_vault_files(&vault_dir)?;
+
     Ok(())
 }
 
@@ -308,7 +559,7 @@ pub async fn set_lock_timeout(
         return Err("Timeout must be between 0 (disabled) and 1440 minutes (24 hours)".to_string());
     }
 
-    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
     let db = db_guard.as_ref()
         .ok_or("Database not initialized. Please unlock vault first.")?;
 
@@ -318,16 +569,250 @@ pub async fn set_lock_timeout(
     )
     .map_err(|e| format!("Failed to set lock timeout: {}", e))?;
 
+    sync_working_copy(&mut db_guard)
+}
+
+/// Lock the vault when the window closes or the app exits
+///
+/// The in-memory database and key are always cleared. Under the "hard" exit lock
+/// policy the keychain entry is removed as well, so the next launch needs the
+/// master password; under "soft" (the default) "Remember Me" keeps working.
+pub fn lock_on_exit(state: &DatabaseState) {
+    let hard_lock = state.db.lock()
+        .ok()
+        .and_then(|guard| guard.as_ref().map(read_exit_lock_policy))
+        .is_some_and(|policy| policy == EXIT_LOCK_HARD);
+
+    let _ = state.clear();
+
+    if hard_lock {
+        let _ = KeychainManager::new().delete_key();
+    }
+}
+
+/// Read the exit lock policy, falling back to "soft" if it cannot be read
+fn read_exit_lock_policy(db: &Database) -> String {
+    db.connection()
+        .query_row(
+            "SELECT COALESCE(exit_lock_policy, 'soft') FROM vault_metadata WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap_or_else(|_| EXIT_LOCK_SOFT.to_string())
+}
+
+/// Skip auto-unlock for the rest of this app session, even if a keychain key exists
+///
+/// Not persisted: the next launch auto-unlocks again unless started with `--no-keychain`.
+#[tauri::command]
+pub async fn disable_auto_unlock(
+    state: State<'_, DatabaseState>,
+    disabled: bool,
+) -> Result<(), String> {
+    state.auto_unlock_disabled.store(disabled, Ordering::SeqCst);
     Ok(())
 }
 
-/// Vault metadata structure
-#[derive(Serialize, Deserialize)]
-struct VaultMetadata {
-    version: u32,
-    salt: Vec<u8>,
-    password_hash: String,
-    created_at: i64,
+/// Whether auto-unlock is disabled for this app session
+#[tauri::command]
+pub async fn is_auto_unlock_disabled(
+    state: State<'_, DatabaseState>,
+) -> Result<bool, String> {
+    Ok(state.auto_unlock_disabled.load(Ordering::SeqCst))
+}
+
+/// Get the exit lock policy ("soft" keeps the keychain entry, "hard" removes it)
+#[tauri::command]
+pub async fn get_exit_lock_policy(
+    state: State<'_, DatabaseState>,
+) -> Result<String, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    Ok(read_exit_lock_policy(db))
+}
+
+/// Set the exit lock policy ("soft" or "hard")
+#[tauri::command]
+pub async fn set_exit_lock_policy(
+    state: State<'_, DatabaseState>,
+    policy: String,
+) -> Result<(), String> {
+    if policy != EXIT_LOCK_SOFT && policy != EXIT_LOCK_HARD {
+        return Err("Exit lock policy must be 'soft' or 'hard'".to_string());
+    }
+
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    db.connection().execute(
+        "UPDATE vault_metadata SET exit_lock_policy = ?1, last_modified = ?2 WHERE id = 1",
+        rusqlite::params![policy, chrono::Utc::now().timestamp()],
+    )
+    .map_err(|e| format!("Failed to set exit lock policy: {}", e))?;
+
+    sync_working_copy(&mut db_guard)
+}
+
+/// Get the vault's version, timestamps and lock timeout
+#[tauri::command]
+pub async fn get_vault_metadata(
+    state: State<'_, DatabaseState>,
+) -> Result<VaultMetadataResponse, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    db.connection()
+        .query_row(
+            "SELECT version, created_at, last_accessed, last_modified, COALESCE(lock_timeout_minutes, 0)
+             FROM vault_metadata WHERE id = 1",
+            [],
+            |row| Ok(VaultMetadataResponse {
+                version: row.get(0)?,
+                created_at: row.get(1)?,
+                last_accessed: row.get(2)?,
+                last_modified: row.get(3)?,
+                lock_timeout_minutes: row.get(4)?,
+            }),
+        )
+        .map_err(|e| format!("Failed to get vault metadata: {}", e))
+}
+
+/// Get the nonce mode used for new ciphertexts ("random" or "counter")
+#[tauri::command]
+pub async fn get_nonce_mode(
+    state: State<'_, DatabaseState>,
+) -> Result<String, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    let mode = migrations::get_nonce_mode(db.connection())
+        .map_err(|e| format!("Failed to get nonce mode: {}", e))?;
+
+    Ok(mode.as_str().to_string())
+}
+
+/// Set the nonce mode used for new ciphertexts ("random" or "counter")
+///
+/// Existing values keep their nonces; `decrypt` reads both formats.
+#[tauri::command]
+pub async fn set_nonce_mode(
+    state: State<'_, DatabaseState>,
+    mode: String,
+) -> Result<(), String> {
+    let mode = NonceMode::parse(&mode)?;
+
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    migrations::set_nonce_mode(db.connection(), mode)
+        .map_err(|e| format!("Failed to set nonce mode: {}", e))?;
+
+    sync_working_copy(&mut db_guard)
+}
+
+/// Whether variable descriptions are stored encrypted
+#[tauri::command]
+pub async fn get_description_encryption(
+    state: State<'_, DatabaseState>,
+) -> Result<bool, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    migrations::get_encrypt_descriptions(db.connection())
+        .map_err(|e| format!("Failed to get description encryption: {}", e))
+}
+
+/// Turn description encryption on or off, converting existing descriptions
+///
+/// Returns the number of descriptions converted.
+#[tauri::command]
+pub async fn set_description_encryption(
+    state: State<'_, DatabaseState>,
+    enabled: bool,
+) -> Result<usize, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
+    let encryption_key = key_guard.as_ref().ok_or("Encryption key not available")?;
+
+    let converted = variables::set_description_encryption(db.connection(), enabled, encryption_key)
+        .map_err(|e| format!("Failed to set description encryption: {}", e))?;
+
+    sync_working_copy(&mut db_guard)?;
+    Ok(converted)
+}
+
+/// Whether each variable's values are sealed under their own subkey
+#[tauri::command]
+pub async fn get_per_value_keys(
+    state: State<'_, DatabaseState>,
+) -> Result<bool, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    migrations::get_per_value_keys(db.connection())
+        .map_err(|e| format!("Failed to get per-value keys: {}", e))
+}
+
+/// Turn per-value keys on or off, re-sealing existing values
+///
+/// Returns the number of variables converted.
+#[tauri::command]
+pub async fn set_per_value_keys(
+    state: State<'_, DatabaseState>,
+    enabled: bool,
+) -> Result<usize, String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    let key_guard = state.encryption_key.lock().map_err(|e| e.to_string())?;
+    let encryption_key = key_guard.as_ref().ok_or("Encryption key not available")?;
+
+    let converted = variables::set_per_value_keys(db.connection(), enabled, encryption_key)
+        .map_err(|e| format!("Failed to set per-value keys: {}", e))?;
+
+    sync_working_copy(&mut db_guard)?;
+    Ok(converted)
+}
+
+/// Get the vault size quota (0 = unlimited)
+#[tauri::command]
+pub async fn get_quota(
+    state: State<'_, DatabaseState>,
+) -> Result<Quota, String> {
+    let db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    quota::get_quota(db.connection())
+        .map_err(|e| format!("Failed to get quota: {}", e))
+}
+
+/// Set the vault size quota (0 = unlimited). Existing data above a new limit is kept.
+#[tauri::command]
+pub async fn set_quota(
+    state: State<'_, DatabaseState>,
+    quota: Quota,
+) -> Result<(), String> {
+    let mut db_guard = state.db.lock().map_err(|e| e.to_string())?;
+    let db = db_guard.as_ref()
+        .ok_or("Database not initialized. Please unlock vault first.")?;
+
+    quota::set_quota(db.connection(), &quota)
+        .map_err(|e| format!("Failed to set quota: {}", e))?;
+
+    sync_working_copy(&mut db_guard)
 }
 
 #[cfg(test)]
@@ -349,4 +834,55 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn test_lock_on_exit_clears_state() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        assert_eq!(read_exit_lock_policy(&db), EXIT_LOCK_SOFT);
+
+        let state = DatabaseState::new();
+        *state.db.lock().unwrap() = Some(db);
+        *state.encryption_key.lock().unwrap() = Some([7u8; 32]);
+
+        lock_on_exit(&state);
+
+        assert!(state.db.lock().unwrap().is_none());
+        assert!(state.encryption_key.lock().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_replace_open_vault_keeps_unsynced_remote_edits() {
+        let dir = std::env::temp_dir().join(format!("clerk-rotate-remote-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(vault::REMOTE_MARKER), "").unwrap();
+        let manager = VaultManager::new(&dir);
+        let kdf = KdfParams { memory_kib: KdfParams::MIN_MEMORY_KIB, iterations: 1, parallelism: 1 };
+        drop(manager.create("correct horse", kdf).unwrap());
+
+        // An edit the GUI made but has not written back to the remote file yet
+        let (db, _, old_key) = manager.unlock("correct horse", false).unwrap();
+        let env = crate::database::operations::environments::ensure_project_environment(db.connection(), "app", "dev").unwrap().0;
+        let id = variables::create_variable_encrypted(db.connection(), env, "TOKEN".to_string(), "value".to_string(), None, &old_key).unwrap();
+        let state = DatabaseState::new();
+        *state.db.lock().unwrap() = Some(db);
+        *state.encryption_key.lock().unwrap() = Some(old_key);
+
+        // A failed change reopens the vault on the old key
+        assert!(replace_open_vault(&state, &manager, "wrong password", "battery staple").is_err());
+        assert_eq!(*state.encryption_key.lock().unwrap(), Some(old_key));
+        assert!(state.db.lock().unwrap().is_some());
+
+        let (new_key, count) = replace_open_vault(&state, &manager, "correct horse", "battery staple").unwrap();
+        assert_eq!(count, 1);
+        assert_eq!(*state.encryption_key.lock().unwrap(), Some(new_key));
+        drop(state);
+
+        let (db, _, _) = manager.unlock("battery staple", false).unwrap();
+        assert_eq!(variables::get_variable_decrypted(db.connection(), id, &new_key).unwrap().value.expose(), "value");
+        drop(db);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -0,0 +1,358 @@
+// Config module - declarative vault structure (clerk.toml)
+//
+// A repo can describe its projects, environments and expected key names
+// (never values) in a `clerk.toml`:
+//
+//   include = ["../shared/clerk.toml"]
+//
+//   [projects.my-app.environments.base]
+//   template = true
+//   keys = ["DATABASE_URL", "API_KEY"]
+//
+//   [projects.my-app.environments.prod]
+//   extends = "base"
+//   keys = ["SENTRY_DSN"]
+//
+// Included files are merged first, so the including file wins on conflicts.
+// `extends` inherits the expected keys of another environment in the same
+// project; `template` environments only exist to be extended and are never
+// created in the vault.
+
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::database::{DatabaseError, operations::{self, Environment, Project}};
+
+/// Default config file name looked up by `clerk apply`
+pub const CONFIG_FILE_NAME: &str = "clerk.toml";
+
+/// Deepest include chain accepted before assuming a mistake
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    #[serde(default)]
+    include: Vec<PathBuf>,
+    #[serde(default)]
+    projects: BTreeMap<String, ProjectSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProjectSpec {
+    description: Option<String>,
+    #[serde(default)]
+    environments: BTreeMap<String, EnvironmentSpec>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EnvironmentSpec {
+    description: Option<String>,
+    extends: Option<String>,
+    template: Option<bool>,
+    #[serde(default)]
+    keys: Vec<String>,
+}
+
+/// An environment with its `extends` chain expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentConfig {
+    pub name: String,
+    pub description: Option<String>,
+    /// Expected key names, sorted and deduplicated
+    pub keys: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProjectConfig {
+    pub name: String,
+    pub description: Option<String>,
+    /// Environments to create (templates are left out)
+    pub environments: Vec<EnvironmentConfig>,
+}
+
+/// Fully resolved config: includes merged and `extends` expanded
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClerkConfig {
+    pub projects: Vec<ProjectConfig>,
+}
+
+/// Load a config file and everything it includes
+pub fn load(path: &Path) -> Result<ClerkConfig, String> {
+    let mut merged = ConfigFile::default();
+    load_into(path, &mut merged, &mut Vec::new())?;
+    resolve(merged)
+}
+
+/// Parse a single config file without following includes
+pub fn parse(contents: &str) -> Result<ClerkConfig, String> {
+    let file: ConfigFile = toml::from_str(contents).map_err(|e| e.to_string())?;
+    if !file.include.is_empty() {
+        return Err("'include' is only supported when loading from a file".to_string());
+    }
+    resolve(file)
+}
+
+fn load_into(path: &Path, merged: &mut ConfigFile, stack: &mut Vec<PathBuf>) -> Result<(), String> {
+    let canonical = fs::canonicalize(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+
+    if stack.contains(&canonical) {
+        return Err(format!("Include cycle detected at {}", path.display()));
+    }
+    if stack.len() >= MAX_INCLUDE_DEPTH {
+        return Err(format!("Includes nested deeper than {} levels", MAX_INCLUDE_DEPTH));
+    }
+
+    let contents = fs::read_to_string(&canonical)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let file: ConfigFile = toml::from_str(&contents)
+        .map_err(|e| format!("Invalid config {}: {}", path.display(), e))?;
+
+    // Include paths are relative to the including file
+    let base_dir = canonical.parent().map(Path::to_path_buf).unwrap_or_default();
+    stack.push(canonical);
+    for include in &file.include {
+        load_into(&base_dir.join(include), merged, stack)?;
+    }
+    stack.pop();
+
+    merge(merged, file);
+    Ok(())
+}
+
+/// Merge `file` over `merged`: set fields override, key lists are unioned
+fn merge(merged: &mut ConfigFile, file: ConfigFile) {
+    for (name, project) in file.projects {
+        let target = merged.projects.entry(name).or_default();
+        if project.description.is_some() {
+            target.description = project.description;
+        }
+
+        for (env_name, env) in project.environments {
+            let target_env = target.environments.entry(env_name).or_default();
+            if env.description.is_some() {
+                target_env.description = env.description;
+            }
+            if env.extends.is_some() {
+                target_env.extends = env.extends;
+            }
+            if env.template.is_some() {
+                target_env.template = env.template;
+            }
+            target_env.keys.extend(env.keys);
+        }
+    }
+}
+
+fn resolve(file: ConfigFile) -> Result<ClerkConfig, String> {
+    let mut projects = Vec::new();
+
+    for (name, project) in &file.projects {
+        let mut environments = Vec::new();
+
+        for (env_name, env) in &project.environments {
+            if env.template == Some(true) {
+                continue;
+            }
+
+            let mut keys = BTreeSet::new();
+            let mut chain = vec![env_name.as_str()];
+            let mut current = env;
+            loop {
+                keys.extend(current.keys.iter().cloned());
+
+                let Some(parent) = current.extends.as_deref() else {
+                    break;
+                };
+                if chain.contains(&parent) {
+                    return Err(format!("Project '{}': 'extends' cycle through '{}'", name, parent));
+                }
+                current = project.environments.get(parent).ok_or_else(|| {
+                    format!("Project '{}': environment '{}' extends unknown environment '{}'", name, chain[chain.len() - 1], parent)
+                })?;
+                chain.push(parent);
+            }
+
+            environments.push(EnvironmentConfig {
+                name: env_name.clone(),
+                description: env.description.clone(),
+                keys: keys.into_iter().collect(),
+            });
+        }
+
+        projects.push(ProjectConfig {
+            name: name.clone(),
+            description: project.description.clone(),
+            environments,
+        });
+    }
+
+    Ok(ClerkConfig { projects })
+}
+
+/// What `apply` changed and which expected keys are still unset
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ApplyReport {
+    pub created_projects: Vec<String>,
+    /// (project, environment)
+    pub created_environments: Vec<(String, String)>,
+    /// (project, environment, unset keys)
+    pub missing_keys: Vec<(String, String, Vec<String>)>,
+}
+
+/// Create missing projects and environments and report unset expected keys
+///
+/// Existing projects and environments are left as they are. Only key names are
+/// read, so no encryption key is needed. Runs in a single transaction.
+pub fn apply(conn: &Connection, config: &ClerkConfig) -> Result<ApplyReport, DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    let mut report = ApplyReport::default();
+
+    for project_config in &config.projects {
+        let existing = operations::projects::get_all_projects(&tx)?
+            .into_iter()
+            .find(|p| p.name == project_config.name);
+
+        let project_id = match existing.and_then(|p| p.id) {
+            Some(id) => id,
+            None => {
+                let project = Project::new(project_config.name.clone(), project_config.description.clone());
+                report.created_projects.push(project_config.name.clone());
+                operations::projects::create_project(&tx, &project)?
+            }
+        };
+
+        let environments = operations::environments::get_environments_by_project(&tx, project_id)?;
+
+        for env_config in &project_config.environments {
+            let env_id = match environments.iter().find(|e| e.name == env_config.name).and_then(|e| e.id) {
+                Some(id) => id,
+                None => {
+                    let env = Environment::new(project_id, env_config.name.clone(), env_config.description.clone());
+                    report.created_environments.push((project_config.name.clone(), env_config.name.clone()));
+                    operations::environments::create_environment(&tx, &env)?
+                }
+            };
+
+            let present: BTreeSet<String> = operations::variables::get_variables_by_environment(&tx, env_id)?
+                .into_iter()
+                .map(|v| v.key)
+                .collect();
+
+            let missing: Vec<String> = env_config.keys.iter()
+                .filter(|k| !present.contains(*k))
+                .cloned()
+                .collect();
+
+            if !missing.is_empty() {
+                report.missing_keys.push((project_config.name.clone(), env_config.name.clone(), missing));
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    #[test]
+    fn test_extends_and_templates() {
+        let config = parse(r#"
+            [projects.app]
+            description = "Main API"
+
+            [projects.app.environments.base]
+            template = true
+            keys = ["DATABASE_URL", "API_KEY"]
+
+            [projects.app.environments.prod]
+            extends = "base"
+            keys = ["SENTRY_DSN", "API_KEY"]
+        "#).unwrap();
+
+        assert_eq!(config.projects.len(), 1);
+        assert_eq!(config.projects[0].environments, vec![EnvironmentConfig {
+            name: "prod".to_string(),
+            description: None,
+            keys: vec!["API_KEY".to_string(), "DATABASE_URL".to_string(), "SENTRY_DSN".to_string()],
+        }]);
+
+        let cycle = parse(r#"
+            [projects.app.environments.a]
+            extends = "b"
+            [projects.app.environments.b]
+            extends = "a"
+        "#);
+        assert!(cycle.unwrap_err().contains("cycle"));
+
+        assert!(parse("[projects.app.environments.a]\nextends = \"nope\"").is_err());
+        assert!(parse("[projects.app]\nunknown = 1").is_err());
+    }
+
+    #[test]
+    fn test_include_merges_files() {
+        let dir = std::env::temp_dir().join(format!("clerk-config-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("shared")).unwrap();
+        fs::write(dir.join("shared").join("base.toml"), r#"
+            [projects.app.environments.dev]
+            description = "shared"
+            keys = ["DATABASE_URL"]
+        "#).unwrap();
+        fs::write(dir.join(CONFIG_FILE_NAME), r#"
+            include = ["shared/base.toml"]
+            [projects.app.environments.dev]
+            keys = ["DEBUG"]
+        "#).unwrap();
+
+        let config = load(&dir.join(CONFIG_FILE_NAME));
+
+        fs::write(dir.join("loop.toml"), "include = [\"loop.toml\"]").unwrap();
+        let looped = load(&dir.join("loop.toml"));
+        let _ = fs::remove_dir_all(&dir);
+
+        let env = &config.unwrap().projects[0].environments[0];
+        assert_eq!(env.description.as_deref(), Some("shared"));
+        assert_eq!(env.keys, vec!["DATABASE_URL", "DEBUG"]);
+        assert!(looped.unwrap_err().contains("cycle"));
+    }
+
+    #[test]
+    fn test_apply_creates_missing_structure() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+
+        let project_id = operations::projects::create_project(conn, &Project::new("app".to_string(), None)).unwrap();
+        let dev = operations::environments::create_environment(conn, &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        operations::variables::create_variable(conn, &operations::Variable::new(dev, "DATABASE_URL".to_string(), vec![1], None)).unwrap();
+
+        let config = parse(r#"
+            [projects.app.environments.dev]
+            keys = ["DATABASE_URL", "DEBUG"]
+            [projects.app.environments.prod]
+            keys = ["DATABASE_URL"]
+        "#).unwrap();
+
+        let report = apply(conn, &config).unwrap();
+        assert!(report.created_projects.is_empty());
+        assert_eq!(report.created_environments, vec![("app".to_string(), "prod".to_string())]);
+        assert_eq!(report.missing_keys, vec![
+            ("app".to_string(), "dev".to_string(), vec!["DEBUG".to_string()]),
+            ("app".to_string(), "prod".to_string(), vec!["DATABASE_URL".to_string()]),
+        ]);
+
+        // Applying again is a no-op apart from the report
+        let again = apply(conn, &config).unwrap();
+        assert!(again.created_environments.is_empty());
+        assert_eq!(again.missing_keys.len(), 2);
+    }
+}
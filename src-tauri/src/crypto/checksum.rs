@@ -0,0 +1,54 @@
+use ring::hmac;
+use crate::crypto::derive_subkey;
+
+/// HKDF info for the checksum key
+const CHECKSUM_KEY_INFO: &[u8] = b"clerk-environment-checksum-v1";
+
+/// Keyed checksum over an environment's key/value pairs, as lowercase hex
+///
+/// HMAC-SHA256 over the pairs sorted by key, each field length-prefixed so
+/// `A=bc` and `Ab=c` can't collide. The HMAC key is derived from the vault key:
+/// environments with the same pairs in one vault get the same checksum, any
+/// change alters it, and without the vault key it can't be used to guess values.
+pub fn environment_checksum(vault_key: &[u8; 32], pairs: &[(&str, &str)]) -> Result<String, String> {
+    let derived = derive_subkey(vault_key, CHECKSUM_KEY_INFO)
+        .map_err(|_| "Failed to derive the checksum key".to_string())?;
+    let key = hmac::Key::new(hmac::HMAC_SHA256, derived.as_ref());
+
+    let mut sorted = pairs.to_vec();
+    sorted.sort_unstable();
+
+    let mut context = hmac::Context::with_key(&key);
+    for (name, value) in sorted {
+        for field in [name, value] {
+            context.update(&(field.len() as u64).to_be_bytes());
+            context.update(field.as_bytes());
+        }
+    }
+
+    Ok(context.sign().as_ref().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checksum_of(vault_key: &[u8; 32], pairs: &[(&str, &str)]) -> String {
+        environment_checksum(vault_key, pairs).unwrap()
+    }
+
+    #[test]
+    fn test_checksum_ignores_order_and_detects_changes() {
+        let key = [3u8; 32];
+        let checksum = checksum_of(&key, &[("A", "1"), ("B", "2")]);
+
+        assert_eq!(checksum.len(), 64);
+        assert_eq!(checksum, checksum_of(&key, &[("B", "2"), ("A", "1")]));
+        assert_ne!(checksum, checksum_of(&key, &[("A", "1"), ("B", "3")]));
+        assert_ne!(checksum, checksum_of(&key, &[("A", "1")]));
+        assert_ne!(checksum, checksum_of(&[4u8; 32], &[("A", "1"), ("B", "2")]));
+
+        // Field boundaries are part of the input
+        assert_ne!(checksum_of(&key, &[("A", "bc")]), checksum_of(&key, &[("Ab", "c")]));
+    }
+}
@@ -0,0 +1,64 @@
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use std::io::{Read, Write};
+use zeroize::Zeroizing;
+
+/// Values shorter than this are stored as-is; deflate rarely helps below it
+pub const MIN_COMPRESS_LEN: usize = 256;
+
+/// Refuse to inflate past this size, so a corrupt value can't exhaust memory
+pub const MAX_DECOMPRESSED_LEN: usize = 16 * 1024 * 1024;
+
+/// Deflate a plaintext value, returning `None` unless it actually gets smaller
+///
+/// Compression runs before encryption, so the ciphertext length reflects how
+/// compressible the value is. That is fine for stored secrets, which an
+/// attacker cannot mix their own input into.
+pub fn compress_if_smaller(plaintext: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+    if plaintext.len() < MIN_COMPRESS_LEN {
+        return None;
+    }
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(plaintext).ok()?;
+    let compressed = Zeroizing::new(encoder.finish().ok()?);
+
+    (compressed.len() < plaintext.len()).then_some(compressed)
+}
+
+/// Inflate a value produced by `compress_if_smaller`
+pub fn decompress(data: &[u8]) -> Result<Zeroizing<Vec<u8>>, String> {
+    let mut plaintext = Zeroizing::new(Vec::new());
+    DeflateDecoder::new(data)
+        .take(MAX_DECOMPRESSED_LEN as u64 + 1)
+        .read_to_end(&mut plaintext)
+        .map_err(|e| format!("Corrupt compressed value: {}", e))?;
+
+    if plaintext.len() > MAX_DECOMPRESSED_LEN {
+        return Err(format!("Decompressed value exceeds {} bytes", MAX_DECOMPRESSED_LEN));
+    }
+
+    Ok(plaintext)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compression_roundtrip_only_when_smaller() {
+        let json = format!("{{\"items\": [{}]}}", vec!["{\"name\": \"value\"}"; 100].join(", "));
+        let compressed = compress_if_smaller(json.as_bytes()).expect("repetitive JSON should compress");
+        assert!(compressed.len() < json.len());
+        assert_eq!(decompress(&compressed).unwrap().as_slice(), json.as_bytes());
+
+        // Short values are never compressed
+        assert!(compress_if_smaller(b"short").is_none());
+
+        // Incompressible data is kept as-is
+        let mut random = vec![0u8; 1024];
+        ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut random).unwrap();
+        assert!(compress_if_smaller(&random).is_none());
+
+        assert!(decompress(b"not deflate data").is_err());
+    }
+}
@@ -3,6 +3,44 @@ use ring::error::Unspecified;
 use ring::rand::{SecureRandom, SystemRandom};
 use zeroize::Zeroizing;
 
+/// How AES-GCM nonces are chosen for new ciphertexts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonceMode {
+    /// 96 random bits per encryption (default)
+    Random,
+    /// A persisted, monotonically increasing counter followed by 32 random bits
+    Counter,
+}
+
+impl NonceMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NonceMode::Random => "random",
+            NonceMode::Counter => "counter",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "random" => Ok(NonceMode::Random),
+            "counter" => Ok(NonceMode::Counter),
+            other => Err(format!("Unknown nonce mode '{}'. Use 'random' or 'counter'", other)),
+        }
+    }
+}
+
+/// Build a nonce from a counter: 8-byte big-endian counter || 4 random bytes
+///
+/// The counter makes nonces unique for as long as it only moves forward. The
+/// random tail keeps a repeat unlikely even if the counter is ever rolled back,
+/// e.g. by restoring an older database backup.
+pub fn counter_nonce(counter: u64) -> Result<[u8; 12], Unspecified> {
+    let mut nonce_bytes = [0u8; 12];
+    nonce_bytes[..8].copy_from_slice(&counter.to_be_bytes());
+    SystemRandom::new().fill(&mut nonce_bytes[8..])?;
+    Ok(nonce_bytes)
+}
+
 /// Encrypts data using AES-256-GCM
 /// 
 /// # Arguments
@@ -17,14 +55,28 @@ pub fn encrypt(
     plaintext: &[u8],
     aad: &[u8],
 ) -> Result<Vec<u8>, Unspecified> {
-    let unbound_key = UnboundKey::new(&AES_256_GCM, key)?;
-    let sealing_key = LessSafeKey::new(unbound_key);
-
     // Generate random nonce for this encryption
     let rng = SystemRandom::new();
     let mut nonce_bytes = [0u8; 12];
     rng.fill(&mut nonce_bytes)?;
-    let nonce = Nonce::try_assume_unique_for_key(&nonce_bytes)?;
+
+    encrypt_with_nonce(key, nonce_bytes, plaintext, aad)
+}
+
+/// Encrypts data using AES-256-GCM with a caller-supplied nonce
+///
+/// The caller must guarantee the nonce is never reused with the same key.
+/// Output format is identical to `encrypt`, so `decrypt` handles both.
+pub fn encrypt_with_nonce(
+    key: &[u8; 32],
+    nonce_bytes: [u8; 12],
+    plaintext: &[u8],
+    aad: &[u8],
+) -> Result<Vec<u8>, Unspecified> {
+    let unbound_key = UnboundKey::new(&AES_256_GCM, key)?;
+    let sealing_key = LessSafeKey::new(unbound_key);
+
+    let nonce = Nonce::assume_unique_for_key(nonce_bytes);
 
     // Create a copy of plaintext that we can mutate
     let mut in_out = plaintext.to_vec();
@@ -115,4 +167,15 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_counter_nonce_layout_and_roundtrip() {
+        let key = [3u8; 32];
+        let nonce = counter_nonce(258).unwrap();
+        assert_eq!(&nonce[..8], &258u64.to_be_bytes());
+
+        let encrypted = encrypt_with_nonce(&key, nonce, b"value", b"aad").unwrap();
+        assert_eq!(&encrypted[..12], &nonce);
+        assert_eq!(&**decrypt(&key, &encrypted, b"aad").unwrap(), b"value");
+    }
 }
@@ -2,9 +2,93 @@ use argon2::{
     password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
     Argon2, Params, Version,
 };
+use ring::hkdf;
 use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
 use zeroize::Zeroizing;
 
+/// Argon2id cost parameters used when deriving the vault key
+///
+/// Stored in the vault metadata so a vault created with custom parameters
+/// can still be unlocked. Vaults created before these were recorded use
+/// the defaults.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory cost in KiB
+    pub memory_kib: u32,
+    /// Number of iterations (time cost)
+    pub iterations: u32,
+    /// Number of parallel lanes
+    pub parallelism: u32,
+}
+
+impl KdfParams {
+    /// Lowest memory cost accepted (8 MiB)
+    pub const MIN_MEMORY_KIB: u32 = 8192;
+    /// Highest memory cost accepted (1 GiB)
+    pub const MAX_MEMORY_KIB: u32 = 1_048_576;
+    /// Lowest iteration count accepted
+    pub const MIN_ITERATIONS: u32 = 1;
+    /// Highest iteration count accepted
+    pub const MAX_ITERATIONS: u32 = 64;
+
+    /// Builds parameters from optional overrides, falling back to the defaults
+    pub fn with_overrides(memory_kib: Option<u32>, iterations: Option<u32>) -> Result<Self, String> {
+        let defaults = Self::default();
+        let params = Self {
+            memory_kib: memory_kib.unwrap_or(defaults.memory_kib),
+            iterations: iterations.unwrap_or(defaults.iterations),
+            parallelism: defaults.parallelism,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// Checks the parameters against the enforced minimums and maximums
+    pub fn validate(&self) -> Result<(), String> {
+        if !(Self::MIN_MEMORY_KIB..=Self::MAX_MEMORY_KIB).contains(&self.memory_kib) {
+            return Err(format!(
+                "KDF memory must be between {} and {} KiB",
+                Self::MIN_MEMORY_KIB,
+                Self::MAX_MEMORY_KIB
+            ));
+        }
+
+        if !(Self::MIN_ITERATIONS..=Self::MAX_ITERATIONS).contains(&self.iterations) {
+            return Err(format!(
+                "KDF iterations must be between {} and {}",
+                Self::MIN_ITERATIONS,
+                Self::MAX_ITERATIONS
+            ));
+        }
+
+        if self.parallelism == 0 {
+            return Err("KDF parallelism must be at least 1".to_string());
+        }
+
+        Ok(())
+    }
+
+    fn argon2(&self) -> Result<Argon2<'static>, argon2::Error> {
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))?;
+        Ok(Argon2::new(argon2::Algorithm::Argon2id, Version::V0x13, params))
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        // Argon2id parameters (OWASP recommendations for sensitive data)
+        // m_cost: 64 MB memory
+        // t_cost: 3 iterations
+        // p_cost: 4 parallel lanes
+        Self {
+            memory_kib: 65536,
+            iterations: 3,
+            parallelism: 4,
+        }
+    }
+}
+
 /// Derives a 32-byte encryption key from a password using Argon2id
 /// 
 /// # Arguments
@@ -14,16 +98,16 @@ use zeroize::Zeroizing;
 /// # Returns
 /// * 32-byte encryption key suitable for AES-256-GCM
 pub fn derive_key(password: &str, salt: &[u8; 16]) -> Result<[u8; 32], argon2::Error> {
-    // Argon2id parameters (OWASP recommendations for sensitive data)
-    // m_cost: 64 MB memory
-    // t_cost: 3 iterations
-    // p_cost: 4 parallel lanes
-    let params = Params::new(65536, 3, 4, Some(32))?;
-    let argon2 = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        Version::V0x13,
-        params,
-    );
+    derive_key_with_params(password, salt, &KdfParams::default())
+}
+
+/// Derives a 32-byte encryption key using explicit Argon2id parameters
+pub fn derive_key_with_params(
+    password: &str,
+    salt: &[u8; 16],
+    kdf: &KdfParams,
+) -> Result<[u8; 32], argon2::Error> {
+    let argon2 = kdf.argon2()?;
 
     let mut key = Zeroizing::new([0u8; 32]);
     
@@ -39,6 +123,14 @@ pub fn derive_key(password: &str, salt: &[u8; 16]) -> Result<[u8; 32], argon2::E
 /// Hashes a password for verification purposes (not for encryption)
 /// Returns a PHC string format hash
 pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    hash_password_with_params(password, &KdfParams::default())
+}
+
+/// Hashes a password for verification using explicit Argon2id parameters
+pub fn hash_password_with_params(
+    password: &str,
+    kdf: &KdfParams,
+) -> Result<String, argon2::password_hash::Error> {
     let rng = SystemRandom::new();
     let mut salt_bytes = [0u8; 16];
     rng.fill(&mut salt_bytes)
@@ -47,14 +139,8 @@ pub fn hash_password(password: &str) -> Result<String, argon2::password_hash::Er
     let salt = SaltString::encode_b64(&salt_bytes)
         .map_err(|_| argon2::password_hash::Error::Password)?;
 
-    let params = Params::new(65536, 3, 4, Some(32))
+    let argon2 = kdf.argon2()
         .map_err(|_| argon2::password_hash::Error::ParamNameInvalid)?;
-    
-    let argon2 = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        Version::V0x13,
-        params,
-    );
 
     let password_hash = argon2.hash_password(password.as_bytes(), &salt)?;
     Ok(password_hash.to_string())
@@ -72,6 +158,47 @@ pub fn verify_password(password: &str, hash: &str) -> Result<bool, argon2::passw
     }
 }
 
+/// HKDF info for per-value subkeys, so they can't equal any other key derived from the vault key
+const VALUE_KEY_INFO: &[u8] = b"clerk-value-key-v1";
+
+/// HKDF info for the SQLCipher key of the database file
+const DATABASE_KEY_INFO: &[u8] = b"clerk-database-key-v1";
+
+/// Derives the subkey one variable's values are sealed with (HKDF-SHA256)
+///
+/// The vault key is the input keying material and `salt` the variable's own
+/// random salt, so every variable gets an independent AES-256-GCM key.
+pub fn derive_value_key(
+    vault_key: &[u8; 32],
+    salt: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, ring::error::Unspecified> {
+    hkdf_subkey(vault_key, salt, VALUE_KEY_INFO)
+}
+
+/// Derives the key SQLCipher encrypts the whole database file with (HKDF-SHA256)
+pub fn derive_database_key(vault_key: &[u8; 32]) -> Result<Zeroizing<[u8; 32]>, ring::error::Unspecified> {
+    derive_subkey(vault_key, DATABASE_KEY_INFO)
+}
+
+/// Derives a subkey for one purpose from the vault key (HKDF-SHA256, no salt)
+///
+/// `info` names the purpose, so keys for different uses never coincide and the
+/// raw vault key is never used for anything but encryption.
+pub fn derive_subkey(vault_key: &[u8; 32], info: &[u8]) -> Result<Zeroizing<[u8; 32]>, ring::error::Unspecified> {
+    hkdf_subkey(vault_key, &[], info)
+}
+
+fn hkdf_subkey(
+    vault_key: &[u8; 32],
+    salt: &[u8],
+    info: &[u8],
+) -> Result<Zeroizing<[u8; 32]>, ring::error::Unspecified> {
+    let prk = hkdf::Salt::new(hkdf::HKDF_SHA256, salt).extract(vault_key);
+    let mut key = Zeroizing::new([0u8; 32]);
+    prk.expand(&[info], hkdf::HKDF_SHA256)?.fill(&mut key[..])?;
+    Ok(key)
+}
+
 /// Generates a cryptographically secure random salt
 pub fn generate_salt() -> Result<[u8; 16], ring::error::Unspecified> {
     let rng = SystemRandom::new();
@@ -118,6 +245,54 @@ mod tests {
         assert!(!verify_password("WrongPassword", &hash).unwrap());
     }
 
+    #[test]
+    fn test_custom_params_change_key() {
+        let password = "MySecurePassword123!";
+        let salt = [1u8; 16];
+        let light = KdfParams::with_overrides(Some(KdfParams::MIN_MEMORY_KIB), Some(1)).unwrap();
+
+        let key1 = derive_key_with_params(password, &salt, &light).unwrap();
+        let key2 = derive_key_with_params(password, &salt, &light).unwrap();
+        let default_key = derive_key(password, &salt).unwrap();
+
+        assert_eq!(key1, key2);
+        assert_ne!(key1, default_key);
+    }
+
+    #[test]
+    fn test_kdf_params_minimums_enforced() {
+        assert!(KdfParams::with_overrides(Some(1024), None).is_err());
+        assert!(KdfParams::with_overrides(None, Some(0)).is_err());
+        assert!(KdfParams::with_overrides(Some(32768), Some(4)).is_ok());
+        assert_eq!(KdfParams::with_overrides(None, None).unwrap(), KdfParams::default());
+    }
+
+    #[test]
+    fn test_hash_with_custom_params_verifies() {
+        let light = KdfParams::with_overrides(Some(KdfParams::MIN_MEMORY_KIB), Some(1)).unwrap();
+        let hash = hash_password_with_params("TestPassword123!", &light).unwrap();
+
+        assert!(verify_password("TestPassword123!", &hash).unwrap());
+        assert!(!verify_password("WrongPassword", &hash).unwrap());
+    }
+
+    #[test]
+    fn test_value_keys_depend_on_salt_and_vault_key() {
+        let vault_key = [7u8; 32];
+
+        let key1 = derive_value_key(&vault_key, &[1u8; 16]).unwrap();
+        assert_eq!(*key1, *derive_value_key(&vault_key, &[1u8; 16]).unwrap());
+        assert_ne!(*key1, *derive_value_key(&vault_key, &[2u8; 16]).unwrap());
+        assert_ne!(*key1, *derive_value_key(&[8u8; 32], &[1u8; 16]).unwrap());
+        assert_ne!(*key1, vault_key);
+
+        // The database key is a separate derivation from both
+        let database_key = derive_database_key(&vault_key).unwrap();
+        assert_eq!(*database_key, *derive_database_key(&vault_key).unwrap());
+        assert_ne!(*database_key, vault_key);
+        assert_ne!(*database_key, *derive_value_key(&vault_key, &[]).unwrap());
+    }
+
     #[test]
     fn test_generate_salt() {
         let salt1 = generate_salt().unwrap();
@@ -1,8 +1,17 @@
 pub mod encryption;
 pub mod key_derivation;
-
-pub use encryption::{encrypt, decrypt};
-pub use key_derivation::{derive_key, hash_password, verify_password, generate_salt};
+pub mod share;
+pub mod secret;
+pub mod compression;
+pub mod classify;
+pub mod checksum;
+
+pub use encryption::{encrypt, encrypt_with_nonce, decrypt, counter_nonce, NonceMode};
+pub use secret::SecretString;
+pub use key_derivation::{
+    derive_key, derive_key_with_params, hash_password, hash_password_with_params, verify_password,
+    derive_database_key, derive_subkey, derive_value_key, generate_salt, KdfParams,
+};
 
 use zeroize::Zeroizing;
 
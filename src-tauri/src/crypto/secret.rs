@@ -0,0 +1,98 @@
+use ring::hmac;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A decrypted secret value that never shows up in logs
+///
+/// `Debug` and `Display` print `[REDACTED]`, so an accidental `{:?}` or `{}`
+/// cannot leak the plaintext. Call `expose()` where the raw value is really
+/// needed (export, `run` injection, clipboard). Serialization is transparent
+/// because the GUI receives values over IPC. The buffer is zeroized on drop.
+#[derive(Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(value: String) -> Self {
+        Self(value)
+    }
+
+    /// Borrow the plaintext value
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// Take the plaintext value out, leaving nothing to zeroize
+    pub fn into_exposed(mut self) -> String {
+        std::mem::take(&mut self.0)
+    }
+
+    /// Compare against `candidate` in constant time
+    ///
+    /// Not `ring::constant_time::verify_slices_are_equal`: ring deprecates that
+    /// module as internal and slated for removal, so it would break the
+    /// `-D warnings` build. `hmac::verify` is the public API over the same
+    /// comparison. Both sides are MAC'd first, so neither the length nor the
+    /// position of the first differing byte shows in the timing; the fixed key
+    /// needs no secrecy for that.
+    pub fn matches(&self, candidate: &str) -> bool {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, b"clerk-secret-compare");
+        let ours = hmac::sign(&key, self.0.as_bytes());
+        hmac::verify(&key, candidate.as_bytes(), ours.as_ref()).is_ok()
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("[REDACTED]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_secret_string_is_redacted() {
+        let secret = SecretString::new("hunter2".to_string());
+
+        assert_eq!(format!("{}", secret), "[REDACTED]");
+        assert_eq!(format!("{:?}", secret), "[REDACTED]");
+        assert_eq!(format!("{:?}", Some(secret.clone())), "Some([REDACTED])");
+        assert_eq!(secret.expose(), "hunter2");
+
+        // Serialization stays transparent for the GUI
+        assert_eq!(serde_json::to_string(&secret).unwrap(), "\"hunter2\"");
+        assert_eq!(secret.into_exposed(), "hunter2");
+    }
+
+    #[test]
+    fn test_secret_string_matches() {
+        let secret = SecretString::new("hunter2".to_string());
+
+        assert!(secret.matches("hunter2"));
+        assert!(!secret.matches("hunter3"));
+        assert!(!secret.matches("hunter"));
+        assert!(!secret.matches(""));
+        assert!(SecretString::default().matches(""));
+    }
+}
@@ -0,0 +1,175 @@
+use base64::{Engine as _, engine::general_purpose::STANDARD as BASE64};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::encryption::{decrypt, encrypt};
+use super::key_derivation::{derive_key_with_params, generate_salt, KdfParams};
+
+/// Identifier written into every shared bundle file
+pub const SHARED_BUNDLE_FORMAT: &str = "clerk-shared-bundle";
+
+/// Current shared bundle format version
+pub const SHARED_BUNDLE_VERSION: u32 = 1;
+
+/// Outer (plaintext) structure of a shared bundle file
+///
+/// Only the data needed to derive the key is stored in the clear. Everything
+/// else, including the expiry, lives in the encrypted payload so it is covered
+/// by the AES-GCM authentication tag.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SharedBundleFile {
+    pub format: String,
+    pub version: u32,
+    pub salt: String,
+    pub kdf_params: KdfParams,
+    pub ciphertext: String,
+}
+
+/// A single variable inside a shared bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedVariable {
+    pub key: String,
+    pub value: String,
+    pub description: Option<String>,
+}
+
+/// Encrypted contents of a shared bundle
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SharedPayload {
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub project: String,
+    pub environment: String,
+    pub variables: Vec<SharedVariable>,
+}
+
+/// AAD binding the ciphertext to the bundle format and version
+fn bundle_aad() -> String {
+    format!("{};v{}", SHARED_BUNDLE_FORMAT, SHARED_BUNDLE_VERSION)
+}
+
+/// Encrypts a payload to a passphrase and returns the bundle as JSON
+pub fn seal_bundle(payload: &SharedPayload, passphrase: &str) -> Result<String, String> {
+    let kdf_params = KdfParams::default();
+    let salt = generate_salt()
+        .map_err(|_| "Failed to generate salt".to_string())?;
+
+    let key = Zeroizing::new(
+        derive_key_with_params(passphrase, &salt, &kdf_params)
+            .map_err(|e| format!("Key derivation failed: {}", e))?,
+    );
+
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(payload)
+            .map_err(|e| format!("Failed to serialize bundle: {}", e))?,
+    );
+
+    let ciphertext = encrypt(&key, &plaintext, bundle_aad().as_bytes())
+        .map_err(|_| "Encryption failed".to_string())?;
+
+    let file = SharedBundleFile {
+        format: SHARED_BUNDLE_FORMAT.to_string(),
+        version: SHARED_BUNDLE_VERSION,
+        salt: BASE64.encode(salt),
+        kdf_params,
+        ciphertext: BASE64.encode(ciphertext),
+    };
+
+    serde_json::to_string_pretty(&file)
+        .map_err(|e| format!("Failed to serialize bundle: {}", e))
+}
+
+/// Decrypts a bundle and checks that it has not expired at `now` (unix seconds)
+pub fn open_bundle(content: &str, passphrase: &str, now: i64) -> Result<SharedPayload, String> {
+    let file: SharedBundleFile = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid shared bundle: {}", e))?;
+
+    if file.format != SHARED_BUNDLE_FORMAT {
+        return Err("File is not a Clerk shared bundle".to_string());
+    }
+
+    if file.version != SHARED_BUNDLE_VERSION {
+        return Err(format!(
+            "Unsupported shared bundle version: {}. Expected {}",
+            file.version, SHARED_BUNDLE_VERSION
+        ));
+    }
+
+    file.kdf_params.validate()?;
+
+    let salt: [u8; 16] = BASE64.decode(&file.salt)
+        .map_err(|_| "Invalid bundle salt encoding".to_string())?
+        .try_into()
+        .map_err(|_| "Invalid bundle salt length".to_string())?;
+
+    let ciphertext = BASE64.decode(&file.ciphertext)
+        .map_err(|_| "Invalid bundle data encoding".to_string())?;
+
+    let key = Zeroizing::new(
+        derive_key_with_params(passphrase, &salt, &file.kdf_params)
+            .map_err(|e| format!("Key derivation failed: {}", e))?,
+    );
+
+    let plaintext = Zeroizing::new(
+        decrypt(&key, &ciphertext, bundle_aad().as_bytes())
+            .map_err(|_| "Failed to decrypt bundle (wrong passphrase or tampered file)".to_string())?,
+    );
+
+    let payload: SharedPayload = serde_json::from_slice(&plaintext)
+        .map_err(|e| format!("Invalid bundle payload: {}", e))?;
+
+    if now > payload.expires_at {
+        return Err("Shared bundle has expired".to_string());
+    }
+
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_payload(expires_at: i64) -> SharedPayload {
+        SharedPayload {
+            created_at: 1_000,
+            expires_at,
+            project: "api".to_string(),
+            environment: "staging".to_string(),
+            variables: vec![SharedVariable {
+                key: "TOKEN".to_string(),
+                value: "secret".to_string(),
+                description: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_seal_and_open_bundle() {
+        let payload = sample_payload(2_000);
+        let bundle = seal_bundle(&payload, "share-pass").unwrap();
+
+        assert!(!bundle.contains("secret"));
+        assert_eq!(open_bundle(&bundle, "share-pass", 1_500).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_expired_bundle_is_refused() {
+        let bundle = seal_bundle(&sample_payload(2_000), "share-pass").unwrap();
+        assert!(open_bundle(&bundle, "share-pass", 2_001).is_err());
+    }
+
+    #[test]
+    fn test_wrong_passphrase_or_tampering_fails() {
+        let bundle = seal_bundle(&sample_payload(2_000), "share-pass").unwrap();
+        assert!(open_bundle(&bundle, "wrong-pass", 1_500).is_err());
+
+        let mut file: SharedBundleFile = serde_json::from_str(&bundle).unwrap();
+        let mut raw = BASE64.decode(&file.ciphertext).unwrap();
+        let last = raw.len() - 1;
+        raw[last] ^= 0x01;
+        file.ciphertext = BASE64.encode(raw);
+        let tampered = serde_json::to_string(&file).unwrap();
+
+        assert!(open_bundle(&tampered, "share-pass", 1_500).is_err());
+    }
+}
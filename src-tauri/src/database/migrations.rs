@@ -1,24 +1,247 @@
 use rusqlite::Connection;
 use chrono::Utc;
+use serde::Serialize;
+use crate::crypto::{generate_salt, NonceMode};
 use crate::database::{DatabaseError, schema::*};
 
+/// One incremental migration for databases created by an older version
+struct Migration {
+    name: &'static str,
+    description: &'static str,
+    /// Reports whether `apply` would change anything, without changing anything
+    is_pending: fn(&Connection) -> Result<bool, DatabaseError>,
+    /// Idempotent: a no-op when the migration has already been applied
+    apply: fn(&Connection) -> Result<(), DatabaseError>,
+}
+
+/// Incremental migrations, in the order they run; appending one bumps `SCHEMA_VERSION`
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        name: "add_lock_timeout",
+        description: "Add vault_metadata.lock_timeout_minutes",
+        is_pending: |conn| missing_columns(conn, "vault_metadata", &["lock_timeout_minutes"]),
+        apply: migrate_add_lock_timeout,
+    },
+    Migration {
+        name: "add_audit_log",
+        description: "Create the audit_log table",
+        is_pending: |conn| Ok(!table_exists(conn, "audit_log")?),
+        apply: migrate_add_audit_log,
+    },
+    Migration {
+        name: "add_vault_id",
+        description: "Add vault_metadata.vault_id and assign an id",
+        is_pending: |conn| Ok(missing_columns(conn, "vault_metadata", &["vault_id"])?
+            || conn.query_row("SELECT COUNT(*) FROM vault_metadata WHERE id = 1 AND vault_id IS NULL", [], |row| row.get::<_, i64>(0))? > 0),
+        apply: migrate_add_vault_id,
+    },
+    Migration {
+        name: "add_variable_aad_version",
+        description: "Add variables.aad_version",
+        is_pending: |conn| missing_columns(conn, "variables", &["aad_version"]),
+        apply: migrate_add_variable_aad_version,
+    },
+    Migration {
+        name: "add_exit_lock_policy",
+        description: "Add vault_metadata.exit_lock_policy",
+        is_pending: |conn| missing_columns(conn, "vault_metadata", &["exit_lock_policy"]),
+        apply: migrate_add_exit_lock_policy,
+    },
+    Migration {
+        name: "add_nonce_settings",
+        description: "Add vault_metadata.nonce_mode and nonce_counter",
+        is_pending: |conn| missing_columns(conn, "vault_metadata", &["nonce_mode", "nonce_counter"]),
+        apply: migrate_add_nonce_settings,
+    },
+    Migration {
+        name: "add_quota_settings",
+        description: "Add the vault_metadata quota columns",
+        is_pending: |conn| missing_columns(conn, "vault_metadata", &["max_projects", "max_environments_per_project", "max_variables_per_env"]),
+        apply: migrate_add_quota_settings,
+    },
+    Migration {
+        name: "add_key_canary",
+        description: "Add vault_metadata.key_canary",
+        is_pending: |conn| missing_columns(conn, "vault_metadata", &["key_canary"]),
+        apply: migrate_add_key_canary,
+    },
+    Migration {
+        name: "add_variable_value_type",
+        description: "Add variables.value_type",
+        is_pending: |conn| missing_columns(conn, "variables", &["value_type"]),
+        apply: migrate_add_variable_value_type,
+    },
+    Migration {
+        name: "add_variable_compressed",
+        description: "Add variables.compressed",
+        is_pending: |conn| missing_columns(conn, "variables", &["compressed"]),
+        apply: migrate_add_variable_compressed,
+    },
+    Migration {
+        name: "add_variable_is_protected",
+        description: "Add variables.is_protected",
+        is_pending: |conn| missing_columns(conn, "variables", &["is_protected"]),
+        apply: migrate_add_variable_is_protected,
+    },
+    Migration {
+        name: "add_description_encryption",
+        description: "Add vault_metadata.encrypt_descriptions and variables.encrypted_description",
+        is_pending: |conn| Ok(missing_columns(conn, "vault_metadata", &["encrypt_descriptions"])?
+            || missing_columns(conn, "variables", &["encrypted_description"])?),
+        apply: migrate_add_description_encryption,
+    },
+    Migration {
+        name: "add_variable_expires_at",
+        description: "Add variables.expires_at",
+        is_pending: |conn| missing_columns(conn, "variables", &["expires_at"]),
+        apply: migrate_add_variable_expires_at,
+    },
+    Migration {
+        name: "add_variable_tags",
+        description: "Add variables.tags",
+        is_pending: |conn| missing_columns(conn, "variables", &["tags"]),
+        apply: migrate_add_variable_tags,
+    },
+    Migration {
+        name: "add_environment_lock",
+        description: "Add environments.locked_by and locked_at",
+        is_pending: |conn| missing_columns(conn, "environments", &["locked_by", "locked_at"]),
+        apply: migrate_add_environment_lock,
+    },
+    Migration {
+        name: "add_variable_pending_value",
+        description: "Add variables.pending_value and pending_compressed",
+        is_pending: |conn| missing_columns(conn, "variables", &["pending_value", "pending_compressed"]),
+        apply: migrate_add_variable_pending_value,
+    },
+    Migration {
+        name: "add_audit_enabled",
+        description: "Add vault_metadata.audit_enabled",
+        is_pending: |conn| missing_columns(conn, "vault_metadata", &["audit_enabled"]),
+        apply: migrate_add_audit_enabled,
+    },
+    Migration {
+        name: "add_per_value_keys",
+        description: "Add vault_metadata.per_value_keys and variables.value_salt",
+        is_pending: |conn| Ok(missing_columns(conn, "vault_metadata", &["per_value_keys"])?
+            || missing_columns(conn, "variables", &["value_salt"])?),
+        apply: migrate_add_per_value_keys,
+    },
+    Migration {
+        name: "add_environment_position",
+        description: "Add environments.position",
+        is_pending: |conn| missing_columns(conn, "environments", &["position"]),
+        apply: migrate_add_environment_position,
+    },
+    Migration {
+        name: "add_metadata_mac_flag",
+        description: "Add vault_metadata.metadata_mac",
+        is_pending: |conn| missing_columns(conn, "vault_metadata", &["metadata_mac"]),
+        apply: migrate_add_metadata_mac_flag,
+    },
+];
+
+/// A migration that has not been applied to a database yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PendingMigration {
+    pub name: &'static str,
+    pub description: &'static str,
+}
+
+/// Schema version of a database and what `run_migrations` would do to it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MigrationStatus {
+    /// 0 for a database that has no schema yet
+    pub current_version: u32,
+    pub target_version: u32,
+    pub pending: Vec<PendingMigration>,
+}
+
+impl MigrationStatus {
+    pub fn is_up_to_date(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+/// Report the migrations `run_migrations` would apply, using read-only queries
+pub fn migration_status(conn: &Connection) -> Result<MigrationStatus, DatabaseError> {
+    let current_version = get_schema_version(conn)?;
+    if current_version > SCHEMA_VERSION {
+        return Err(DatabaseError::NewerSchema { found: current_version, supported: SCHEMA_VERSION });
+    }
+    
+    let pending = if current_version == 0 {
+        vec![PendingMigration { name: "initial_schema", description: "Create all tables and the vault metadata row" }]
+    } else {
+        let mut pending = Vec::new();
+        for migration in MIGRATIONS {
+            if (migration.is_pending)(conn)? {
+                pending.push(PendingMigration { name: migration.name, description: migration.description });
+            }
+        }
+        if current_version < SCHEMA_VERSION {
+            pending.push(RECORD_SCHEMA_VERSION);
+        }
+        pending
+    };
+    
+    Ok(MigrationStatus { current_version, target_version: SCHEMA_VERSION, pending })
+}
+
+/// Stores `SCHEMA_VERSION` once the incremental migrations have run
+const RECORD_SCHEMA_VERSION: PendingMigration = PendingMigration {
+    name: "record_schema_version",
+    description: "Store the current schema version in vault_metadata.version",
+};
+
 /// Run all database migrations
 pub fn run_migrations(conn: &Connection) -> Result<(), DatabaseError> {
     // Check current version
     let current_version = get_schema_version(conn)?;
     
+    // An older binary can't know what a newer schema means; migrating or even
+    // writing to it could corrupt the vault
+    if current_version > SCHEMA_VERSION {
+        return Err(DatabaseError::NewerSchema { found: current_version, supported: SCHEMA_VERSION });
+    }
+    
     if current_version == 0 {
         // Fresh database - run initial migration
         run_initial_migration(conn)?;
     } else {
         // Run incremental migrations for existing databases
-        migrate_add_lock_timeout(conn)?;
-        migrate_add_audit_log(conn)?;
+        for migration in MIGRATIONS {
+            (migration.apply)(conn)?;
+        }
+        
+        if current_version < SCHEMA_VERSION {
+            conn.execute("UPDATE vault_metadata SET version = ?1 WHERE id = 1", [SCHEMA_VERSION])
+                .map_err(|e| DatabaseError::MigrationError(format!("Failed to record the schema version: {}", e)))?;
+        }
     }
     
     Ok(())
 }
 
+/// Whether any of `columns` is missing from `table`
+fn missing_columns(conn: &Connection, table: &str, columns: &[&str]) -> Result<bool, DatabaseError> {
+    for column in columns {
+        if conn.prepare(&format!("SELECT {} FROM {} LIMIT 1", column, table)).is_err() {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn table_exists(conn: &Connection, table: &str) -> Result<bool, DatabaseError> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?1",
+        [table],
+        |row| row.get::<_, i64>(0).map(|count| count > 0),
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))
+}
+
 /// Add lock_timeout_minutes column to vault_metadata (for existing databases)
 fn migrate_add_lock_timeout(conn: &Connection) -> Result<(), DatabaseError> {
     // Check if column already exists
@@ -64,8 +287,462 @@ fn migrate_add_audit_log(conn: &Connection) -> Result<(), DatabaseError> {
     Ok(())
 }
 
+/// Add vault_id column to vault_metadata and assign an id (for existing databases)
+fn migrate_add_vault_id(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT vault_id FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN vault_id TEXT", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add vault_id column: {}", e)))?;
+    }
+    
+    conn.execute(
+        "UPDATE vault_metadata SET vault_id = ? WHERE id = 1 AND vault_id IS NULL",
+        [generate_vault_id()?],
+    )
+    .map_err(|e| DatabaseError::MigrationError(format!("Failed to assign vault id: {}", e)))?;
+    
+    Ok(())
+}
+
+/// Add aad_version column to variables (for existing databases)
+///
+/// Existing rows keep AAD version 1 until they are re-encrypted after unlock,
+/// since re-encryption needs the vault key.
+fn migrate_add_variable_aad_version(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT aad_version FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute(
+            "ALTER TABLE variables ADD COLUMN aad_version INTEGER NOT NULL DEFAULT 1",
+            [],
+        )
+        .map_err(|e| DatabaseError::MigrationError(format!("Failed to add aad_version column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add exit_lock_policy column to vault_metadata (for existing databases)
+fn migrate_add_exit_lock_policy(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT exit_lock_policy FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute(
+            "ALTER TABLE vault_metadata ADD COLUMN exit_lock_policy TEXT DEFAULT 'soft'",
+            [],
+        )
+        .map_err(|e| DatabaseError::MigrationError(format!("Failed to add exit_lock_policy column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add nonce_mode and nonce_counter columns to vault_metadata (for existing databases)
+fn migrate_add_nonce_settings(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT nonce_mode, nonce_counter FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN nonce_mode TEXT DEFAULT 'random'", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add nonce_mode column: {}", e)))?;
+        
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN nonce_counter INTEGER DEFAULT 0", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add nonce_counter column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add quota columns to vault_metadata (for existing databases, 0 = unlimited)
+fn migrate_add_quota_settings(conn: &Connection) -> Result<(), DatabaseError> {
+    for column in ["max_projects", "max_environments_per_project", "max_variables_per_env"] {
+        let column_exists: bool = conn
+            .prepare(&format!("SELECT {} FROM vault_metadata LIMIT 1", column))
+            .is_ok();
+        
+        if !column_exists {
+            conn.execute(
+                &format!("ALTER TABLE vault_metadata ADD COLUMN {} INTEGER DEFAULT 0", column),
+                [],
+            )
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add {} column: {}", column, e)))?;
+        }
+    }
+    
+    Ok(())
+}
+
+/// Add key_canary column to vault_metadata (for existing databases)
+///
+/// The canary itself is written after the next successful unlock, since it
+/// needs the vault key.
+fn migrate_add_key_canary(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT key_canary FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN key_canary BLOB", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add key_canary column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add value_type column to variables (for existing databases)
+fn migrate_add_variable_value_type(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT value_type FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute(
+            "ALTER TABLE variables ADD COLUMN value_type TEXT NOT NULL DEFAULT 'text'",
+            [],
+        )
+        .map_err(|e| DatabaseError::MigrationError(format!("Failed to add value_type column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add compressed flag column to variables (for existing databases)
+fn migrate_add_variable_compressed(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT compressed FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute(
+            "ALTER TABLE variables ADD COLUMN compressed INTEGER NOT NULL DEFAULT 0",
+            [],
+        )
+        .map_err(|e| DatabaseError::MigrationError(format!("Failed to add compressed column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add protected flag column to variables (for existing databases)
+fn migrate_add_variable_is_protected(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT is_protected FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute(
+            "ALTER TABLE variables ADD COLUMN is_protected INTEGER NOT NULL DEFAULT 0",
+            [],
+        )
+        .map_err(|e| DatabaseError::MigrationError(format!("Failed to add is_protected column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add the encrypt_descriptions setting and variables.encrypted_description (for existing databases)
+fn migrate_add_description_encryption(conn: &Connection) -> Result<(), DatabaseError> {
+    let setting_exists: bool = conn
+        .prepare("SELECT encrypt_descriptions FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !setting_exists {
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN encrypt_descriptions INTEGER DEFAULT 0", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add encrypt_descriptions column: {}", e)))?;
+    }
+    
+    let column_exists: bool = conn
+        .prepare("SELECT encrypted_description FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE variables ADD COLUMN encrypted_description BLOB", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add encrypted_description column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add expiry column to variables (for existing databases)
+fn migrate_add_variable_expires_at(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT expires_at FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE variables ADD COLUMN expires_at INTEGER", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add expires_at column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add tags column to variables (for existing databases)
+fn migrate_add_variable_tags(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT tags FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE variables ADD COLUMN tags TEXT NOT NULL DEFAULT '[]'", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add tags column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add advisory lock columns to environments (for existing databases)
+fn migrate_add_environment_lock(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT locked_by FROM environments LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute_batch(
+            "ALTER TABLE environments ADD COLUMN locked_by TEXT;
+             ALTER TABLE environments ADD COLUMN locked_at INTEGER;"
+        )
+        .map_err(|e| DatabaseError::MigrationError(format!("Failed to add environment lock columns: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add the pending (staged) value columns to variables
+fn migrate_add_variable_pending_value(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT pending_value FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute_batch(
+            "ALTER TABLE variables ADD COLUMN pending_value BLOB;
+             ALTER TABLE variables ADD COLUMN pending_compressed INTEGER NOT NULL DEFAULT 0;"
+        )
+        .map_err(|e| DatabaseError::MigrationError(format!("Failed to add pending value columns: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add the audit_enabled setting to vault_metadata (for existing databases)
+fn migrate_add_audit_enabled(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT audit_enabled FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN audit_enabled INTEGER DEFAULT 1", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add audit_enabled column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add the per_value_keys setting and variables.value_salt (for existing databases)
+fn migrate_add_per_value_keys(conn: &Connection) -> Result<(), DatabaseError> {
+    let setting_exists: bool = conn
+        .prepare("SELECT per_value_keys FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !setting_exists {
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN per_value_keys INTEGER DEFAULT 0", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add per_value_keys column: {}", e)))?;
+    }
+    
+    let column_exists: bool = conn
+        .prepare("SELECT value_salt FROM variables LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE variables ADD COLUMN value_salt BLOB", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add value_salt column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add environments.position for a user-defined environment order (for existing databases)
+fn migrate_add_environment_position(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT position FROM environments LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE environments ADD COLUMN position INTEGER", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add position column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Add vault_metadata.metadata_mac (for existing databases)
+fn migrate_add_metadata_mac_flag(conn: &Connection) -> Result<(), DatabaseError> {
+    let column_exists: bool = conn
+        .prepare("SELECT metadata_mac FROM vault_metadata LIMIT 1")
+        .is_ok();
+    
+    if !column_exists {
+        conn.execute("ALTER TABLE vault_metadata ADD COLUMN metadata_mac INTEGER DEFAULT 0", [])
+            .map_err(|e| DatabaseError::MigrationError(format!("Failed to add metadata_mac column: {}", e)))?;
+    }
+    
+    Ok(())
+}
+
+/// Generate a random identifier for a vault (32 hex characters)
+fn generate_vault_id() -> Result<String, DatabaseError> {
+    let bytes = generate_salt()
+        .map_err(|_| DatabaseError::MigrationError("Failed to generate vault id".to_string()))?;
+    
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Get the vault's unique identifier
+pub fn get_vault_id(conn: &Connection) -> Result<String, DatabaseError> {
+    conn.query_row(
+        "SELECT vault_id FROM vault_metadata WHERE id = 1 AND vault_id IS NOT NULL",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| DatabaseError::QueryError(format!("Failed to get vault id: {}", e)))
+}
+
+/// Get how nonces are generated for newly encrypted values
+pub fn get_nonce_mode(conn: &Connection) -> Result<NonceMode, DatabaseError> {
+    let mode: String = conn
+        .query_row(
+            "SELECT COALESCE(nonce_mode, 'random') FROM vault_metadata WHERE id = 1",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    
+    NonceMode::parse(&mode).map_err(DatabaseError::QueryError)
+}
+
+/// Set how nonces are generated for newly encrypted values
+pub fn set_nonce_mode(conn: &Connection, mode: NonceMode) -> Result<(), DatabaseError> {
+    conn.execute(
+        "UPDATE vault_metadata SET nonce_mode = ? WHERE id = 1",
+        [mode.as_str()],
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    Ok(())
+}
+
+/// Whether newly written variable descriptions are encrypted
+pub fn get_encrypt_descriptions(conn: &Connection) -> Result<bool, DatabaseError> {
+    conn.query_row(
+        "SELECT COALESCE(encrypt_descriptions, 0) FROM vault_metadata WHERE id = 1",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))
+}
+
+/// Set whether newly written variable descriptions are encrypted
+///
+/// Only the setting is stored; use `variables::set_description_encryption`
+/// to convert existing descriptions as well.
+pub fn set_encrypt_descriptions(conn: &Connection, enabled: bool) -> Result<(), DatabaseError> {
+    conn.execute(
+        "UPDATE vault_metadata SET encrypt_descriptions = ? WHERE id = 1",
+        [enabled],
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    Ok(())
+}
+
+/// Whether operations write audit log entries (on unless turned off)
+pub fn get_audit_enabled(conn: &Connection) -> Result<bool, DatabaseError> {
+    conn.query_row(
+        "SELECT COALESCE(audit_enabled, 1) FROM vault_metadata WHERE id = 1",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))
+}
+
+/// Set whether operations write audit log entries
+///
+/// Only the setting is stored; use `audit::set_audit_enabled` to also record
+/// the switch in the log.
+pub fn set_audit_enabled(conn: &Connection, enabled: bool) -> Result<(), DatabaseError> {
+    conn.execute(
+        "UPDATE vault_metadata SET audit_enabled = ? WHERE id = 1",
+        [enabled],
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    Ok(())
+}
+
+/// Whether newly written values get their own salt and HKDF subkey
+pub fn get_per_value_keys(conn: &Connection) -> Result<bool, DatabaseError> {
+    conn.query_row(
+        "SELECT COALESCE(per_value_keys, 0) FROM vault_metadata WHERE id = 1",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))
+}
+
+/// Set whether newly written values get their own salt and HKDF subkey
+///
+/// Only the setting is stored; use `variables::set_per_value_keys` to convert
+/// existing values as well.
+pub fn set_per_value_keys(conn: &Connection, enabled: bool) -> Result<(), DatabaseError> {
+    conn.execute(
+        "UPDATE vault_metadata SET per_value_keys = ? WHERE id = 1",
+        [enabled],
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    Ok(())
+}
+
+/// Whether vault.clerk has been seen with a MAC, so it must always carry one
+pub fn get_metadata_mac(conn: &Connection) -> Result<bool, DatabaseError> {
+    conn.query_row(
+        "SELECT COALESCE(metadata_mac, 0) FROM vault_metadata WHERE id = 1",
+        [],
+        |row| row.get(0),
+    )
+    .map_err(|e| DatabaseError::QueryError(e.to_string()))
+}
+
+/// Record that vault.clerk carries a MAC; there is no way back
+pub fn set_metadata_mac(conn: &Connection) -> Result<(), DatabaseError> {
+    conn.execute("UPDATE vault_metadata SET metadata_mac = 1 WHERE id = 1 AND COALESCE(metadata_mac, 0) = 0", [])
+        .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    Ok(())
+}
+
+/// Atomically increment and return the persisted nonce counter
+///
+/// The increment is written before the value is used, so a crash can only skip
+/// counter values, never hand the same one out twice.
+pub fn next_nonce_counter(conn: &Connection) -> Result<u64, DatabaseError> {
+    let counter: i64 = conn
+        .query_row(
+            "UPDATE vault_metadata SET nonce_counter = COALESCE(nonce_counter, 0) + 1 WHERE id = 1 RETURNING nonce_counter",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| DatabaseError::QueryError(e.to_string()))?;
+    
+    Ok(counter as u64)
+}
+
 /// Get current schema version from database
-fn get_schema_version(conn: &Connection) -> Result<u32, DatabaseError> {
+pub fn get_schema_version(conn: &Connection) -> Result<u32, DatabaseError> {
     // Check if vault_metadata table exists
     let table_exists: bool = conn
         .query_row(
@@ -132,8 +809,8 @@ fn run_initial_migration(conn: &Connection) -> Result<(), DatabaseError> {
     // Insert initial metadata
     let now = Utc::now().timestamp();
     conn.execute(
-        "INSERT INTO vault_metadata (id, version, created_at, last_accessed, last_modified) VALUES (?, ?, ?, ?, ?)",
-        [1, SCHEMA_VERSION as i64, now, now, now],
+        "INSERT INTO vault_metadata (id, version, created_at, last_accessed, last_modified, vault_id) VALUES (?, ?, ?, ?, ?, ?)",
+        rusqlite::params![1, SCHEMA_VERSION as i64, now, now, now, generate_vault_id()?],
     )
     .map_err(|e| DatabaseError::MigrationError(format!("Failed to insert vault metadata: {}", e)))?;
     
@@ -179,6 +856,57 @@ mod tests {
         assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
     }
     
+    #[test]
+    fn test_migration_status_lists_pending_without_applying() {
+        let conn = Connection::open_in_memory().unwrap();
+        let status = migration_status(&conn).unwrap();
+        assert_eq!(status.current_version, 0);
+        assert_eq!(status.pending.iter().map(|m| m.name).collect::<Vec<_>>(), ["initial_schema"]);
+        
+        run_migrations(&conn).unwrap();
+        assert!(migration_status(&conn).unwrap().is_up_to_date());
+        
+        // Simulate a database created before tags and environment locks existed
+        conn.execute_batch(
+            "ALTER TABLE variables DROP COLUMN tags;
+             ALTER TABLE environments DROP COLUMN locked_by;
+             ALTER TABLE environments DROP COLUMN locked_at;"
+        ).unwrap();
+        let status = migration_status(&conn).unwrap();
+        assert_eq!(status.pending.iter().map(|m| m.name).collect::<Vec<_>>(), ["add_variable_tags", "add_environment_lock"]);
+        assert!(conn.prepare("SELECT tags FROM variables").is_err());
+        
+        run_migrations(&conn).unwrap();
+        assert!(migration_status(&conn).unwrap().is_up_to_date());
+        
+        // A vault last written as version 1 is brought up to the current version
+        conn.execute("UPDATE vault_metadata SET version = 1", []).unwrap();
+        let status = migration_status(&conn).unwrap();
+        assert_eq!((status.current_version, status.target_version), (1, SCHEMA_VERSION));
+        assert_eq!(status.pending, [RECORD_SCHEMA_VERSION]);
+        
+        run_migrations(&conn).unwrap();
+        assert_eq!(get_schema_version(&conn).unwrap(), SCHEMA_VERSION);
+        assert!(migration_status(&conn).unwrap().is_up_to_date());
+    }
+    
+    #[test]
+    fn test_schema_version_counts_migrations() {
+        assert_eq!(SCHEMA_VERSION as usize, 1 + MIGRATIONS.len());
+    }
+    
+    #[test]
+    fn test_newer_schema_is_refused() {
+        let conn = Connection::open_in_memory().unwrap();
+        run_migrations(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        
+        conn.execute("UPDATE vault_metadata SET version = ?", [SCHEMA_VERSION + 1]).unwrap();
+        let err = run_migrations(&conn).unwrap_err();
+        assert!(matches!(err, DatabaseError::NewerSchema { found, supported } if found == SCHEMA_VERSION + 1 && supported == SCHEMA_VERSION));
+        assert!(err.to_string().contains("please upgrade"));
+    }
+    
     #[test]
     fn test_all_tables_created() {
         let conn = Connection::open_in_memory().unwrap();
@@ -207,9 +935,19 @@ mod tests {
         conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
         run_initial_migration(&conn).unwrap();
         
-        std::thread::sleep(std::time::Duration::from_millis(10));
+        conn.execute("UPDATE vault_metadata SET last_accessed = 0, last_modified = 0 WHERE id = 1", []).unwrap();
         
         assert!(update_last_accessed(&conn).is_ok());
+        let (accessed, modified): (i64, i64) = conn
+            .query_row("SELECT last_accessed, last_modified FROM vault_metadata WHERE id = 1", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert!(accessed > 0);
+        assert_eq!(modified, 0);
+        
         assert!(update_last_modified(&conn).is_ok());
+        let modified: i64 = conn
+            .query_row("SELECT last_modified FROM vault_metadata WHERE id = 1", [], |row| row.get(0))
+            .unwrap();
+        assert!(modified > 0);
     }
 }
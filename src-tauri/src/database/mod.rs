@@ -1,10 +1,33 @@
-use rusqlite::Connection;
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::fmt::Write;
 use std::path::Path;
+use std::sync::Mutex;
 use thiserror::Error;
+use zeroize::Zeroizing;
 
 pub mod schema;
 pub mod migrations;
 pub mod operations;
+pub mod working_copy;
+
+use working_copy::WorkingCopy;
+
+/// Size of the database file before and after `Database::compact`, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CompactReport {
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+/// Write-back failure of the last working copy closed by `Drop` instead of `sync`
+static DROPPED_WRITE_BACK_ERROR: Mutex<Option<DatabaseError>> = Mutex::new(None);
+
+/// Reason given when an opened file is not a Clerk vault database
+pub const NOT_A_VAULT_ERROR: &str = "not a Clerk vault database";
+
+/// Start of the error for an encrypted database opened by a build without SQLCipher
+pub const SQLCIPHER_MISSING_ERROR: &str = "This build of Clerk has no SQLCipher support";
 
 #[derive(Error, Debug)]
 pub enum DatabaseError {
@@ -28,6 +51,24 @@ pub enum DatabaseError {
     
     #[error("Serialization error: {0}")]
     SerializationError(String),
+    
+    #[error("Invalid value: {0}")]
+    InvalidValue(String),
+    
+    #[error("Variable '{0}' is protected")]
+    Protected(String),
+    
+    #[error("Encryption key does not match this vault")]
+    KeyMismatch,
+    
+    #[error("Vault was created by a newer version of Clerk (schema v{found}, this build supports up to v{supported}); please upgrade")]
+    NewerSchema { found: u32, supported: u32 },
+    
+    #[error("Environment is locked by {locked_by}")]
+    EnvironmentLocked { locked_by: String, locked_at: i64 },
+    
+    #[error("Remote vault {remote} changed since it was opened; your changes were kept in {local}")]
+    WorkingCopyConflict { remote: String, local: String },
 }
 
 impl From<rusqlite::Error> for DatabaseError {
@@ -51,19 +92,71 @@ impl From<rusqlite::Error> for DatabaseError {
 /// Database manager for the vault
 pub struct Database {
     conn: Connection,
+    /// Set when the file lives on a remote path and `conn` is open on a local copy
+    working_copy: Option<WorkingCopy>,
 }
 
 impl Database {
     /// Create a new database connection
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, DatabaseError> {
+        Database::open(path, None)
+    }
+    
+    /// Open a database, encrypted at rest with SQLCipher when `cipher_key` is given
+    ///
+    /// The key is only checked once something is read, so a wrong one shows up
+    /// as `validate` failing.
+    pub fn open<P: AsRef<Path>>(path: P, cipher_key: Option<&[u8; 32]>) -> Result<Self, DatabaseError> {
         let conn = Connection::open(path)
             .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
         
+        if let Some(cipher_key) = cipher_key {
+            apply_cipher_key(&conn, cipher_key)?;
+        }
+        
         // Enable foreign keys
         conn.execute("PRAGMA foreign_keys = ON", [])
             .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
         
-        Ok(Database { conn })
+        Ok(Database { conn, working_copy: None })
+    }
+    
+    /// Open an existing database read-only, so no migration can run
+    ///
+    /// For inspecting a vault (e.g. `migrations::migration_status`) without
+    /// the upgrade `initialize` would apply.
+    pub fn open_no_migrate<P: AsRef<Path>>(path: P, cipher_key: Option<&[u8; 32]>) -> Result<Self, DatabaseError> {
+        let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+            .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
+        
+        if let Some(cipher_key) = cipher_key {
+            apply_cipher_key(&conn, cipher_key)?;
+        }
+        
+        Ok(Database { conn, working_copy: None })
+    }
+    
+    /// Open a database that lives on a network share or synced folder
+    ///
+    /// The file is copied locally and SQLite works on the copy; changes are
+    /// copied back by `sync`, or when the database is dropped.
+    pub fn open_working_copy<P: AsRef<Path>>(remote: P, cipher_key: Option<&[u8; 32]>) -> Result<Self, DatabaseError> {
+        let working_copy = WorkingCopy::checkout(remote.as_ref())?;
+        let mut db = Database::open(working_copy.local_path(), cipher_key)?;
+        db.working_copy = Some(working_copy);
+        Ok(db)
+    }
+    
+    /// Copy a working copy's changes back to the remote file
+    ///
+    /// Returns whether anything was written; always `false` for a local database.
+    /// Call it after each write: dropping the database also writes back, but an
+    /// error there can only be picked up later with `take_write_back_error`.
+    pub fn sync(&mut self) -> Result<bool, DatabaseError> {
+        match &mut self.working_copy {
+            Some(working_copy) => working_copy.write_back(),
+            None => Ok(false),
+        }
     }
     
     /// Create an in-memory database (for testing)
@@ -75,7 +168,33 @@ impl Database {
         conn.execute("PRAGMA foreign_keys = ON", [])
             .map_err(|e| DatabaseError::ConnectionError(e.to_string()))?;
         
-        Ok(Database { conn })
+        Ok(Database { conn, working_copy: None })
+    }
+    
+    /// Check that the opened file is a Clerk vault database (or still empty)
+    ///
+    /// SQLite opens any file without complaint, so a wrong directory or a
+    /// corrupt file would otherwise fail later with a cryptic query error. An
+    /// empty database is fine: it is a vault being created.
+    pub fn validate(&self) -> Result<(), DatabaseError> {
+        let not_a_vault = |detail: &str| DatabaseError::ConnectionError(format!("{} ({})", NOT_A_VAULT_ERROR, detail));
+        
+        let tables: Vec<String> = self.conn
+            .prepare("SELECT name FROM sqlite_master WHERE type = 'table'")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect())
+            .map_err(|e| not_a_vault(&e.to_string()))?;
+        if tables.is_empty() {
+            return Ok(());
+        }
+        if !tables.iter().any(|name| name == "vault_metadata") {
+            return Err(not_a_vault("no vault_metadata table"));
+        }
+        
+        let version = migrations::get_schema_version(&self.conn)?;
+        if version > schema::SCHEMA_VERSION {
+            return Err(DatabaseError::NewerSchema { found: version, supported: schema::SCHEMA_VERSION });
+        }
+        Ok(())
     }
     
     /// Initialize the database with schema
@@ -88,6 +207,132 @@ impl Database {
     pub fn connection(&self) -> &Connection {
         &self.conn
     }
+    
+    /// Write a copy of the whole database to `target`, encrypted with `cipher_key` or in plain text
+    ///
+    /// Uses `sqlcipher_export`, so it needs SQLCipher in either direction.
+    /// `target` must not exist yet.
+    pub fn export_to(&self, target: &Path, cipher_key: Option<&[u8; 32]>) -> Result<(), DatabaseError> {
+        if !connection_has_sqlcipher(&self.conn) {
+            return Err(DatabaseError::EncryptionError(SQLCIPHER_MISSING_ERROR.to_string()));
+        }
+        
+        // An empty key attaches the target unencrypted
+        let key = match cipher_key {
+            Some(cipher_key) => raw_key_literal(cipher_key),
+            None => Zeroizing::new(String::new()),
+        };
+        self.conn.execute("ATTACH DATABASE ?1 AS export KEY ?2", (target.to_string_lossy(), key.as_str()))?;
+        let exported = self.conn.query_row("SELECT sqlcipher_export('export')", [], |_| Ok(()));
+        self.conn.execute("DETACH DATABASE export", [])?;
+        Ok(exported?)
+    }
+    
+    /// Re-encrypt an encrypted database in place under a new key
+    pub fn rekey_cipher(&self, cipher_key: &[u8; 32]) -> Result<(), DatabaseError> {
+        self.conn.execute_batch(&key_pragma("rekey", cipher_key))
+            .map_err(|e| DatabaseError::EncryptionError(format!("Failed to rekey the database: {}", e)))
+    }
+    
+    /// Rebuild the database file without free pages left by deletions
+    ///
+    /// Runs `VACUUM`, then truncates the write-ahead log if the database is in
+    /// WAL mode. VACUUM needs the file to itself: while another connection is
+    /// writing (or reading) it fails with a busy error and changes nothing.
+    pub fn compact(&self) -> Result<CompactReport, DatabaseError> {
+        if !self.conn.is_autocommit() {
+            return Err(DatabaseError::QueryError("Cannot compact inside a transaction".to_string()));
+        }
+        
+        let size_before = self.file_size()?;
+        self.conn.execute_batch("VACUUM")?;
+        
+        let journal_mode: String = self.conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?;
+        if journal_mode.eq_ignore_ascii_case("wal") {
+            self.conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+        }
+        
+        Ok(CompactReport { size_before, size_after: self.file_size()? })
+    }
+    
+    /// Size of the main database file (pages in use and free), without any WAL
+    fn file_size(&self) -> Result<u64, DatabaseError> {
+        let pages: u64 = self.conn.query_row("PRAGMA page_count", [], |row| row.get(0))?;
+        let page_size: u64 = self.conn.query_row("PRAGMA page_size", [], |row| row.get(0))?;
+        Ok(pages * page_size)
+    }
+}
+
+/// Take the error of a write-back that failed while a `Database` was dropped
+///
+/// Dropping is only a fallback for a missed `sync`; callers that let a remote
+/// database close (the CLI at exit, the GUI on lock) check here afterwards.
+pub fn take_write_back_error() -> Option<DatabaseError> {
+    DROPPED_WRITE_BACK_ERROR.lock().ok().and_then(|mut error| error.take())
+}
+
+/// Whether this build's SQLite is SQLCipher, so databases can be encrypted at rest
+pub fn sqlcipher_available() -> bool {
+    Connection::open_in_memory().is_ok_and(|conn| connection_has_sqlcipher(&conn))
+}
+
+/// Plain SQLite ignores unknown pragmas, so `cipher_version` returns no row there
+fn connection_has_sqlcipher(conn: &Connection) -> bool {
+    conn.query_row("PRAGMA cipher_version", [], |row| row.get::<_, String>(0)).is_ok()
+}
+
+/// Key the connection, before anything touches the file
+fn apply_cipher_key(conn: &Connection, cipher_key: &[u8; 32]) -> Result<(), DatabaseError> {
+    if !connection_has_sqlcipher(conn) {
+        return Err(DatabaseError::ConnectionError(format!(
+            "{}; rebuild with `--features sqlcipher` to open an encrypted vault",
+            SQLCIPHER_MISSING_ERROR
+        )));
+    }
+    
+    conn.execute_batch(&key_pragma("key", cipher_key))
+        .map_err(|e| DatabaseError::ConnectionError(format!("Failed to key the database: {}", e)))
+}
+
+/// `PRAGMA key` or `PRAGMA rekey` statement; sized up front so no unwiped copy is left by growing
+fn key_pragma(pragma: &str, cipher_key: &[u8; 32]) -> Zeroizing<String> {
+    let mut statement = Zeroizing::new(String::with_capacity(96));
+    let _ = write!(statement, "PRAGMA {} = \"", pragma);
+    statement.push_str(&raw_key_literal(cipher_key));
+    statement.push_str("\";");
+    statement
+}
+
+/// SQLCipher's `x'...'` form, which uses the key as is instead of running its own KDF
+fn raw_key_literal(cipher_key: &[u8; 32]) -> Zeroizing<String> {
+    let mut literal = Zeroizing::new(String::with_capacity(67));
+    literal.push_str("x'");
+    for byte in cipher_key {
+        let _ = write!(literal, "{:02x}", byte);
+    }
+    literal.push('\'');
+    literal
+}
+
+impl Drop for Database {
+    fn drop(&mut self) {
+        let Some(mut working_copy) = self.working_copy.take() else { return };
+        
+        // Close the working file before copying it back
+        if let Ok(placeholder) = Connection::open_in_memory() {
+            let _ = std::mem::replace(&mut self.conn, placeholder).close();
+        }
+        
+        // Best effort: the working file is kept if this fails
+        match working_copy.write_back() {
+            Ok(_) => working_copy.discard(),
+            Err(e) => {
+                if let Ok(mut error) = DROPPED_WRITE_BACK_ERROR.lock() {
+                    *error = Some(e);
+                }
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -108,4 +353,57 @@ mod tests {
             .unwrap();
         assert_eq!(foreign_keys, 1);
     }
+    
+    #[test]
+    fn test_compact_reclaims_deleted_pages() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        for i in 0..200 {
+            conn.execute(
+                "INSERT INTO projects (name, description, created_at, updated_at) VALUES (?1, ?2, 0, 0)",
+                (format!("p{}", i), "x".repeat(1000)),
+            ).unwrap();
+        }
+        conn.execute("DELETE FROM projects", []).unwrap();
+        
+        let report = db.compact().unwrap();
+        assert!(report.size_after < report.size_before, "{:?}", report);
+        assert_eq!(db.compact().unwrap().size_before, report.size_after);
+        
+        // VACUUM can't run inside a transaction
+        let tx = conn.unchecked_transaction().unwrap();
+        assert!(db.compact().is_err());
+        drop(tx);
+    }
+    
+    #[test]
+    fn test_validate_rejects_files_that_are_not_vaults() {
+        let dir = std::env::temp_dir().join(format!("clerk-validate-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let error = |path: &Path| Database::new(path).unwrap().validate().unwrap_err().to_string();
+        
+        // Empty and initialized databases are both fine
+        let vault = dir.join("vault.db");
+        let db = Database::new(&vault).unwrap();
+        assert!(db.validate().is_ok());
+        db.initialize().unwrap();
+        assert!(db.validate().is_ok());
+        
+        let garbage = dir.join("garbage.db");
+        std::fs::write(&garbage, vec![0x42u8; 4096]).unwrap();
+        assert!(error(&garbage).contains(NOT_A_VAULT_ERROR));
+        
+        let other = dir.join("other.db");
+        Database::new(&other).unwrap().conn.execute("CREATE TABLE notes (body TEXT)", []).unwrap();
+        assert!(error(&other).contains("no vault_metadata table"));
+        
+        db.conn.execute("UPDATE vault_metadata SET version = ?1 WHERE id = 1", [schema::SCHEMA_VERSION + 1]).unwrap();
+        assert!(matches!(db.validate(), Err(DatabaseError::NewerSchema { .. })));
+        
+        drop(db);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
@@ -1,7 +1,38 @@
 use rusqlite::Connection;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use crate::database::migrations::{get_audit_enabled, set_audit_enabled as store_audit_enabled};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditLogEntry {
+    pub id: i64,
+    pub timestamp: i64,
+    pub operation_type: String,
+    pub entity_type: String,
+    pub entity_id: Option<i64>,
+    pub entity_name: Option<String>,
+    pub details: Option<String>,
+    pub created_at: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditLogFilter {
+    pub entity_type: Option<String>,
+    pub entity_id: Option<i64>,
+    pub operation_type: Option<String>,
+    pub start_date: Option<i64>,
+    pub end_date: Option<i64>,
+    /// Only return entries with an id greater than this cursor
+    pub after_id: Option<i64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
 
 /// Log an audit entry to the audit_log table
+///
+/// A no-op while the vault's `audit_enabled` setting is off. A database
+/// without the setting (or its metadata row) logs as before.
 pub fn log_audit(
     conn: &Connection,
     operation_type: &str,
@@ -9,6 +40,22 @@ pub fn log_audit(
     entity_id: Option<i64>,
     entity_name: Option<&str>,
     details: Option<serde_json::Value>,
+) -> Result<(), String> {
+    if !get_audit_enabled(conn).unwrap_or(true) {
+        return Ok(());
+    }
+    
+    insert_audit_entry(conn, operation_type, entity_type, entity_id, entity_name, details)
+}
+
+/// Write an audit entry whether or not audit logging is enabled
+fn insert_audit_entry(
+    conn: &Connection,
+    operation_type: &str,
+    entity_type: &str,
+    entity_id: Option<i64>,
+    entity_name: Option<&str>,
+    details: Option<serde_json::Value>,
 ) -> Result<(), String> {
     let now = Utc::now().timestamp();
     let details_str = details.map(|d| d.to_string());
@@ -31,6 +78,299 @@ pub fn log_audit(
     Ok(())
 }
 
+/// Query audit logs with optional filtering and pagination, most recent first
+pub fn query_audit_logs(
+    conn: &Connection,
+    filter: Option<&AuditLogFilter>,
+) -> Result<Vec<AuditLogEntry>, String> {
+    // Build query dynamically based on filters
+    let mut query = String::from(
+        "SELECT id, timestamp, operation_type, entity_type, entity_id, entity_name, details, created_at 
+         FROM audit_log WHERE 1=1"
+    );
+    
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    
+    if let Some(f) = filter {
+        if let Some(ref et) = f.entity_type {
+            query.push_str(" AND entity_type = ?");
+            params.push(Box::new(et.clone()));
+        }
+        
+        if let Some(eid) = f.entity_id {
+            query.push_str(" AND entity_id = ?");
+            params.push(Box::new(eid));
+        }
+        
+        if let Some(ref ot) = f.operation_type {
+            query.push_str(" AND operation_type = ?");
+            params.push(Box::new(ot.clone()));
+        }
+        
+        if let Some(start) = f.start_date {
+            query.push_str(" AND timestamp >= ?");
+            params.push(Box::new(start));
+        }
+        
+        if let Some(end) = f.end_date {
+            query.push_str(" AND timestamp <= ?");
+            params.push(Box::new(end));
+        }
+        
+        if let Some(after) = f.after_id {
+            query.push_str(" AND id > ?");
+            params.push(Box::new(after));
+        }
+    }
+    
+    // Always order by timestamp DESC (most recent first)
+    query.push_str(" ORDER BY timestamp DESC, id DESC");
+    
+    // Add pagination
+    if let Some(f) = filter {
+        if let Some(limit) = f.limit {
+            query.push_str(" LIMIT ?");
+            params.push(Box::new(limit));
+        }
+        
+        if let Some(offset) = f.offset {
+            query.push_str(" OFFSET ?");
+            params.push(Box::new(offset));
+        }
+    }
+    
+    let mut stmt = conn.prepare(&query)
+        .map_err(|e| format!("Failed to prepare query: {}", e))?;
+    
+    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+    
+    let logs = stmt.query_map(param_refs.as_slice(), |row| {
+        Ok(AuditLogEntry {
+            id: row.get(0)?,
+            timestamp: row.get(1)?,
+            operation_type: row.get(2)?,
+            entity_type: row.get(3)?,
+            entity_id: row.get(4)?,
+            entity_name: row.get(5)?,
+            details: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    })
+    .map_err(|e| format!("Failed to query audit logs: {}", e))?
+    .collect::<Result<Vec<_>, _>>()
+    .map_err(|e| format!("Failed to collect audit logs: {}", e))?;
+    
+    Ok(logs)
+}
+
+/// Every entry matching `filter`, for export
+///
+/// Same query as `query_audit_logs`, but `limit` and `offset` are ignored: a
+/// filter taken from a paged view must still export the whole match.
+pub fn export_audit_logs(conn: &Connection, filter: Option<&AuditLogFilter>) -> Result<Vec<AuditLogEntry>, String> {
+    let unpaged = AuditLogFilter {
+        limit: None,
+        offset: None,
+        ..filter.cloned().unwrap_or_default()
+    };
+    query_audit_logs(conn, Some(&unpaged))
+}
+
+/// Render entries as CSV with a header row, one line per entry
+pub fn audit_logs_to_csv(logs: &[AuditLogEntry]) -> String {
+    let mut csv_content = String::from("Timestamp,Operation,Entity Type,Entity ID,Entity Name,Details\n");
+    
+    for log in logs {
+        let timestamp = DateTime::from_timestamp(log.timestamp, 0)
+            .unwrap_or_else(Utc::now)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        
+        let entity_id = log.entity_id.map(|id| id.to_string()).unwrap_or_default();
+        let entity_name = log.entity_name.as_deref().unwrap_or("");
+        let details = log.details.as_deref().unwrap_or("");
+        
+        // Escape CSV fields
+        let escaped_name = entity_name.replace('"', "\"\"");
+        let escaped_details = details.replace('"', "\"\"");
+        
+        csv_content.push_str(&format!(
+            "\"{}\",\"{}\",\"{}\",\"{}\",\"{}\",\"{}\"\n",
+            timestamp,
+            log.operation_type,
+            log.entity_type,
+            entity_id,
+            escaped_name,
+            escaped_details
+        ));
+    }
+    
+    csv_content
+}
+
+/// Format version written into audit archives
+pub const AUDIT_ARCHIVE_VERSION: u32 = 1;
+
+/// A portable copy of (part of) the audit log, written by `clerk audit archive`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditArchive {
+    pub version: u32,
+    pub exported_at: i64,
+    /// Entries oldest first
+    pub entries: Vec<AuditLogEntry>,
+}
+
+/// Result of merging an archive back into the audit log
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct AuditImportReport {
+    pub imported: usize,
+    /// Entries already present (same id and timestamp)
+    pub skipped: usize,
+}
+
+/// Collect audit entries older than `before` (or all of them) into an archive
+pub fn archive_audit_logs(conn: &Connection, before: Option<i64>) -> Result<AuditArchive, String> {
+    let filter = AuditLogFilter {
+        end_date: before.map(|b| b - 1),
+        ..Default::default()
+    };
+    let mut entries = query_audit_logs(conn, Some(&filter))?;
+    entries.sort_by_key(|e| (e.timestamp, e.id));
+    
+    Ok(AuditArchive {
+        version: AUDIT_ARCHIVE_VERSION,
+        exported_at: Utc::now().timestamp(),
+        entries,
+    })
+}
+
+/// Delete the entries contained in `archive` from the audit log
+///
+/// Only rows matching both id and timestamp are removed, so entries written
+/// after the archive was taken are never touched. The prune itself is logged.
+pub fn prune_archived_logs(conn: &Connection, archive: &AuditArchive) -> Result<usize, String> {
+    let tx = conn.unchecked_transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    
+    let mut removed = 0;
+    {
+        let mut stmt = tx.prepare("DELETE FROM audit_log WHERE id = ?1 AND timestamp = ?2")
+            .map_err(|e| format!("Failed to prepare query: {}", e))?;
+        for entry in &archive.entries {
+            removed += stmt.execute((entry.id, entry.timestamp))
+                .map_err(|e| format!("Failed to prune audit log: {}", e))?;
+        }
+    }
+    
+    log_audit(&tx, "prune", "audit_log", None, None, Some(json!({ "removed": removed })))?;
+    
+    tx.commit().map_err(|e| format!("Failed to commit prune: {}", e))?;
+    Ok(removed)
+}
+
+/// Delete every audit entry, leaving one `audit_cleared` entry that records the wipe
+///
+/// The wipe is recorded even while audit logging is disabled. Returns the
+/// number of entries removed.
+pub fn clear_audit_logs(conn: &Connection) -> Result<usize, String> {
+    let tx = conn.unchecked_transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    
+    let removed = tx.execute("DELETE FROM audit_log", [])
+        .map_err(|e| format!("Failed to clear audit log: {}", e))?;
+    insert_audit_entry(&tx, "audit_cleared", "audit_log", None, None, Some(json!({ "removed": removed })))?;
+    
+    tx.commit().map_err(|e| format!("Failed to commit clear: {}", e))?;
+    Ok(removed)
+}
+
+/// Turn audit logging on or off
+///
+/// The switch itself is logged either way: before logging stops, or once it
+/// has restarted. Existing entries are kept.
+pub fn set_audit_enabled(conn: &Connection, enabled: bool) -> Result<(), String> {
+    let tx = conn.unchecked_transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    
+    if !enabled {
+        log_audit(&tx, "audit_disabled", "audit_log", None, None, None)?;
+    }
+    store_audit_enabled(&tx, enabled).map_err(|e| format!("Failed to save audit setting: {}", e))?;
+    if enabled {
+        log_audit(&tx, "audit_enabled", "audit_log", None, None, None)?;
+    }
+    
+    tx.commit().map_err(|e| format!("Failed to commit audit setting: {}", e))
+}
+
+/// Merge an archive back into the audit log, skipping entries already present
+///
+/// Entries keep their original id when it is free. If the id is taken by a
+/// different entry (e.g. an archive from another vault), the entry is stored
+/// under a new id unless an identical entry is already there.
+pub fn import_audit_archive(conn: &Connection, archive: &AuditArchive) -> Result<AuditImportReport, String> {
+    if archive.version > AUDIT_ARCHIVE_VERSION {
+        return Err(format!("Unsupported audit archive version {} (expected {} or lower)", archive.version, AUDIT_ARCHIVE_VERSION));
+    }
+    
+    let tx = conn.unchecked_transaction()
+        .map_err(|e| format!("Failed to start transaction: {}", e))?;
+    let mut report = AuditImportReport::default();
+    
+    for entry in &archive.entries {
+        let existing_timestamp: Option<i64> = tx
+            .query_row("SELECT timestamp FROM audit_log WHERE id = ?1", [entry.id], |row| row.get(0))
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                other => Err(other),
+            })
+            .map_err(|e| format!("Failed to read audit log: {}", e))?;
+        
+        let id = match existing_timestamp {
+            Some(ts) if ts == entry.timestamp => {
+                report.skipped += 1;
+                continue;
+            }
+            Some(_) => {
+                let duplicate: bool = tx.query_row(
+                    "SELECT EXISTS(SELECT 1 FROM audit_log WHERE timestamp = ?1 AND operation_type = ?2
+                     AND entity_type = ?3 AND entity_id IS ?4 AND entity_name IS ?5 AND details IS ?6)",
+                    (entry.timestamp, &entry.operation_type, &entry.entity_type, entry.entity_id, &entry.entity_name, &entry.details),
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Failed to read audit log: {}", e))?;
+                if duplicate {
+                    report.skipped += 1;
+                    continue;
+                }
+                None
+            }
+            None => Some(entry.id),
+        };
+        
+        tx.execute(
+            "INSERT INTO audit_log (id, timestamp, operation_type, entity_type, entity_id, entity_name, details, created_at) 
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            (
+                id,
+                entry.timestamp,
+                &entry.operation_type,
+                &entry.entity_type,
+                entry.entity_id,
+                &entry.entity_name,
+                &entry.details,
+                entry.created_at,
+            ),
+        )
+        .map_err(|e| format!("Failed to import audit entry: {}", e))?;
+        report.imported += 1;
+    }
+    
+    tx.commit().map_err(|e| format!("Failed to commit import: {}", e))?;
+    Ok(report)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -75,4 +415,136 @@ mod tests {
         
         assert_eq!(count, 1);
     }
+
+    #[test]
+    fn test_query_audit_logs_after_cursor() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        for name in ["A", "B", "C"] {
+            log_audit(conn, "create", "variable", None, Some(name), None).unwrap();
+        }
+        
+        let all = query_audit_logs(conn, None).unwrap();
+        assert_eq!(all.len(), 3);
+        
+        let cursor = all.iter().find(|e| e.entity_name.as_deref() == Some("A")).unwrap().id;
+        let filter = AuditLogFilter { after_id: Some(cursor), ..Default::default() };
+        let newer = query_audit_logs(conn, Some(&filter)).unwrap();
+        
+        let names: Vec<_> = newer.iter().filter_map(|e| e.entity_name.as_deref()).collect();
+        assert_eq!(names, vec!["C", "B"]);
+    }
+
+    #[test]
+    fn test_exports_ignore_pagination_and_agree() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        for name in ["A", "B", "C"] {
+            log_audit(conn, "create", "variable", None, Some(name), None).unwrap();
+        }
+        log_audit(conn, "delete", "variable", None, Some("A"), None).unwrap();
+        
+        // A filter from the paged GUI view still exports every match
+        let filter = AuditLogFilter { operation_type: Some("create".to_string()), limit: Some(1), offset: Some(1), ..Default::default() };
+        assert_eq!(query_audit_logs(conn, Some(&filter)).unwrap().len(), 1);
+        
+        let exported = export_audit_logs(conn, Some(&filter)).unwrap();
+        let json: Vec<AuditLogEntry> = serde_json::from_str(&serde_json::to_string(&exported).unwrap()).unwrap();
+        let csv = audit_logs_to_csv(&exported);
+        assert_eq!(json.len(), 3);
+        assert_eq!(csv.lines().count() - 1, json.len());
+        assert_eq!(export_audit_logs(conn, None).unwrap().len(), 4);
+    }
+    
+    #[test]
+    fn test_clear_audit_logs_leaves_a_record() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        for name in ["A", "B"] {
+            log_audit(conn, "create", "variable", None, Some(name), None).unwrap();
+        }
+        
+        assert_eq!(clear_audit_logs(conn).unwrap(), 2);
+        let remaining = query_audit_logs(conn, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].operation_type, "audit_cleared");
+        assert_eq!(remaining[0].details.as_deref(), Some(r#"{"removed":2}"#));
+        
+        // Disabling the log doesn't hide a wipe
+        set_audit_enabled(conn, false).unwrap();
+        assert_eq!(clear_audit_logs(conn).unwrap(), 2);
+        let remaining = query_audit_logs(conn, None).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].operation_type, "audit_cleared");
+    }
+    
+    #[test]
+    fn test_disabled_audit_log_records_nothing_but_the_switch() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        assert!(get_audit_enabled(conn).unwrap());
+        
+        set_audit_enabled(conn, false).unwrap();
+        log_audit(conn, "create", "variable", None, Some("HIDDEN"), None).unwrap();
+        set_audit_enabled(conn, true).unwrap();
+        log_audit(conn, "create", "variable", None, Some("SEEN"), None).unwrap();
+        
+        let operations: Vec<(String, Option<String>)> = query_audit_logs(conn, None).unwrap()
+            .into_iter()
+            .rev()
+            .map(|entry| (entry.operation_type, entry.entity_name))
+            .collect();
+        assert_eq!(operations, vec![
+            ("audit_disabled".to_string(), None),
+            ("audit_enabled".to_string(), None),
+            ("create".to_string(), Some("SEEN".to_string())),
+        ]);
+    }
+    
+    #[test]
+    fn test_archive_prune_and_import_roundtrip() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        for name in ["A", "B"] {
+            log_audit(conn, "create", "variable", None, Some(name), None).unwrap();
+        }
+        conn.execute("UPDATE audit_log SET timestamp = 100", []).unwrap();
+        log_audit(conn, "create", "variable", None, Some("C"), None).unwrap();
+        
+        let archive = archive_audit_logs(conn, Some(1000)).unwrap();
+        let names: Vec<_> = archive.entries.iter().filter_map(|e| e.entity_name.as_deref()).collect();
+        assert_eq!(names, vec!["A", "B"]);
+        
+        // Importing into the same log is a no-op
+        let report = import_audit_archive(conn, &archive).unwrap();
+        assert_eq!(report, AuditImportReport { imported: 0, skipped: 2 });
+        
+        assert_eq!(prune_archived_logs(conn, &archive).unwrap(), 2);
+        let remaining = query_audit_logs(conn, None).unwrap();
+        assert!(remaining.iter().all(|e| e.timestamp > 100));
+        
+        // Round-trip through JSON, then merge back twice
+        let json = serde_json::to_string(&archive).unwrap();
+        let restored: AuditArchive = serde_json::from_str(&json).unwrap();
+        assert_eq!(import_audit_archive(conn, &restored).unwrap().imported, 2);
+        assert_eq!(import_audit_archive(conn, &restored).unwrap().skipped, 2);
+        assert_eq!(query_audit_logs(conn, None).unwrap().len(), 4);
+        
+        // An entry whose id is taken by something else gets a new id
+        let mut foreign = restored.clone();
+        foreign.entries.truncate(1);
+        foreign.entries[0].id = remaining[0].id;
+        assert_eq!(import_audit_archive(conn, &foreign).unwrap(), AuditImportReport { imported: 0, skipped: 1 });
+        foreign.entries[0].entity_name = Some("D".to_string());
+        assert_eq!(import_audit_archive(conn, &foreign).unwrap().imported, 1);
+    }
 }
@@ -0,0 +1,107 @@
+use rusqlite::{Connection, params};
+use crate::crypto::encryption;
+use crate::database::{DatabaseError, migrations::get_vault_id, operations::variables::verify_encryption_key};
+
+/// Known plaintext encrypted with the vault key to detect a wrong key at unlock
+const CANARY_PLAINTEXT: &[u8] = b"clerk-key-canary-v1";
+
+/// AAD binding the canary to this vault
+fn canary_aad(conn: &Connection) -> Result<String, DatabaseError> {
+    Ok(format!("vault:{};canary", get_vault_id(conn)?))
+}
+
+/// Encrypt the canary with `encryption_key` and store it, replacing any previous one
+pub fn store_key_canary(conn: &Connection, encryption_key: &[u8; 32]) -> Result<(), DatabaseError> {
+    let aad = canary_aad(conn)?;
+    let canary = encryption::encrypt(encryption_key, CANARY_PLAINTEXT, aad.as_bytes())
+        .map_err(|e| DatabaseError::EncryptionError(e.to_string()))?;
+    
+    conn.execute(
+        "UPDATE vault_metadata SET key_canary = ? WHERE id = 1",
+        params![canary],
+    )?;
+    
+    Ok(())
+}
+
+/// Write a canary if the vault has none yet (vaults created before canaries existed)
+///
+/// Only call this with a key derived from a verified password: for an empty
+/// vault there is nothing else to check the key against.
+pub fn ensure_key_canary(conn: &Connection, encryption_key: &[u8; 32]) -> Result<(), DatabaseError> {
+    if load_canary(conn)?.is_none() {
+        store_key_canary(conn, encryption_key)?;
+    }
+    
+    Ok(())
+}
+
+/// Confirm `encryption_key` belongs to this vault before any user data is touched
+///
+/// Checks the canary when one is stored, otherwise falls back to decrypting one
+/// variable. Returns `DatabaseError::KeyMismatch` for a wrong key.
+pub fn verify_key(conn: &Connection, encryption_key: &[u8; 32]) -> Result<(), DatabaseError> {
+    match load_canary(conn)? {
+        Some(canary) => {
+            let aad = canary_aad(conn)?;
+            match encryption::decrypt(encryption_key, &canary, aad.as_bytes()) {
+                Ok(plaintext) if plaintext.as_slice() == CANARY_PLAINTEXT => Ok(()),
+                _ => Err(DatabaseError::KeyMismatch),
+            }
+        }
+        None if verify_encryption_key(conn, encryption_key)? => Ok(()),
+        None => Err(DatabaseError::KeyMismatch),
+    }
+}
+
+fn load_canary(conn: &Connection) -> Result<Option<Vec<u8>>, DatabaseError> {
+    let canary = conn.query_row(
+        "SELECT key_canary FROM vault_metadata WHERE id = 1",
+        [],
+        |row| row.get(0),
+    )?;
+    
+    Ok(canary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, operations::{Environment, Project, environments, projects, variables}};
+    
+    const KEY: [u8; 32] = [1u8; 32];
+    const WRONG_KEY: [u8; 32] = [2u8; 32];
+    
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        db
+    }
+    
+    #[test]
+    fn test_canary_detects_wrong_key() {
+        let db = setup_db();
+        store_key_canary(db.connection(), &KEY).unwrap();
+        
+        assert!(verify_key(db.connection(), &KEY).is_ok());
+        assert!(matches!(verify_key(db.connection(), &WRONG_KEY), Err(DatabaseError::KeyMismatch)));
+    }
+    
+    #[test]
+    fn test_canary_added_to_existing_vault() {
+        let db = setup_db();
+        let project_id = projects::create_project(db.connection(), &Project::new("P".to_string(), None)).unwrap();
+        let env_id = environments::create_environment(db.connection(), &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        variables::create_variable_encrypted(db.connection(), env_id, "A".to_string(), "1".to_string(), None, &KEY).unwrap();
+        
+        // No canary yet: a wrong key is still caught via the stored variable
+        assert!(matches!(verify_key(db.connection(), &WRONG_KEY), Err(DatabaseError::KeyMismatch)));
+        
+        verify_key(db.connection(), &KEY).unwrap();
+        ensure_key_canary(db.connection(), &KEY).unwrap();
+        
+        // The canary now exists, so the check no longer depends on user data
+        variables::delete_variable(db.connection(), 1, false).unwrap();
+        assert!(matches!(verify_key(db.connection(), &WRONG_KEY), Err(DatabaseError::KeyMismatch)));
+    }
+}
@@ -0,0 +1,94 @@
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::database::{DatabaseError, operations::environments::get_environments_by_project};
+
+/// Which environments of a project define a given key
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyCoverageRow {
+    pub key: String,
+    /// One entry per environment, in the same order as `KeyCoverage::environments`
+    pub present: Vec<bool>,
+}
+
+impl KeyCoverageRow {
+    /// True if at least one environment is missing this key
+    pub fn has_gaps(&self) -> bool {
+        self.present.iter().any(|p| !p)
+    }
+}
+
+/// Key/environment matrix for one project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyCoverage {
+    pub environments: Vec<String>,
+    pub keys: Vec<KeyCoverageRow>,
+}
+
+impl KeyCoverage {
+    /// Rows for keys missing from at least one environment
+    pub fn gaps(&self) -> impl Iterator<Item = &KeyCoverageRow> {
+        self.keys.iter().filter(|row| row.has_gaps())
+    }
+}
+
+/// Build the key coverage matrix of a project
+///
+/// Only key names are read, so no decryption is needed. Keys and environments
+/// are sorted by name.
+pub fn key_coverage(conn: &Connection, project_id: i64) -> Result<KeyCoverage, DatabaseError> {
+    let environments = get_environments_by_project(conn, project_id)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT v.key, v.environment_id FROM variables v
+         JOIN environments e ON e.id = v.environment_id
+         WHERE e.project_id = ?"
+    )?;
+    
+    let pairs = stmt.query_map(params![project_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    let mut matrix: BTreeMap<String, Vec<bool>> = BTreeMap::new();
+    for (key, env_id) in pairs {
+        let row = matrix.entry(key).or_insert_with(|| vec![false; environments.len()]);
+        if let Some(index) = environments.iter().position(|e| e.id == Some(env_id)) {
+            row[index] = true;
+        }
+    }
+    
+    Ok(KeyCoverage {
+        environments: environments.into_iter().map(|e| e.name).collect(),
+        keys: matrix.into_iter().map(|(key, present)| KeyCoverageRow { key, present }).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, operations::{Environment, Project, Variable, environments, projects, variables}};
+    
+    #[test]
+    fn test_key_coverage_finds_gaps() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        let project_id = projects::create_project(conn, &Project::new("app".to_string(), None)).unwrap();
+        let prod = environments::create_environment(conn, &Environment::new(project_id, "prod".to_string(), None)).unwrap();
+        let staging = environments::create_environment(conn, &Environment::new(project_id, "staging".to_string(), None)).unwrap();
+        
+        for (env, key) in [(prod, "API_KEY"), (prod, "DB_URL"), (staging, "DB_URL")] {
+            variables::create_variable(conn, &Variable::new(env, key.to_string(), vec![1], None)).unwrap();
+        }
+        
+        let coverage = key_coverage(conn, project_id).unwrap();
+        assert_eq!(coverage.environments, vec!["prod", "staging"]);
+        assert_eq!(coverage.keys, vec![
+            KeyCoverageRow { key: "API_KEY".to_string(), present: vec![true, false] },
+            KeyCoverageRow { key: "DB_URL".to_string(), present: vec![true, true] },
+        ]);
+        
+        let gaps: Vec<_> = coverage.gaps().map(|row| row.key.as_str()).collect();
+        assert_eq!(gaps, vec!["API_KEY"]);
+    }
+}
@@ -0,0 +1,170 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use crate::database::{DatabaseError, migrations::{get_audit_enabled, get_per_value_keys, get_schema_version}};
+use crate::database::operations::quota::{get_quota, Quota};
+
+/// Vault-wide settings that change how data is stored or locked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpSettings {
+    pub nonce_mode: String,
+    pub encrypt_descriptions: bool,
+    pub per_value_keys: bool,
+    pub lock_timeout_minutes: i64,
+    pub exit_lock_policy: String,
+    pub audit_enabled: bool,
+    pub quota: Quota,
+}
+
+/// One variable, described by its storage shape only
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpVariable {
+    pub key: String,
+    /// Length of the stored ciphertext (nonce and tag included)
+    pub encrypted_bytes: i64,
+    pub value_type: String,
+    pub aad_version: u32,
+    pub compressed: bool,
+    pub is_protected: bool,
+    pub has_description: bool,
+    pub description_encrypted: bool,
+    pub expires_at: Option<i64>,
+    pub tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpEnvironment {
+    pub name: String,
+    pub variables: Vec<DumpVariable>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DumpProject {
+    pub name: String,
+    pub has_description: bool,
+    pub environments: Vec<DumpEnvironment>,
+}
+
+/// Redacted picture of a vault for bug reports
+///
+/// Holds names, sizes and flags only: no value, ciphertext or description text
+/// is ever read into it, so it is safe to attach to a public issue.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DebugDump {
+    pub schema_version: u32,
+    pub settings: DumpSettings,
+    pub projects: Vec<DumpProject>,
+}
+
+/// Build a `DebugDump` with read-only queries
+pub fn debug_dump(conn: &Connection) -> Result<DebugDump, DatabaseError> {
+    let settings = conn.query_row(
+        "SELECT COALESCE(nonce_mode, 'random'), COALESCE(encrypt_descriptions, 0),
+                COALESCE(lock_timeout_minutes, 0), COALESCE(exit_lock_policy, 'soft')
+         FROM vault_metadata WHERE id = 1",
+        [],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?, row.get::<_, i64>(2)?, row.get::<_, String>(3)?)),
+    )?;
+    let (nonce_mode, encrypt_descriptions, lock_timeout_minutes, exit_lock_policy) = settings;
+    
+    // Sizes and flags are computed in SQL so the blobs never leave the database
+    let mut stmt = conn.prepare(
+        "SELECT p.name, p.description IS NOT NULL, e.name, v.key, LENGTH(v.encrypted_value), v.value_type,
+                v.aad_version, v.compressed, v.is_protected,
+                v.description IS NOT NULL OR v.encrypted_description IS NOT NULL,
+                v.encrypted_description IS NOT NULL, v.expires_at, v.tags
+         FROM projects p
+         LEFT JOIN environments e ON e.project_id = p.id
+         LEFT JOIN variables v ON v.environment_id = e.id
+         ORDER BY p.name, e.name, v.key",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        let variable = match row.get::<_, Option<String>>(3)? {
+            Some(key) => Some(DumpVariable {
+                key,
+                encrypted_bytes: row.get(4)?,
+                value_type: row.get(5)?,
+                aad_version: row.get(6)?,
+                compressed: row.get(7)?,
+                is_protected: row.get(8)?,
+                has_description: row.get(9)?,
+                description_encrypted: row.get(10)?,
+                expires_at: row.get(11)?,
+                tags: serde_json::from_str(&row.get::<_, String>(12)?).unwrap_or_default(),
+            }),
+            None => None,
+        };
+        Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?, row.get::<_, Option<String>>(2)?, variable))
+    })?;
+    
+    let mut projects: Vec<DumpProject> = Vec::new();
+    for row in rows {
+        let (project, has_description, environment, variable) = row?;
+        if projects.last().is_none_or(|last| last.name != project) {
+            projects.push(DumpProject { name: project, has_description, environments: Vec::new() });
+        }
+        let current = projects.last_mut().expect("pushed above");
+        
+        // Projects without environments, and environments without variables, yield NULL columns
+        let Some(environment) = environment else { continue };
+        if current.environments.last().is_none_or(|last| last.name != environment) {
+            current.environments.push(DumpEnvironment { name: environment, variables: Vec::new() });
+        }
+        if let Some(variable) = variable {
+            current.environments.last_mut().expect("pushed above").variables.push(variable);
+        }
+    }
+    
+    Ok(DebugDump {
+        schema_version: get_schema_version(conn)?,
+        settings: DumpSettings {
+            nonce_mode,
+            encrypt_descriptions,
+            per_value_keys: get_per_value_keys(conn)?,
+            lock_timeout_minutes,
+            exit_lock_policy,
+            audit_enabled: get_audit_enabled(conn)?,
+            quota: get_quota(conn)?,
+        },
+        projects,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, operations::{Environment, Project, environments, projects, variables}};
+    
+    #[test]
+    fn test_debug_dump_has_structure_but_no_secrets() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        let key = [7u8; 32];
+        
+        let app = projects::create_project(conn, &Project::new("app".to_string(), Some("internal notes".to_string()))).unwrap();
+        projects::create_project(conn, &Project::new("empty".to_string(), None)).unwrap();
+        let dev = environments::create_environment(conn, &Environment::new(app, "dev".to_string(), None)).unwrap();
+        environments::create_environment(conn, &Environment::new(app, "prod".to_string(), None)).unwrap();
+        let id = variables::create_variable_encrypted(conn, dev, "API_KEY".to_string(), "sk-very-secret".to_string(), Some("rotated monthly".to_string()), &key).unwrap();
+        variables::set_variable_protected(conn, id, true).unwrap();
+        
+        let dump = debug_dump(conn).unwrap();
+        assert_eq!(dump.schema_version, crate::database::schema::SCHEMA_VERSION);
+        assert_eq!(dump.settings.nonce_mode, "random");
+        assert_eq!(dump.projects.len(), 2);
+        assert!(dump.projects[0].has_description);
+        assert_eq!(dump.projects[0].environments.iter().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["dev", "prod"]);
+        assert!(dump.projects[0].environments[1].variables.is_empty());
+        assert!(dump.projects[1].environments.is_empty());
+        
+        let var = &dump.projects[0].environments[0].variables[0];
+        assert_eq!(var.key, "API_KEY");
+        assert!(var.encrypted_bytes > "sk-very-secret".len() as i64);
+        assert!(var.is_protected && var.has_description && !var.description_encrypted);
+        
+        let json = serde_json::to_string(&dump).unwrap();
+        for secret in ["sk-very-secret", "rotated monthly", "internal notes"] {
+            assert!(!json.contains(secret), "{} leaked", secret);
+        }
+    }
+}
@@ -1,10 +1,13 @@
 use rusqlite::{Connection, params};
 use chrono::Utc;
 use serde_json::json;
-use crate::database::{DatabaseError, operations::{Environment, audit::log_audit}};
+use crate::database::{DatabaseError, migrations::update_last_modified, operations::{Environment, Project, audit::log_audit, projects::{create_project, get_project}, quota::check_environment_quota, variables::{decrypt_description, decrypt_pending_value, decrypt_variable, get_variables_by_environment, rewrite_encrypted_value}}};
+use crate::crypto::SecretString;
 
 /// Create a new environment
 pub fn create_environment(conn: &Connection, env: &Environment) -> Result<i64, DatabaseError> {
+    check_environment_quota(conn, env.project_id)?;
+    
     conn.execute(
         "INSERT INTO environments (project_id, name, description, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
         params![
@@ -31,6 +34,8 @@ pub fn create_environment(conn: &Connection, env: &Environment) -> Result<i64, D
         })),
     );
     
+    let _ = update_last_modified(conn);
+    
     Ok(env_id)
 }
 
@@ -54,10 +59,35 @@ pub fn get_environment(conn: &Connection, id: i64) -> Result<Environment, Databa
     Ok(env)
 }
 
-/// Get all environments for a project
+/// Get an environment of a project by name, or `None` if it doesn't exist
+///
+/// Served by the `UNIQUE(project_id, name)` index.
+pub fn get_environment_by_name(conn: &Connection, project_id: i64, name: &str) -> Result<Option<Environment>, DatabaseError> {
+    let env = conn.query_row(
+        "SELECT id, project_id, name, description, created_at, updated_at FROM environments WHERE project_id = ? AND name = ?",
+        params![project_id, name],
+        |row| Ok(Environment {
+            id: Some(row.get(0)?),
+            project_id: row.get(1)?,
+            name: row.get(2)?,
+            description: row.get(3)?,
+            created_at: row.get(4)?,
+            updated_at: row.get(5)?,
+        }),
+    );
+    
+    match env {
+        Ok(env) => Ok(Some(env)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Get all environments for a project, in their set order (see `set_environment_order`) and then by name
 pub fn get_environments_by_project(conn: &Connection, project_id: i64) -> Result<Vec<Environment>, DatabaseError> {
     let mut stmt = conn.prepare(
-        "SELECT id, project_id, name, description, created_at, updated_at FROM environments WHERE project_id = ? ORDER BY name"
+        "SELECT id, project_id, name, description, created_at, updated_at FROM environments WHERE project_id = ?
+         ORDER BY position IS NULL, position, name"
     )?;
     
     let environments = stmt.query_map(params![project_id], |row| {
@@ -75,10 +105,11 @@ pub fn get_environments_by_project(conn: &Connection, project_id: i64) -> Result
     Ok(environments)
 }
 
-/// Get all environments
+/// Get all environments, grouped by project in each project's order
 pub fn get_all_environments(conn: &Connection) -> Result<Vec<Environment>, DatabaseError> {
     let mut stmt = conn.prepare(
-        "SELECT id, project_id, name, description, created_at, updated_at FROM environments ORDER BY project_id, name"
+        "SELECT id, project_id, name, description, created_at, updated_at FROM environments
+         ORDER BY project_id, position IS NULL, position, name"
     )?;
     
     let environments = stmt.query_map([], |row| {
@@ -121,6 +152,8 @@ pub fn update_environment(conn: &Connection, id: i64, env: &Environment) -> Resu
         })),
     );
     
+    let _ = update_last_modified(conn);
+    
     Ok(())
 }
 
@@ -149,9 +182,282 @@ pub fn delete_environment(conn: &Connection, id: i64) -> Result<(), DatabaseErro
         None,
     );
     
+    let _ = update_last_modified(conn);
+    
+    Ok(())
+}
+
+/// Set the order of a project's environments, first to last
+///
+/// `environment_ids` must list every environment of the project exactly once,
+/// as a drag-and-drop reorder would send it.
+pub fn set_environment_order(conn: &Connection, project_id: i64, environment_ids: &[i64]) -> Result<(), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    
+    let project = get_project(&tx, project_id)?;
+    let mut current: Vec<i64> = get_environments_by_project(&tx, project_id)?
+        .into_iter()
+        .filter_map(|env| env.id)
+        .collect();
+    let mut requested = environment_ids.to_vec();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Err(DatabaseError::InvalidValue(format!(
+            "The new order must list each of the {} environment(s) of project '{}' exactly once",
+            current.len(), project.name
+        )));
+    }
+    
+    for (position, id) in environment_ids.iter().enumerate() {
+        tx.execute("UPDATE environments SET position = ? WHERE id = ?", params![position as i64, id])?;
+    }
+    
+    // Log the audit entry
+    let _ = log_audit(
+        &tx,
+        "reorder_environments",
+        "project",
+        Some(project_id),
+        Some(&project.name),
+        Some(json!({ "environment_ids": environment_ids })),
+    );
+    
+    let _ = update_last_modified(&tx);
+    
+    tx.commit()?;
     Ok(())
 }
 
+/// Move an environment, with all its variables, to another project
+///
+/// Variable AAD includes the project, so every value is decrypted and
+/// re-encrypted for the new project. Runs in one transaction. Fails if the
+/// target project already has an environment with the same name. The moved
+/// environment goes after the target project's ordered ones.
+pub fn move_environment(
+    conn: &Connection,
+    id: i64,
+    new_project_id: i64,
+    encryption_key: &[u8; 32],
+) -> Result<(), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    
+    let env = get_environment(&tx, id)?;
+    if env.project_id == new_project_id {
+        return Ok(());
+    }
+    
+    let target = get_project(&tx, new_project_id)?;
+    if environment_exists(&tx, new_project_id, &env.name)? {
+        return Err(DatabaseError::ConstraintViolation(format!(
+            "Project '{}' already has an environment named '{}'",
+            target.name, env.name
+        )));
+    }
+    check_environment_quota(&tx, new_project_id)?;
+    
+    // Decrypt under the old project before the AAD inputs change
+    let values = get_variables_by_environment(&tx, id)?
+        .into_iter()
+        .map(|var| {
+            let value = decrypt_variable(&tx, &var, encryption_key)?;
+            let description = decrypt_description(&tx, &var, encryption_key)?;
+            let pending = decrypt_pending_value(&tx, &var, encryption_key)?;
+            Ok((var, value, description, pending))
+        })
+        .collect::<Result<Vec<_>, DatabaseError>>()?;
+    
+    tx.execute(
+        "UPDATE environments SET project_id = ?, position = NULL, updated_at = ? WHERE id = ?",
+        params![new_project_id, Utc::now().timestamp(), id],
+    )?;
+    
+    for (var, value, description, pending) in &values {
+        rewrite_encrypted_value(&tx, var, value.expose(), description.clone(), pending.as_ref().map(SecretString::expose), encryption_key)?;
+    }
+    
+    // Log the audit entry
+    let _ = log_audit(
+        &tx,
+        "move",
+        "environment",
+        Some(id),
+        Some(&env.name),
+        Some(json!({
+            "from_project_id": env.project_id,
+            "to_project_id": new_project_id,
+            "variables": values.len(),
+        })),
+    );
+    
+    let _ = update_last_modified(&tx);
+    
+    tx.commit()?;
+    Ok(())
+}
+
+/// Look up a project and one of its environments by name in a single query
+///
+/// Returns `None` when the project doesn't exist, and the project without an
+/// environment when only the environment is missing, so callers can say which.
+pub fn find_project_environment(
+    conn: &Connection,
+    project_name: &str,
+    env_name: &str,
+) -> Result<Option<(Project, Option<Environment>)>, DatabaseError> {
+    let found = conn.query_row(
+        "SELECT p.id, p.name, p.description, p.created_at, p.updated_at,
+                e.id, e.name, e.description, e.created_at, e.updated_at
+         FROM projects p
+         LEFT JOIN environments e ON e.project_id = p.id AND e.name = ?2
+         WHERE p.name = ?1",
+        params![project_name, env_name],
+        |row| {
+            let project = Project {
+                id: Some(row.get(0)?),
+                name: row.get(1)?,
+                description: row.get(2)?,
+                created_at: row.get(3)?,
+                updated_at: row.get(4)?,
+            };
+            let environment = match row.get::<_, Option<i64>>(5)? {
+                Some(id) => Some(Environment {
+                    id: Some(id),
+                    project_id: row.get(0)?,
+                    name: row.get(6)?,
+                    description: row.get(7)?,
+                    created_at: row.get(8)?,
+                    updated_at: row.get(9)?,
+                }),
+                None => None,
+            };
+            Ok((project, environment))
+        },
+    );
+    
+    match found {
+        Ok(found) => Ok(Some(found)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// What `ensure_project_environment` had to create
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Created {
+    pub project: bool,
+    pub environment: bool,
+}
+
+/// Find an environment by project and name, creating whichever of the two is missing
+///
+/// Opens no transaction of its own: run it inside the caller's so a failed
+/// write afterwards also drops what was created here.
+pub fn ensure_project_environment(
+    conn: &Connection,
+    project_name: &str,
+    env_name: &str,
+) -> Result<(i64, Created), DatabaseError> {
+    let mut created = Created::default();
+    
+    let project_id = match find_project_environment(conn, project_name, env_name)? {
+        Some((_, Some(environment))) => {
+            let env_id = environment.id.ok_or_else(|| DatabaseError::NotFound("Environment ID is missing".to_string()))?;
+            return Ok((env_id, created));
+        }
+        Some((project, None)) => project.id.ok_or_else(|| DatabaseError::NotFound("Project ID is missing".to_string()))?,
+        None => {
+            created.project = true;
+            create_project(conn, &Project::new(project_name.to_string(), None))?
+        }
+    };
+    
+    created.environment = true;
+    let env_id = create_environment(conn, &Environment::new(project_id, env_name.to_string(), None))?;
+    Ok((env_id, created))
+}
+
+/// Locks older than this (in seconds) are stale and no longer block anyone
+pub const ENVIRONMENT_LOCK_STALE_SECS: i64 = 24 * 60 * 60;
+
+/// Advisory edit lock on an environment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvironmentLock {
+    pub locked_by: String,
+    pub locked_at: i64,
+}
+
+impl EnvironmentLock {
+    pub fn is_stale(&self, now: i64) -> bool {
+        now - self.locked_at >= ENVIRONMENT_LOCK_STALE_SECS
+    }
+    
+    /// Whether this lock should stop `owner` from writing at `now`
+    pub fn blocks(&self, owner: &str, now: i64) -> bool {
+        self.locked_by != owner && !self.is_stale(now)
+    }
+}
+
+/// Get an environment's lock, stale or not (`None` if unlocked)
+pub fn get_environment_lock(conn: &Connection, env_id: i64) -> Result<Option<EnvironmentLock>, DatabaseError> {
+    let (locked_by, locked_at) = conn.query_row(
+        "SELECT locked_by, locked_at FROM environments WHERE id = ?",
+        params![env_id],
+        |row| Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<i64>>(1)?)),
+    )?;
+    
+    Ok(locked_by.map(|locked_by| EnvironmentLock { locked_by, locked_at: locked_at.unwrap_or(0) }))
+}
+
+/// Take the lock on an environment for `owner`
+///
+/// Re-locking refreshes `locked_at`. A live lock held by someone else fails
+/// with `EnvironmentLocked` unless `force` is set; stale locks are taken over.
+pub fn lock_environment(conn: &Connection, env_id: i64, owner: &str, force: bool) -> Result<(), DatabaseError> {
+    let now = Utc::now().timestamp();
+    
+    let changed = conn.execute(
+        "UPDATE environments SET locked_by = ?1, locked_at = ?2
+         WHERE id = ?3 AND (?4 OR locked_by IS NULL OR locked_by = ?1 OR locked_at <= ?2 - ?5)",
+        params![owner, now, env_id, force, ENVIRONMENT_LOCK_STALE_SECS],
+    )?;
+    
+    if changed == 0 {
+        return match get_environment_lock(conn, env_id)? {
+            Some(lock) => Err(DatabaseError::EnvironmentLocked { locked_by: lock.locked_by, locked_at: lock.locked_at }),
+            None => Err(DatabaseError::NotFound(format!("Environment {} not found", env_id))),
+        };
+    }
+    
+    let _ = log_audit(conn, "lock", "environment", Some(env_id), None, Some(json!({ "locked_by": owner, "forced": force })));
+    
+    Ok(())
+}
+
+/// Release an environment's lock
+///
+/// Only the owner may unlock, unless `force` is set or the lock is stale.
+/// Returns the released lock, or `None` if there was none.
+pub fn unlock_environment(conn: &Connection, env_id: i64, owner: &str, force: bool) -> Result<Option<EnvironmentLock>, DatabaseError> {
+    let Some(lock) = get_environment_lock(conn, env_id)? else {
+        return Ok(None);
+    };
+    
+    if !force && lock.blocks(owner, Utc::now().timestamp()) {
+        return Err(DatabaseError::EnvironmentLocked { locked_by: lock.locked_by, locked_at: lock.locked_at });
+    }
+    
+    conn.execute(
+        "UPDATE environments SET locked_by = NULL, locked_at = NULL WHERE id = ?",
+        params![env_id],
+    )?;
+    
+    let _ = log_audit(conn, "unlock", "environment", Some(env_id), None, Some(json!({ "locked_by": &lock.locked_by, "forced": force })));
+    
+    Ok(Some(lock))
+}
+
 /// Check if an environment exists by name within a project
 pub fn environment_exists(conn: &Connection, project_id: i64, name: &str) -> Result<bool, DatabaseError> {
     let count: i64 = conn.query_row(
@@ -166,7 +472,7 @@ pub fn environment_exists(conn: &Connection, project_id: i64, name: &str) -> Res
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::database::{Database, operations::{Project, projects}};
+    use crate::database::{Database, operations::{Project, projects, variables}};
     
     fn setup_test_db() -> (Database, i64) {
         let db = Database::new_in_memory().unwrap();
@@ -178,6 +484,48 @@ mod tests {
         (db, project_id)
     }
     
+    #[test]
+    fn test_ensure_project_environment_creates_only_what_is_missing() {
+        let (db, project_id) = setup_test_db();
+        let conn = db.connection();
+        let existing = create_environment(conn, &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        
+        assert_eq!(ensure_project_environment(conn, "TestProject", "dev").unwrap(), (existing, Created::default()));
+        
+        let (prod, created) = ensure_project_environment(conn, "TestProject", "prod").unwrap();
+        assert_eq!(created, Created { project: false, environment: true });
+        assert_eq!(get_environment(conn, prod).unwrap().project_id, project_id);
+        
+        let (_, created) = ensure_project_environment(conn, "NewProject", "dev").unwrap();
+        assert_eq!(created, Created { project: true, environment: true });
+        assert!(find_project_environment(conn, "NewProject", "dev").unwrap().unwrap().1.is_some());
+    }
+    
+    #[test]
+    fn test_set_environment_order() {
+        let (db, project_id) = setup_test_db();
+        let conn = db.connection();
+        let names = |conn: &Connection| -> Vec<String> {
+            get_environments_by_project(conn, project_id).unwrap().into_iter().map(|env| env.name).collect()
+        };
+        let prod = create_environment(conn, &Environment::new(project_id, "production".to_string(), None)).unwrap();
+        let dev = create_environment(conn, &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        let staging = create_environment(conn, &Environment::new(project_id, "staging".to_string(), None)).unwrap();
+        assert_eq!(names(conn), ["dev", "production", "staging"]);
+        
+        set_environment_order(conn, project_id, &[dev, staging, prod]).unwrap();
+        assert_eq!(names(conn), ["dev", "staging", "production"]);
+        
+        // New environments go after the ordered ones
+        create_environment(conn, &Environment::new(project_id, "alpha".to_string(), None)).unwrap();
+        assert_eq!(names(conn), ["dev", "staging", "production", "alpha"]);
+        
+        // The list must be the project's environments, each once
+        assert!(matches!(set_environment_order(conn, project_id, &[dev, staging, prod]), Err(DatabaseError::InvalidValue(_))));
+        assert!(matches!(set_environment_order(conn, project_id, &[dev, dev, staging, prod]), Err(DatabaseError::InvalidValue(_))));
+        assert_eq!(names(conn), ["dev", "staging", "production", "alpha"]);
+    }
+    
     #[test]
     fn test_create_and_get_environment() {
         let (db, project_id) = setup_test_db();
@@ -256,4 +604,73 @@ mod tests {
         // Environment should be deleted too
         assert!(get_environment(db.connection(), env_id).is_err());
     }
+    
+    #[test]
+    fn test_move_environment_keeps_values_decryptable() {
+        let (db, project_id) = setup_test_db();
+        let conn = db.connection();
+        let key = [7u8; 32];
+        
+        let other_id = projects::create_project(conn, &Project::new("Other".to_string(), None)).unwrap();
+        let env_id = create_environment(conn, &Environment::new(project_id, "staging".to_string(), None)).unwrap();
+        variables::create_variable_encrypted(conn, env_id, "TOKEN".to_string(), "secret".to_string(), None, &key).unwrap();
+        
+        move_environment(conn, env_id, other_id, &key).unwrap();
+        
+        assert_eq!(get_environment(conn, env_id).unwrap().project_id, other_id);
+        let vars = variables::get_variables_by_environment_decrypted(conn, env_id, &key).unwrap();
+        assert_eq!(vars[0].value.expose(), "secret");
+        
+        // Moving back into a project that already has "staging" is refused
+        create_environment(conn, &Environment::new(project_id, "staging".to_string(), None)).unwrap();
+        assert!(matches!(move_environment(conn, env_id, project_id, &key), Err(DatabaseError::ConstraintViolation(_))));
+        assert_eq!(get_environment(conn, env_id).unwrap().project_id, other_id);
+    }
+    
+    #[test]
+    fn test_environment_lock_blocks_others_until_stale() {
+        let (db, project_id) = setup_test_db();
+        let conn = db.connection();
+        let env_id = create_environment(conn, &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        
+        assert_eq!(get_environment_lock(conn, env_id).unwrap(), None);
+        lock_environment(conn, env_id, "alice@laptop", false).unwrap();
+        lock_environment(conn, env_id, "alice@laptop", false).unwrap();
+        
+        let now = Utc::now().timestamp();
+        let lock = get_environment_lock(conn, env_id).unwrap().unwrap();
+        assert_eq!(lock.locked_by, "alice@laptop");
+        assert!(lock.blocks("bob@desktop", now) && !lock.blocks("alice@laptop", now));
+        
+        assert!(matches!(lock_environment(conn, env_id, "bob@desktop", false), Err(DatabaseError::EnvironmentLocked { .. })));
+        assert!(matches!(unlock_environment(conn, env_id, "bob@desktop", false), Err(DatabaseError::EnvironmentLocked { .. })));
+        
+        // A stale lock can be taken over without --force
+        conn.execute("UPDATE environments SET locked_at = ?1 WHERE id = ?2", params![now - ENVIRONMENT_LOCK_STALE_SECS, env_id]).unwrap();
+        lock_environment(conn, env_id, "bob@desktop", false).unwrap();
+        
+        let released = unlock_environment(conn, env_id, "alice@laptop", true).unwrap().unwrap();
+        assert_eq!(released.locked_by, "bob@desktop");
+        assert_eq!(unlock_environment(conn, env_id, "alice@laptop", false).unwrap(), None);
+    }
+    
+    #[test]
+    fn test_find_project_environment() {
+        let (db, project_id) = setup_test_db();
+        let conn = db.connection();
+        let env_id = create_environment(conn, &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        
+        let (project, environment) = find_project_environment(conn, "TestProject", "dev").unwrap().unwrap();
+        assert_eq!(project.id, Some(project_id));
+        assert_eq!(environment.unwrap().id, Some(env_id));
+        
+        let (_, missing) = find_project_environment(conn, "TestProject", "prod").unwrap().unwrap();
+        assert!(missing.is_none());
+        assert!(find_project_environment(conn, "Nope", "dev").unwrap().is_none());
+        
+        assert_eq!(get_environment_by_name(conn, project_id, "dev").unwrap().and_then(|e| e.id), Some(env_id));
+        assert!(get_environment_by_name(conn, project_id, "prod").unwrap().is_none());
+        assert_eq!(projects::get_project_by_name(conn, "TestProject").unwrap().and_then(|p| p.id), Some(project_id));
+        assert!(projects::get_project_by_name(conn, "Nope").unwrap().is_none());
+    }
 }
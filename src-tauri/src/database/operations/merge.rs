@@ -0,0 +1,254 @@
+use rusqlite::Connection;
+use crate::database::{DatabaseError, operations::{Environment, Project, environments, projects, variables}};
+
+/// How to handle a source project whose name already exists in the target vault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeConflictStrategy {
+    /// Leave the existing target project untouched
+    Skip,
+    /// Merge environments and variables into the existing project (target values win)
+    Merge,
+    /// Merge into the existing project, replacing values of duplicate keys (source values win)
+    Overwrite,
+    /// Import the source project under a new, unused name
+    Rename,
+}
+
+/// Counts of what a merge created, updated or skipped
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct MergeSummary {
+    pub projects_created: usize,
+    pub projects_merged: usize,
+    pub projects_skipped: usize,
+    pub environments_created: usize,
+    pub variables_created: usize,
+    pub variables_updated: usize,
+    pub variables_skipped: usize,
+}
+
+/// Copy every project, environment and variable from the source vault into the target vault
+///
+/// Values are decrypted with the source key and re-encrypted with the target key.
+/// All writes to the target happen in a single transaction, so a failure leaves
+/// the target vault unchanged.
+pub fn merge_vaults(
+    source: &Connection,
+    source_key: &[u8; 32],
+    target: &Connection,
+    target_key: &[u8; 32],
+    strategy: MergeConflictStrategy,
+) -> Result<MergeSummary, DatabaseError> {
+    let tx = target.unchecked_transaction()?;
+    let mut summary = MergeSummary::default();
+
+    for src_project in projects::get_all_projects(source)? {
+        let src_project_id = src_project.id
+            .ok_or_else(|| DatabaseError::NotFound("Source project ID is missing".to_string()))?;
+
+        let existing = projects::get_project_by_name(&tx, &src_project.name)?;
+
+        let (target_project_id, overwrite) = match (existing.and_then(|p| p.id), strategy) {
+            (None, _) => {
+                summary.projects_created += 1;
+                (copy_project(&tx, &src_project.name, &src_project)?, false)
+            }
+            (Some(_), MergeConflictStrategy::Skip) => {
+                summary.projects_skipped += 1;
+                continue;
+            }
+            (Some(_), MergeConflictStrategy::Rename) => {
+                let name = unused_project_name(&tx, &src_project.name)?;
+                summary.projects_created += 1;
+                (copy_project(&tx, &name, &src_project)?, false)
+            }
+            (Some(id), MergeConflictStrategy::Merge) => {
+                summary.projects_merged += 1;
+                (id, false)
+            }
+            (Some(id), MergeConflictStrategy::Overwrite) => {
+                summary.projects_merged += 1;
+                (id, true)
+            }
+        };
+
+        for src_env in environments::get_environments_by_project(source, src_project_id)? {
+            merge_environment(
+                (source, source_key),
+                (&tx, target_key),
+                &src_env,
+                target_project_id,
+                overwrite,
+                &mut summary,
+            )?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(summary)
+}
+
+/// Create a copy of a source project in the target vault under the given name
+fn copy_project(target: &Connection, name: &str, src_project: &Project) -> Result<i64, DatabaseError> {
+    let project = Project::new(name.to_string(), src_project.description.clone());
+    projects::create_project(target, &project)
+}
+
+/// Find a project name that is not taken yet, e.g. "api (merged)", "api (merged 2)"
+fn unused_project_name(target: &Connection, name: &str) -> Result<String, DatabaseError> {
+    let mut candidate = format!("{} (merged)", name);
+    let mut counter = 2;
+
+    while projects::project_exists_by_name(target, &candidate)? {
+        candidate = format!("{} (merged {})", name, counter);
+        counter += 1;
+    }
+
+    Ok(candidate)
+}
+
+/// Copy one environment and its variables into a target project, re-encrypting values
+fn merge_environment(
+    (source, source_key): (&Connection, &[u8; 32]),
+    (target, target_key): (&Connection, &[u8; 32]),
+    src_env: &Environment,
+    target_project_id: i64,
+    overwrite: bool,
+    summary: &mut MergeSummary,
+) -> Result<(), DatabaseError> {
+    let src_env_id = src_env.id
+        .ok_or_else(|| DatabaseError::NotFound("Source environment ID is missing".to_string()))?;
+
+    let existing_env = environments::get_environment_by_name(target, target_project_id, &src_env.name)?
+        .and_then(|e| e.id);
+
+    let target_env_id = match existing_env {
+        Some(id) => id,
+        None => {
+            let env = Environment::new(target_project_id, src_env.name.clone(), src_env.description.clone());
+            summary.environments_created += 1;
+            environments::create_environment(target, &env)?
+        }
+    };
+
+    let existing_vars = variables::get_variables_by_environment(target, target_env_id)?;
+
+    for var in variables::get_variables_by_environment_decrypted(source, src_env_id, source_key)? {
+        match existing_vars.iter().find(|v| v.key == var.key).and_then(|v| v.id) {
+            Some(existing_id) if overwrite => {
+                variables::update_variable_encrypted(
+                    target,
+                    existing_id,
+                    var.key,
+                    var.value.into_exposed(),
+                    var.description,
+                    false,
+                    target_key,
+                )?;
+                summary.variables_updated += 1;
+            }
+            Some(_) => summary.variables_skipped += 1,
+            None => {
+                variables::create_variable_encrypted(
+                    target,
+                    target_env_id,
+                    var.key,
+                    var.value.into_exposed(),
+                    var.description,
+                    target_key,
+                )?;
+                summary.variables_created += 1;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    const SOURCE_KEY: [u8; 32] = [1u8; 32];
+    const TARGET_KEY: [u8; 32] = [2u8; 32];
+
+    fn setup_vault(key: &[u8; 32], project: &str, vars: &[(&str, &str)]) -> Database {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+
+        let project_id = projects::create_project(db.connection(), &Project::new(project.to_string(), None)).unwrap();
+        let env_id = environments::create_environment(
+            db.connection(),
+            &Environment::new(project_id, "dev".to_string(), None),
+        ).unwrap();
+
+        for (k, v) in vars {
+            variables::create_variable_encrypted(db.connection(), env_id, k.to_string(), v.to_string(), None, key).unwrap();
+        }
+
+        db
+    }
+
+    fn target_values(db: &Database, project: &str) -> Vec<(String, String)> {
+        let project = projects::get_project_by_name(db.connection(), project).unwrap().unwrap();
+        let env = environments::get_environments_by_project(db.connection(), project.id.unwrap()).unwrap()
+            .remove(0);
+
+        variables::get_variables_by_environment_decrypted(db.connection(), env.id.unwrap(), &TARGET_KEY).unwrap()
+            .into_iter()
+            .map(|v| (v.key, v.value.into_exposed()))
+            .collect()
+    }
+
+    #[test]
+    fn test_merge_new_project_reencrypts() {
+        let source = setup_vault(&SOURCE_KEY, "api", &[("TOKEN", "abc")]);
+        let target = setup_vault(&TARGET_KEY, "web", &[]);
+
+        let summary = merge_vaults(
+            source.connection(), &SOURCE_KEY,
+            target.connection(), &TARGET_KEY,
+            MergeConflictStrategy::Skip,
+        ).unwrap();
+
+        assert_eq!(summary.projects_created, 1);
+        assert_eq!(summary.variables_created, 1);
+        assert_eq!(target_values(&target, "api"), vec![("TOKEN".to_string(), "abc".to_string())]);
+    }
+
+    #[test]
+    fn test_merge_conflict_strategies() {
+        let source = setup_vault(&SOURCE_KEY, "api", &[("TOKEN", "new"), ("EXTRA", "1")]);
+
+        let target = setup_vault(&TARGET_KEY, "api", &[("TOKEN", "old")]);
+        let summary = merge_vaults(source.connection(), &SOURCE_KEY, target.connection(), &TARGET_KEY, MergeConflictStrategy::Skip).unwrap();
+        assert_eq!(summary.projects_skipped, 1);
+        assert_eq!(target_values(&target, "api"), vec![("TOKEN".to_string(), "old".to_string())]);
+
+        let target = setup_vault(&TARGET_KEY, "api", &[("TOKEN", "old")]);
+        let summary = merge_vaults(source.connection(), &SOURCE_KEY, target.connection(), &TARGET_KEY, MergeConflictStrategy::Merge).unwrap();
+        assert_eq!(summary.variables_skipped, 1);
+        assert_eq!(summary.variables_created, 1);
+        assert_eq!(target_values(&target, "api")[1], ("TOKEN".to_string(), "old".to_string()));
+
+        let target = setup_vault(&TARGET_KEY, "api", &[("TOKEN", "old")]);
+        let summary = merge_vaults(source.connection(), &SOURCE_KEY, target.connection(), &TARGET_KEY, MergeConflictStrategy::Overwrite).unwrap();
+        assert_eq!(summary.variables_updated, 1);
+        assert_eq!(target_values(&target, "api")[1], ("TOKEN".to_string(), "new".to_string()));
+
+        let target = setup_vault(&TARGET_KEY, "api", &[("TOKEN", "old")]);
+        merge_vaults(source.connection(), &SOURCE_KEY, target.connection(), &TARGET_KEY, MergeConflictStrategy::Rename).unwrap();
+        assert_eq!(target_values(&target, "api (merged)").len(), 2);
+    }
+
+    #[test]
+    fn test_merge_with_wrong_source_key_leaves_target_unchanged() {
+        let source = setup_vault(&SOURCE_KEY, "api", &[("TOKEN", "abc")]);
+        let target = setup_vault(&TARGET_KEY, "web", &[]);
+
+        let wrong_key = [9u8; 32];
+        assert!(merge_vaults(source.connection(), &wrong_key, target.connection(), &TARGET_KEY, MergeConflictStrategy::Skip).is_err());
+
+        assert_eq!(projects::get_all_projects(target.connection()).unwrap().len(), 1);
+    }
+}
@@ -1,10 +1,20 @@
 use serde::{Deserialize, Serialize};
 use chrono::Utc;
+use std::fmt;
+use std::str::FromStr;
+use crate::crypto::SecretString;
 
 pub mod projects;
 pub mod environments;
 pub mod variables;
 pub mod audit;
+pub mod merge;
+pub mod quota;
+pub mod canary;
+pub mod coverage;
+pub mod stats;
+pub mod dump;
+pub mod records;
 
 /// Project model
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -54,6 +64,103 @@ impl Environment {
     }
 }
 
+/// AAD format of values written before the vault and project ids were bound in
+pub const LEGACY_AAD_VERSION: u32 = 1;
+
+/// AAD format used for all newly encrypted values
+pub const CURRENT_AAD_VERSION: u32 = 2;
+
+/// Placeholder sent to the GUI instead of a value it did not ask to reveal
+pub const MASKED_VALUE: &str = "********";
+
+/// Expected shape of a variable's value, checked on create and update
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueType {
+    #[default]
+    Text,
+    Url,
+    Json,
+    Bool,
+    Number,
+}
+
+impl ValueType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValueType::Text => "text",
+            ValueType::Url => "url",
+            ValueType::Json => "json",
+            ValueType::Bool => "bool",
+            ValueType::Number => "number",
+        }
+    }
+    
+    /// Check that a value fits this type, returning the reason if it does not
+    pub fn validate(&self, value: &str) -> Result<(), String> {
+        match self {
+            ValueType::Text => Ok(()),
+            ValueType::Url => validate_url(value),
+            ValueType::Json => serde_json::from_str::<serde_json::Value>(value)
+                .map(|_| ())
+                .map_err(|e| format!("not valid JSON ({})", e)),
+            ValueType::Bool => match value {
+                "true" | "false" | "1" | "0" => Ok(()),
+                _ => Err("expected true, false, 1 or 0".to_string()),
+            },
+            ValueType::Number => match value.parse::<f64>() {
+                Ok(n) if n.is_finite() && value.trim() == value => Ok(()),
+                _ => Err("expected a finite number".to_string()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(ValueType::Text),
+            "url" => Ok(ValueType::Url),
+            "json" => Ok(ValueType::Json),
+            "bool" => Ok(ValueType::Bool),
+            "number" => Ok(ValueType::Number),
+            other => Err(format!("Unknown value type '{}' (expected text, url, json, bool or number)", other)),
+        }
+    }
+}
+
+/// Require `scheme://host...` with no whitespace
+fn validate_url(value: &str) -> Result<(), String> {
+    let Some((scheme, rest)) = value.split_once("://") else {
+        return Err("expected scheme://host".to_string());
+    };
+    
+    let valid_scheme = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    if !valid_scheme {
+        return Err(format!("invalid scheme '{}'", scheme));
+    }
+    
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    if host.is_empty() {
+        return Err("missing host".to_string());
+    }
+    
+    if value.chars().any(char::is_whitespace) {
+        return Err("must not contain whitespace".to_string());
+    }
+    
+    Ok(())
+}
+
 /// Variable model (encrypted value)
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Variable {
@@ -62,7 +169,33 @@ pub struct Variable {
     pub key: String,
     #[serde(skip)] // Don't serialize the encrypted bytes
     pub encrypted_value: Vec<u8>,
+    pub aad_version: u32,
+    /// Plaintext was deflated before encryption
+    #[serde(skip)]
+    pub compressed: bool,
+    #[serde(default)]
+    pub value_type: ValueType,
+    /// Updates and deletes are refused unless explicitly forced
+    #[serde(default)]
+    pub is_protected: bool,
     pub description: Option<String>,
+    /// Description encrypted like the value; `description` is `None` when this is set
+    #[serde(skip)]
+    pub encrypted_description: Option<Vec<u8>>,
+    /// Unix time after which the variable is inactive; it is never deleted automatically
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    /// Sorted, unique labels such as `db`
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Value staged for rotation, encrypted; reads use `encrypted_value` until it is promoted
+    #[serde(skip)]
+    pub pending_value: Option<Vec<u8>>,
+    #[serde(skip)]
+    pub pending_compressed: bool,
+    /// Salt of the HKDF subkey the value and pending value are sealed with; `None` means the vault key
+    #[serde(skip)]
+    pub value_salt: Option<Vec<u8>>,
     pub created_at: i64,
     pub updated_at: i64,
 }
@@ -80,11 +213,25 @@ impl Variable {
             environment_id,
             key,
             encrypted_value,
+            aad_version: CURRENT_AAD_VERSION,
+            compressed: false,
+            value_type: ValueType::Text,
+            is_protected: false,
             description,
+            encrypted_description: None,
+            expires_at: None,
+            tags: Vec::new(),
+            pending_value: None,
+            pending_compressed: false,
+            value_salt: None,
             created_at: now,
             updated_at: now,
         }
     }
+    
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
 }
 
 /// Variable with decrypted value (for API responses)
@@ -93,12 +240,44 @@ pub struct VariableDecrypted {
     pub id: i64,
     pub environment_id: i64,
     pub key: String,
-    pub value: String, // Decrypted value
+    pub value: SecretString, // Decrypted value, redacted in Debug output
+    /// Length of the value in characters, set when `value` was masked (see `masked`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value_length: Option<usize>,
+    #[serde(default)]
+    pub value_type: ValueType,
+    #[serde(default)]
+    pub is_protected: bool,
+    /// The plaintext was not valid UTF-8 and invalid bytes were replaced (lossy reads only)
+    #[serde(default)]
+    pub lossy: bool,
     pub description: Option<String>,
+    #[serde(default)]
+    pub expires_at: Option<i64>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// A next value is staged in the pending slot (its plaintext is not included)
+    #[serde(default)]
+    pub has_pending: bool,
     pub created_at: i64,
     pub updated_at: i64,
 }
 
+impl VariableDecrypted {
+    pub fn is_expired(&self, now: i64) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+    
+    /// The same variable with its value replaced by `MASKED_VALUE`, keeping its length
+    ///
+    /// For listings that only show dots, so the plaintext never leaves the backend.
+    pub fn masked(mut self) -> Self {
+        self.value_length = Some(self.value.expose().chars().count());
+        self.value = SecretString::new(MASKED_VALUE.to_string());
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -132,4 +311,54 @@ mod tests {
         assert_eq!(var.key, "API_KEY");
         assert_eq!(var.encrypted_value, vec![1, 2, 3, 4]);
     }
+    
+    #[test]
+    fn test_masked_variable_keeps_only_the_length() {
+        let variable = VariableDecrypted {
+            id: 1,
+            environment_id: 1,
+            key: "API_KEY".to_string(),
+            value: SecretString::new("sk-ü123".to_string()),
+            value_length: None,
+            value_type: ValueType::Text,
+            is_protected: false,
+            lossy: false,
+            description: None,
+            expires_at: None,
+            tags: Vec::new(),
+            has_pending: false,
+            created_at: 0,
+            updated_at: 0,
+        };
+        
+        let json = serde_json::to_value(variable.clone().masked()).unwrap();
+        assert_eq!(json["value"], MASKED_VALUE);
+        assert_eq!(json["value_length"], 7);
+        assert!(serde_json::to_value(variable).unwrap().get("value_length").is_none());
+    }
+    
+    #[test]
+    fn test_value_type_validation() {
+        assert!(ValueType::Text.validate("anything at all").is_ok());
+        
+        assert!(ValueType::Url.validate("https://example.com/path?q=1").is_ok());
+        assert!(ValueType::Url.validate("postgres://user:pw@db:5432/app").is_ok());
+        assert!(ValueType::Url.validate("example.com").is_err());
+        assert!(ValueType::Url.validate("https:///path").is_err());
+        assert!(ValueType::Url.validate("https://exa mple.com").is_err());
+        
+        assert!(ValueType::Json.validate(r#"{"a": [1, 2]}"#).is_ok());
+        assert!(ValueType::Json.validate("{a: 1}").is_err());
+        
+        assert!(ValueType::Bool.validate("true").is_ok());
+        assert!(ValueType::Bool.validate("0").is_ok());
+        assert!(ValueType::Bool.validate("yes").is_err());
+        
+        assert!(ValueType::Number.validate("-1.5e3").is_ok());
+        assert!(ValueType::Number.validate("NaN").is_err());
+        assert!(ValueType::Number.validate(" 42").is_err());
+        
+        assert_eq!("json".parse::<ValueType>(), Ok(ValueType::Json));
+        assert!("float".parse::<ValueType>().is_err());
+    }
 }
@@ -1,10 +1,12 @@
 use rusqlite::{Connection, params};
 use chrono::Utc;
 use serde_json::json;
-use crate::database::{DatabaseError, operations::{Project, audit::log_audit}};
+use crate::database::{DatabaseError, migrations::update_last_modified, operations::{Project, audit::log_audit, quota::check_project_quota}};
 
 /// Create a new project
 pub fn create_project(conn: &Connection, project: &Project) -> Result<i64, DatabaseError> {
+    check_project_quota(conn)?;
+    
     conn.execute(
         "INSERT INTO projects (name, description, created_at, updated_at) VALUES (?, ?, ?, ?)",
         params![
@@ -29,6 +31,8 @@ pub fn create_project(conn: &Connection, project: &Project) -> Result<i64, Datab
         })),
     );
     
+    let _ = update_last_modified(conn);
+    
     Ok(project_id)
 }
 
@@ -95,6 +99,8 @@ pub fn update_project(conn: &Connection, id: i64, project: &Project) -> Result<(
         })),
     );
     
+    let _ = update_last_modified(conn);
+    
     Ok(())
 }
 
@@ -123,9 +129,32 @@ pub fn delete_project(conn: &Connection, id: i64) -> Result<(), DatabaseError> {
         None,
     );
     
+    let _ = update_last_modified(conn);
+    
     Ok(())
 }
 
+/// Get a project by name, or `None` if there is no such project
+pub fn get_project_by_name(conn: &Connection, name: &str) -> Result<Option<Project>, DatabaseError> {
+    let project = conn.query_row(
+        "SELECT id, name, description, created_at, updated_at FROM projects WHERE name = ?",
+        params![name],
+        |row| Ok(Project {
+            id: Some(row.get(0)?),
+            name: row.get(1)?,
+            description: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        }),
+    );
+    
+    match project {
+        Ok(project) => Ok(Some(project)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Check if a project exists by name
 pub fn project_exists_by_name(conn: &Connection, name: &str) -> Result<bool, DatabaseError> {
     let count: i64 = conn.query_row(
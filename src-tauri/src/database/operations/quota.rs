@@ -0,0 +1,125 @@
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use crate::database::DatabaseError;
+
+/// Optional caps on vault size (0 means unlimited)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quota {
+    pub max_projects: u32,
+    pub max_environments_per_project: u32,
+    pub max_variables_per_env: u32,
+}
+
+/// Get the configured quota
+pub fn get_quota(conn: &Connection) -> Result<Quota, DatabaseError> {
+    let quota = conn.query_row(
+        "SELECT COALESCE(max_projects, 0), COALESCE(max_environments_per_project, 0), COALESCE(max_variables_per_env, 0)
+         FROM vault_metadata WHERE id = 1",
+        [],
+        |row| Ok(Quota {
+            max_projects: row.get(0)?,
+            max_environments_per_project: row.get(1)?,
+            max_variables_per_env: row.get(2)?,
+        }),
+    )?;
+    
+    Ok(quota)
+}
+
+/// Replace the configured quota
+pub fn set_quota(conn: &Connection, quota: &Quota) -> Result<(), DatabaseError> {
+    conn.execute(
+        "UPDATE vault_metadata SET max_projects = ?, max_environments_per_project = ?, max_variables_per_env = ? WHERE id = 1",
+        params![quota.max_projects, quota.max_environments_per_project, quota.max_variables_per_env],
+    )?;
+    
+    Ok(())
+}
+
+/// Fail if creating one more project would exceed the quota
+pub fn check_project_quota(conn: &Connection) -> Result<(), DatabaseError> {
+    let limit = get_quota(conn)?.max_projects;
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM projects", [], |row| row.get(0))?;
+    
+    check_limit(count, limit, "projects in this vault")
+}
+
+/// Fail if creating one more environment in the project would exceed the quota
+pub fn check_environment_quota(conn: &Connection, project_id: i64) -> Result<(), DatabaseError> {
+    let limit = get_quota(conn)?.max_environments_per_project;
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM environments WHERE project_id = ?",
+        params![project_id],
+        |row| row.get(0),
+    )?;
+    
+    check_limit(count, limit, "environments per project")
+}
+
+/// Fail if creating one more variable in the environment would exceed the quota
+pub fn check_variable_quota(conn: &Connection, environment_id: i64) -> Result<(), DatabaseError> {
+    let limit = get_quota(conn)?.max_variables_per_env;
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM variables WHERE environment_id = ?",
+        params![environment_id],
+        |row| row.get(0),
+    )?;
+    
+    check_limit(count, limit, "variables per environment")
+}
+
+fn check_limit(count: i64, limit: u32, what: &str) -> Result<(), DatabaseError> {
+    if limit > 0 && count >= i64::from(limit) {
+        return Err(DatabaseError::ConstraintViolation(format!(
+            "Quota exceeded: at most {} {} allowed",
+            limit, what
+        )));
+    }
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, operations::{Environment, Project, environments, projects, variables}};
+    
+    fn setup_db() -> Database {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        db
+    }
+    
+    #[test]
+    fn test_default_quota_is_unlimited() {
+        let db = setup_db();
+        assert_eq!(get_quota(db.connection()).unwrap(), Quota::default());
+        
+        for i in 0..5 {
+            projects::create_project(db.connection(), &Project::new(format!("P{}", i), None)).unwrap();
+        }
+    }
+    
+    #[test]
+    fn test_quota_enforced_on_create() {
+        let db = setup_db();
+        let conn = db.connection();
+        set_quota(conn, &Quota { max_projects: 1, max_environments_per_project: 1, max_variables_per_env: 1 }).unwrap();
+        
+        let project_id = projects::create_project(conn, &Project::new("P".to_string(), None)).unwrap();
+        assert!(matches!(
+            projects::create_project(conn, &Project::new("Q".to_string(), None)),
+            Err(DatabaseError::ConstraintViolation(_))
+        ));
+        
+        let env_id = environments::create_environment(conn, &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        assert!(environments::create_environment(conn, &Environment::new(project_id, "prod".to_string(), None)).is_err());
+        
+        let key = [1u8; 32];
+        variables::create_variable_encrypted(conn, env_id, "A".to_string(), "1".to_string(), None, &key).unwrap();
+        assert!(matches!(
+            variables::create_variable_encrypted(conn, env_id, "B".to_string(), "2".to_string(), None, &key),
+            Err(DatabaseError::ConstraintViolation(_))
+        ));
+    }
+}
@@ -0,0 +1,223 @@
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use crate::database::{DatabaseError, operations::{ValueType, VariableDecrypted, environments::{Created, ensure_project_environment}, variables::{create_variable_encrypted_with_type, get_variables_by_environment, update_variable_encrypted_with_type, validate_tag}}};
+
+/// Identifies the document written by `clerk export --include-metadata`
+pub const RECORDS_FORMAT: &str = "clerk-variables-v1";
+
+/// A variable with everything needed to recreate it, plaintext value included
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VariableRecord {
+    pub key: String,
+    pub value: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub value_type: ValueType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub is_protected: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<i64>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl From<&VariableDecrypted> for VariableRecord {
+    fn from(var: &VariableDecrypted) -> Self {
+        VariableRecord {
+            key: var.key.clone(),
+            value: var.value.expose().to_string(),
+            description: var.description.clone(),
+            value_type: var.value_type,
+            tags: var.tags.clone(),
+            is_protected: var.is_protected,
+            expires_at: var.expires_at,
+            created_at: var.created_at,
+            updated_at: var.updated_at,
+        }
+    }
+}
+
+/// An environment's variables with their metadata, for lossless export and import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvironmentRecords {
+    pub format: String,
+    pub project: String,
+    pub environment: String,
+    pub variables: Vec<VariableRecord>,
+}
+
+impl EnvironmentRecords {
+    pub fn new(project: &str, environment: &str, variables: Vec<VariableRecord>) -> Self {
+        EnvironmentRecords {
+            format: RECORDS_FORMAT.to_string(),
+            project: project.to_string(),
+            environment: environment.to_string(),
+            variables,
+        }
+    }
+    
+    /// Parse `content` if it is a records document, or return `None` for any other input
+    ///
+    /// Other JSON (such as `export --format json`) is left to the plain parsers;
+    /// a document claiming a records format this build doesn't know is an error.
+    pub fn parse(content: &str) -> Result<Option<Self>, DatabaseError> {
+        let Ok(value) = serde_json::from_str::<serde_json::Value>(content) else {
+            return Ok(None);
+        };
+        let Some(format) = value.get("format").and_then(|f| f.as_str()) else {
+            return Ok(None);
+        };
+        if format != RECORDS_FORMAT {
+            if format.starts_with("clerk-variables-") {
+                return Err(DatabaseError::InvalidValue(format!("Unsupported export format '{}'", format)));
+            }
+            return Ok(None);
+        }
+        
+        serde_json::from_value(value)
+            .map(Some)
+            .map_err(|e| DatabaseError::SerializationError(format!("Invalid {} document: {}", RECORDS_FORMAT, e)))
+    }
+}
+
+/// Create or update records in an environment, restoring their metadata
+///
+/// Returns (created, updated, skipped) like `variables::import_variables` and
+/// runs in a single transaction. Tags, protection, expiry and both timestamps
+/// are taken from the record, so an export imported into an empty environment
+/// reads back unchanged. Existing protected variables are not overwritten.
+pub fn import_records(
+    conn: &Connection,
+    environment_id: i64,
+    records: Vec<VariableRecord>,
+    overwrite: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(usize, usize, usize), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    let counts = import_record_entries(&tx, environment_id, records, overwrite, encryption_key)?;
+    tx.commit()?;
+    Ok(counts)
+}
+
+/// Like `import_records`, creating the project and environment first if they are missing
+pub fn import_records_creating(
+    conn: &Connection,
+    project_name: &str,
+    env_name: &str,
+    records: Vec<VariableRecord>,
+    overwrite: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(Created, (usize, usize, usize)), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    let (environment_id, created) = ensure_project_environment(&tx, project_name, env_name)?;
+    let counts = import_record_entries(&tx, environment_id, records, overwrite, encryption_key)?;
+    tx.commit()?;
+    Ok((created, counts))
+}
+
+fn import_record_entries(
+    conn: &Connection,
+    environment_id: i64,
+    records: Vec<VariableRecord>,
+    overwrite: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(usize, usize, usize), DatabaseError> {
+    let existing_variables = get_variables_by_environment(conn, environment_id)?;
+    
+    let mut imported_count = 0;
+    let mut updated_count = 0;
+    let mut skipped_count = 0;
+    
+    for record in records {
+        for tag in &record.tags {
+            validate_tag(tag)?;
+        }
+        
+        let id = match existing_variables.iter().find(|v| v.key == record.key) {
+            Some(_) if !overwrite => {
+                skipped_count += 1;
+                continue;
+            }
+            Some(var) => {
+                let id = var.id.ok_or_else(|| DatabaseError::NotFound("Variable ID is missing".to_string()))?;
+                update_variable_encrypted_with_type(
+                    conn, id, record.key.clone(), record.value.clone(), record.description.clone(),
+                    Some(record.value_type), false, encryption_key,
+                )?;
+                updated_count += 1;
+                id
+            }
+            None => {
+                imported_count += 1;
+                create_variable_encrypted_with_type(
+                    conn, environment_id, record.key.clone(), record.value.clone(), record.description.clone(),
+                    record.value_type, encryption_key,
+                )?
+            }
+        };
+        
+        conn.execute(
+            "UPDATE variables SET tags = ?, is_protected = ?, expires_at = ?, created_at = ?, updated_at = ? WHERE id = ?",
+            params![
+                serde_json::to_string(&record.tags).unwrap_or_else(|_| "[]".to_string()),
+                record.is_protected,
+                record.expires_at,
+                record.created_at,
+                record.updated_at,
+                id,
+            ],
+        )?;
+    }
+    
+    Ok((imported_count, updated_count, skipped_count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, operations::{Environment, Project, environments, projects, variables}};
+    
+    #[test]
+    fn test_records_round_trip_with_metadata() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        let key = [5u8; 32];
+        
+        let app = projects::create_project(conn, &Project::new("app".to_string(), None)).unwrap();
+        let dev = environments::create_environment(conn, &Environment::new(app, "dev".to_string(), None)).unwrap();
+        let copy = environments::create_environment(conn, &Environment::new(app, "copy".to_string(), None)).unwrap();
+        
+        let id = variables::create_variable_encrypted_with_type(conn, dev, "PORT".to_string(), "8080".to_string(), Some("listen port".to_string()), ValueType::Number, &key).unwrap();
+        variables::set_variable_protected(conn, id, true).unwrap();
+        variables::set_variable_expiry(conn, id, Some(4_000_000_000)).unwrap();
+        variables::tag_variables(conn, dev, &["PORT".to_string()], "network", true).unwrap();
+        conn.execute("UPDATE variables SET created_at = 100, updated_at = 200 WHERE id = ?", [id]).unwrap();
+        variables::create_variable_encrypted(conn, dev, "NAME".to_string(), "clerk".to_string(), None, &key).unwrap();
+        
+        let records = |env| -> Vec<VariableRecord> {
+            variables::get_variables_by_environment_decrypted(conn, env, &key).unwrap().iter().map(VariableRecord::from).collect()
+        };
+        let exported = records(dev);
+        let document = EnvironmentRecords::new("app", "dev", exported.clone());
+        let parsed = EnvironmentRecords::parse(&serde_json::to_string(&document).unwrap()).unwrap().unwrap();
+        assert_eq!(parsed, document);
+        
+        assert_eq!(import_records(conn, copy, parsed.variables.clone(), false, &key).unwrap(), (2, 0, 0));
+        assert_eq!(records(copy), exported);
+        let port = exported.iter().find(|r| r.key == "PORT").unwrap();
+        assert_eq!((port.created_at, port.updated_at, port.tags.as_slice()), (100, 200, ["network".to_string()].as_slice()));
+        
+        // A second import skips everything, and protected variables refuse an overwrite
+        assert_eq!(import_records(conn, copy, parsed.variables.clone(), false, &key).unwrap(), (0, 0, 2));
+        assert!(matches!(import_records(conn, copy, parsed.variables, true, &key), Err(DatabaseError::Protected(_))));
+        
+        // Plain JSON is not a records document, an unknown version is rejected
+        assert!(EnvironmentRecords::parse(r#"{"A":"1"}"#).unwrap().is_none());
+        assert!(EnvironmentRecords::parse("A=1").unwrap().is_none());
+        assert!(EnvironmentRecords::parse(r#"{"format":"clerk-variables-v9"}"#).is_err());
+    }
+}
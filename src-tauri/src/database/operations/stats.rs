@@ -0,0 +1,232 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::database::DatabaseError;
+
+/// The variable with the largest stored value
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LargestValue {
+    pub project: String,
+    pub environment: String,
+    pub key: String,
+    pub encrypted_bytes: i64,
+}
+
+/// Storage figures for the whole vault
+///
+/// Sizes are the lengths of the stored `encrypted_value` blobs, so they include
+/// the nonce and auth tag and reflect compression. No value is decrypted.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub total_encrypted_bytes: i64,
+    pub average_encrypted_bytes: f64,
+    pub largest_value: Option<LargestValue>,
+    /// Variable count per value type (`text`, `url`, ...)
+    pub value_types: BTreeMap<String, usize>,
+}
+
+/// Row counts for the whole vault
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultCounts {
+    pub project_count: usize,
+    pub environment_count: usize,
+    pub variable_count: usize,
+}
+
+/// Variable count of one environment
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EnvironmentCount {
+    pub name: String,
+    pub variable_count: usize,
+}
+
+/// Environment and variable counts of one project
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectCounts {
+    pub name: String,
+    pub environments: Vec<EnvironmentCount>,
+    pub variable_count: usize,
+}
+
+/// Global counts, the per-project breakdown, and whether the two agree
+///
+/// The breakdown is built by walking projects and environments, so rows that
+/// no project reaches (e.g. variables left behind by a broken delete) show up
+/// as a mismatch with the global counts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VaultStats {
+    pub totals: VaultCounts,
+    pub projects: Vec<ProjectCounts>,
+    /// Empty when the breakdown adds up to the totals
+    pub inconsistencies: Vec<String>,
+}
+
+impl VaultStats {
+    pub fn is_consistent(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+}
+
+/// Count projects, environments and variables (the dashboard totals)
+pub fn vault_counts(conn: &Connection) -> Result<VaultCounts, DatabaseError> {
+    let count = |table: &str| -> Result<usize, DatabaseError> {
+        Ok(conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))?)
+    };
+    
+    Ok(VaultCounts {
+        project_count: count("projects")?,
+        environment_count: count("environments")?,
+        variable_count: count("variables")?,
+    })
+}
+
+/// Recompute counts per project and environment and cross-check them against `vault_counts`
+pub fn vault_stats(conn: &Connection) -> Result<VaultStats, DatabaseError> {
+    let totals = vault_counts(conn)?;
+    
+    let mut stmt = conn.prepare(
+        "SELECT p.name, e.name, COUNT(v.id) FROM projects p
+         LEFT JOIN environments e ON e.project_id = p.id
+         LEFT JOIN variables v ON v.environment_id = e.id
+         GROUP BY p.id, e.id
+         ORDER BY p.name, e.name",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, usize>(2)?))
+    })?;
+    
+    let mut projects: Vec<ProjectCounts> = Vec::new();
+    for row in rows {
+        let (project, environment, variable_count) = row?;
+        if projects.last().is_none_or(|last| last.name != project) {
+            projects.push(ProjectCounts { name: project, environments: Vec::new(), variable_count: 0 });
+        }
+        let current = projects.last_mut().expect("pushed above");
+        // A project without environments yields one row with a NULL environment
+        if let Some(name) = environment {
+            current.environments.push(EnvironmentCount { name, variable_count });
+            current.variable_count += variable_count;
+        }
+    }
+    
+    let mut inconsistencies = Vec::new();
+    let mut check = |what: &str, breakdown: usize, total: usize| {
+        if breakdown != total {
+            inconsistencies.push(format!(
+                "{} {} counted per project, but the vault holds {}",
+                breakdown, what, total
+            ));
+        }
+    };
+    check("projects", projects.len(), totals.project_count);
+    check("environments", projects.iter().map(|p| p.environments.len()).sum(), totals.environment_count);
+    check("variables", projects.iter().map(|p| p.variable_count).sum(), totals.variable_count);
+    
+    Ok(VaultStats { totals, projects, inconsistencies })
+}
+
+/// Compute vault storage stats with aggregate queries
+pub fn storage_stats(conn: &Connection) -> Result<StorageStats, DatabaseError> {
+    let (total_encrypted_bytes, average_encrypted_bytes) = conn.query_row(
+        "SELECT COALESCE(SUM(LENGTH(encrypted_value)), 0), COALESCE(AVG(LENGTH(encrypted_value)), 0.0) FROM variables",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    
+    let largest_value = conn.query_row(
+        "SELECT p.name, e.name, v.key, LENGTH(v.encrypted_value) AS size FROM variables v
+         JOIN environments e ON e.id = v.environment_id
+         JOIN projects p ON p.id = e.project_id
+         ORDER BY size DESC, v.id ASC LIMIT 1",
+        [],
+        |row| Ok(LargestValue {
+            project: row.get(0)?,
+            environment: row.get(1)?,
+            key: row.get(2)?,
+            encrypted_bytes: row.get(3)?,
+        }),
+    );
+    let largest_value = match largest_value {
+        Ok(largest) => Some(largest),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+    
+    let mut stmt = conn.prepare("SELECT value_type, COUNT(*) FROM variables GROUP BY value_type")?;
+    let value_types = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, usize>(1)?)))?
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+    
+    Ok(StorageStats {
+        total_encrypted_bytes,
+        average_encrypted_bytes,
+        largest_value,
+        value_types,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{Database, operations::{Environment, Project, Variable, environments, projects, variables}};
+
+    #[test]
+    fn test_storage_stats_aggregates_blob_sizes() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        assert_eq!(storage_stats(conn).unwrap(), StorageStats::default());
+        
+        let project_id = projects::create_project(conn, &Project::new("app".to_string(), None)).unwrap();
+        let dev = environments::create_environment(conn, &Environment::new(project_id, "dev".to_string(), None)).unwrap();
+        variables::create_variable(conn, &Variable::new(dev, "SHORT".to_string(), vec![0; 10], None)).unwrap();
+        variables::create_variable(conn, &Variable::new(dev, "LONG".to_string(), vec![0; 30], None)).unwrap();
+        conn.execute("UPDATE variables SET value_type = 'url' WHERE key = 'LONG'", []).unwrap();
+        
+        let stats = storage_stats(conn).unwrap();
+        assert_eq!(stats.total_encrypted_bytes, 40);
+        assert_eq!(stats.average_encrypted_bytes, 20.0);
+        assert_eq!(stats.largest_value, Some(LargestValue {
+            project: "app".to_string(),
+            environment: "dev".to_string(),
+            key: "LONG".to_string(),
+            encrypted_bytes: 30,
+        }));
+        assert_eq!(stats.value_types, BTreeMap::from([("text".to_string(), 1), ("url".to_string(), 1)]));
+    }
+    
+    #[test]
+    fn test_vault_stats_breakdown_and_cross_check() {
+        let db = Database::new_in_memory().unwrap();
+        db.initialize().unwrap();
+        let conn = db.connection();
+        
+        let app = projects::create_project(conn, &Project::new("app".to_string(), None)).unwrap();
+        projects::create_project(conn, &Project::new("empty".to_string(), None)).unwrap();
+        let dev = environments::create_environment(conn, &Environment::new(app, "dev".to_string(), None)).unwrap();
+        environments::create_environment(conn, &Environment::new(app, "prod".to_string(), None)).unwrap();
+        variables::create_variable(conn, &Variable::new(dev, "A".to_string(), vec![0; 10], None)).unwrap();
+        variables::create_variable(conn, &Variable::new(dev, "B".to_string(), vec![0; 10], None)).unwrap();
+        
+        let stats = vault_stats(conn).unwrap();
+        assert_eq!(stats.totals, VaultCounts { project_count: 2, environment_count: 2, variable_count: 2 });
+        assert_eq!(stats.projects, vec![
+            ProjectCounts {
+                name: "app".to_string(),
+                environments: vec![
+                    EnvironmentCount { name: "dev".to_string(), variable_count: 2 },
+                    EnvironmentCount { name: "prod".to_string(), variable_count: 0 },
+                ],
+                variable_count: 2,
+            },
+            ProjectCounts { name: "empty".to_string(), environments: vec![], variable_count: 0 },
+        ]);
+        assert!(stats.is_consistent());
+        
+        // A variable whose environment is gone is only seen by the global count
+        conn.execute_batch("PRAGMA foreign_keys = OFF").unwrap();
+        conn.execute("DELETE FROM environments WHERE id = ?1", [dev]).unwrap();
+        let stats = vault_stats(conn).unwrap();
+        assert_eq!(stats.inconsistencies, vec!["0 variables counted per project, but the vault holds 2".to_string()]);
+    }
+}
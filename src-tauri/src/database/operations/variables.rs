@@ -1,18 +1,28 @@
 use rusqlite::{Connection, params};
 use chrono::Utc;
 use serde_json::json;
-use crate::database::{DatabaseError, operations::{Variable, VariableDecrypted, audit::log_audit}};
-use crate::crypto::encryption;
+use crate::database::{DatabaseError, migrations::{get_encrypt_descriptions, get_nonce_mode, get_per_value_keys, set_encrypt_descriptions, get_vault_id, next_nonce_counter, update_last_accessed, update_last_modified}, operations::{CURRENT_AAD_VERSION, LEGACY_AAD_VERSION, ValueType, Variable, VariableDecrypted, audit::log_audit, canary::store_key_canary, environments::{Created, ensure_project_environment}, quota::check_variable_quota}};
+use crate::crypto::{compression, derive_value_key, encryption, generate_salt, NonceMode, SecretString};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use zeroize::{Zeroize, Zeroizing};
 
 /// Create a new variable (value must already be encrypted)
 pub fn create_variable(conn: &Connection, var: &Variable) -> Result<i64, DatabaseError> {
+    check_variable_quota(conn, var.environment_id)?;
+    
     conn.execute(
-        "INSERT INTO variables (environment_id, key, encrypted_value, description, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?)",
+        "INSERT INTO variables (environment_id, key, encrypted_value, aad_version, compressed, value_type, description, encrypted_description, value_salt, created_at, updated_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         params![
             var.environment_id,
             &var.key,
             &var.encrypted_value,
+            var.aad_version,
+            var.compressed,
+            var.value_type.as_str(),
             &var.description,
+            &var.encrypted_description,
+            &var.value_salt,
             var.created_at,
             var.updated_at,
         ],
@@ -29,17 +39,20 @@ pub fn create_variable(conn: &Connection, var: &Variable) -> Result<i64, Databas
         Some(&var.key),
         Some(json!({
             "environment_id": var.environment_id,
+            "value_type": var.value_type,
             "description": &var.description,
         })),
     );
     
+    let _ = update_last_modified(conn);
+    
     Ok(var_id)
 }
 
 /// Get a variable by ID (returns encrypted value)
 pub fn get_variable(conn: &Connection, id: i64) -> Result<Variable, DatabaseError> {
     let mut stmt = conn.prepare(
-        "SELECT id, environment_id, key, encrypted_value, description, created_at, updated_at FROM variables WHERE id = ?"
+        "SELECT id, environment_id, key, encrypted_value, description, created_at, updated_at, aad_version, value_type, compressed, is_protected, encrypted_description, expires_at, tags, pending_value, pending_compressed, value_salt FROM variables WHERE id = ?"
     )?;
     
     let var = stmt.query_row(params![id], |row| {
@@ -51,6 +64,16 @@ pub fn get_variable(conn: &Connection, id: i64) -> Result<Variable, DatabaseErro
             description: row.get(4)?,
             created_at: row.get(5)?,
             updated_at: row.get(6)?,
+            aad_version: row.get(7)?,
+            value_type: row.get::<_, String>(8)?.parse().unwrap_or_default(),
+            compressed: row.get(9)?,
+            is_protected: row.get(10)?,
+            encrypted_description: row.get(11)?,
+            expires_at: row.get(12)?,
+            tags: serde_json::from_str(&row.get::<_, String>(13)?).unwrap_or_default(),
+            pending_value: row.get(14)?,
+            pending_compressed: row.get(15)?,
+            value_salt: row.get(16)?,
         })
     })?;
     
@@ -60,7 +83,7 @@ pub fn get_variable(conn: &Connection, id: i64) -> Result<Variable, DatabaseErro
 /// Get all variables for an environment (returns encrypted values)
 pub fn get_variables_by_environment(conn: &Connection, environment_id: i64) -> Result<Vec<Variable>, DatabaseError> {
     let mut stmt = conn.prepare(
-        "SELECT id, environment_id, key, encrypted_value, description, created_at, updated_at FROM variables WHERE environment_id = ? ORDER BY key"
+        "SELECT id, environment_id, key, encrypted_value, description, created_at, updated_at, aad_version, value_type, compressed, is_protected, encrypted_description, expires_at, tags, pending_value, pending_compressed, value_salt FROM variables WHERE environment_id = ? ORDER BY key"
     )?;
     
     let variables = stmt.query_map(params![environment_id], |row| {
@@ -72,6 +95,16 @@ pub fn get_variables_by_environment(conn: &Connection, environment_id: i64) -> R
             description: row.get(4)?,
             created_at: row.get(5)?,
             updated_at: row.get(6)?,
+            aad_version: row.get(7)?,
+            value_type: row.get::<_, String>(8)?.parse().unwrap_or_default(),
+            compressed: row.get(9)?,
+            is_protected: row.get(10)?,
+            encrypted_description: row.get(11)?,
+            expires_at: row.get(12)?,
+            tags: serde_json::from_str(&row.get::<_, String>(13)?).unwrap_or_default(),
+            pending_value: row.get(14)?,
+            pending_compressed: row.get(15)?,
+            value_salt: row.get(16)?,
         })
     })?
     .collect::<Result<Vec<_>, _>>()?;
@@ -83,8 +116,8 @@ pub fn get_variables_by_environment(conn: &Connection, environment_id: i64) -> R
 pub fn update_variable(conn: &Connection, id: i64, var: &Variable) -> Result<(), DatabaseError> {
     let now = Utc::now().timestamp();
     let rows_affected = conn.execute(
-        "UPDATE variables SET key = ?, encrypted_value = ?, description = ?, updated_at = ? WHERE id = ?",
-        params![&var.key, &var.encrypted_value, &var.description, now, id],
+        "UPDATE variables SET key = ?, encrypted_value = ?, aad_version = ?, compressed = ?, value_type = ?, description = ?, encrypted_description = ?, pending_value = ?, pending_compressed = ?, value_salt = ?, updated_at = ? WHERE id = ?",
+        params![&var.key, &var.encrypted_value, var.aad_version, var.compressed, var.value_type.as_str(), &var.description, &var.encrypted_description, &var.pending_value, var.pending_compressed, &var.value_salt, now, id],
     )?;
     
     if rows_affected == 0 {
@@ -100,22 +133,34 @@ pub fn update_variable(conn: &Connection, id: i64, var: &Variable) -> Result<(),
         Some(&var.key),
         Some(json!({
             "environment_id": var.environment_id,
+            "value_type": var.value_type,
             "description": &var.description,
         })),
     );
     
+    let _ = update_last_modified(conn);
+    
     Ok(())
 }
 
 /// Delete a variable
-pub fn delete_variable(conn: &Connection, id: i64) -> Result<(), DatabaseError> {
+///
+/// Protected variables are only deleted when `force` is set.
+pub fn delete_variable(conn: &Connection, id: i64, force: bool) -> Result<(), DatabaseError> {
     // Get variable key before deleting for audit log
-    let var_key: Option<String> = conn.query_row(
-        "SELECT key FROM variables WHERE id = ?",
+    let existing: Option<(String, bool)> = conn.query_row(
+        "SELECT key, is_protected FROM variables WHERE id = ?",
         params![id],
-        |row| row.get(0),
+        |row| Ok((row.get(0)?, row.get(1)?)),
     ).ok();
     
+    if let Some((key, true)) = &existing {
+        if !force {
+            return Err(DatabaseError::Protected(key.clone()));
+        }
+    }
+    let var_key = existing.map(|(key, _)| key);
+    
     let rows_affected = conn.execute("DELETE FROM variables WHERE id = ?", params![id])?;
     
     if rows_affected == 0 {
@@ -132,9 +177,296 @@ pub fn delete_variable(conn: &Connection, id: i64) -> Result<(), DatabaseError>
         None,
     );
     
+    let _ = update_last_modified(conn);
+    
+    Ok(())
+}
+
+/// Delete every variable of an environment, keeping the environment itself
+///
+/// Runs in one transaction with one audit entry per deleted key. Protected
+/// variables are only deleted with `force`; otherwise nothing is deleted.
+/// Returns the deleted keys.
+pub fn delete_all_variables(conn: &Connection, environment_id: i64, force: bool) -> Result<Vec<String>, DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    let variables = get_variables_by_environment(&tx, environment_id)?;
+    
+    if let Some(protected) = variables.iter().find(|v| v.is_protected && !force) {
+        return Err(DatabaseError::Protected(protected.key.clone()));
+    }
+    
+    let mut deleted = Vec::with_capacity(variables.len());
+    for var in variables {
+        let id = var.id.ok_or_else(|| DatabaseError::NotFound("Variable ID is missing".to_string()))?;
+        delete_variable(&tx, id, true)?;
+        deleted.push(var.key);
+    }
+    tx.commit()?;
+    
+    Ok(deleted)
+}
+
+/// Mark a variable as protected (or clear the flag)
+pub fn set_variable_protected(conn: &Connection, id: i64, protected: bool) -> Result<(), DatabaseError> {
+    let var = get_variable(conn, id)?;
+    
+    conn.execute(
+        "UPDATE variables SET is_protected = ? WHERE id = ?",
+        params![protected, id],
+    )?;
+    
+    let _ = log_audit(
+        conn,
+        if protected { "protect" } else { "unprotect" },
+        "variable",
+        Some(id),
+        Some(&var.key),
+        Some(json!({ "environment_id": var.environment_id })),
+    );
+    
+    let _ = update_last_modified(conn);
+    
+    Ok(())
+}
+
+/// Set when a variable expires (unix seconds), or clear it with `None`
+pub fn set_variable_expiry(conn: &Connection, id: i64, expires_at: Option<i64>) -> Result<(), DatabaseError> {
+    let var = get_variable(conn, id)?;
+    
+    conn.execute(
+        "UPDATE variables SET expires_at = ? WHERE id = ?",
+        params![expires_at, id],
+    )?;
+    
+    let _ = log_audit(
+        conn,
+        if expires_at.is_some() { "set_expiry" } else { "clear_expiry" },
+        "variable",
+        Some(id),
+        Some(&var.key),
+        Some(json!({ "environment_id": var.environment_id, "expires_at": expires_at })),
+    );
+    
+    let _ = update_last_modified(conn);
+    
     Ok(())
 }
 
+/// A variable whose expiry has passed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExpiredVariable {
+    pub project: String,
+    pub environment: String,
+    pub key: String,
+    pub expires_at: i64,
+}
+
+/// List variables that expired at or before `now`, oldest expiry first
+pub fn get_expired_variables(conn: &Connection, now: i64) -> Result<Vec<ExpiredVariable>, DatabaseError> {
+    let mut stmt = conn.prepare(
+        "SELECT p.name, e.name, v.key, v.expires_at FROM variables v
+         JOIN environments e ON e.id = v.environment_id
+         JOIN projects p ON p.id = e.project_id
+         WHERE v.expires_at IS NOT NULL AND v.expires_at <= ?
+         ORDER BY v.expires_at, p.name, e.name, v.key",
+    )?;
+    
+    let expired = stmt.query_map(params![now], |row| {
+        Ok(ExpiredVariable {
+            project: row.get(0)?,
+            environment: row.get(1)?,
+            key: row.get(2)?,
+            expires_at: row.get(3)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(expired)
+}
+
+/// A variable created or updated after some point in time
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangedVariable {
+    pub project: String,
+    pub environment: String,
+    pub key: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl ChangedVariable {
+    /// Whether the variable was added (rather than updated) after `since`
+    pub fn is_new(&self, since: i64) -> bool {
+        self.created_at >= since
+    }
+}
+
+/// List variables created or updated at or after `since`, most recent first
+///
+/// Only the timestamp columns are read; deleted variables leave no row and are
+/// not reported.
+pub fn get_variables_modified_since(conn: &Connection, since: i64) -> Result<Vec<ChangedVariable>, DatabaseError> {
+    let mut stmt = conn.prepare(
+        "SELECT p.name, e.name, v.key, v.created_at, v.updated_at FROM variables v
+         JOIN environments e ON e.id = v.environment_id
+         JOIN projects p ON p.id = e.project_id
+         WHERE v.updated_at >= ?1 OR v.created_at >= ?1
+         ORDER BY MAX(v.updated_at, v.created_at) DESC, p.name, e.name, v.key",
+    )?;
+    
+    let changed = stmt.query_map(params![since], |row| {
+        Ok(ChangedVariable {
+            project: row.get(0)?,
+            environment: row.get(1)?,
+            key: row.get(2)?,
+            created_at: row.get(3)?,
+            updated_at: row.get(4)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()?;
+    
+    Ok(changed)
+}
+
+/// Per-key markers of an environment's stored state, compared between polls
+///
+/// Each key maps to its `updated_at` and a SHA-256 of its stored ciphertexts.
+/// Every write seals the value under a fresh nonce, so the digest changes even
+/// when two writes land in the same second. Nothing is decrypted.
+pub type ChangeMarkers = BTreeMap<String, (i64, Vec<u8>)>;
+
+/// Read the change markers of every variable in an environment
+pub fn environment_change_markers(conn: &Connection, environment_id: i64) -> Result<ChangeMarkers, DatabaseError> {
+    let mut stmt = conn.prepare(
+        "SELECT key, updated_at, encrypted_value, pending_value FROM variables WHERE environment_id = ?"
+    )?;
+    
+    let markers = stmt.query_map(params![environment_id], |row| {
+        let (value, pending): (Vec<u8>, Option<Vec<u8>>) = (row.get(2)?, row.get(3)?);
+        let mut context = ring::digest::Context::new(&ring::digest::SHA256);
+        context.update(&value);
+        if let Some(pending) = pending {
+            context.update(&pending);
+        }
+        Ok((row.get(0)?, (row.get(1)?, context.finish().as_ref().to_vec())))
+    })?
+    .collect::<Result<ChangeMarkers, _>>()?;
+    
+    Ok(markers)
+}
+
+/// Keys added, changed or removed between two sets of markers, sorted
+pub fn changed_keys(before: &ChangeMarkers, after: &ChangeMarkers) -> Vec<String> {
+    let mut changed: Vec<String> = after.iter()
+        .filter(|(key, marker)| before.get(*key) != Some(marker))
+        .map(|(key, _)| key.clone())
+        .chain(before.keys().filter(|key| !after.contains_key(*key)).cloned())
+        .collect();
+    changed.sort();
+    changed
+}
+
+/// Whether `key` matches a key `pattern` where `*` is any run of characters and `?` one character
+pub fn key_matches(pattern: &str, key: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let key: Vec<char> = key.chars().collect();
+    
+    // Greedy wildcard matching, backtracking to the last `*`
+    let (mut p, mut k) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while k < key.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == key[k]) {
+            p += 1;
+            k += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, k));
+            p += 1;
+        } else if let Some((star_p, star_k)) = star {
+            p = star_p + 1;
+            k = star_k + 1;
+            star = Some((star_p, star_k + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Check that a tag is a short label of letters, digits, `-`, `_` or `.`
+pub fn validate_tag(tag: &str) -> Result<(), DatabaseError> {
+    let valid = !tag.is_empty()
+        && tag.len() <= 64
+        && tag.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    
+    if valid {
+        Ok(())
+    } else {
+        Err(DatabaseError::ConstraintViolation(format!(
+            "Invalid tag '{}': use up to 64 letters, digits, '-', '_' or '.'", tag
+        )))
+    }
+}
+
+/// Add `tag` to (or remove it from) every variable in an environment matching one of `patterns`
+///
+/// Patterns are exact keys or `*`/`?` wildcards, and each must match at least
+/// one variable. Runs in a single transaction with one audit entry per changed
+/// variable. Returns the keys that changed; variables that already had (or
+/// lacked) the tag are left alone.
+pub fn tag_variables(
+    conn: &Connection,
+    environment_id: i64,
+    patterns: &[String],
+    tag: &str,
+    add: bool,
+) -> Result<Vec<String>, DatabaseError> {
+    validate_tag(tag)?;
+    
+    let tx = conn.unchecked_transaction()?;
+    let variables = get_variables_by_environment(&tx, environment_id)?;
+    
+    if let Some(unmatched) = patterns.iter().find(|p| !variables.iter().any(|v| key_matches(p, &v.key))) {
+        return Err(DatabaseError::NotFound(format!("No variable matches '{}'", unmatched)));
+    }
+    
+    let mut changed = Vec::new();
+    for var in variables {
+        if !patterns.iter().any(|p| key_matches(p, &var.key)) || var.tags.iter().any(|t| t == tag) == add {
+            continue;
+        }
+        
+        let mut tags = var.tags;
+        if add {
+            tags.push(tag.to_string());
+            tags.sort();
+        } else {
+            tags.retain(|t| t != tag);
+        }
+        let id = var.id.ok_or_else(|| DatabaseError::NotFound("Variable ID is missing".to_string()))?;
+        tx.execute(
+            "UPDATE variables SET tags = ? WHERE id = ?",
+            params![serde_json::to_string(&tags).unwrap_or_else(|_| "[]".to_string()), id],
+        )?;
+        
+        let _ = log_audit(
+            &tx,
+            if add { "tag" } else { "untag" },
+            "variable",
+            Some(id),
+            Some(&var.key),
+            Some(json!({ "environment_id": environment_id, "tag": tag })),
+        );
+        changed.push(var.key);
+    }
+    
+    if !changed.is_empty() {
+        let _ = update_last_modified(&tx);
+    }
+    tx.commit()?;
+    
+    Ok(changed)
+}
+
 /// Check if a variable exists by key within an environment
 pub fn variable_exists(conn: &Connection, environment_id: i64, key: &str) -> Result<bool, DatabaseError> {
     let count: i64 = conn.query_row(
@@ -146,7 +478,312 @@ pub fn variable_exists(conn: &Connection, environment_id: i64, key: &str) -> Res
     Ok(count > 0)
 }
 
-/// Encrypt and create a variable (high-level helper)
+/// Suggest existing keys starting with `prefix` (case-insensitive)
+///
+/// Keys from `environment_id` come first; with `across_vault` keys from other
+/// environments are included too. Only key names are read, values stay encrypted.
+pub fn suggest_keys(
+    conn: &Connection,
+    environment_id: i64,
+    prefix: &str,
+    across_vault: bool,
+    limit: usize,
+) -> Result<Vec<String>, DatabaseError> {
+    let mut stmt = conn.prepare(
+        "SELECT key FROM variables
+         WHERE (environment_id = ?1 OR ?2) AND lower(substr(key, 1, ?3)) = lower(?4)
+         GROUP BY key
+         ORDER BY MAX(environment_id = ?1) DESC, key
+         LIMIT ?5"
+    )?;
+    
+    let keys = stmt.query_map(
+        params![environment_id, across_vault, prefix.chars().count() as i64, prefix, limit as i64],
+        |row| row.get(0),
+    )?
+    .collect::<Result<Vec<String>, _>>()?;
+    
+    Ok(keys)
+}
+
+/// Build the AAD that binds a value to its vault, project, environment and key
+///
+/// See the schema docs for the format of each version. Version 1 is only
+/// accepted for reading legacy rows; new values always use `CURRENT_AAD_VERSION`.
+pub fn variable_aad(
+    conn: &Connection,
+    environment_id: i64,
+    key: &str,
+    aad_version: u32,
+) -> Result<String, DatabaseError> {
+    AadContext::load(conn, environment_id)?.aad(key, aad_version)
+}
+
+/// The ids bound into the AAD of every value in one environment
+///
+/// Loading them once lets a whole environment be decrypted without touching
+/// the connection per value.
+struct AadContext {
+    vault_id: String,
+    project_id: i64,
+    environment_id: i64,
+}
+
+impl AadContext {
+    fn load(conn: &Connection, environment_id: i64) -> Result<Self, DatabaseError> {
+        let vault_id = get_vault_id(conn)?;
+        let project_id: i64 = conn.query_row(
+            "SELECT project_id FROM environments WHERE id = ?",
+            params![environment_id],
+            |row| row.get(0),
+        )?;
+        
+        Ok(Self { vault_id, project_id, environment_id })
+    }
+    
+    fn aad(&self, key: &str, aad_version: u32) -> Result<String, DatabaseError> {
+        match aad_version {
+            LEGACY_AAD_VERSION => Ok(format!("env:{};key:{}", self.environment_id, key)),
+            CURRENT_AAD_VERSION => Ok(format!(
+                "vault:{};proj:{};env:{};key:{}",
+                self.vault_id, self.project_id, self.environment_id, key
+            )),
+            other => Err(DatabaseError::EncryptionError(format!("Unsupported AAD version: {}", other))),
+        }
+    }
+}
+
+/// Encrypt a value using the vault's configured nonce mode
+fn encrypt_value(
+    conn: &Connection,
+    encryption_key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &str,
+) -> Result<Vec<u8>, DatabaseError> {
+    let encrypted = match get_nonce_mode(conn)? {
+        NonceMode::Random => encryption::encrypt(encryption_key, plaintext, aad.as_bytes()),
+        NonceMode::Counter => {
+            let nonce = encryption::counter_nonce(next_nonce_counter(conn)?)
+                .map_err(|e| DatabaseError::EncryptionError(e.to_string()))?;
+            encryption::encrypt_with_nonce(encryption_key, nonce, plaintext, aad.as_bytes())
+        }
+    };
+    
+    encrypted.map_err(|e| DatabaseError::EncryptionError(e.to_string()))
+}
+
+/// Encrypt a plaintext value, deflating it first when that makes it smaller
+///
+/// Returns the ciphertext and whether it holds compressed plaintext.
+fn seal_value(
+    conn: &Connection,
+    encryption_key: &[u8; 32],
+    plaintext: &[u8],
+    aad: &str,
+) -> Result<(Vec<u8>, bool), DatabaseError> {
+    match compression::compress_if_smaller(plaintext) {
+        Some(compressed) => Ok((encrypt_value(conn, encryption_key, &compressed, aad)?, true)),
+        None => Ok((encrypt_value(conn, encryption_key, plaintext, aad)?, false)),
+    }
+}
+
+/// Key a variable's value and pending value are sealed with
+///
+/// The vault key itself, or its HKDF subkey for rows that have a `value_salt`.
+fn value_key(encryption_key: &[u8; 32], value_salt: Option<&[u8]>) -> Result<Zeroizing<[u8; 32]>, DatabaseError> {
+    match value_salt {
+        Some(salt) => derive_value_key(encryption_key, salt)
+            .map_err(|_| DatabaseError::EncryptionError("Failed to derive the value key".to_string())),
+        None => Ok(Zeroizing::new(*encryption_key)),
+    }
+}
+
+/// Salt for a value being written from scratch: fresh when the vault's
+/// `per_value_keys` setting is on, otherwise `None`
+fn new_value_salt(conn: &Connection) -> Result<Option<Vec<u8>>, DatabaseError> {
+    if !get_per_value_keys(conn)? {
+        return Ok(None);
+    }
+    generate_salt()
+        .map(|salt| Some(salt.to_vec()))
+        .map_err(|_| DatabaseError::EncryptionError("Failed to generate a value salt".to_string()))
+}
+
+/// The AAD of a description: its value's AAD, so it can't be moved to another variable,
+/// plus a field tag, so a description and a value can't be swapped
+fn description_aad(value_aad: &str) -> String {
+    format!("{};field:description", value_aad)
+}
+
+/// Split a description into its stored form: `(plaintext, ciphertext)`
+///
+/// Encrypted only when the vault's `encrypt_descriptions` setting is on.
+fn seal_description(
+    conn: &Connection,
+    encryption_key: &[u8; 32],
+    description: Option<String>,
+    aad: &str,
+) -> Result<(Option<String>, Option<Vec<u8>>), DatabaseError> {
+    match description {
+        Some(description) if get_encrypt_descriptions(conn)? => {
+            let encrypted = encrypt_value(conn, encryption_key, description.as_bytes(), &description_aad(aad))?;
+            Ok((None, Some(encrypted)))
+        }
+        description => Ok((description, None)),
+    }
+}
+
+/// Return a variable's description, decrypting it if it is stored encrypted
+fn open_description(
+    var: &Variable,
+    aad: &str,
+    encryption_key: &[u8; 32],
+    lossy: bool,
+) -> Result<Option<String>, DatabaseError> {
+    let Some(encrypted) = &var.encrypted_description else {
+        return Ok(var.description.clone());
+    };
+    
+    let bytes = encryption::decrypt(encryption_key, encrypted, description_aad(aad).as_bytes())
+        .map_err(|e| DatabaseError::EncryptionError(format!("Failed to decrypt the description of '{}': {}", var.key, e)))?;
+    
+    match String::from_utf8(bytes.to_vec()) {
+        Ok(description) => Ok(Some(description)),
+        Err(e) if lossy => Ok(Some(String::from_utf8_lossy(e.as_bytes()).into_owned())),
+        Err(e) => Err(DatabaseError::SerializationError(format!("The description of '{}' is not valid UTF-8: {}", var.key, e))),
+    }
+}
+
+/// Decrypt a variable's description using the AAD version it was stored with
+///
+/// Plaintext descriptions are returned as-is.
+pub fn decrypt_description(
+    conn: &Connection,
+    var: &Variable,
+    encryption_key: &[u8; 32],
+) -> Result<Option<String>, DatabaseError> {
+    if var.encrypted_description.is_none() {
+        return Ok(var.description.clone());
+    }
+    let aad = variable_aad(conn, var.environment_id, &var.key, var.aad_version)?;
+    open_description(var, &aad, encryption_key, false)
+}
+
+/// The AAD of a pending value: its variable's current-version AAD plus a slot
+/// tag, so the pending and current values can't be swapped in storage
+fn pending_aad(conn: &Connection, environment_id: i64, key: &str) -> Result<String, DatabaseError> {
+    Ok(format!("{};slot:pending", variable_aad(conn, environment_id, key, CURRENT_AAD_VERSION)?))
+}
+
+/// Decrypt a variable's pending value, or `None` if nothing is staged
+pub fn decrypt_pending_value(
+    conn: &Connection,
+    var: &Variable,
+    encryption_key: &[u8; 32],
+) -> Result<Option<SecretString>, DatabaseError> {
+    let Some(encrypted) = &var.pending_value else {
+        return Ok(None);
+    };
+    let aad = pending_aad(conn, var.environment_id, &var.key)?;
+    let pending = Variable {
+        encrypted_value: encrypted.clone(),
+        compressed: var.pending_compressed,
+        ..var.clone()
+    };
+    open_value(&pending, &aad, encryption_key, false).map(|(value, _)| Some(value))
+}
+
+/// Decrypt a variable's value using the AAD version it was stored with
+pub fn decrypt_variable(
+    conn: &Connection,
+    var: &Variable,
+    encryption_key: &[u8; 32],
+) -> Result<SecretString, DatabaseError> {
+    let aad = variable_aad(conn, var.environment_id, &var.key, var.aad_version)?;
+    open_value(var, &aad, encryption_key, false).map(|(value, _)| value)
+}
+
+/// Decrypt (and inflate) one value given its AAD; errors name the key
+///
+/// Non-UTF-8 plaintext is an error unless `lossy` is set, in which case invalid
+/// bytes become U+FFFD and the returned flag is `true`.
+fn open_value(
+    var: &Variable,
+    aad: &str,
+    encryption_key: &[u8; 32],
+    lossy: bool,
+) -> Result<(SecretString, bool), DatabaseError> {
+    let key = value_key(encryption_key, var.value_salt.as_deref())?;
+    let mut decrypted_bytes = encryption::decrypt(&key, &var.encrypted_value, aad.as_bytes())
+        .map_err(|e| DatabaseError::EncryptionError(format!("Failed to decrypt '{}': {}", var.key, e)))?;
+    
+    if var.compressed {
+        decrypted_bytes = compression::decompress(&decrypted_bytes)
+            .map_err(|e| DatabaseError::SerializationError(format!("'{}': {}", var.key, e)))?;
+    }
+    
+    match String::from_utf8(decrypted_bytes.to_vec()) {
+        Ok(value) => Ok((SecretString::new(value), false)),
+        Err(e) if lossy => {
+            let mut bytes = e.into_bytes();
+            let value = String::from_utf8_lossy(&bytes).into_owned();
+            bytes.zeroize();
+            Ok((SecretString::new(value), true))
+        }
+        Err(e) => Err(DatabaseError::SerializationError(format!("'{}' is not valid UTF-8: {}", var.key, e))),
+    }
+}
+
+/// Environments smaller than this are decrypted on the calling thread
+const PARALLEL_DECRYPT_THRESHOLD: usize = 256;
+
+/// Decrypt values across up to `threads` scoped threads, keeping their order
+///
+/// AES-GCM operations are independent, so each thread takes a contiguous chunk.
+/// If several values fail, the error for the first one in order is returned.
+fn open_values(
+    variables: &[Variable],
+    aads: &[String],
+    encryption_key: &[u8; 32],
+    threads: usize,
+    lossy: bool,
+) -> Result<Vec<(SecretString, bool)>, DatabaseError> {
+    let open_chunk = |vars: &[Variable], aads: &[String]| {
+        vars.iter()
+            .zip(aads)
+            .map(|(var, aad)| open_value(var, aad, encryption_key, lossy))
+            .collect::<Result<Vec<_>, _>>()
+    };
+    
+    if threads < 2 || variables.len() < PARALLEL_DECRYPT_THRESHOLD {
+        return open_chunk(variables, aads);
+    }
+    
+    let chunk_size = variables.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = variables.chunks(chunk_size)
+            .zip(aads.chunks(chunk_size))
+            .map(|(vars, aads)| scope.spawn(move || open_chunk(vars, aads)))
+            .collect();
+        
+        let mut values = Vec::with_capacity(variables.len());
+        for worker in workers {
+            let chunk = worker.join()
+                .map_err(|_| DatabaseError::EncryptionError("Decryption thread panicked".to_string()))??;
+            values.extend(chunk);
+        }
+        Ok(values)
+    })
+}
+
+/// Return `DatabaseError::InvalidValue` if a value does not fit its declared type
+fn validate_value(key: &str, value: &str, value_type: ValueType) -> Result<(), DatabaseError> {
+    value_type
+        .validate(value)
+        .map_err(|reason| DatabaseError::InvalidValue(format!("{} is declared as {}: {}", key, value_type, reason)))
+}
+
+/// Encrypt and create a plain text variable (high-level helper)
 pub fn create_variable_encrypted(
     conn: &Connection,
     environment_id: i64,
@@ -155,106 +792,700 @@ pub fn create_variable_encrypted(
     description: Option<String>,
     encryption_key: &[u8; 32],
 ) -> Result<i64, DatabaseError> {
+    create_variable_encrypted_with_type(conn, environment_id, key, value, description, ValueType::Text, encryption_key)
+}
+
+/// Validate, encrypt and create a variable of the given value type
+pub fn create_variable_encrypted_with_type(
+    conn: &Connection,
+    environment_id: i64,
+    key: String,
+    value: String,
+    description: Option<String>,
+    value_type: ValueType,
+    encryption_key: &[u8; 32],
+) -> Result<i64, DatabaseError> {
+    validate_value(&key, &value, value_type)?;
+    
     // Create AAD (Additional Authenticated Data) from context
-    let aad = format!("env:{};key:{}", environment_id, key);
+    let aad = variable_aad(conn, environment_id, &key, CURRENT_AAD_VERSION)?;
     
     // Encrypt the value
-    let encrypted_value = encryption::encrypt(encryption_key, value.as_bytes(), aad.as_bytes())
-        .map_err(|e| DatabaseError::EncryptionError(e.to_string()))?;
+    let value_salt = new_value_salt(conn)?;
+    let value_key = value_key(encryption_key, value_salt.as_deref())?;
+    let (encrypted_value, compressed) = seal_value(conn, &value_key, value.as_bytes(), &aad)?;
+    let (description, encrypted_description) = seal_description(conn, encryption_key, description, &aad)?;
     
-    let var = Variable::new(environment_id, key, encrypted_value, description);
+    let mut var = Variable::new(environment_id, key, encrypted_value, description);
+    var.value_salt = value_salt;
+    var.encrypted_description = encrypted_description;
+    var.compressed = compressed;
+    var.value_type = value_type;
     create_variable(conn, &var)
 }
 
+/// Create a variable, or update it in place if its key already exists in the environment
+///
+/// The write is a single `INSERT ... ON CONFLICT DO UPDATE`, so a concurrent
+/// create of the same key can't slip in between a check and the insert. When
+/// updating, an omitted `value_type` keeps the current type and protected
+/// variables are refused. Returns the variable id and whether it was created.
+pub fn upsert_variable_encrypted(
+    conn: &Connection,
+    environment_id: i64,
+    key: String,
+    value: String,
+    description: Option<String>,
+    value_type: Option<ValueType>,
+    encryption_key: &[u8; 32],
+) -> Result<(i64, bool), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    
+    // The current type decides validation; the conflict clause decides the write.
+    // An existing row keeps its salt, which its pending value is sealed under.
+    let existing: Option<(ValueType, Option<Vec<u8>>)> = match tx.query_row(
+        "SELECT value_type, value_salt FROM variables WHERE environment_id = ? AND key = ?",
+        params![environment_id, &key],
+        |row| Ok((row.get::<_, String>(0)?, row.get(1)?)),
+    ) {
+        Ok((value_type, value_salt)) => Some((value_type.parse().unwrap_or_default(), value_salt)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => None,
+        Err(e) => return Err(e.into()),
+    };
+    let created = existing.is_none();
+    let (existing_type, value_salt) = match existing {
+        Some((value_type, value_salt)) => (Some(value_type), value_salt),
+        None => {
+            check_variable_quota(&tx, environment_id)?;
+            (None, new_value_salt(&tx)?)
+        }
+    };
+    
+    let value_type = value_type.or(existing_type).unwrap_or_default();
+    validate_value(&key, &value, value_type)?;
+    
+    // Same AAD either way: it depends only on the environment and key
+    let aad = variable_aad(&tx, environment_id, &key, CURRENT_AAD_VERSION)?;
+    let value_key = value_key(encryption_key, value_salt.as_deref())?;
+    let (encrypted_value, compressed) = seal_value(&tx, &value_key, value.as_bytes(), &aad)?;
+    let (description, encrypted_description) = seal_description(&tx, encryption_key, description, &aad)?;
+    
+    let now = Utc::now().timestamp();
+    let id = tx.query_row(
+        "INSERT INTO variables (environment_id, key, encrypted_value, aad_version, compressed, value_type, description, encrypted_description, value_salt, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?10, ?9, ?9)
+         ON CONFLICT(environment_id, key) DO UPDATE SET
+             encrypted_value = excluded.encrypted_value,
+             aad_version = excluded.aad_version,
+             compressed = excluded.compressed,
+             value_type = excluded.value_type,
+             description = excluded.description,
+             encrypted_description = excluded.encrypted_description,
+             updated_at = excluded.updated_at
+         WHERE variables.is_protected = 0
+         RETURNING id",
+        params![environment_id, &key, encrypted_value, CURRENT_AAD_VERSION, compressed, value_type.as_str(), &description, encrypted_description, now, value_salt],
+        |row| row.get::<_, i64>(0),
+    );
+    // A protected row makes the conflict update a no-op, so nothing is returned
+    let id = match id {
+        Ok(id) => id,
+        Err(rusqlite::Error::QueryReturnedNoRows) => return Err(DatabaseError::Protected(key)),
+        Err(e) => return Err(e.into()),
+    };
+    
+    let _ = log_audit(
+        &tx,
+        if created { "create" } else { "update" },
+        "variable",
+        Some(id),
+        Some(&key),
+        Some(json!({
+            "environment_id": environment_id,
+            "value_type": value_type,
+            "description": &description,
+            "upsert": true,
+        })),
+    );
+    let _ = update_last_modified(&tx);
+    
+    tx.commit()?;
+    Ok((id, created))
+}
+
 /// Get and decrypt a variable (high-level helper)
+///
+/// Fails if the plaintext is not valid UTF-8; see `get_variable_decrypted_lossy`.
 pub fn get_variable_decrypted(
     conn: &Connection,
     id: i64,
     encryption_key: &[u8; 32],
+) -> Result<VariableDecrypted, DatabaseError> {
+    read_variable_decrypted(conn, id, encryption_key, false)
+}
+
+/// Like `get_variable_decrypted`, but invalid UTF-8 is replaced and flagged as `lossy`
+///
+/// For display only: saving a lossy value back would store the replacement characters.
+pub fn get_variable_decrypted_lossy(
+    conn: &Connection,
+    id: i64,
+    encryption_key: &[u8; 32],
+) -> Result<VariableDecrypted, DatabaseError> {
+    read_variable_decrypted(conn, id, encryption_key, true)
+}
+
+fn read_variable_decrypted(
+    conn: &Connection,
+    id: i64,
+    encryption_key: &[u8; 32],
+    lossy: bool,
 ) -> Result<VariableDecrypted, DatabaseError> {
     let var = get_variable(conn, id)?;
+    let aad = variable_aad(conn, var.environment_id, &var.key, var.aad_version)?;
+    let (decrypted_value, replaced) = open_value(&var, &aad, encryption_key, lossy)?;
+    let description = open_description(&var, &aad, encryption_key, lossy)?;
     
-    // Create AAD from context
-    let aad = format!("env:{};key:{}", var.environment_id, var.key);
-    
-    // Decrypt the value
-    let decrypted_bytes = encryption::decrypt(encryption_key, &var.encrypted_value, aad.as_bytes())
-        .map_err(|e| DatabaseError::EncryptionError(e.to_string()))?;
-    
-    let decrypted_value = String::from_utf8(decrypted_bytes.to_vec())
-        .map_err(|e| DatabaseError::SerializationError(format!("Invalid UTF-8: {}", e)))?;
+    let _ = update_last_accessed(conn);
     
     Ok(VariableDecrypted {
         id: var.id.unwrap(),
         environment_id: var.environment_id,
         key: var.key,
         value: decrypted_value,
-        description: var.description,
+        value_length: None,
+        value_type: var.value_type,
+        is_protected: var.is_protected,
+        lossy: replaced,
+        description,
+        expires_at: var.expires_at,
+        tags: var.tags,
+        has_pending: var.pending_value.is_some(),
         created_at: var.created_at,
         updated_at: var.updated_at,
     })
 }
 
 /// Get all variables for an environment with decryption (high-level helper)
+///
+/// One value that is not valid UTF-8 fails the whole call; see
+/// `get_variables_by_environment_decrypted_lossy`.
 pub fn get_variables_by_environment_decrypted(
     conn: &Connection,
     environment_id: i64,
     encryption_key: &[u8; 32],
 ) -> Result<Vec<VariableDecrypted>, DatabaseError> {
-    let variables = get_variables_by_environment(conn, environment_id)?;
-    
-    let mut decrypted_vars = Vec::new();
-    for var in variables {
-        let aad = format!("env:{};key:{}", var.environment_id, var.key);
-        
-        let decrypted_bytes = encryption::decrypt(encryption_key, &var.encrypted_value, aad.as_bytes())
-            .map_err(|e| DatabaseError::EncryptionError(e.to_string()))?;
-        
-        let decrypted_value = String::from_utf8(decrypted_bytes.to_vec())
-            .map_err(|e| DatabaseError::SerializationError(format!("Invalid UTF-8: {}", e)))?;
-        
-        decrypted_vars.push(VariableDecrypted {
-            id: var.id.unwrap(),
-            environment_id: var.environment_id,
-            key: var.key,
-            value: decrypted_value,
-            description: var.description,
-            created_at: var.created_at,
-            updated_at: var.updated_at,
-        });
-    }
-    
-    Ok(decrypted_vars)
+    read_environment_decrypted(conn, environment_id, encryption_key, false)
 }
 
-/// Update a variable with encryption (high-level helper)
-pub fn update_variable_encrypted(
+/// Like `get_variables_by_environment_decrypted`, but invalid UTF-8 is replaced and flagged as `lossy`
+pub fn get_variables_by_environment_decrypted_lossy(
     conn: &Connection,
-    id: i64,
+    environment_id: i64,
+    encryption_key: &[u8; 32],
+) -> Result<Vec<VariableDecrypted>, DatabaseError> {
+    read_environment_decrypted(conn, environment_id, encryption_key, true)
+}
+
+fn read_environment_decrypted(
+    conn: &Connection,
+    environment_id: i64,
+    encryption_key: &[u8; 32],
+    lossy: bool,
+) -> Result<Vec<VariableDecrypted>, DatabaseError> {
+    let variables = get_variables_by_environment(conn, environment_id)?;
+    if variables.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    // The connection isn't shared across threads, so build every AAD up front
+    let context = AadContext::load(conn, environment_id)?;
+    let aads = variables.iter()
+        .map(|var| context.aad(&var.key, var.aad_version))
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    let threads = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let values = open_values(&variables, &aads, encryption_key, threads, lossy)?;
+    let descriptions = variables.iter()
+        .zip(&aads)
+        .map(|(var, aad)| open_description(var, aad, encryption_key, lossy))
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    let decrypted_vars = variables.into_iter()
+        .zip(values)
+        .zip(descriptions)
+        .map(|((var, (value, replaced)), description)| VariableDecrypted {
+            id: var.id.unwrap(),
+            environment_id: var.environment_id,
+            key: var.key,
+            value,
+            value_length: None,
+            value_type: var.value_type,
+            is_protected: var.is_protected,
+            lossy: replaced,
+            description,
+            expires_at: var.expires_at,
+            tags: var.tags,
+            has_pending: var.pending_value.is_some(),
+            created_at: var.created_at,
+            updated_at: var.updated_at,
+        })
+        .collect();
+    
+    let _ = update_last_accessed(conn);
+    
+    Ok(decrypted_vars)
+}
+
+/// Update a variable with encryption, keeping its value type (high-level helper)
+pub fn update_variable_encrypted(
+    conn: &Connection,
+    id: i64,
+    key: String,
+    value: String,
+    description: Option<String>,
+    force: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(), DatabaseError> {
+    update_variable_encrypted_with_type(conn, id, key, value, description, None, force, encryption_key)
+}
+
+/// Update a variable with encryption, optionally changing its value type
+///
+/// The new value is validated against the new type, or the stored type if
+/// `value_type` is `None`. Protected variables are only updated when `force`
+/// is set.
+#[allow(clippy::too_many_arguments)]
+pub fn update_variable_encrypted_with_type(
+    conn: &Connection,
+    id: i64,
     key: String,
     value: String,
     description: Option<String>,
+    value_type: Option<ValueType>,
+    force: bool,
     encryption_key: &[u8; 32],
 ) -> Result<(), DatabaseError> {
     // Get the existing variable to know the environment_id
     let existing = get_variable(conn, id)?;
+    if existing.is_protected && !force {
+        return Err(DatabaseError::Protected(existing.key));
+    }
+    
+    let value_type = value_type.unwrap_or(existing.value_type);
+    validate_value(&key, &value, value_type)?;
     
     // Create AAD from context
-    let aad = format!("env:{};key:{}", existing.environment_id, key);
+    let aad = variable_aad(conn, existing.environment_id, &key, CURRENT_AAD_VERSION)?;
     
-    // Encrypt the new value
-    let encrypted_value = encryption::encrypt(encryption_key, value.as_bytes(), aad.as_bytes())
-        .map_err(|e| DatabaseError::EncryptionError(e.to_string()))?;
+    // Encrypt the new value, keeping the salt the pending value is sealed under
+    let value_key = value_key(encryption_key, existing.value_salt.as_deref())?;
+    let (encrypted_value, compressed) = seal_value(conn, &value_key, value.as_bytes(), &aad)?;
+    let (description, encrypted_description) = seal_description(conn, encryption_key, description, &aad)?;
     
-    let var = Variable::new(existing.environment_id, key, encrypted_value, description);
+    let mut var = Variable::new(existing.environment_id, key, encrypted_value, description);
+    var.value_salt = existing.value_salt.clone();
+    var.encrypted_description = encrypted_description;
+    var.compressed = compressed;
+    var.value_type = value_type;
+    
+    // A staged value is bound to the key, so a rename has to re-seal it
+    if existing.pending_value.is_some() && var.key != existing.key {
+        if let Some(pending) = decrypt_pending_value(conn, &existing, encryption_key)? {
+            let aad = pending_aad(conn, var.environment_id, &var.key)?;
+            let (pending_value, pending_compressed) = seal_value(conn, &value_key, pending.expose().as_bytes(), &aad)?;
+            var.pending_value = Some(pending_value);
+            var.pending_compressed = pending_compressed;
+        }
+    } else {
+        var.pending_value = existing.pending_value;
+        var.pending_compressed = existing.pending_compressed;
+    }
     update_variable(conn, id, &var)
 }
 
+/// Stage `value` in a variable's pending slot, next to its current value
+///
+/// Reads keep returning the current value until `promote_pending_value`.
+/// Staging again replaces the previous pending value.
+pub fn set_pending_value(
+    conn: &Connection,
+    id: i64,
+    value: &str,
+    encryption_key: &[u8; 32],
+) -> Result<(), DatabaseError> {
+    let var = get_variable(conn, id)?;
+    validate_value(&var.key, value, var.value_type)?;
+    
+    let aad = pending_aad(conn, var.environment_id, &var.key)?;
+    let value_key = value_key(encryption_key, var.value_salt.as_deref())?;
+    let (pending_value, pending_compressed) = seal_value(conn, &value_key, value.as_bytes(), &aad)?;
+    conn.execute(
+        "UPDATE variables SET pending_value = ?, pending_compressed = ?, updated_at = ? WHERE id = ?",
+        params![pending_value, pending_compressed, Utc::now().timestamp(), id],
+    )?;
+    
+    let _ = log_audit(
+        conn,
+        "stage",
+        "variable",
+        Some(id),
+        Some(&var.key),
+        Some(json!({ "environment_id": var.environment_id })),
+    );
+    let _ = update_last_modified(conn);
+    
+    Ok(())
+}
+
+/// Make a variable's pending value its current value and empty the pending slot
+///
+/// The previous current value is discarded. Protected variables are only
+/// promoted when `force` is set.
+pub fn promote_pending_value(
+    conn: &Connection,
+    id: i64,
+    force: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    
+    let var = get_variable(&tx, id)?;
+    if var.is_protected && !force {
+        return Err(DatabaseError::Protected(var.key));
+    }
+    let pending = decrypt_pending_value(&tx, &var, encryption_key)?
+        .ok_or_else(|| DatabaseError::NotFound(format!("'{}' has no pending value", var.key)))?;
+    let description = decrypt_description(&tx, &var, encryption_key)?;
+    
+    rewrite_encrypted_value(&tx, &var, pending.expose(), description, None, encryption_key)?;
+    tx.execute("UPDATE variables SET updated_at = ? WHERE id = ?", params![Utc::now().timestamp(), id])?;
+    
+    let _ = log_audit(
+        &tx,
+        "promote",
+        "variable",
+        Some(id),
+        Some(&var.key),
+        Some(json!({ "environment_id": var.environment_id })),
+    );
+    let _ = update_last_modified(&tx);
+    
+    tx.commit()?;
+    Ok(())
+}
+
+/// Check that a key can decrypt this vault by decrypting one stored value
+///
+/// Returns `Ok(true)` for an empty vault, since there is nothing to check against.
+pub fn verify_encryption_key(conn: &Connection, encryption_key: &[u8; 32]) -> Result<bool, DatabaseError> {
+    let sample_id: Option<i64> = conn
+        .query_row("SELECT id FROM variables ORDER BY id LIMIT 1", [], |row| row.get(0))
+        .ok();
+    
+    let Some(id) = sample_id else {
+        return Ok(true);
+    };
+    
+    let var = get_variable(conn, id)?;
+    match decrypt_variable(conn, &var, encryption_key) {
+        Ok(_) => Ok(true),
+        Err(DatabaseError::EncryptionError(_)) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Create or update parsed entries in an environment, returning (created, updated, skipped)
+///
+/// Runs in a single transaction: if any entry fails (e.g. a quota is hit) none
+/// of the entries are written.
+pub fn import_variables(
+    conn: &Connection,
+    environment_id: i64,
+    entries: Vec<(String, String, Option<String>)>,
+    overwrite: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(usize, usize, usize), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    let counts = import_entries(&tx, environment_id, entries, overwrite, encryption_key)?;
+    tx.commit()?;
+    Ok(counts)
+}
+
+/// Like `import_variables`, creating the project and environment first if they are missing
+///
+/// Creation and import share one transaction, so a failed import leaves no
+/// empty project or environment behind.
+pub fn import_variables_creating(
+    conn: &Connection,
+    project_name: &str,
+    env_name: &str,
+    entries: Vec<(String, String, Option<String>)>,
+    overwrite: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(Created, (usize, usize, usize)), DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    let (environment_id, created) = ensure_project_environment(&tx, project_name, env_name)?;
+    let counts = import_entries(&tx, environment_id, entries, overwrite, encryption_key)?;
+    tx.commit()?;
+    Ok((created, counts))
+}
+
+fn import_entries(
+    conn: &Connection,
+    environment_id: i64,
+    entries: Vec<(String, String, Option<String>)>,
+    overwrite: bool,
+    encryption_key: &[u8; 32],
+) -> Result<(usize, usize, usize), DatabaseError> {
+    let existing_variables = get_variables_by_environment(conn, environment_id)?;
+    
+    let mut imported_count = 0;
+    let mut updated_count = 0;
+    let mut skipped_count = 0;
+    
+    for (key, value, description) in entries {
+        match existing_variables.iter().find(|v| v.key == key) {
+            Some(_) if !overwrite => skipped_count += 1,
+            Some(var) => {
+                let var_id = var.id.ok_or_else(|| DatabaseError::NotFound("Variable ID is missing".to_string()))?;
+                update_variable_encrypted(conn, var_id, key, value, description, false, encryption_key)?;
+                updated_count += 1;
+            }
+            None => {
+                create_variable_encrypted(conn, environment_id, key, value, description, encryption_key)?;
+                imported_count += 1;
+            }
+        }
+    }
+    
+    Ok((imported_count, updated_count, skipped_count))
+}
+
+/// Copy a variable into another environment, re-encrypting it for its new AAD
+///
+/// The existence check and write run in one transaction. Returns true if an
+/// existing variable in the target was replaced (only allowed with `overwrite`).
+pub fn copy_variable(
+    conn: &Connection,
+    from_environment_id: i64,
+    to_environment_id: i64,
+    key: &str,
+    overwrite: bool,
+    encryption_key: &[u8; 32],
+) -> Result<bool, DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    
+    let source = get_variables_by_environment(&tx, from_environment_id)?
+        .into_iter()
+        .find(|v| v.key == key)
+        .ok_or_else(|| DatabaseError::NotFound(format!("Variable '{}' not found in source environment", key)))?;
+    let value = decrypt_variable(&tx, &source, encryption_key)?;
+    let description = decrypt_description(&tx, &source, encryption_key)?;
+    
+    let target = get_variables_by_environment(&tx, to_environment_id)?
+        .into_iter()
+        .find(|v| v.key == key);
+    
+    let replaced = match target {
+        Some(_) if !overwrite => {
+            return Err(DatabaseError::ConstraintViolation(format!("Variable '{}' already exists in the target environment", key)));
+        }
+        Some(var) => {
+            let var_id = var.id.ok_or_else(|| DatabaseError::NotFound("Variable ID is missing".to_string()))?;
+            update_variable_encrypted_with_type(&tx, var_id, key.to_string(), value.into_exposed(), description, Some(source.value_type), false, encryption_key)?;
+            true
+        }
+        None => {
+            create_variable_encrypted_with_type(&tx, to_environment_id, key.to_string(), value.into_exposed(), description, source.value_type, encryption_key)?;
+            false
+        }
+    };
+    
+    tx.commit()?;
+    Ok(replaced)
+}
+
+/// Re-encrypt every variable from `old_key` to `new_key` using the current AAD format
+///
+/// Runs in a single transaction, so a wrong key or corrupt value leaves the vault
+/// unchanged. Passing the same key twice upgrades legacy AAD rows in place.
+/// Returns the number of values re-encrypted.
+pub fn reencrypt_all(
+    conn: &Connection,
+    old_key: &[u8; 32],
+    new_key: &[u8; 32],
+) -> Result<usize, DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    
+    let ids: Vec<i64> = tx
+        .prepare("SELECT id FROM variables ORDER BY id")?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    for id in &ids {
+        let var = get_variable(&tx, *id)?;
+        let value = decrypt_variable(&tx, &var, old_key)?;
+        let description = decrypt_description(&tx, &var, old_key)?;
+        let pending = decrypt_pending_value(&tx, &var, old_key)?;
+        rewrite_encrypted_value(&tx, &var, value.expose(), description, pending.as_ref().map(SecretString::expose), new_key)?;
+    }
+    
+    // Keep the key canary in step with the values
+    if old_key != new_key {
+        store_key_canary(&tx, new_key)?;
+    }
+    
+    tx.commit()?;
+    Ok(ids.len())
+}
+
+/// Store `plaintext`, `description` and `pending` for `var`, encrypted under its current AAD
+///
+/// Used after the AAD inputs change (new key, new project). The description is
+/// stored according to the current `encrypt_descriptions` setting, the values
+/// get a fresh salt or none according to `per_value_keys`, and a `None`
+/// pending value empties the pending slot. Writes no audit entry.
+pub(crate) fn rewrite_encrypted_value(
+    conn: &Connection,
+    var: &Variable,
+    plaintext: &str,
+    description: Option<String>,
+    pending: Option<&str>,
+    encryption_key: &[u8; 32],
+) -> Result<(), DatabaseError> {
+    let id = var.id.ok_or_else(|| DatabaseError::NotFound("Variable ID is missing".to_string()))?;
+    let aad = variable_aad(conn, var.environment_id, &var.key, CURRENT_AAD_VERSION)?;
+    let value_salt = new_value_salt(conn)?;
+    let value_key = value_key(encryption_key, value_salt.as_deref())?;
+    let (encrypted_value, compressed) = seal_value(conn, &value_key, plaintext.as_bytes(), &aad)?;
+    let (description, encrypted_description) = seal_description(conn, encryption_key, description, &aad)?;
+    let (pending_value, pending_compressed) = match pending {
+        Some(pending) => {
+            let (sealed, compressed) = seal_value(conn, &value_key, pending.as_bytes(), &pending_aad(conn, var.environment_id, &var.key)?)?;
+            (Some(sealed), compressed)
+        }
+        None => (None, false),
+    };
+    
+    conn.execute(
+        "UPDATE variables SET encrypted_value = ?, aad_version = ?, compressed = ?, description = ?, encrypted_description = ?, pending_value = ?, pending_compressed = ?, value_salt = ? WHERE id = ?",
+        params![encrypted_value, CURRENT_AAD_VERSION, compressed, description, encrypted_description, pending_value, pending_compressed, value_salt, id],
+    )?;
+    
+    Ok(())
+}
+
+/// Turn description encryption on or off and convert every existing description
+///
+/// Runs in a single transaction. Returns the number of descriptions converted.
+pub fn set_description_encryption(
+    conn: &Connection,
+    enabled: bool,
+    encryption_key: &[u8; 32],
+) -> Result<usize, DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    set_encrypt_descriptions(&tx, enabled)?;
+    
+    // Only rows stored the other way need converting
+    let stored_as = if enabled { "description" } else { "encrypted_description" };
+    let ids: Vec<i64> = tx
+        .prepare(&format!("SELECT id FROM variables WHERE {} IS NOT NULL ORDER BY id", stored_as))?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    for id in &ids {
+        let var = get_variable(&tx, *id)?;
+        let aad = variable_aad(&tx, var.environment_id, &var.key, var.aad_version)?;
+        let description = open_description(&var, &aad, encryption_key, false)?;
+        let (description, encrypted_description) = seal_description(&tx, encryption_key, description, &aad)?;
+        
+        tx.execute(
+            "UPDATE variables SET description = ?, encrypted_description = ? WHERE id = ?",
+            params![description, encrypted_description, id],
+        )?;
+    }
+    
+    let _ = log_audit(
+        &tx,
+        if enabled { "encrypt_descriptions" } else { "decrypt_descriptions" },
+        "vault",
+        None,
+        None,
+        Some(json!({ "descriptions": ids.len() })),
+    );
+    let _ = update_last_modified(&tx);
+    
+    tx.commit()?;
+    Ok(ids.len())
+}
+
+/// Turn per-value keys on or off and re-seal every value stored the other way
+///
+/// With the setting on, each variable gets a random salt and its value and
+/// pending value are sealed under an HKDF subkey of the vault key, so no two
+/// variables share an AES-GCM key. Runs in a single transaction. Returns the
+/// number of variables converted.
+pub fn set_per_value_keys(
+    conn: &Connection,
+    enabled: bool,
+    encryption_key: &[u8; 32],
+) -> Result<usize, DatabaseError> {
+    let tx = conn.unchecked_transaction()?;
+    crate::database::migrations::set_per_value_keys(&tx, enabled)?;
+    
+    let stored_as = if enabled { "IS NULL" } else { "IS NOT NULL" };
+    let ids: Vec<i64> = tx
+        .prepare(&format!("SELECT id FROM variables WHERE value_salt {} ORDER BY id", stored_as))?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    
+    for id in &ids {
+        let var = get_variable(&tx, *id)?;
+        let value = decrypt_variable(&tx, &var, encryption_key)?;
+        let description = decrypt_description(&tx, &var, encryption_key)?;
+        let pending = decrypt_pending_value(&tx, &var, encryption_key)?;
+        rewrite_encrypted_value(&tx, &var, value.expose(), description, pending.as_ref().map(SecretString::expose), encryption_key)?;
+    }
+    
+    let _ = log_audit(
+        &tx,
+        if enabled { "enable_per_value_keys" } else { "disable_per_value_keys" },
+        "vault",
+        None,
+        None,
+        Some(json!({ "variables": ids.len() })),
+    );
+    let _ = update_last_modified(&tx);
+    
+    tx.commit()?;
+    Ok(ids.len())
+}
+
+/// Re-encrypt values still using an older AAD format (called after unlock)
+///
+/// Returns the number of values re-encrypted, or 0 if the vault is already current.
+pub fn upgrade_legacy_aad(conn: &Connection, encryption_key: &[u8; 32]) -> Result<usize, DatabaseError> {
+    let legacy_count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM variables WHERE aad_version < ?",
+        params![CURRENT_AAD_VERSION],
+        |row| row.get(0),
+    )?;
+    
+    if legacy_count == 0 {
+        return Ok(0);
+    }
+    
+    reencrypt_all(conn, encryption_key, encryption_key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::database::{Database, operations::{Project, Environment, projects, environments}};
     use crate::crypto::key_derivation;
+    use crate::database::migrations::set_nonce_mode;
+    use crate::database::operations::quota::{set_quota, Quota};
     
     fn setup_test_db() -> (Database, i64, [u8; 32]) {
         let db = Database::new_in_memory().unwrap();
@@ -289,7 +1520,7 @@ mod tests {
         let decrypted = get_variable_decrypted(db.connection(), var_id, &key).unwrap();
         
         assert_eq!(decrypted.key, "API_KEY");
-        assert_eq!(decrypted.value, "secret_value_123");
+        assert_eq!(decrypted.value.expose(), "secret_value_123");
         assert_eq!(decrypted.description, Some("API Key".to_string()));
     }
     
@@ -321,8 +1552,8 @@ mod tests {
         let vars = get_variables_by_environment_decrypted(db.connection(), env_id, &key).unwrap();
         
         assert_eq!(vars.len(), 2);
-        assert_eq!(vars[0].value, "value1");
-        assert_eq!(vars[1].value, "value2");
+        assert_eq!(vars[0].value.expose(), "value1");
+        assert_eq!(vars[1].value.expose(), "value2");
     }
     
     #[test]
@@ -344,14 +1575,215 @@ mod tests {
             "NEW_KEY".to_string(),
             "new_value".to_string(),
             Some("Updated".to_string()),
+            false,
             &key,
         ).unwrap();
         
         let decrypted = get_variable_decrypted(db.connection(), var_id, &key).unwrap();
         assert_eq!(decrypted.key, "NEW_KEY");
-        assert_eq!(decrypted.value, "new_value");
+        assert_eq!(decrypted.value.expose(), "new_value");
+    }
+    
+    #[test]
+    fn test_pending_value_is_staged_and_promoted() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        let var_id = create_variable_encrypted(conn, env_id, "TOKEN".to_string(), "old".to_string(), None, &key).unwrap();
+        
+        set_pending_value(conn, var_id, "new", &key).unwrap();
+        assert_eq!(get_variable_decrypted(conn, var_id, &key).unwrap().value.expose(), "old");
+        
+        // Renames and key rotation carry the staged value along
+        update_variable_encrypted(conn, var_id, "API_TOKEN".to_string(), "old".to_string(), None, false, &key).unwrap();
+        let new_key = [9u8; 32];
+        reencrypt_all(conn, &key, &new_key).unwrap();
+        let var = get_variable(conn, var_id).unwrap();
+        assert_eq!(decrypt_pending_value(conn, &var, &new_key).unwrap().unwrap().expose(), "new");
+        
+        set_variable_protected(conn, var_id, true).unwrap();
+        assert!(matches!(promote_pending_value(conn, var_id, false, &new_key), Err(DatabaseError::Protected(_))));
+        promote_pending_value(conn, var_id, true, &new_key).unwrap();
+        
+        let var = get_variable(conn, var_id).unwrap();
+        assert_eq!(decrypt_variable(conn, &var, &new_key).unwrap().expose(), "new");
+        assert!(var.pending_value.is_none());
+        assert!(matches!(promote_pending_value(conn, var_id, true, &new_key), Err(DatabaseError::NotFound(_))));
+    }
+    
+    #[test]
+    fn test_parallel_decryption_keeps_order_and_names_failures() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let count = PARALLEL_DECRYPT_THRESHOLD + 10;
+        for i in 0..count {
+            create_variable_encrypted(conn, env_id, format!("KEY_{:04}", i), format!("value-{}", i), None, &key).unwrap();
+        }
+        
+        let variables = get_variables_by_environment(conn, env_id).unwrap();
+        let context = AadContext::load(conn, env_id).unwrap();
+        let aads: Vec<_> = variables.iter().map(|v| context.aad(&v.key, v.aad_version).unwrap()).collect();
+        
+        let values = open_values(&variables, &aads, &key, 4, false).unwrap();
+        assert_eq!(values.len(), count);
+        for (i, (value, _)) in values.iter().enumerate() {
+            assert_eq!(value.expose(), format!("value-{}", i));
+        }
+        
+        // Corrupt two values; the error names the first one in key order
+        let mut corrupted = variables.clone();
+        corrupted[count - 1].encrypted_value[20] ^= 1;
+        corrupted[100].encrypted_value[20] ^= 1;
+        let err = open_values(&corrupted, &aads, &key, 4, false).unwrap_err().to_string();
+        assert!(err.contains("KEY_0100"), "{}", err);
+    }
+    
+    #[test]
+    fn test_encrypted_descriptions() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let plain_id = create_variable_encrypted(conn, env_id, "PLAIN".to_string(), "v".to_string(), Some("EU cluster admin".to_string()), &key).unwrap();
+        assert_eq!(set_description_encryption(conn, true, &key).unwrap(), 1);
+        let new_id = create_variable_encrypted(conn, env_id, "NEW".to_string(), "v".to_string(), Some("billing key".to_string()), &key).unwrap();
+        
+        // Nothing readable is left in the description column
+        for id in [plain_id, new_id] {
+            let stored = get_variable(conn, id).unwrap();
+            assert_eq!(stored.description, None);
+            assert!(stored.encrypted_description.is_some());
+        }
+        assert_eq!(get_variable_decrypted(conn, plain_id, &key).unwrap().description.as_deref(), Some("EU cluster admin"));
+        let listed = get_variables_by_environment_decrypted(conn, env_id, &key).unwrap();
+        assert_eq!(listed.iter().find(|v| v.key == "NEW").unwrap().description.as_deref(), Some("billing key"));
+        
+        // A description is bound to its variable
+        let mut swapped = get_variable(conn, plain_id).unwrap();
+        swapped.encrypted_description = get_variable(conn, new_id).unwrap().encrypted_description;
+        assert!(decrypt_description(conn, &swapped, &key).is_err());
+        
+        // Re-keying carries encrypted descriptions along
+        let new_key = [9u8; 32];
+        reencrypt_all(conn, &key, &new_key).unwrap();
+        assert_eq!(get_variable_decrypted(conn, new_id, &new_key).unwrap().description.as_deref(), Some("billing key"));
+        
+        assert_eq!(set_description_encryption(conn, false, &new_key).unwrap(), 2);
+        let stored = get_variable(conn, new_id).unwrap();
+        assert_eq!(stored.description.as_deref(), Some("billing key"));
+        assert!(stored.encrypted_description.is_none());
+    }
+    
+    #[test]
+    fn test_per_value_keys() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let old_id = create_variable_encrypted(conn, env_id, "OLD".to_string(), "old".to_string(), None, &key).unwrap();
+        set_pending_value(conn, old_id, "staged", &key).unwrap();
+        assert_eq!(set_per_value_keys(conn, true, &key).unwrap(), 1);
+        let new_id = create_variable_encrypted(conn, env_id, "NEW".to_string(), "new".to_string(), None, &key).unwrap();
+        
+        // Each value has its own salt and is sealed under the subkey, not the vault key
+        let old = get_variable(conn, old_id).unwrap();
+        let new = get_variable(conn, new_id).unwrap();
+        assert!(old.value_salt.is_some() && new.value_salt.is_some() && old.value_salt != new.value_salt);
+        let aad = variable_aad(conn, env_id, "NEW", CURRENT_AAD_VERSION).unwrap();
+        assert!(encryption::decrypt(&key, &new.encrypted_value, aad.as_bytes()).is_err());
+        let subkey = derive_value_key(&key, new.value_salt.as_deref().unwrap()).unwrap();
+        assert!(encryption::decrypt(&subkey, &new.encrypted_value, aad.as_bytes()).is_ok());
+        assert_eq!(decrypt_variable(conn, &old, &key).unwrap().expose(), "old");
+        assert_eq!(decrypt_pending_value(conn, &old, &key).unwrap().unwrap().expose(), "staged");
+        
+        // A salt only opens its own variable
+        let swapped = Variable { value_salt: old.value_salt.clone(), ..new.clone() };
+        assert!(decrypt_variable(conn, &swapped, &key).is_err());
+        
+        // Updates keep the salt, so a staged value stays readable across a rename
+        upsert_variable_encrypted(conn, env_id, "NEW".to_string(), "newer".to_string(), None, None, &key).unwrap();
+        assert_eq!(get_variable(conn, new_id).unwrap().value_salt, new.value_salt);
+        update_variable_encrypted_with_type(conn, old_id, "RENAMED".to_string(), "old2".to_string(), None, None, false, &key).unwrap();
+        let renamed = get_variable(conn, old_id).unwrap();
+        assert_eq!(renamed.value_salt, old.value_salt);
+        assert_eq!(decrypt_pending_value(conn, &renamed, &key).unwrap().unwrap().expose(), "staged");
+        
+        // Re-keying and turning the setting off carry the values along
+        let new_key = [9u8; 32];
+        reencrypt_all(conn, &key, &new_key).unwrap();
+        assert_eq!(set_per_value_keys(conn, false, &new_key).unwrap(), 2);
+        let listed = get_variables_by_environment_decrypted(conn, env_id, &new_key).unwrap();
+        assert_eq!(listed.iter().map(|v| v.value.expose()).collect::<Vec<_>>(), ["newer", "old2"]);
+        assert!(get_variables_by_environment(conn, env_id).unwrap().iter().all(|v| v.value_salt.is_none()));
+        assert_eq!(decrypt_pending_value(conn, &get_variable(conn, old_id).unwrap(), &new_key).unwrap().unwrap().expose(), "staged");
+    }
+    
+    #[test]
+    fn test_lossy_reads_flag_invalid_utf8() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        create_variable_encrypted(conn, env_id, "TEXT".to_string(), "fine".to_string(), None, &key).unwrap();
+        let aad = variable_aad(conn, env_id, "BINARY", CURRENT_AAD_VERSION).unwrap();
+        let encrypted = encrypt_value(conn, &key, b"ab\xffcd", &aad).unwrap();
+        let binary_id = create_variable(conn, &Variable::new(env_id, "BINARY".to_string(), encrypted, None)).unwrap();
+        
+        // The strict readers keep failing on the bad value
+        let err = get_variables_by_environment_decrypted(conn, env_id, &key).unwrap_err().to_string();
+        assert!(err.contains("'BINARY' is not valid UTF-8"), "{}", err);
+        assert!(get_variable_decrypted(conn, binary_id, &key).is_err());
+        
+        let variables = get_variables_by_environment_decrypted_lossy(conn, env_id, &key).unwrap();
+        let text = variables.iter().find(|v| v.key == "TEXT").unwrap();
+        assert_eq!(text.value.expose(), "fine");
+        assert!(!text.lossy);
+        
+        let binary = get_variable_decrypted_lossy(conn, binary_id, &key).unwrap();
+        assert_eq!(binary.value.expose(), "ab\u{FFFD}cd");
+        assert!(binary.lossy);
+    }
+    
+    #[test]
+    fn test_large_values_are_compressed_transparently() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let cert = "-----BEGIN CERTIFICATE-----\n".to_string() + &"MIIBszCCAVmgAwIBAgIU\n".repeat(60);
+        let big_id = create_variable_encrypted(conn, env_id, "CERT".to_string(), cert.clone(), None, &key).unwrap();
+        let small_id = create_variable_encrypted(conn, env_id, "PORT".to_string(), "8080".to_string(), None, &key).unwrap();
+        
+        let stored = get_variable(conn, big_id).unwrap();
+        assert!(stored.compressed);
+        assert!(stored.encrypted_value.len() < cert.len());
+        assert!(!get_variable(conn, small_id).unwrap().compressed);
+        
+        assert_eq!(get_variable_decrypted(conn, big_id, &key).unwrap().value.expose(), cert);
+        
+        // Re-encryption keeps values readable
+        let new_key = [9u8; 32];
+        reencrypt_all(conn, &key, &new_key).unwrap();
+        assert_eq!(get_variable_decrypted(conn, big_id, &new_key).unwrap().value.expose(), cert);
     }
     
+    #[test]
+    fn test_value_type_is_validated_and_kept() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let err = create_variable_encrypted_with_type(conn, env_id, "PORT".to_string(), "eighty".to_string(), None, ValueType::Number, &key);
+        assert!(matches!(err, Err(DatabaseError::InvalidValue(_))));
+        
+        let var_id = create_variable_encrypted_with_type(conn, env_id, "PORT".to_string(), "80".to_string(), None, ValueType::Number, &key).unwrap();
+        assert_eq!(get_variable_decrypted(conn, var_id, &key).unwrap().value_type, ValueType::Number);
+        
+        // Plain updates keep the stored type and are validated against it
+        let err = update_variable_encrypted(conn, var_id, "PORT".to_string(), "http".to_string(), None, false, &key);
+        assert!(matches!(err, Err(DatabaseError::InvalidValue(_))));
+        update_variable_encrypted(conn, var_id, "PORT".to_string(), "8080".to_string(), None, false, &key).unwrap();
+        assert_eq!(get_variable(conn, var_id).unwrap().value_type, ValueType::Number);
+        
+        update_variable_encrypted_with_type(conn, var_id, "PORT".to_string(), "http".to_string(), None, Some(ValueType::Text), false, &key).unwrap();
+        assert_eq!(get_variable(conn, var_id).unwrap().value_type, ValueType::Text);
+    }
+
     #[test]
     fn test_delete_variable() {
         let (db, env_id, key) = setup_test_db();
@@ -365,11 +1797,191 @@ mod tests {
             &key,
         ).unwrap();
         
-        delete_variable(db.connection(), var_id).unwrap();
+        delete_variable(db.connection(), var_id, false).unwrap();
         
         assert!(get_variable(db.connection(), var_id).is_err());
     }
     
+    #[test]
+    fn test_protected_variables_need_force() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let var_id = create_variable_encrypted(conn, env_id, "PROD_DB".to_string(), "old".to_string(), None, &key).unwrap();
+        set_variable_protected(conn, var_id, true).unwrap();
+        assert!(get_variable_decrypted(conn, var_id, &key).unwrap().is_protected);
+        
+        let err = update_variable_encrypted(conn, var_id, "PROD_DB".to_string(), "new".to_string(), None, false, &key);
+        assert!(matches!(err, Err(DatabaseError::Protected(ref k)) if k == "PROD_DB"));
+        assert!(matches!(delete_variable(conn, var_id, false), Err(DatabaseError::Protected(_))));
+        
+        // Imports with --overwrite are refused as a whole
+        let entries = vec![("PROD_DB".to_string(), "imported".to_string(), None)];
+        assert!(import_variables(conn, env_id, entries, true, &key).is_err());
+        assert_eq!(get_variable_decrypted(conn, var_id, &key).unwrap().value.expose(), "old");
+        
+        // Forced updates keep the flag
+        update_variable_encrypted(conn, var_id, "PROD_DB".to_string(), "new".to_string(), None, true, &key).unwrap();
+        assert!(get_variable(conn, var_id).unwrap().is_protected);
+        
+        set_variable_protected(conn, var_id, false).unwrap();
+        delete_variable(conn, var_id, false).unwrap();
+    }
+    
+    #[test]
+    fn test_delete_all_variables_keeps_the_environment() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        create_variable_encrypted(conn, env_id, "A".to_string(), "1".to_string(), None, &key).unwrap();
+        let protected = create_variable_encrypted(conn, env_id, "B".to_string(), "2".to_string(), None, &key).unwrap();
+        set_variable_protected(conn, protected, true).unwrap();
+        
+        // A protected variable stops the whole clear unless forced
+        assert!(matches!(delete_all_variables(conn, env_id, false), Err(DatabaseError::Protected(ref k)) if k == "B"));
+        assert_eq!(get_variables_by_environment(conn, env_id).unwrap().len(), 2);
+        
+        assert_eq!(delete_all_variables(conn, env_id, true).unwrap(), ["A", "B"]);
+        assert!(get_variables_by_environment(conn, env_id).unwrap().is_empty());
+        assert!(crate::database::operations::environments::get_environment(conn, env_id).is_ok());
+        
+        let deletes: i64 = conn.query_row("SELECT COUNT(*) FROM audit_log WHERE operation_type = 'delete'", [], |row| row.get(0)).unwrap();
+        assert_eq!(deletes, 2);
+    }
+    
+    #[test]
+    fn test_variable_expiry() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let var_id = create_variable_encrypted(conn, env_id, "TEMP_TOKEN".to_string(), "abc".to_string(), None, &key).unwrap();
+        create_variable_encrypted(conn, env_id, "STABLE".to_string(), "xyz".to_string(), None, &key).unwrap();
+        assert!(get_expired_variables(conn, i64::MAX).unwrap().is_empty());
+        
+        set_variable_expiry(conn, var_id, Some(1_000)).unwrap();
+        let decrypted = get_variable_decrypted(conn, var_id, &key).unwrap();
+        assert_eq!(decrypted.expires_at, Some(1_000));
+        assert!(!decrypted.is_expired(999));
+        assert!(decrypted.is_expired(1_000));
+        
+        // Updating the value keeps the expiry, and expired rows are still readable
+        update_variable_encrypted(conn, var_id, "TEMP_TOKEN".to_string(), "def".to_string(), None, false, &key).unwrap();
+        assert_eq!(get_variable(conn, var_id).unwrap().expires_at, Some(1_000));
+        assert_eq!(get_variable_decrypted(conn, var_id, &key).unwrap().value.expose(), "def");
+        
+        assert!(get_expired_variables(conn, 999).unwrap().is_empty());
+        let expired = get_expired_variables(conn, 2_000).unwrap();
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].key, "TEMP_TOKEN");
+        assert_eq!(expired[0].expires_at, 1_000);
+        
+        set_variable_expiry(conn, var_id, None).unwrap();
+        assert!(get_expired_variables(conn, i64::MAX).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_variables_modified_since() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        create_variable_encrypted(conn, env_id, "OLD".to_string(), "a".to_string(), None, &key).unwrap();
+        create_variable_encrypted(conn, env_id, "EDITED".to_string(), "b".to_string(), None, &key).unwrap();
+        create_variable_encrypted(conn, env_id, "ADDED".to_string(), "c".to_string(), None, &key).unwrap();
+        conn.execute("UPDATE variables SET created_at = 100, updated_at = 100 WHERE key = 'OLD'", []).unwrap();
+        conn.execute("UPDATE variables SET created_at = 100, updated_at = 500 WHERE key = 'EDITED'", []).unwrap();
+        conn.execute("UPDATE variables SET created_at = 400, updated_at = 400 WHERE key = 'ADDED'", []).unwrap();
+        
+        let changed = get_variables_modified_since(conn, 300).unwrap();
+        assert_eq!(changed.iter().map(|v| v.key.as_str()).collect::<Vec<_>>(), ["EDITED", "ADDED"]);
+        assert_eq!((changed[0].project.as_str(), changed[0].environment.as_str()), ("TestProject", "test-env"));
+        assert!(!changed[0].is_new(300));
+        assert!(changed[1].is_new(300));
+        
+        assert_eq!(get_variables_modified_since(conn, 0).unwrap().len(), 3);
+        assert!(get_variables_modified_since(conn, 501).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_change_markers_catch_every_write() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let a = create_variable_encrypted(conn, env_id, "A".to_string(), "1".to_string(), None, &key).unwrap();
+        create_variable_encrypted(conn, env_id, "B".to_string(), "2".to_string(), None, &key).unwrap();
+        let before = environment_change_markers(conn, env_id).unwrap();
+        assert!(changed_keys(&before, &environment_change_markers(conn, env_id).unwrap()).is_empty());
+        
+        // Same value, same second: the fresh nonce still shows up
+        update_variable_encrypted(conn, a, "A".to_string(), "1".to_string(), None, false, &key).unwrap();
+        set_pending_value(conn, a, "next", &key).unwrap();
+        create_variable_encrypted(conn, env_id, "C".to_string(), "3".to_string(), None, &key).unwrap();
+        delete_variable(conn, get_variables_by_environment(conn, env_id).unwrap()[1].id.unwrap(), false).unwrap();
+        
+        let after = environment_change_markers(conn, env_id).unwrap();
+        assert_eq!(changed_keys(&before, &after), ["A", "B", "C"]);
+    }
+    
+    #[test]
+    fn test_bulk_tagging() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        for name in ["DB_HOST", "DB_PASS", "API_KEY"] {
+            create_variable_encrypted(conn, env_id, name.to_string(), "v".to_string(), None, &key).unwrap();
+        }
+        let tags_of = |name: &str| {
+            get_variables_by_environment(conn, env_id).unwrap().into_iter().find(|v| v.key == name).unwrap().tags
+        };
+        
+        let changed = tag_variables(conn, env_id, &["DB_*".to_string()], "db", true).unwrap();
+        assert_eq!(changed, vec!["DB_HOST".to_string(), "DB_PASS".to_string()]);
+        let changed = tag_variables(conn, env_id, &["API_KEY".to_string(), "DB_HOST".to_string()], "auth", true).unwrap();
+        assert_eq!(changed.len(), 2);
+        assert_eq!(tags_of("DB_HOST"), vec!["auth".to_string(), "db".to_string()]);
+        assert_eq!(get_variables_by_environment_decrypted(conn, env_id, &key).unwrap()[0].tags, vec!["auth".to_string()]);
+        
+        // Re-adding is a no-op, and an unmatched pattern fails before anything changes
+        assert!(tag_variables(conn, env_id, &["DB_*".to_string()], "db", true).unwrap().is_empty());
+        assert!(tag_variables(conn, env_id, &["DB_*".to_string(), "MISSING".to_string()], "x", true).is_err());
+        assert_eq!(tags_of("DB_PASS"), vec!["db".to_string()]);
+        assert!(tag_variables(conn, env_id, &["*".to_string()], "not valid", true).is_err());
+        
+        let changed = tag_variables(conn, env_id, &["*".to_string()], "db", false).unwrap();
+        assert_eq!(changed.len(), 2);
+        assert_eq!(tags_of("DB_HOST"), vec!["auth".to_string()]);
+        
+        assert!(key_matches("DB_?OST", "DB_HOST"));
+        assert!(key_matches("*_KEY", "API_KEY"));
+        assert!(!key_matches("DB_*", "API_KEY"));
+    }
+    
+    #[test]
+    fn test_upsert_variable() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        
+        let (id, created) = upsert_variable_encrypted(conn, env_id, "PORT".to_string(), "3000".to_string(), None, Some(ValueType::Number), &key).unwrap();
+        assert!(created);
+        
+        // Same key again updates the row in place and keeps its type
+        let (same_id, created) = upsert_variable_encrypted(conn, env_id, "PORT".to_string(), "8080".to_string(), Some("http".to_string()), None, &key).unwrap();
+        assert_eq!((same_id, created), (id, false));
+        let var = get_variable_decrypted(conn, id, &key).unwrap();
+        assert_eq!(var.value.expose(), "8080");
+        assert_eq!(var.value_type, ValueType::Number);
+        assert_eq!(var.description, Some("http".to_string()));
+        assert!(matches!(
+            upsert_variable_encrypted(conn, env_id, "PORT".to_string(), "eighty".to_string(), None, None, &key),
+            Err(DatabaseError::InvalidValue(_))
+        ));
+        
+        set_variable_protected(conn, id, true).unwrap();
+        let err = upsert_variable_encrypted(conn, env_id, "PORT".to_string(), "1".to_string(), None, None, &key);
+        assert!(matches!(err, Err(DatabaseError::Protected(ref k)) if k == "PORT"));
+        assert_eq!(get_variable_decrypted(conn, id, &key).unwrap().value.expose(), "8080");
+        assert_eq!(get_variables_by_environment(conn, env_id).unwrap().len(), 1);
+    }
+    
     #[test]
     fn test_unique_key_per_environment() {
         let (db, env_id, key) = setup_test_db();
@@ -396,4 +2008,213 @@ mod tests {
         
         assert!(get_variable(db.connection(), var_id).is_err());
     }
+    
+    /// Reset the vault timestamps so any update is observable within the same second
+    fn reset_timestamps(db: &Database) {
+        db.connection().execute(
+            "UPDATE vault_metadata SET last_accessed = 0, last_modified = 0 WHERE id = 1",
+            [],
+        ).unwrap();
+    }
+    
+    fn timestamps(db: &Database) -> (i64, i64) {
+        db.connection().query_row(
+            "SELECT last_accessed, last_modified FROM vault_metadata WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).unwrap()
+    }
+    
+    #[test]
+    fn test_writes_advance_last_modified() {
+        let (db, env_id, key) = setup_test_db();
+        
+        reset_timestamps(&db);
+        let var_id = create_variable_encrypted(db.connection(), env_id, "A".to_string(), "1".to_string(), None, &key).unwrap();
+        let (accessed, modified) = timestamps(&db);
+        assert_eq!(accessed, 0);
+        assert!(modified > 0);
+        
+        reset_timestamps(&db);
+        update_variable_encrypted(db.connection(), var_id, "A".to_string(), "2".to_string(), None, false, &key).unwrap();
+        assert!(timestamps(&db).1 > 0);
+        
+        reset_timestamps(&db);
+        delete_variable(db.connection(), var_id, false).unwrap();
+        assert!(timestamps(&db).1 > 0);
+    }
+    
+    #[test]
+    fn test_decrypted_reads_advance_last_accessed() {
+        let (db, env_id, key) = setup_test_db();
+        let var_id = create_variable_encrypted(db.connection(), env_id, "A".to_string(), "1".to_string(), None, &key).unwrap();
+        
+        reset_timestamps(&db);
+        get_variable_decrypted(db.connection(), var_id, &key).unwrap();
+        assert!(timestamps(&db).0 > 0);
+        assert_eq!(timestamps(&db).1, 0);
+        
+        reset_timestamps(&db);
+        get_variables_by_environment_decrypted(db.connection(), env_id, &key).unwrap();
+        assert!(timestamps(&db).0 > 0);
+    }
+    
+    /// Rewrite a variable the way builds before AAD v2 stored it
+    fn store_as_legacy(db: &Database, var_id: i64, value: &str, key: &[u8; 32]) {
+        let var = get_variable(db.connection(), var_id).unwrap();
+        let aad = format!("env:{};key:{}", var.environment_id, var.key);
+        let encrypted = encryption::encrypt(key, value.as_bytes(), aad.as_bytes()).unwrap();
+        
+        db.connection().execute(
+            "UPDATE variables SET encrypted_value = ?, aad_version = ? WHERE id = ?",
+            params![encrypted, LEGACY_AAD_VERSION, var_id],
+        ).unwrap();
+    }
+    
+    #[test]
+    fn test_verify_encryption_key() {
+        let (db, env_id, key) = setup_test_db();
+        assert!(verify_encryption_key(db.connection(), &[9u8; 32]).unwrap());
+        
+        create_variable_encrypted(db.connection(), env_id, "A".to_string(), "1".to_string(), None, &key).unwrap();
+        assert!(verify_encryption_key(db.connection(), &key).unwrap());
+        assert!(!verify_encryption_key(db.connection(), &[9u8; 32]).unwrap());
+    }
+    
+    #[test]
+    fn test_legacy_aad_is_readable_and_upgraded() {
+        let (db, env_id, key) = setup_test_db();
+        let var_id = create_variable_encrypted(db.connection(), env_id, "A".to_string(), "old".to_string(), None, &key).unwrap();
+        store_as_legacy(&db, var_id, "legacy", &key);
+        
+        assert_eq!(get_variable_decrypted(db.connection(), var_id, &key).unwrap().value.expose(), "legacy");
+        
+        assert_eq!(upgrade_legacy_aad(db.connection(), &key).unwrap(), 1);
+        assert_eq!(get_variable(db.connection(), var_id).unwrap().aad_version, CURRENT_AAD_VERSION);
+        assert_eq!(get_variable_decrypted(db.connection(), var_id, &key).unwrap().value.expose(), "legacy");
+        
+        assert_eq!(upgrade_legacy_aad(db.connection(), &key).unwrap(), 0);
+    }
+    
+    #[test]
+    fn test_current_aad_binds_project() {
+        let (db, env_id, key) = setup_test_db();
+        let var_id = create_variable_encrypted(db.connection(), env_id, "A".to_string(), "v".to_string(), None, &key).unwrap();
+        
+        let other_project = projects::create_project(db.connection(), &Project::new("Other".to_string(), None)).unwrap();
+        db.connection().execute(
+            "UPDATE environments SET project_id = ? WHERE id = ?",
+            params![other_project, env_id],
+        ).unwrap();
+        
+        assert!(get_variable_decrypted(db.connection(), var_id, &key).is_err());
+    }
+    
+    #[test]
+    fn test_reencrypt_all_with_new_key() {
+        let (db, env_id, key) = setup_test_db();
+        let var_id = create_variable_encrypted(db.connection(), env_id, "A".to_string(), "v".to_string(), None, &key).unwrap();
+        let new_key = [7u8; 32];
+        
+        assert!(reencrypt_all(db.connection(), &new_key, &key).is_err());
+        assert_eq!(get_variable_decrypted(db.connection(), var_id, &key).unwrap().value.expose(), "v");
+        
+        assert_eq!(reencrypt_all(db.connection(), &key, &new_key).unwrap(), 1);
+        assert!(get_variable_decrypted(db.connection(), var_id, &key).is_err());
+        assert_eq!(get_variable_decrypted(db.connection(), var_id, &new_key).unwrap().value.expose(), "v");
+    }
+    
+    #[test]
+    fn test_counter_nonces_persist_across_restarts() {
+        let path = std::env::temp_dir().join(format!("clerk-nonce-test-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let key = [4u8; 32];
+        let mut nonces = Vec::new();
+        
+        for restart in 0..3 {
+            // Each iteration reopens the file like a fresh process would
+            let db = Database::new(&path).unwrap();
+            db.initialize().unwrap();
+            
+            let env_id = if restart == 0 {
+                set_nonce_mode(db.connection(), NonceMode::Counter).unwrap();
+                let project_id = projects::create_project(db.connection(), &Project::new("P".to_string(), None)).unwrap();
+                environments::create_environment(db.connection(), &Environment::new(project_id, "dev".to_string(), None)).unwrap()
+            } else {
+                environments::get_environments_by_project(db.connection(), 1).unwrap()[0].id.unwrap()
+            };
+            
+            for i in 0..2 {
+                let var_id = create_variable_encrypted(
+                    db.connection(), env_id, format!("K{}_{}", restart, i), "v".to_string(), None, &key,
+                ).unwrap();
+                let var = get_variable(db.connection(), var_id).unwrap();
+                nonces.push(u64::from_be_bytes(var.encrypted_value[..8].try_into().unwrap()));
+                
+                assert_eq!(get_variable_decrypted(db.connection(), var_id, &key).unwrap().value.expose(), "v");
+            }
+        }
+        
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(nonces, vec![1, 2, 3, 4, 5, 6]);
+    }
+    
+    #[test]
+    fn test_suggest_keys() {
+        let (db, dev, key) = setup_test_db();
+        let conn = db.connection();
+        let prod = environments::create_environment(conn, &Environment::new(1, "prod".to_string(), None)).unwrap();
+        
+        for (env, name) in [(dev, "API_KEY"), (dev, "DB_URL"), (prod, "APIKEY"), (prod, "API_KEY")] {
+            create_variable_encrypted(conn, env, name.to_string(), "v".to_string(), None, &key).unwrap();
+        }
+        
+        assert_eq!(suggest_keys(conn, dev, "api", false, 10).unwrap(), vec!["API_KEY"]);
+        assert_eq!(suggest_keys(conn, dev, "API", true, 10).unwrap(), vec!["API_KEY", "APIKEY"]);
+        assert_eq!(suggest_keys(conn, dev, "", false, 10).unwrap(), vec!["API_KEY", "DB_URL"]);
+        assert_eq!(suggest_keys(conn, dev, "%", true, 10).unwrap(), Vec::<String>::new());
+    }
+    
+    #[test]
+    fn test_import_variables_rolls_back_on_error() {
+        let (db, env_id, key) = setup_test_db();
+        let conn = db.connection();
+        create_variable_encrypted(conn, env_id, "KEEP".to_string(), "old".to_string(), None, &key).unwrap();
+        
+        // Allow only two variables so the second new entry fails mid-import
+        let quota = Quota { max_variables_per_env: 2, ..Default::default() };
+        set_quota(conn, &quota).unwrap();
+        
+        let entries = vec![
+            ("KEEP".to_string(), "new".to_string(), None),
+            ("A".to_string(), "1".to_string(), None),
+            ("B".to_string(), "2".to_string(), None),
+        ];
+        assert!(import_variables(conn, env_id, entries, true, &key).is_err());
+        
+        let vars = get_variables_by_environment_decrypted(conn, env_id, &key).unwrap();
+        assert_eq!(vars.len(), 1);
+        assert_eq!(vars[0].value.expose(), "old");
+    }
+    
+    #[test]
+    fn test_copy_variable_reencrypts_for_target() {
+        let (db, dev, key) = setup_test_db();
+        let conn = db.connection();
+        let prod = environments::create_environment(conn, &Environment::new(1, "prod".to_string(), None)).unwrap();
+        create_variable_encrypted(conn, dev, "TOKEN".to_string(), "secret".to_string(), None, &key).unwrap();
+        
+        assert!(!copy_variable(conn, dev, prod, "TOKEN", false, &key).unwrap());
+        let copied = get_variables_by_environment_decrypted(conn, prod, &key).unwrap();
+        assert_eq!(copied[0].value.expose(), "secret");
+        
+        // Without overwrite the existing target is left alone
+        assert!(copy_variable(conn, dev, prod, "TOKEN", false, &key).is_err());
+        assert!(copy_variable(conn, dev, prod, "TOKEN", true, &key).unwrap());
+        
+        // A failed decrypt (wrong key) writes nothing
+        let other = environments::create_environment(conn, &Environment::new(1, "other".to_string(), None)).unwrap();
+        assert!(copy_variable(conn, dev, other, "TOKEN", false, &[9u8; 32]).is_err());
+        assert!(get_variables_by_environment(conn, other).unwrap().is_empty());
+    }
 }
@@ -2,12 +2,37 @@
 /// 
 /// Schema Structure:
 /// - vault_metadata: Stores vault configuration and metadata
+///   * `metadata_mac` = 1 once vault.clerk carries a MAC; a MAC missing after
+///     that means the file was tampered with
 /// - projects: Top-level organization unit (e.g., "MyApp", "Backend API")
 /// - environments: Belongs to a project (e.g., "development", "production")
+///   * `locked_by` / `locked_at` hold an advisory edit lock (`clerk env-lock`);
+///     locks older than a day count as stale and are ignored
+///   * `audit_enabled` = 0 turns `log_audit` into a no-op (`clerk audit disable`)
+///   * `position` orders a project's environments (NULL = after the ordered
+///     ones, by name); set for all of them at once by `set_environment_order`
 /// - variables: Belongs to an environment (e.g., "DATABASE_URL", "API_KEY")
 ///   * Values are encrypted using AES-256-GCM before storage
-///   * AAD (Additional Authenticated Data) includes project_id, env_id, key name
-pub const SCHEMA_VERSION: u32 = 1;
+///   * AAD (Additional Authenticated Data) binds each value to its location. The
+///     format is versioned per row in `variables.aad_version`:
+///     - v1 (legacy): `env:{environment_id};key:{key}`
+///     - v2: `vault:{vault_id};proj:{project_id};env:{environment_id};key:{key}`
+///   * Descriptions are plaintext unless `vault_metadata.encrypt_descriptions` is
+///     set; encrypted ones live in `encrypted_description` (with `description`
+///     NULL) under the value's AAD plus `;field:description`
+///   * `expires_at` (unix seconds, NULL = never) marks temporary credentials;
+///     expired rows are kept for the audit trail and skipped by CLI reads
+///   * `tags` is a JSON array of labels (e.g. `["db"]`) for grouping variables
+///   * `pending_value` holds an optional second value staged for rotation
+///     (`clerk set --slot pending`), sealed under the current-version AAD plus
+///     `;slot:pending`; `clerk promote` moves it into `encrypted_value`
+///   * `value_salt` (NULL = none) is set on rows written while
+///     `vault_metadata.per_value_keys` is on: the value and pending value are
+///     then sealed under HKDF-SHA256(vault key, value_salt) instead of the vault key
+///
+/// `SCHEMA_VERSION` is stored in `vault_metadata.version`: 1 is the initial
+/// schema and each entry of `migrations::MIGRATIONS` adds one.
+pub const SCHEMA_VERSION: u32 = 21;
 
 /// SQL to create the vault_metadata table
 pub const CREATE_VAULT_METADATA_TABLE: &str = r#"
@@ -17,7 +42,19 @@ CREATE TABLE IF NOT EXISTS vault_metadata (
     created_at INTEGER NOT NULL,
     last_accessed INTEGER NOT NULL,
     last_modified INTEGER NOT NULL,
-    lock_timeout_minutes INTEGER DEFAULT 0
+    lock_timeout_minutes INTEGER DEFAULT 0,
+    vault_id TEXT,
+    exit_lock_policy TEXT DEFAULT 'soft',
+    nonce_mode TEXT DEFAULT 'random',
+    nonce_counter INTEGER DEFAULT 0,
+    max_projects INTEGER DEFAULT 0,
+    max_environments_per_project INTEGER DEFAULT 0,
+    max_variables_per_env INTEGER DEFAULT 0,
+    key_canary BLOB,
+    encrypt_descriptions INTEGER DEFAULT 0,
+    audit_enabled INTEGER DEFAULT 1,
+    per_value_keys INTEGER DEFAULT 0,
+    metadata_mac INTEGER DEFAULT 0
 );
 "#;
 
@@ -46,6 +83,9 @@ CREATE TABLE IF NOT EXISTS environments (
     description TEXT,
     created_at INTEGER NOT NULL,
     updated_at INTEGER NOT NULL,
+    locked_by TEXT,
+    locked_at INTEGER,
+    position INTEGER,
     FOREIGN KEY (project_id) REFERENCES projects(id) ON DELETE CASCADE,
     UNIQUE(project_id, name)
 );
@@ -63,7 +103,17 @@ CREATE TABLE IF NOT EXISTS variables (
     environment_id INTEGER NOT NULL,
     key TEXT NOT NULL,
     encrypted_value BLOB NOT NULL,
+    aad_version INTEGER NOT NULL DEFAULT 1,
+    value_type TEXT NOT NULL DEFAULT 'text',
+    compressed INTEGER NOT NULL DEFAULT 0,
+    is_protected INTEGER NOT NULL DEFAULT 0,
     description TEXT,
+    encrypted_description BLOB,
+    expires_at INTEGER,
+    tags TEXT NOT NULL DEFAULT '[]',
+    pending_value BLOB,
+    pending_compressed INTEGER NOT NULL DEFAULT 0,
+    value_salt BLOB,
     created_at INTEGER NOT NULL,
     updated_at INTEGER NOT NULL,
     FOREIGN KEY (environment_id) REFERENCES environments(id) ON DELETE CASCADE,
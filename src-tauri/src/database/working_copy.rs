@@ -0,0 +1,139 @@
+use ring::digest::{digest, SHA256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use super::DatabaseError;
+
+/// Local copy of a database file kept on a network share or synced folder
+///
+/// SQLite's file locking is not reliable over network filesystems, so the file
+/// is copied to the temp directory on checkout and SQLite only ever touches that
+/// copy. `write_back` copies it back with a rename, after checking that nobody
+/// changed the remote file since it was read.
+pub struct WorkingCopy {
+    remote: PathBuf,
+    local: PathBuf,
+    /// SHA-256 of the remote file as last read or written (`None` if it didn't exist)
+    remote_hash: Option<Vec<u8>>,
+}
+
+impl WorkingCopy {
+    /// Copy `remote` into a fresh local working file
+    ///
+    /// A missing remote file is fine (a vault being created); the working copy
+    /// then starts empty and the first write-back creates it.
+    pub fn checkout(remote: &Path) -> Result<Self, DatabaseError> {
+        let local = local_path_for(remote);
+        let remote_hash = file_hash(remote)?;
+
+        if remote_hash.is_some() {
+            fs::copy(remote, &local)
+                .map_err(|e| io_error("copy remote vault to", &local, e))?;
+        } else {
+            let _ = fs::remove_file(&local);
+        }
+
+        Ok(WorkingCopy { remote: remote.to_path_buf(), local, remote_hash })
+    }
+
+    /// Path SQLite should open instead of the remote file
+    pub fn local_path(&self) -> &Path {
+        &self.local
+    }
+
+    /// Copy the working copy back over the remote file if it changed
+    ///
+    /// Returns whether anything was written. Fails with `WorkingCopyConflict`,
+    /// leaving the remote file alone, if it changed since checkout.
+    pub fn write_back(&mut self) -> Result<bool, DatabaseError> {
+        let local_hash = file_hash(&self.local)?;
+        if local_hash == self.remote_hash {
+            return Ok(false);
+        }
+
+        if file_hash(&self.remote)? != self.remote_hash {
+            return Err(DatabaseError::WorkingCopyConflict {
+                remote: self.remote.display().to_string(),
+                local: self.local.display().to_string(),
+            });
+        }
+
+        // Copy next to the target first so the final step is an atomic rename
+        let file_name = self.remote.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let staging = self.remote.with_file_name(format!(".{}.clerk-upload", file_name));
+        fs::copy(&self.local, &staging)
+            .map_err(|e| io_error("stage working copy at", &staging, e))?;
+        fs::rename(&staging, &self.remote)
+            .map_err(|e| {
+                let _ = fs::remove_file(&staging);
+                io_error("replace remote vault", &self.remote, e)
+            })?;
+
+        self.remote_hash = local_hash;
+        Ok(true)
+    }
+
+    /// Delete the local working file
+    pub fn discard(self) {
+        let _ = fs::remove_file(&self.local);
+    }
+}
+
+/// Working file in the temp directory, unique per remote path and process
+fn local_path_for(remote: &Path) -> PathBuf {
+    let path_hash = digest(&SHA256, remote.to_string_lossy().as_bytes());
+    let prefix: String = path_hash.as_ref()[..8].iter().map(|b| format!("{:02x}", b)).collect();
+
+    std::env::temp_dir().join(format!(".clerk_working-{}-{}.db", prefix, std::process::id()))
+}
+
+/// SHA-256 of a file's contents, or `None` if it doesn't exist
+fn file_hash(path: &Path) -> Result<Option<Vec<u8>>, DatabaseError> {
+    match fs::read(path) {
+        Ok(bytes) => Ok(Some(digest(&SHA256, &bytes).as_ref().to_vec())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(io_error("read", path, e)),
+    }
+}
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> DatabaseError {
+    DatabaseError::ConnectionError(format!("Failed to {} {}: {}", action, path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::Database;
+
+    #[test]
+    fn test_working_copy_writes_back_and_detects_conflicts() {
+        let dir = std::env::temp_dir().join(format!("clerk-working-copy-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let remote = dir.join("vault.db");
+
+        // Creating through a working copy only touches the remote on write-back
+        let db = Database::open_working_copy(&remote, None).unwrap();
+        db.initialize().unwrap();
+        drop(db);
+        assert!(remote.exists());
+
+        let mut db = Database::open_working_copy(&remote, None).unwrap();
+        db.connection().execute("INSERT INTO projects (name, created_at, updated_at) VALUES ('app', 0, 0)", []).unwrap();
+        assert!(db.sync().unwrap());
+        assert!(!db.sync().unwrap());
+
+        // Someone else replaces the remote file while we hold a changed copy
+        db.connection().execute("INSERT INTO projects (name, created_at, updated_at) VALUES ('web', 0, 0)", []).unwrap();
+        fs::write(&remote, b"changed elsewhere").unwrap();
+        assert!(matches!(db.sync(), Err(DatabaseError::WorkingCopyConflict { .. })));
+        assert_eq!(fs::read(&remote).unwrap(), b"changed elsewhere");
+        drop(db);
+
+        // The conflicting changes stay in the working file, and the failed write-back is kept
+        assert!(local_path_for(&remote).exists());
+        assert!(matches!(crate::database::take_write_back_error(), Some(DatabaseError::WorkingCopyConflict { .. })));
+        assert!(crate::database::take_write_back_error().is_none());
+        let _ = fs::remove_file(local_path_for(&remote));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -4,31 +4,105 @@
 // - Windows: Windows Credential Manager
 // - macOS: macOS Keychain
 // - Linux: Secret Service API (gnome-keyring, KWallet)
+//
+// Headless Linux has no Secret Service, so an encrypted file can be opted
+// into as a fallback with CLERK_KEYCHAIN_FALLBACK=file.
 
 use keyring::Entry;
 use base64::{Engine as _, engine::general_purpose};
+use ring::digest::{digest, SHA256};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use crate::crypto::encryption::{decrypt, encrypt};
 
 const SERVICE_NAME: &str = "com.clerk.app";
 const USERNAME: &str = "clerk_user"; // Username for keychain entry
 
-/// Keychain manager for storing encryption keys securely
-pub struct KeychainManager;
+/// Environment variable that opts into the file fallback (`file`)
+pub const FALLBACK_ENV_VAR: &str = "CLERK_KEYCHAIN_FALLBACK";
 
-impl KeychainManager {
-    /// Create a new keychain manager
-    pub fn new() -> Self {
-        Self
+/// File the fallback backend keeps the key in, inside the app config directory
+const FALLBACK_FILE_NAME: &str = "keychain.key";
+
+/// AAD binding the fallback file's ciphertext to its purpose
+const FALLBACK_FILE_AAD: &[u8] = b"clerk-keychain-file-v1";
+
+/// Start of the message for `KeychainError::Locked`, for the GUI to match on
+pub const KEYCHAIN_LOCKED_ERROR: &str = "Your keychain is locked";
+
+/// Why a stored key could not be read (a missing entry is not an error)
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum KeychainError {
+    /// The entry exists but does not hold a valid 32-byte key
+    #[error("Stored key is unreadable: {0}")]
+    Decode(String),
+    
+    /// The keychain is there but locked and won't prompt (macOS login keychain, locked Secret Service collection)
+    #[error("{KEYCHAIN_LOCKED_ERROR}")]
+    Locked,
+    
+    /// The OS refused access (locked keychain, denied prompt, no secret service)
+    #[error("Keychain access denied: {0}")]
+    AccessDenied(String),
+    
+    /// Any other platform failure
+    #[error("Keychain error: {0}")]
+    Platform(String),
+}
+
+impl From<keyring::Error> for KeychainError {
+    fn from(err: keyring::Error) -> Self {
+        match err {
+            keyring::Error::NoStorageAccess(e) | keyring::Error::PlatformFailure(e) if is_locked_error(e.as_ref()) => KeychainError::Locked,
+            keyring::Error::NoStorageAccess(e) => KeychainError::AccessDenied(e.to_string()),
+            keyring::Error::BadEncoding(_) => KeychainError::Decode("entry is not valid UTF-8".to_string()),
+            e => KeychainError::Platform(e.to_string()),
+        }
     }
+}
 
-    /// Save encryption key to OS keychain
-    /// 
-    /// # Arguments
-    /// * `key` - 32-byte encryption key to store
-    /// 
-    /// # Returns
-    /// * `Ok(())` if key was saved successfully
-    /// * `Err(String)` if saving failed
-    pub fn save_key(&self, key: &[u8; 32]) -> Result<(), String> {
+/// Whether a platform error means the store is present but locked
+///
+/// keyring passes macOS's errSecInteractionNotAllowed (-25308) through as a
+/// generic platform failure, so the underlying error is inspected.
+fn is_locked_error(err: &(dyn std::error::Error + Send + Sync)) -> bool {
+    let description = format!("{} {:?}", err, err).to_ascii_lowercase();
+    description.contains("-25308") || description.contains("interaction is not allowed") || description.contains("locked")
+}
+
+/// Decode a base64 keychain entry into a 32-byte key
+fn decode_key(key_b64: &str) -> Result<[u8; 32], KeychainError> {
+    let key_bytes = general_purpose::STANDARD.decode(key_b64)
+        .map_err(|e| KeychainError::Decode(format!("invalid base64: {}", e)))?;
+    
+    key_bytes.as_slice()
+        .try_into()
+        .map_err(|_| KeychainError::Decode(format!("invalid key length: expected 32, got {}", key_bytes.len())))
+}
+
+/// Storage for the "Remember Me" key
+pub trait KeychainBackend {
+    /// Store `key`, replacing any key already stored
+    fn save_key(&self, key: &[u8; 32]) -> Result<(), String>;
+
+    /// Read the stored key (`Ok(None)` if there is none)
+    fn get_key(&self) -> Result<Option<[u8; 32]>, KeychainError>;
+
+    /// Remove the stored key (succeeds if there is none)
+    fn delete_key(&self) -> Result<(), String>;
+
+    /// Whether a readable key is stored
+    fn has_key(&self) -> bool {
+        matches!(self.get_key(), Ok(Some(_)))
+    }
+}
+
+/// The platform credential store, through the `keyring` crate
+pub struct OsKeychain;
+
+impl KeychainBackend for OsKeychain {
+    fn save_key(&self, key: &[u8; 32]) -> Result<(), String> {
         // Convert key to base64 for string storage
         let key_b64 = general_purpose::STANDARD.encode(key);
         
@@ -43,34 +117,13 @@ impl KeychainManager {
         Ok(())
     }
 
-    /// Retrieve encryption key from OS keychain
-    /// 
-    /// # Returns
-    /// * `Ok(Some([u8; 32]))` if key was found and decoded
-    /// * `Ok(None)` if no key is stored
-    /// * `Err(String)` if retrieval or decoding failed
-    pub fn get_key(&self) -> Result<Option<[u8; 32]>, String> {
+    fn get_key(&self) -> Result<Option<[u8; 32]>, KeychainError> {
         // Create keyring entry with same parameters as save
-        let entry = Entry::new(SERVICE_NAME, USERNAME)
-            .map_err(|e| format!("Failed to create keychain entry: {}", e))?;
+        let entry = Entry::new(SERVICE_NAME, USERNAME)?;
         
         // Try to get password
         match entry.get_password() {
-            Ok(key_b64) => {
-                // Decode from base64
-                let key_bytes = general_purpose::STANDARD.decode(&key_b64)
-                    .map_err(|e| format!("Failed to decode key: {}", e))?;
-                
-                // Convert to fixed-size array
-                if key_bytes.len() != 32 {
-                    return Err(format!("Invalid key length: expected 32, got {}", key_bytes.len()));
-                }
-                
-                let mut key = [0u8; 32];
-                key.copy_from_slice(&key_bytes);
-                
-                Ok(Some(key))
-            },
+            Ok(key_b64) => decode_key(&key_b64).map(Some),
             Err(keyring::Error::NoEntry) => {
                 // No key stored - this is OK
                 Ok(None)
@@ -78,17 +131,12 @@ impl KeychainManager {
             Err(e) => {
                 // Log the actual error for debugging
                 eprintln!("Keychain error: {:?}", e);
-                Err(format!("Failed to get key from keychain: {}", e))
+                Err(e.into())
             }
         }
     }
 
-    /// Delete encryption key from OS keychain
-    /// 
-    /// # Returns
-    /// * `Ok(())` if key was deleted or didn't exist
-    /// * `Err(String)` if deletion failed
-    pub fn delete_key(&self) -> Result<(), String> {
+    fn delete_key(&self) -> Result<(), String> {
         // Create keyring entry
         let entry = Entry::new(SERVICE_NAME, USERNAME)
             .map_err(|e| format!("Failed to create keychain entry: {}", e))?;
@@ -100,6 +148,211 @@ impl KeychainManager {
             Err(e) => Err(format!("Failed to delete key from keychain: {}", e))
         }
     }
+}
+
+/// An owner-only file holding the key encrypted under a machine-bound key
+///
+/// The wrapping key is derived from the machine id and user name, so the file
+/// is useless when copied to another machine, but anyone who can read it as
+/// this user on this machine can recover the key. This is weaker than an OS
+/// keychain and is only used when the user opts in.
+pub struct FileKeychain {
+    path: PathBuf,
+}
+
+impl FileKeychain {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    /// The fallback file in the app config directory (next to the default vault)
+    pub fn default_location() -> Result<Self, String> {
+        let config_dir = dirs::config_dir().ok_or("Failed to get config directory")?;
+        Ok(Self::new(config_dir.join(SERVICE_NAME).join(FALLBACK_FILE_NAME)))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl KeychainBackend for FileKeychain {
+    fn save_key(&self, key: &[u8; 32]) -> Result<(), String> {
+        let wrapping_key = machine_key().map_err(|e| e.to_string())?;
+        let sealed = encrypt(&wrapping_key, key, FALLBACK_FILE_AAD)
+            .map_err(|_| "Failed to encrypt key for the keychain file".to_string())?;
+        
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        write_owner_only(&self.path, general_purpose::STANDARD.encode(sealed).as_bytes())
+            .map_err(|e| format!("Failed to write keychain file {}: {}", self.path.display(), e))
+    }
+
+    fn get_key(&self) -> Result<Option<[u8; 32]>, KeychainError> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                return Err(KeychainError::AccessDenied(e.to_string()));
+            },
+            Err(e) => return Err(KeychainError::Platform(e.to_string())),
+        };
+        
+        let sealed = general_purpose::STANDARD.decode(contents.trim())
+            .map_err(|e| KeychainError::Decode(format!("invalid base64: {}", e)))?;
+        let key = decrypt(&machine_key()?, &sealed, FALLBACK_FILE_AAD)
+            .map_err(|_| KeychainError::Decode("keychain file was written on another machine or is corrupted".to_string()))?;
+        
+        key.as_slice()
+            .try_into()
+            .map(Some)
+            .map_err(|_| KeychainError::Decode(format!("invalid key length: expected 32, got {}", key.len())))
+    }
+
+    fn delete_key(&self) -> Result<(), String> {
+        match fs::remove_file(&self.path) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to delete keychain file {}: {}", self.path.display(), e)),
+        }
+    }
+}
+
+/// Key wrapping the fallback file: SHA-256 over the machine id and user name
+fn machine_key() -> Result<[u8; 32], KeychainError> {
+    let machine_id = ["/etc/machine-id", "/var/lib/dbus/machine-id", "/etc/hostname"]
+        .iter()
+        .filter_map(|path| fs::read_to_string(path).ok())
+        .map(|id| id.trim().to_string())
+        .find(|id| !id.is_empty())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .ok_or_else(|| KeychainError::Platform("no machine id found to bind the keychain file to".to_string()))?;
+    let user = std::env::var("USER").or_else(|_| std::env::var("USERNAME")).unwrap_or_default();
+    
+    let material = format!("{}\0{}\0{}", String::from_utf8_lossy(FALLBACK_FILE_AAD), machine_id, user);
+    let mut key = [0u8; 32];
+    key.copy_from_slice(digest(&SHA256, material.as_bytes()).as_ref());
+    Ok(key)
+}
+
+/// Write `contents` to `path`, readable and writable by the owner only
+fn write_owner_only(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    use std::io::Write;
+    
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // `mode` only applies on creation, so tighten a file that already existed
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    options.open(path)?.write_all(contents)
+}
+
+/// OS keychain first, with the file used whenever the OS keychain fails
+///
+/// A key saved to the file is only found again while the OS keychain keeps
+/// failing or has no key of its own, so `delete_key` clears both.
+struct FallbackKeychain {
+    primary: OsKeychain,
+    fallback: FileKeychain,
+}
+
+impl KeychainBackend for FallbackKeychain {
+    fn save_key(&self, key: &[u8; 32]) -> Result<(), String> {
+        match self.primary.save_key(key) {
+            Ok(()) => {
+                // Don't leave an older key behind in the file
+                let _ = self.fallback.delete_key();
+                Ok(())
+            },
+            Err(e) => {
+                log::warn!("OS keychain unavailable ({}), saving key to {}", e, self.fallback.path().display());
+                self.fallback.save_key(key)
+            }
+        }
+    }
+
+    fn get_key(&self) -> Result<Option<[u8; 32]>, KeychainError> {
+        match self.primary.get_key() {
+            Ok(Some(key)) => Ok(Some(key)),
+            Ok(None) | Err(KeychainError::AccessDenied(_)) | Err(KeychainError::Platform(_)) => self.fallback.get_key(),
+            // Nothing in the file either: the key is most likely in the locked keychain
+            Err(KeychainError::Locked) => self.fallback.get_key()?.map(Some).ok_or(KeychainError::Locked),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn delete_key(&self) -> Result<(), String> {
+        let fallback = self.fallback.delete_key();
+        // A keychain that can't be reached holds nothing to delete
+        let _ = self.primary.delete_key();
+        fallback
+    }
+}
+
+/// Keychain manager for storing encryption keys securely
+///
+/// Uses the OS keychain, falling back to a `FileKeychain` when
+/// `CLERK_KEYCHAIN_FALLBACK=file` is set and the OS keychain is unavailable.
+pub struct KeychainManager {
+    backend: Box<dyn KeychainBackend + Send + Sync>,
+}
+
+impl KeychainManager {
+    /// Create a new keychain manager for the backend selected by the environment
+    pub fn new() -> Self {
+        let fallback = std::env::var(FALLBACK_ENV_VAR).ok()
+            .filter(|value| value.eq_ignore_ascii_case("file"))
+            .and_then(|_| FileKeychain::default_location().ok());
+        
+        match fallback {
+            Some(fallback) => Self::with_backend(FallbackKeychain { primary: OsKeychain, fallback }),
+            None => Self::with_backend(OsKeychain),
+        }
+    }
+
+    /// Create a keychain manager that stores the key in `backend`
+    pub fn with_backend(backend: impl KeychainBackend + Send + Sync + 'static) -> Self {
+        Self { backend: Box::new(backend) }
+    }
+
+    /// Save encryption key to the keychain
+    /// 
+    /// # Arguments
+    /// * `key` - 32-byte encryption key to store
+    /// 
+    /// # Returns
+    /// * `Ok(())` if key was saved successfully
+    /// * `Err(String)` if saving failed
+    pub fn save_key(&self, key: &[u8; 32]) -> Result<(), String> {
+        self.backend.save_key(key)
+    }
+
+    /// Retrieve encryption key from the keychain
+    /// 
+    /// # Returns
+    /// * `Ok(Some([u8; 32]))` if key was found and decoded
+    /// * `Ok(None)` if no key is stored
+    /// * `Err(KeychainError)` classifying why the stored key could not be read
+    pub fn get_key(&self) -> Result<Option<[u8; 32]>, KeychainError> {
+        self.backend.get_key()
+    }
+
+    /// Delete encryption key from the keychain
+    /// 
+    /// # Returns
+    /// * `Ok(())` if key was deleted or didn't exist
+    /// * `Err(String)` if deletion failed
+    pub fn delete_key(&self) -> Result<(), String> {
+        self.backend.delete_key()
+    }
 
     /// Check if a key is stored in the keychain
     /// 
@@ -107,7 +360,7 @@ impl KeychainManager {
     /// * `true` if a key exists
     /// * `false` if no key is stored
     pub fn has_key(&self) -> bool {
-        matches!(self.get_key(), Ok(Some(_)))
+        self.backend.has_key()
     }
 }
 
@@ -158,6 +411,58 @@ mod tests {
         manager.delete_key().unwrap();
     }
 
+    #[test]
+    fn test_decode_key_classifies_bad_entries() {
+        let valid = general_purpose::STANDARD.encode([5u8; 32]);
+        assert_eq!(decode_key(&valid).unwrap(), [5u8; 32]);
+        
+        assert!(matches!(decode_key("not base64!"), Err(KeychainError::Decode(_))));
+        
+        let short = general_purpose::STANDARD.encode([5u8; 16]);
+        assert!(matches!(decode_key(&short), Err(KeychainError::Decode(_))));
+    }
+
+    #[test]
+    fn test_locked_keychain_errors_are_recognized() {
+        let platform = |message: &str| keyring::Error::PlatformFailure(Box::new(std::io::Error::other(message.to_string())));
+        
+        assert_eq!(KeychainError::from(platform("User interaction is not allowed.")), KeychainError::Locked);
+        assert_eq!(KeychainError::from(keyring::Error::NoStorageAccess(Box::new(std::io::Error::other("collection is locked")))), KeychainError::Locked);
+        assert!(matches!(KeychainError::from(platform("disk on fire")), KeychainError::Platform(_)));
+        assert!(KeychainError::Locked.to_string().starts_with(KEYCHAIN_LOCKED_ERROR));
+    }
+
+    #[test]
+    fn test_file_keychain_round_trip() {
+        let dir = std::env::temp_dir().join(format!("clerk-file-keychain-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let manager = KeychainManager::with_backend(FileKeychain::new(dir.join(FALLBACK_FILE_NAME)));
+        
+        assert_eq!(manager.get_key().unwrap(), None);
+        manager.save_key(&[9u8; 32]).unwrap();
+        assert_eq!(manager.get_key().unwrap(), Some([9u8; 32]));
+        
+        // The key is never written in the clear, and only the owner can read the file
+        let contents = fs::read_to_string(dir.join(FALLBACK_FILE_NAME)).unwrap();
+        assert!(!contents.contains(&general_purpose::STANDARD.encode([9u8; 32])));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(dir.join(FALLBACK_FILE_NAME)).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        
+        // A file sealed under a different machine key is reported, not returned
+        let foreign = encrypt(&[1u8; 32], &[9u8; 32], FALLBACK_FILE_AAD).unwrap();
+        fs::write(dir.join(FALLBACK_FILE_NAME), general_purpose::STANDARD.encode(foreign)).unwrap();
+        assert!(matches!(manager.get_key(), Err(KeychainError::Decode(_))));
+        
+        manager.delete_key().unwrap();
+        assert!(!manager.has_key());
+        manager.delete_key().unwrap();
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_get_nonexistent_key() {
         let manager = KeychainManager::new();
@@ -4,11 +4,19 @@ pub mod crypto;
 pub mod database;
 pub mod vault;
 pub mod keychain;
+pub mod config;
 
 use commands::database::DatabaseState;
+use std::sync::atomic::Ordering;
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+  let state = DatabaseState::new();
+  if std::env::args().any(|arg| arg == commands::vault::NO_KEYCHAIN_FLAG) {
+    state.auto_unlock_disabled.store(true, Ordering::SeqCst);
+  }
+
   tauri::Builder::default()
     .plugin(tauri_plugin_dialog::init())
     .plugin(tauri_plugin_shell::init())
@@ -20,9 +28,16 @@ pub fn run() {
             .build(),
         )?;
       }
+      commands::vault::spawn_keychain_monitor(app.handle().clone());
       Ok(())
     })
-    .manage(DatabaseState::new())
+    .manage(state)
+    .on_window_event(|window, event| {
+      // Lock as soon as the window is gone instead of waiting for process exit
+      if let tauri::WindowEvent::Destroyed = event {
+        commands::vault::lock_on_exit(&window.state::<DatabaseState>());
+      }
+    })
     .invoke_handler(tauri::generate_handler![
       commands::greet,
       commands::vault::create_vault,
@@ -30,8 +45,27 @@ pub fn run() {
       commands::vault::auto_unlock,
       commands::vault::lock_vault,
       commands::vault::check_vault_exists,
+      commands::vault::get_password_hint,
+      commands::vault::set_password_hint,
+      commands::vault::verify_current_password,
+      commands::vault::rotate_password,
+      commands::vault::repair_keychain,
+      commands::vault::reset_vault,
       commands::vault::get_lock_timeout,
       commands::vault::set_lock_timeout,
+      commands::vault::get_vault_metadata,
+      commands::vault::get_exit_lock_policy,
+      commands::vault::set_exit_lock_policy,
+      commands::vault::disable_auto_unlock,
+      commands::vault::is_auto_unlock_disabled,
+      commands::vault::get_nonce_mode,
+      commands::vault::set_nonce_mode,
+      commands::vault::get_description_encryption,
+      commands::vault::set_description_encryption,
+      commands::vault::get_per_value_keys,
+      commands::vault::set_per_value_keys,
+      commands::vault::get_quota,
+      commands::vault::set_quota,
       // Project commands
       commands::database::create_project,
       commands::database::get_projects,
@@ -42,16 +76,22 @@ pub fn run() {
       commands::database::get_environments,
       commands::database::update_environment,
       commands::database::delete_environment,
+      commands::database::clear_environment,
+      commands::database::reorder_environments,
       // Variable commands
       commands::database::create_variable,
       commands::database::get_variables,
       commands::database::update_variable,
       commands::database::delete_variable,
+      commands::database::set_variable_protected,
+      commands::database::suggest_keys,
       // Dashboard commands
       commands::database::get_dashboard_stats,
+      commands::database::compact_database,
       // Export/Import commands
       commands::export::export_env,
       commands::export::export_env_to_file,
+      commands::export::get_environment_as_json,
       commands::export::import_env,
       commands::export::read_file_content,
       commands::export::write_file_content,
@@ -59,6 +99,9 @@ pub fn run() {
       commands::audit::get_audit_logs,
       commands::audit::export_audit_logs_csv,
       commands::audit::export_audit_logs_json,
+      commands::audit::clear_audit_logs,
+      commands::audit::get_audit_enabled,
+      commands::audit::set_audit_enabled,
       // Backup commands
       commands::backup::create_backup,
       commands::backup::restore_backup,
@@ -69,6 +112,11 @@ pub fn run() {
       commands::system::add_cli_to_path,
       commands::system::remove_cli_from_path,
     ])
-    .run(tauri::generate_context!())
-    .expect("error while running tauri application");
+    .build(tauri::generate_context!())
+    .expect("error while building tauri application")
+    .run(|app, event| {
+      if let tauri::RunEvent::Exit = event {
+        commands::vault::lock_on_exit(&app.state::<DatabaseState>());
+      }
+    });
 }
@@ -1,18 +1,528 @@
 // Vault module - handles vault operations
+use ring::hmac;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use zeroize::Zeroizing;
 
-pub struct VaultManager;
+use crate::crypto::{derive_database_key, derive_key_with_params, derive_subkey, generate_salt, hash_password_with_params, verify_password, KdfParams};
+use crate::database::{Database, DatabaseError, SQLCIPHER_MISSING_ERROR, migrations::{self, update_last_accessed}, operations::{canary, variables::{reencrypt_all, upgrade_legacy_aad}}, sqlcipher_available};
+use crate::keychain::KeychainManager;
 
-impl VaultManager {
-    pub fn new() -> Self {
-        Self
-    }
+/// Phrase that must be typed exactly to reset (delete) a vault
+pub const RESET_CONFIRMATION: &str = "reset my vault";
+
+/// Files that make up a vault, relative to its directory
+const VAULT_FILES: &[&str] = &[METADATA_FILE, DATABASE_FILE, "vault.db-wal", "vault.db-shm", "vault.db-journal", REMOTE_MARKER];
+
+/// Marker file flagging a vault directory as remote (network share or synced folder)
+pub const REMOTE_MARKER: &str = ".clerk-remote";
+
+/// CLI session file name prefix (stored in the temp directory)
+const SESSION_FILE_PREFIX: &str = ".clerk_session";
+
+/// Metadata file of a vault (salt, password hash, KDF parameters)
+pub const METADATA_FILE: &str = "vault.clerk";
+
+/// Database file of a vault
+pub const DATABASE_FILE: &str = "vault.db";
+
+/// Start of the `VaultManager::unlock` error for a vault whose database is
+/// gone; the GUI matches on it to offer recreating the database
+pub const DATABASE_MISSING_ERROR: &str = "Vault database is missing";
+
+/// Error of `VaultManager::derive_key` (and everything built on it) for a wrong password
+pub const INVALID_PASSWORD_ERROR: &str = "Invalid password";
+
+/// Start of the error for metadata whose MAC doesn't match its fields
+pub const METADATA_TAMPERED_ERROR: &str = "Vault metadata failed its integrity check";
+
+/// Longest password hint accepted, in characters
+pub const MAX_HINT_LENGTH: usize = 120;
+
+/// HKDF info for the metadata MAC key
+const METADATA_MAC_INFO: &[u8] = b"clerk-metadata-mac-v1";
+
+/// Lifecycle of the vault in one directory: create, unlock, lock
+///
+/// Shared by the Tauri commands and the CLI so both read the metadata, derive
+/// the key and open the database the same way. Prompting, sessions and app
+/// state stay with the caller.
+pub struct VaultManager {
+    dir: PathBuf,
+    /// Open the database through a local working copy (remote vaults)
+    remote: bool,
 }
 
-impl Default for VaultManager {
-    fn default() -> Self {
-        Self::new()
+impl VaultManager {
+    /// Manage the vault in `dir`; it is treated as remote if it carries `REMOTE_MARKER`
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        let dir = dir.into();
+        let remote = is_remote(&dir);
+        Self { dir, remote }
+    }
+    
+    /// Also treat the vault as remote when `remote` is set (e.g. `clerk --remote`)
+    pub fn with_remote(mut self, remote: bool) -> Self {
+        self.remote |= remote;
+        self
+    }
+    
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    
+    pub fn metadata_path(&self) -> PathBuf {
+        self.dir.join(METADATA_FILE)
+    }
+    
+    pub fn database_path(&self) -> PathBuf {
+        self.dir.join(DATABASE_FILE)
+    }
+    
+    /// Whether the vault has been created (its metadata file exists)
+    pub fn exists(&self) -> bool {
+        self.metadata_path().exists()
+    }
+    
+    /// Read and parse the metadata file
+    pub fn metadata(&self) -> Result<VaultMetadata, String> {
+        let content = std::fs::read_to_string(self.metadata_path())
+            .map_err(|e| format!("Failed to read vault metadata: {}", e))?;
+        
+        serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse vault metadata: {}", e))
+    }
+    
+    /// Write new metadata (fresh salt and password hash) and derive its key
+    ///
+    /// Replaces any existing metadata file; `create` is the checked entry point.
+    pub fn write_metadata(&self, password: &str, kdf_params: KdfParams) -> Result<(VaultMetadata, [u8; 32]), String> {
+        let salt = generate_salt()
+            .map_err(|_| "Failed to generate salt".to_string())?;
+        
+        let password_hash = hash_password_with_params(password, &kdf_params)
+            .map_err(|e| format!("Failed to hash password: {}", e))?;
+        
+        let mut metadata = VaultMetadata {
+            version: 1,
+            salt: salt.to_vec(),
+            password_hash,
+            created_at: chrono::Utc::now().timestamp(),
+            kdf_params,
+            hint: None,
+            database_encrypted: false,
+            mac: None,
+        };
+        
+        let key = derive_key_with_params(password, &salt, &kdf_params)
+            .map_err(|e| format!("Failed to derive key: {}", e))?;
+        
+        metadata.mac = Some(metadata.compute_mac(&key)?);
+        self.save_metadata(&metadata)?;
+        
+        Ok((metadata, key))
+    }
+    
+    /// The password hint, readable without unlocking (`None` if unset or unreadable)
+    pub fn password_hint(&self) -> Option<String> {
+        self.metadata().ok()?.hint
+    }
+    
+    /// Set or remove (`None` or blank) the password hint
+    ///
+    /// The hint is covered by the metadata MAC, so `key` must be the vault key.
+    /// A hint that is the password itself is refused. Metadata written for a new
+    /// password (`write_metadata`) starts without a hint.
+    pub fn set_password_hint(&self, key: &[u8; 32], hint: Option<&str>) -> Result<(), String> {
+        let mut metadata = self.verified_metadata(key)?;
+        
+        let hint = hint.map(str::trim).filter(|hint| !hint.is_empty());
+        if let Some(hint) = hint {
+            if hint.chars().count() > MAX_HINT_LENGTH {
+                return Err(format!("Password hint is too long (at most {} characters)", MAX_HINT_LENGTH));
+            }
+            if verify_password(hint, &metadata.password_hash).unwrap_or(false) {
+                return Err("The hint must not be the password itself".to_string());
+            }
+        }
+        
+        metadata.hint = hint.map(str::to_string);
+        metadata.mac = Some(metadata.compute_mac(key)?);
+        self.save_metadata(&metadata)
+    }
+    
+    /// The metadata, checked against the MAC under `key` before a field is changed
+    fn verified_metadata(&self, key: &[u8; 32]) -> Result<VaultMetadata, String> {
+        let metadata = self.metadata()?;
+        if let Some(mac) = &metadata.mac {
+            if !metadata.mac_matches(key, mac)? {
+                return Err(format!("{}: {} does not match the unlocked vault", METADATA_TAMPERED_ERROR, METADATA_FILE));
+            }
+        }
+        Ok(metadata)
+    }
+    
+    fn save_metadata(&self, metadata: &VaultMetadata) -> Result<(), String> {
+        let metadata_json = serde_json::to_string_pretty(metadata)
+            .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+        
+        std::fs::write(self.metadata_path(), metadata_json)
+            .map_err(|e| format!("Failed to write vault file: {}", e))
+    }
+    
+    /// Create a new vault: metadata, an initialized database and its key canary
+    pub fn create(&self, password: &str, kdf_params: KdfParams) -> Result<(Database, VaultMetadata, [u8; 32]), String> {
+        std::fs::create_dir_all(&self.dir)
+            .map_err(|e| format!("Failed to create vault directory: {}", e))?;
+        
+        if self.exists() {
+            return Err(format!("Vault already exists at {}", self.dir.display()));
+        }
+        
+        let (metadata, key) = self.write_metadata(password, kdf_params)?;
+        
+        let db = self.open_database(&key)
+            .map_err(|e| format!("Failed to create database: {}", e))?;
+        db.initialize()
+            .map_err(|e| format!("Failed to initialize database: {}", e))?;
+        
+        // Store a canary so a wrong key is detected at unlock time
+        canary::store_key_canary(db.connection(), &key)
+            .map_err(|e| format!("Failed to store key canary: {}", e))?;
+        migrations::set_metadata_mac(db.connection())
+            .map_err(|e| format!("Failed to initialize database: {}", e))?;
+        
+        Ok((db, metadata, key))
+    }
+    
+    /// Check `password` against the metadata and derive the vault key
+    ///
+    /// Fails with `INVALID_PASSWORD_ERROR` when it doesn't match, and with
+    /// `METADATA_TAMPERED_ERROR` when the derived key doesn't reproduce the
+    /// metadata's MAC (e.g. the salt or KDF parameters were edited). Metadata
+    /// written before MACs existed is returned without one; `unlock` adds it.
+    pub fn derive_key(&self, password: &str) -> Result<(VaultMetadata, [u8; 32]), String> {
+        let metadata = self.metadata()?;
+        
+        if !verify_password(password, &metadata.password_hash)
+            .map_err(|e| format!("Failed to verify password: {}", e))? {
+            return Err(INVALID_PASSWORD_ERROR.to_string());
+        }
+        
+        let salt: [u8; 16] = metadata.salt.as_slice()
+            .try_into()
+            .map_err(|_| "Invalid salt length".to_string())?;
+        
+        let key = derive_key_with_params(password, &salt, &metadata.kdf_params)
+            .map_err(|e| format!("Failed to derive key: {}", e))?;
+        
+        if let Some(mac) = &metadata.mac {
+            if !metadata.mac_matches(&key, mac)? {
+                return Err(format!(
+                    "{}: {} was modified since it was written. Restore it from a backup; the vault was not opened.",
+                    METADATA_TAMPERED_ERROR, METADATA_FILE
+                ));
+            }
+        }
+        
+        Ok((metadata, key))
+    }
+    
+    /// Give legacy metadata its MAC, or refuse metadata whose MAC was stripped
+    ///
+    /// Only called once the key has passed the database's canary, so a MAC is
+    /// never computed from a wrong key. `vault_metadata.metadata_mac` records
+    /// that the metadata has carried a MAC, after which a missing one fails
+    /// with `METADATA_TAMPERED_ERROR`.
+    pub fn check_metadata_mac(&self, db: &Database, metadata: &mut VaultMetadata, key: &[u8; 32]) -> Result<(), String> {
+        let recorded = migrations::get_metadata_mac(db.connection())
+            .map_err(|e| format!("Failed to open vault: {}", e))?;
+        
+        match metadata.mac {
+            Some(_) if recorded => {}
+            None if recorded => {
+                return Err(format!(
+                    "{}: {} lost its MAC since it was written. Restore it from a backup; the vault was not opened.",
+                    METADATA_TAMPERED_ERROR, METADATA_FILE
+                ));
+            }
+            // Not fatal: a read-only vault stays usable, and the next unlock retries
+            Some(_) => {
+                let _ = migrations::set_metadata_mac(db.connection());
+            }
+            None => {
+                metadata.mac = Some(metadata.compute_mac(key)?);
+                if self.save_metadata(metadata).is_ok() {
+                    let _ = migrations::set_metadata_mac(db.connection());
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Open the database file (creating it if missing), through a working copy when remote
+    ///
+    /// `key` is the vault key; the SQLCipher key is derived from it when the
+    /// metadata says the file is encrypted.
+    pub fn open_database(&self, key: &[u8; 32]) -> Result<Database, DatabaseError> {
+        let cipher_key = self.database_cipher_key(key)?;
+        self.open_database_with(cipher_key.as_deref())
+    }
+    
+    /// SQLCipher key of the database file, or `None` while it is stored in plain text
+    pub fn database_cipher_key(&self, key: &[u8; 32]) -> Result<Option<Zeroizing<[u8; 32]>>, DatabaseError> {
+        // An unreadable vault.clerk must not open an encrypted database without its key
+        let metadata = self.metadata().map_err(DatabaseError::ConnectionError)?;
+        if !metadata.database_encrypted {
+            return Ok(None);
+        }
+        derive_database_key(key)
+            .map(Some)
+            .map_err(|_| DatabaseError::EncryptionError("Failed to derive the database key".to_string()))
+    }
+    
+    fn open_database_with(&self, cipher_key: Option<&[u8; 32]>) -> Result<Database, DatabaseError> {
+        if self.remote {
+            Database::open_working_copy(self.database_path(), cipher_key)
+        } else {
+            Database::open(self.database_path(), cipher_key)
+        }
+    }
+    
+    /// Encrypt the whole database file with SQLCipher, or decrypt it back to plain SQLite
+    ///
+    /// Values are encrypted either way; this also hides key names, projects and
+    /// the audit log. The database is exported to a new file under the target
+    /// key, which replaces `vault.db` before the metadata flag is written; if
+    /// that write fails the old file is put back. Returns whether anything
+    /// changed. Needs a build with the `sqlcipher` feature.
+    pub fn set_database_encryption(&self, key: &[u8; 32], enabled: bool) -> Result<bool, String> {
+        let mut metadata = self.verified_metadata(key)?;
+        if metadata.database_encrypted == enabled {
+            return Ok(false);
+        }
+        if !sqlcipher_available() {
+            return Err(format!("{}; rebuild with `--features sqlcipher` to change database encryption", SQLCIPHER_MISSING_ERROR));
+        }
+        if !self.database_path().exists() {
+            return Err(format!("{} ({} was deleted or moved).", DATABASE_MISSING_ERROR, DATABASE_FILE));
+        }
+        
+        let database_key = derive_database_key(key)
+            .map_err(|_| "Failed to derive the database key".to_string())?;
+        let (current, target) = if enabled { (None, Some(&*database_key)) } else { (Some(&*database_key), None) };
+        
+        let staged = self.database_path().with_extension("convert");
+        let _ = std::fs::remove_file(&staged);
+        let exported = self.open_database_with(current).and_then(|db| {
+            db.validate()?;
+            canary::verify_key(db.connection(), key)?;
+            db.export_to(&staged, target)
+        });
+        if let Err(e) = exported {
+            let _ = std::fs::remove_file(&staged);
+            return Err(format!("Failed to convert the database, nothing was changed: {}", e));
+        }
+        
+        let previous = self.database_path().with_extension("pre-convert");
+        let _ = std::fs::remove_file(&previous);
+        let replaced = std::fs::rename(self.database_path(), &previous)
+            .and_then(|_| std::fs::rename(&staged, self.database_path()).inspect_err(|_| {
+                let _ = std::fs::rename(&previous, self.database_path());
+            }));
+        if let Err(e) = replaced {
+            let _ = std::fs::remove_file(&staged);
+            return Err(format!("Failed to replace {}, nothing was changed: {}", DATABASE_FILE, e));
+        }
+        
+        metadata.database_encrypted = enabled;
+        metadata.mac = Some(metadata.compute_mac(key)?);
+        if let Err(e) = self.save_metadata(&metadata) {
+            let _ = std::fs::remove_file(self.database_path());
+            let _ = std::fs::rename(&previous, self.database_path());
+            return Err(format!("{}; the previous database was put back", e));
+        }
+        
+        // The old file holds everything in the old form, so it must not linger
+        std::fs::remove_file(&previous)
+            .map_err(|e| format!("Converted the database but failed to delete {}: {}", previous.display(), e))?;
+        Ok(true)
+    }
+    
+    /// Open the database with a key, migrate it and check the key against its canary
+    ///
+    /// `password_verified` marks a key freshly derived from the master password,
+    /// which may seed a missing canary. Records the access and upgrades values
+    /// still using the legacy AAD format (failure there is not fatal: they stay
+    /// readable and the upgrade is retried next time).
+    pub fn open_with_key(&self, key: &[u8; 32], password_verified: bool) -> Result<Database, DatabaseError> {
+        let db = self.open_database(key)?;
+        db.validate()?;
+        db.initialize()?;
+        
+        canary::verify_key(db.connection(), key)?;
+        
+        if password_verified {
+            canary::ensure_key_canary(db.connection(), key)?;
+        }
+        
+        let _ = update_last_accessed(db.connection());
+        let _ = upgrade_legacy_aad(db.connection(), key);
+        
+        Ok(db)
+    }
+    
+    /// Verify `password` and open the database with the derived key
+    ///
+    /// Opening a missing database would silently create an empty vault, so that
+    /// fails with `DATABASE_MISSING_ERROR` unless `create_missing_database` is set.
+    pub fn unlock(&self, password: &str, create_missing_database: bool) -> Result<(Database, VaultMetadata, [u8; 32]), String> {
+        let (mut metadata, key) = self.derive_key(password)?;
+        
+        if !create_missing_database && !self.database_path().exists() {
+            return Err(format!("{} ({} was deleted or moved).", DATABASE_MISSING_ERROR, DATABASE_FILE));
+        }
+        
+        let db = self.open_with_key(&key, true)
+            .map_err(|e| match e {
+                DatabaseError::KeyMismatch => "Password is correct but does not decrypt this vault's data. The vault files may be out of sync.".to_string(),
+                e => format!("Failed to open vault: {}", e),
+            })?;
+        self.check_metadata_mac(&db, &mut metadata, &key)?;
+        
+        Ok((db, metadata, key))
+    }
+    
+    /// Give the vault a new salt (so a new key) for the same password and re-encrypt every value
+    ///
+    /// See `change_password`, which this calls with `password` as both passwords.
+    pub fn rekey(&self, password: &str) -> Result<(Database, VaultMetadata, [u8; 32], usize), String> {
+        self.change_password(password, password)
+    }
+    
+    /// Switch the vault to `new_password` with a new salt, re-encrypting every value
+    ///
+    /// `old_password` is verified first, and a password hint that is the new
+    /// password is refused. Both files are copied aside first. The new metadata
+    /// is staged in a temp file, the values are re-encrypted in one transaction,
+    /// and only then is the metadata renamed into place; if that rename fails the
+    /// database is restored from the copy. The copies are deleted once both files agree again, since
+    /// they would keep the old key usable. An encrypted database file is rekeyed
+    /// to the new key's SQLCipher key as well. Returns the database, the new metadata
+    /// and key, and the number of values re-encrypted. Sessions and keychain
+    /// entries still hold the old key; call `lock` afterwards.
+    pub fn change_password(&self, old_password: &str, new_password: &str) -> Result<(Database, VaultMetadata, [u8; 32], usize), String> {
+        // Unlocking also checks the metadata's MAC against the database
+        let (db, old_metadata, old_key) = self.unlock(old_password, false)?;
+        drop(db);
+        
+        let salt = generate_salt()
+            .map_err(|_| "Failed to generate salt".to_string())?;
+        let password_hash = hash_password_with_params(new_password, &old_metadata.kdf_params)
+            .map_err(|e| format!("Failed to hash password: {}", e))?;
+        // The hint is kept, so it must not give the new password away either
+        if let Some(hint) = &old_metadata.hint {
+            if verify_password(hint, &password_hash).unwrap_or(false) {
+                return Err("The password hint is the new password; change or clear the hint first".to_string());
+            }
+        }
+        let new_key = derive_key_with_params(new_password, &salt, &old_metadata.kdf_params)
+            .map_err(|e| format!("Failed to derive key: {}", e))?;
+        let mut metadata = VaultMetadata {
+            salt: salt.to_vec(),
+            password_hash,
+            mac: None,
+            ..old_metadata
+        };
+        metadata.mac = Some(metadata.compute_mac(&new_key)?);
+        
+        let snapshot = |path: PathBuf| path.with_extension("pre-rekey");
+        for path in [self.metadata_path(), self.database_path()] {
+            std::fs::copy(&path, snapshot(path.clone()))
+                .map_err(|e| format!("Failed to snapshot {} before rekeying: {}", path.display(), e))?;
+        }
+        let remove_snapshots = || {
+            let _ = std::fs::remove_file(snapshot(self.metadata_path()));
+            let _ = std::fs::remove_file(snapshot(self.database_path()));
+        };
+        
+        let staged_metadata = self.metadata_path().with_extension("rekey");
+        let staged = serde_json::to_string_pretty(&metadata)
+            .map_err(|e| format!("Failed to serialize metadata: {}", e))
+            .and_then(|json| std::fs::write(&staged_metadata, json).map_err(|e| format!("Failed to write new metadata: {}", e)));
+        if let Err(e) = staged {
+            remove_snapshots();
+            return Err(e);
+        }
+        
+        // A remote vault's working copy is written back before the metadata moves
+        let reencrypted = self.open_with_key(&old_key, true).and_then(|mut db| {
+            let count = reencrypt_all(db.connection(), &old_key, &new_key)?;
+            if metadata.database_encrypted {
+                let database_key = derive_database_key(&new_key)
+                    .map_err(|_| DatabaseError::EncryptionError("Failed to derive the database key".to_string()))?;
+                db.rekey_cipher(&database_key)?;
+            }
+            db.sync()?;
+            Ok((db, count))
+        });
+        let (db, count) = match reencrypted {
+            Ok(result) => result,
+            Err(e) if metadata.database_encrypted => {
+                // The values' transaction may have committed before the file rekey failed
+                let restored = std::fs::copy(snapshot(self.database_path()), self.database_path());
+                let _ = std::fs::remove_file(&staged_metadata);
+                return Err(match restored {
+                    Ok(_) => {
+                        remove_snapshots();
+                        format!("Failed to re-encrypt the vault, so the database was restored and nothing changed: {}", e)
+                    }
+                    Err(restore_error) => format!(
+                        "Failed to re-encrypt the vault ({}) and to restore the database ({}). Restore it from the .pre-rekey copy in {}",
+                        e, restore_error, self.dir.display()
+                    ),
+                });
+            }
+            Err(e) => {
+                // The transaction rolled back, so the old files are still consistent
+                let _ = std::fs::remove_file(&staged_metadata);
+                remove_snapshots();
+                return Err(format!("Failed to re-encrypt the vault, nothing was changed: {}", e));
+            }
+        };
+        
+        if let Err(e) = std::fs::rename(&staged_metadata, self.metadata_path()) {
+            drop(db);
+            let restored = std::fs::copy(snapshot(self.database_path()), self.database_path());
+            let _ = std::fs::remove_file(&staged_metadata);
+            return Err(match restored {
+                Ok(_) => {
+                    remove_snapshots();
+                    format!("Failed to replace {}, so the database was restored and nothing changed: {}", METADATA_FILE, e)
+                }
+                Err(restore_error) => format!(
+                    "Failed to replace {} ({}) and to restore the database ({}). Restore both from the .pre-rekey copies in {}",
+                    METADATA_FILE, e, restore_error, self.dir.display()
+                ),
+            });
+        }
+        
+        remove_snapshots();
+        Ok((db, metadata, new_key, count))
+    }
+    
+    /// Remove cached copies of the key outside the process: with `end_session`
+    /// the CLI session, with `forget_keychain` the "Remember Me" keychain entry
+    pub fn lock(&self, end_session: bool, forget_keychain: bool) -> Result<(), String> {
+        if end_session {
+            let _ = std::fs::remove_file(session_file_path(&self.dir));
+        }
+        
+        if forget_keychain {
+            KeychainManager::new().delete_key()
+                .map_err(|e| format!("Failed to clear keychain: {}", e))?;
+        }
+        Ok(())
     }
 }
 
@@ -23,6 +533,55 @@ pub struct VaultMetadata {
     pub salt: Vec<u8>,
     pub password_hash: String,
     pub created_at: i64,
+    /// Argon2id parameters the vault key was derived with
+    #[serde(default)]
+    pub kdf_params: KdfParams,
+    /// Reminder shown on the unlock screen; stored in plain text, so it must not reveal the password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+    /// Whether `vault.db` is encrypted with SQLCipher under a key derived from the vault key
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub database_encrypted: bool,
+    /// HMAC-SHA256 of the fields above, keyed from the vault key (`None` in
+    /// metadata written before it was added)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mac: Option<Vec<u8>>,
+}
+
+impl VaultMetadata {
+    /// MAC over every field except `mac`, under a key derived from the vault key
+    ///
+    /// Editing any field changes either the MAC input or the key derived from
+    /// the password, so only someone who knows the password can forge it.
+    fn compute_mac(&self, key: &[u8; 32]) -> Result<Vec<u8>, String> {
+        Ok(hmac::sign(&metadata_mac_key(key)?, &self.mac_input()?).as_ref().to_vec())
+    }
+    
+    /// Compare `mac` with the expected MAC in constant time
+    fn mac_matches(&self, key: &[u8; 32], mac: &[u8]) -> Result<bool, String> {
+        Ok(hmac::verify(&metadata_mac_key(key)?, &self.mac_input()?, mac).is_ok())
+    }
+    
+    /// The hint and the encryption flag are only appended when set, so MACs
+    /// written before they existed still verify
+    fn mac_input(&self) -> Result<Vec<u8>, String> {
+        let fields = (self.version, &self.salt, &self.password_hash, self.created_at, &self.kdf_params);
+        let mut input = match &self.hint {
+            None => serde_json::to_vec(&fields),
+            Some(hint) => serde_json::to_vec(&(fields, hint)),
+        }
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+        if self.database_encrypted {
+            input.extend_from_slice(b"database_encrypted");
+        }
+        Ok(input)
+    }
+}
+
+fn metadata_mac_key(key: &[u8; 32]) -> Result<hmac::Key, String> {
+    let derived = derive_subkey(key, METADATA_MAC_INFO)
+        .map_err(|_| "Failed to derive the metadata MAC key".to_string())?;
+    Ok(hmac::Key::new(hmac::HMAC_SHA256, derived.as_ref()))
 }
 
 /// Get the default vault directory
@@ -42,3 +601,268 @@ pub fn get_vault_directory() -> Result<PathBuf, String> {
     
     Ok(vault_dir)
 }
+
+/// Whether `vault_dir` is flagged remote, so its database should be opened as a working copy
+pub fn is_remote(vault_dir: &Path) -> bool {
+    vault_dir.join(REMOTE_MARKER).exists()
+}
+
+/// Path of the CLI session file caching the key for `vault_dir`
+pub fn session_file_path(vault_dir: &Path) -> PathBuf {
+    // Use vault directory hash to create unique session file
+    use std::collections::hash_map::DefaultHasher;
+    use std::hash::{Hash, Hasher};
+    
+    let mut hasher = DefaultHasher::new();
+    vault_dir.to_string_lossy().hash(&mut hasher);
+    let hash = hasher.finish();
+    
+    std::env::temp_dir().join(format!("{}-{:x}", SESSION_FILE_PREFIX, hash))
+}
+
+/// Delete a vault's files and its CLI session, returning the paths removed
+///
+/// The keychain entry is left to the caller, since only the GUI's vault uses it.
+pub fn delete_vault_files(vault_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut removed = Vec::new();
+    
+    let session_file = session_file_path(vault_dir);
+    let paths = VAULT_FILES.iter().map(|name| vault_dir.join(name)).chain(std::iter::once(session_file));
+    
+    for path in paths {
+        if path.exists() {
+            std::fs::remove_file(&path)
+                .map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+            removed.push(path);
+        }
+    }
+    
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Cheapest accepted KDF parameters, to keep the tests fast
+    const TEST_KDF: KdfParams = KdfParams { memory_kib: KdfParams::MIN_MEMORY_KIB, iterations: 1, parallelism: 1 };
+    
+    #[test]
+    fn test_vault_manager_lifecycle() {
+        let dir = std::env::temp_dir().join(format!("clerk-vault-manager-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = VaultManager::new(&dir);
+        assert!(!manager.exists());
+        
+        let (db, metadata, key) = manager.create("correct horse", TEST_KDF).unwrap();
+        drop(db);
+        assert!(manager.exists());
+        assert_eq!(manager.metadata().unwrap().password_hash, metadata.password_hash);
+        assert!(manager.create("correct horse", TEST_KDF).err().unwrap().contains("already exists"));
+        
+        assert_eq!(manager.unlock("wrong password", false).err().as_deref(), Some("Invalid password"));
+        let (_, _, unlocked_key) = manager.unlock("correct horse", false).unwrap();
+        assert_eq!(unlocked_key, key);
+        
+        // A lost database is only recreated when asked to
+        std::fs::remove_file(manager.database_path()).unwrap();
+        assert!(manager.unlock("correct horse", false).err().unwrap().starts_with(DATABASE_MISSING_ERROR));
+        manager.unlock("correct horse", true).unwrap();
+        
+        // A key that doesn't match the canary is refused
+        assert!(matches!(manager.open_with_key(&[0u8; 32], false), Err(DatabaseError::KeyMismatch)));
+        
+        // Locking removes the CLI session file for this vault and no other
+        let other = dir.join("other");
+        std::fs::write(session_file_path(&dir), "session").unwrap();
+        std::fs::write(session_file_path(&other), "session").unwrap();
+        manager.lock(false, false).unwrap();
+        assert!(session_file_path(&dir).exists());
+        manager.lock(true, false).unwrap();
+        assert!(!session_file_path(&dir).exists());
+        assert!(session_file_path(&other).exists());
+        let _ = std::fs::remove_file(session_file_path(&other));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_rekey_changes_salt_and_key_but_not_values() {
+        let dir = std::env::temp_dir().join(format!("clerk-rekey-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = VaultManager::new(&dir);
+        let (db, original, old_key) = manager.create("correct horse", TEST_KDF).unwrap();
+        let env = crate::database::operations::environments::ensure_project_environment(db.connection(), "app", "dev").unwrap().0;
+        let id = crate::database::operations::variables::create_variable_encrypted(db.connection(), env, "TOKEN".to_string(), "value".to_string(), None, &old_key).unwrap();
+        drop(db);
+        
+        assert!(manager.rekey("wrong password").is_err());
+        let (db, metadata, new_key, count) = manager.rekey("correct horse").unwrap();
+        assert_eq!(count, 1);
+        assert_ne!(metadata.salt, original.salt);
+        assert_ne!(new_key, old_key);
+        assert_eq!(crate::database::operations::variables::get_variable_decrypted(db.connection(), id, &new_key).unwrap().value.expose(), "value");
+        drop(db);
+        
+        // Same password, new key; the old key no longer opens the vault
+        let (_, _, unlocked_key) = manager.unlock("correct horse", false).unwrap();
+        assert_eq!(unlocked_key, new_key);
+        assert!(matches!(manager.open_with_key(&old_key, false), Err(DatabaseError::KeyMismatch)));
+        assert!(!manager.database_path().with_extension("pre-rekey").exists());
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_change_password_switches_password_and_keeps_values() {
+        let dir = std::env::temp_dir().join(format!("clerk-change-password-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = VaultManager::new(&dir);
+        let (db, _, old_key) = manager.create("correct horse", TEST_KDF).unwrap();
+        let env = crate::database::operations::environments::ensure_project_environment(db.connection(), "app", "dev").unwrap().0;
+        let id = crate::database::operations::variables::create_variable_encrypted(db.connection(), env, "TOKEN".to_string(), "value".to_string(), None, &old_key).unwrap();
+        drop(db);
+        
+        // A wrong current password changes nothing
+        assert!(manager.change_password("wrong password", "battery staple").is_err());
+        assert!(manager.unlock("correct horse", false).is_ok());
+        
+        // Nor does a new password that the kept hint would give away
+        manager.set_password_hint(&old_key, Some("battery staple")).unwrap();
+        assert!(manager.change_password("correct horse", "battery staple").err().unwrap().contains("hint is the new password"));
+        manager.set_password_hint(&old_key, None).unwrap();
+        
+        let (db, _, new_key, count) = manager.change_password("correct horse", "battery staple").unwrap();
+        assert_eq!(count, 1);
+        drop(db);
+        
+        assert_eq!(manager.unlock("correct horse", false).err().as_deref(), Some("Invalid password"));
+        let (db, _, unlocked_key) = manager.unlock("battery staple", false).unwrap();
+        assert_eq!(unlocked_key, new_key);
+        assert_eq!(crate::database::operations::variables::get_variable_decrypted(db.connection(), id, &new_key).unwrap().value.expose(), "value");
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_old_metadata_without_kdf_params_still_parses() {
+        // vault.clerk as written before the KDF parameters were stored
+        let json = r#"{"version":1,"salt":[1,2,3],"password_hash":"$argon2id$v=19$stub","created_at":1700000000}"#;
+        let metadata: VaultMetadata = serde_json::from_str(json).unwrap();
+        assert_eq!(metadata.version, 1);
+        assert_eq!(metadata.salt, vec![1, 2, 3]);
+        assert_eq!(metadata.kdf_params, KdfParams::default());
+        assert!(metadata.mac.is_none());
+    }
+    
+    #[test]
+    fn test_metadata_mac_detects_tampering() {
+        let dir = std::env::temp_dir().join(format!("clerk-metadata-mac-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = VaultManager::new(&dir);
+        let (db, original, _) = manager.create("correct horse", TEST_KDF).unwrap();
+        drop(db);
+        assert!(original.mac.is_some());
+        
+        // Stripping the MAC of a vault that had one is tampering too
+        let mut metadata = manager.metadata().unwrap();
+        metadata.mac = None;
+        manager.save_metadata(&metadata).unwrap();
+        assert!(manager.derive_key("correct horse").is_ok());
+        assert!(manager.unlock("correct horse", false).err().unwrap().starts_with(METADATA_TAMPERED_ERROR));
+        
+        // Metadata from before MACs existed gets one when unlocking has checked the key
+        let db = manager.open_database(&manager.derive_key("correct horse").unwrap().1).unwrap();
+        db.connection().execute("UPDATE vault_metadata SET metadata_mac = 0", []).unwrap();
+        drop(db);
+        manager.derive_key("correct horse").unwrap();
+        assert!(manager.metadata().unwrap().mac.is_none());
+        manager.unlock("correct horse", false).unwrap();
+        assert_eq!(manager.metadata().unwrap().mac, original.mac);
+        
+        // Weakened KDF parameters no longer match the MAC
+        let mut metadata = manager.metadata().unwrap();
+        metadata.kdf_params.memory_kib += 1024;
+        manager.save_metadata(&metadata).unwrap();
+        assert!(manager.derive_key("correct horse").err().unwrap().starts_with(METADATA_TAMPERED_ERROR));
+        
+        // Unreadable metadata can't say whether the database is encrypted, so nothing opens
+        std::fs::write(manager.metadata_path(), "not json").unwrap();
+        assert!(matches!(manager.open_database(&[0u8; 32]), Err(DatabaseError::ConnectionError(_))));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_password_hint_is_maced_and_never_the_password() {
+        let dir = std::env::temp_dir().join(format!("clerk-hint-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = VaultManager::new(&dir);
+        let (db, _, key) = manager.create("correct horse", TEST_KDF).unwrap();
+        drop(db);
+        assert_eq!(manager.password_hint(), None);
+        
+        assert!(manager.set_password_hint(&key, Some(" correct horse ")).err().unwrap().contains("must not be the password"));
+        assert!(manager.set_password_hint(&key, Some(&"x".repeat(MAX_HINT_LENGTH + 1))).is_err());
+        assert!(manager.set_password_hint(&[0u8; 32], Some("stable animal")).is_err());
+        
+        manager.set_password_hint(&key, Some("stable animal")).unwrap();
+        assert_eq!(manager.password_hint().as_deref(), Some("stable animal"));
+        manager.derive_key("correct horse").unwrap();
+        
+        // An edited hint fails the integrity check like any other field
+        let mut metadata = manager.metadata().unwrap();
+        metadata.hint = Some("call this number".to_string());
+        manager.save_metadata(&metadata).unwrap();
+        assert!(manager.derive_key("correct horse").err().unwrap().starts_with(METADATA_TAMPERED_ERROR));
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    
+    #[test]
+    fn test_database_encryption_round_trip() {
+        use crate::database::operations::{environments, variables};
+        
+        let dir = std::env::temp_dir().join(format!("clerk-database-encryption-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let manager = VaultManager::new(&dir);
+        let (db, _, key) = manager.create("correct horse", TEST_KDF).unwrap();
+        let env = environments::ensure_project_environment(db.connection(), "app", "dev").unwrap().0;
+        let id = variables::create_variable_encrypted(db.connection(), env, "TOKEN".to_string(), "value".to_string(), None, &key).unwrap();
+        drop(db);
+        let is_plain_sqlite = |manager: &VaultManager| std::fs::read(manager.database_path()).unwrap().starts_with(b"SQLite format 3");
+        
+        // Builds without SQLCipher refuse and leave the vault as it was
+        if !sqlcipher_available() {
+            assert!(manager.set_database_encryption(&key, true).err().unwrap().starts_with(SQLCIPHER_MISSING_ERROR));
+            assert!(!manager.metadata().unwrap().database_encrypted && is_plain_sqlite(&manager));
+            let _ = std::fs::remove_dir_all(&dir);
+            return;
+        }
+        
+        assert!(manager.set_database_encryption(&[0u8; 32], true).is_err());
+        assert!(manager.set_database_encryption(&key, true).unwrap());
+        assert!(!manager.set_database_encryption(&key, true).unwrap());
+        assert!(!is_plain_sqlite(&manager));
+        assert!(!manager.database_path().with_extension("pre-convert").exists());
+        assert!(Database::new(manager.database_path()).unwrap().validate().is_err());
+        
+        let (db, _, _) = manager.unlock("correct horse", false).unwrap();
+        assert_eq!(variables::get_variable_decrypted(db.connection(), id, &key).unwrap().value.expose(), "value");
+        drop(db);
+        
+        // Rekeying moves the file over to the new key's database key
+        let (db, _, new_key, _) = manager.rekey("correct horse").unwrap();
+        drop(db);
+        let (db, _, _) = manager.unlock("correct horse", false).unwrap();
+        assert_eq!(variables::get_variable_decrypted(db.connection(), id, &new_key).unwrap().value.expose(), "value");
+        drop(db);
+        
+        assert!(manager.set_database_encryption(&new_key, false).unwrap());
+        assert!(is_plain_sqlite(&manager));
+        let (db, _, _) = manager.unlock("correct horse", false).unwrap();
+        assert_eq!(variables::get_variable_decrypted(db.connection(), id, &new_key).unwrap().value.expose(), "value");
+        
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
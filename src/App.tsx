@@ -15,8 +15,12 @@ interface UnlockVaultResponse {
   success: boolean;
 }
 
+// Prefix of the auto_unlock error for a keychain that is present but locked
+const KEYCHAIN_LOCKED_ERROR = 'Your keychain is locked';
+
 function App(): React.ReactElement {
   const [appState, setAppState] = useState<AppState>('loading');
+  const [unlockNotice, setUnlockNotice] = useState('');
 
   useEffect(() => {
     checkVaultStatus();
@@ -43,6 +47,10 @@ function App(): React.ReactElement {
         }
       } catch (error) {
         console.log('Auto-unlock not available:', error);
+        // Most failures just mean "no Remember Me"; a locked keychain is worth telling the user about
+        if (typeof error === 'string' && error.startsWith(KEYCHAIN_LOCKED_ERROR)) {
+          setUnlockNotice(error);
+        }
         setAppState('unlock');
       }
     } catch (error) {
@@ -77,7 +85,7 @@ function App(): React.ReactElement {
 
         {appState === 'create' && <VaultCreation onVaultCreated={handleVaultCreated} />}
 
-        {appState === 'unlock' && <VaultUnlock onVaultUnlocked={handleVaultUnlocked} />}
+        {appState === 'unlock' && <VaultUnlock onVaultUnlocked={handleVaultUnlocked} notice={unlockNotice} />}
 
         {appState === 'main' && <VaultDashboard onLock={handleLock} />}
       </ToastProvider>
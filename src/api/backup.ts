@@ -30,12 +30,17 @@ export async function createBackup(): Promise<BackupFile> {
 
 /**
  * Restores a backup to the specified vault and database paths
+ *
+ * Fails while the vault is unlocked unless forceLock is set; the vault is
+ * locked afterwards either way and must be unlocked again.
  */
 export async function restoreBackup(
-  backupJson: string
+  backupJson: string,
+  forceLock = false
 ): Promise<string> {
   return await invoke<string>('restore_backup', {
     backupJson,
+    forceLock,
   });
 }
 
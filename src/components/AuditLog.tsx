@@ -69,6 +69,9 @@ export const AuditLog: React.FC<AuditLogProps> = ({ onClose }) => {
   const [filterEntityType, setFilterEntityType] = useState<string>('');
   const [filterOperationType, setFilterOperationType] = useState<string>('');
   const [showFilters, setShowFilters] = useState(false);
+  const [showClear, setShowClear] = useState(false);
+  const [clearPassword, setClearPassword] = useState('');
+  const [clearing, setClearing] = useState(false);
   
   // New states for custom dropdowns
   const [isEntityTypeOpen, setIsEntityTypeOpen] = useState(false);
@@ -159,6 +162,24 @@ export const AuditLog: React.FC<AuditLogProps> = ({ onClose }) => {
   };
 
 
+  // The backend asks for the master password again before wiping the log
+  const clearLogs = async (e: React.FormEvent) => {
+    e.preventDefault();
+    try {
+      setClearing(true);
+      const removed = await invoke<number>('clear_audit_logs', { password: clearPassword });
+      setShowClear(false);
+      setCurrentPage(1);
+      await loadLogs();
+      alert(`Cleared ${removed} audit log entries`);
+    } catch (error) {
+      alert(`Failed to clear audit logs: ${error}`);
+    } finally {
+      setClearPassword('');
+      setClearing(false);
+    }
+  };
+
   const hasActiveFilters = filterEntityType || filterOperationType;
 
   return (
@@ -185,9 +206,33 @@ export const AuditLog: React.FC<AuditLogProps> = ({ onClose }) => {
             <button className="audit-log-btn-secondary" onClick={() => exportLogs('json')} title="Export as JSON">
               <Braces size={16} /> Export JSON
             </button>
+            <button className="audit-log-btn-secondary" onClick={() => setShowClear(!showClear)} title="Delete all audit log entries">
+              <Trash2 size={16} /> Clear Log
+            </button>
           </div>
         </div>
 
+        {showClear && (
+          <form className="audit-log-filters-panel" onSubmit={clearLogs}>
+            <div className="audit-log-filter-group">
+              <label htmlFor="audit-clear-password">Delete every entry? Enter your master password:</label>
+              <input
+                id="audit-clear-password"
+                type="password"
+                value={clearPassword}
+                onChange={(e) => setClearPassword(e.target.value)}
+                autoFocus
+              />
+            </div>
+            <button type="submit" className="audit-log-btn-primary" disabled={!clearPassword || clearing}>
+              {clearing ? 'Clearing...' : 'Clear Audit Log'}
+            </button>
+            <button type="button" className="audit-log-clear-filters-button" onClick={() => { setShowClear(false); setClearPassword(''); }}>
+              Cancel
+            </button>
+          </form>
+        )}
+
         {showFilters && (
           <div className="audit-log-filters-panel">
             {/* --- CUSTOM DROPDOWN FOR ENTITY TYPE --- */}
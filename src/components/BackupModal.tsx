@@ -4,6 +4,7 @@ import { save } from '@tauri-apps/plugin-dialog';
 import { invoke } from '@tauri-apps/api/core';
 import { createBackup, saveBackupToFile } from '../api/backup';
 import { generateBackupFilename, estimateBackupSize } from '../utils/backup';
+import type { DashboardStats } from '../types/database';
 import './BackupModal.css';
 
 interface BackupModalProps {
@@ -11,12 +12,6 @@ interface BackupModalProps {
   onClose: () => void;
 }
 
-interface DashboardStats {
-  project_count: number;
-  environment_count: number;
-  variable_count: number;
-}
-
 type BackupStatus = 'idle' | 'creating' | 'saving' | 'success' | 'error';
 
 export const BackupModal: React.FC<BackupModalProps> = ({
@@ -5,14 +5,18 @@ import type {
   GetEnvironmentsRequest, 
   GetEnvironmentsResponse,
   DeleteEnvironmentRequest,
-  DeleteEnvironmentResponse 
+  DeleteEnvironmentResponse,
+  ClearEnvironmentRequest,
+  ClearEnvironmentResponse,
+  ReorderEnvironmentsRequest,
+  ReorderEnvironmentsResponse
 } from '../types/database';
 import { EnvironmentModal } from './EnvironmentModal';
 import { DeleteConfirmModal } from './DeleteConfirmModal';
 import { VariableList } from './VariableList';
 import { useToast } from './Toast';
 import { useKeyboardShortcuts } from '../hooks/useKeyboardShortcuts';
-import { Globe, Plus, FilePenLine, Trash2, AlertTriangle, Inbox } from 'lucide-react';
+import { Globe, Plus, FilePenLine, Trash2, Eraser, AlertTriangle, Inbox } from 'lucide-react';
 import { EnvironmentTabsSkeleton } from './Skeleton';
 import './EnvironmentSection.css';
 
@@ -28,6 +32,12 @@ export const EnvironmentSection: React.FC<EnvironmentSectionProps> = ({ projectI
   const [showCreateEnv, setShowCreateEnv] = useState(false);
   const [editingEnv, setEditingEnv] = useState<Environment | null>(null);
   const [deletingEnv, setDeletingEnv] = useState<Environment | null>(null);
+  const [clearingEnv, setClearingEnv] = useState<Environment | null>(null);
+  // Bumped after a clear so the variable list remounts and reloads
+  const [clearCount, setClearCount] = useState(0);
+  // Tab being dragged to a new position, and the tab it is over
+  const [draggedEnvId, setDraggedEnvId] = useState<number | null>(null);
+  const [dropTargetId, setDropTargetId] = useState<number | null>(null);
   const toast = useToast();
 
   const loadEnvironments = async () => {
@@ -98,6 +108,43 @@ export const EnvironmentSection: React.FC<EnvironmentSectionProps> = ({ projectI
     await deleteEnvironment(deletingEnv);
   };
 
+  // Errors are thrown so the confirm modal shows them (e.g. a protected variable)
+  const confirmClear = async () => {
+    if (!clearingEnv) return;
+    const request: ClearEnvironmentRequest = { environment_id: clearingEnv.id! };
+    const response = await invoke<ClearEnvironmentResponse>('clear_environment', { request });
+    if (!response.success) {
+      throw new Error(response.message);
+    }
+    toast.success(`Environment "${clearingEnv.name}" cleared: ${response.message}`);
+    setClearCount((count) => count + 1);
+  };
+
+  // Dropping a tab on another moves it to that tab's place
+  const handleDrop = async (target: Environment) => {
+    const draggedId = draggedEnvId;
+    setDraggedEnvId(null);
+    setDropTargetId(null);
+    if (draggedId === null || draggedId === target.id) return;
+
+    const reordered = environments.filter(e => e.id !== draggedId);
+    const dragged = environments.find(e => e.id === draggedId);
+    if (!dragged) return;
+    reordered.splice(reordered.findIndex(e => e.id === target.id), 0, dragged);
+    setEnvironments(reordered);
+
+    try {
+      const request: ReorderEnvironmentsRequest = { project_id: projectId, environment_ids: reordered.map(e => e.id!) };
+      const response = await invoke<ReorderEnvironmentsResponse>('reorder_environments', { request });
+      if (!response.success) {
+        throw new Error(response.message);
+      }
+    } catch (err) {
+      toast.error(err instanceof Error ? err.message : 'Failed to reorder environments');
+      await loadEnvironments();
+    }
+  };
+
   // Keyboard shortcuts
   useKeyboardShortcuts([
     {
@@ -114,6 +161,7 @@ export const EnvironmentSection: React.FC<EnvironmentSectionProps> = ({ projectI
         if (showCreateEnv) setShowCreateEnv(false);
         else if (editingEnv) setEditingEnv(null);
         else if (deletingEnv) setDeletingEnv(null);
+        else if (clearingEnv) setClearingEnv(null);
       },
     },
   ]);
@@ -164,8 +212,15 @@ export const EnvironmentSection: React.FC<EnvironmentSectionProps> = ({ projectI
             {environments.map((env) => (
               <div
                 key={env.id}
-                className={`environment-section-tab ${selectedEnv?.id === env.id ? 'active' : ''}`}
+                className={`environment-section-tab ${selectedEnv?.id === env.id ? 'active' : ''} ${dropTargetId === env.id && draggedEnvId !== env.id ? 'drop-target' : ''}`}
                 onClick={() => setSelectedEnv(env)}
+                draggable
+                onDragStart={() => setDraggedEnvId(env.id!)}
+                onDragOver={(e) => { e.preventDefault(); setDropTargetId(env.id!); }}
+                onDragLeave={() => setDropTargetId(prev => (prev === env.id ? null : prev))}
+                onDrop={(e) => { e.preventDefault(); handleDrop(env); }}
+                onDragEnd={() => { setDraggedEnvId(null); setDropTargetId(null); }}
+                title="Drag to reorder"
               >
                 <span className="environment-section-tab-name">{env.name}</span>
                 <div className="environment-section-tab-actions">
@@ -176,6 +231,13 @@ export const EnvironmentSection: React.FC<EnvironmentSectionProps> = ({ projectI
                   >
                     <FilePenLine size={16} />
                   </button>
+                  <button
+                    className="environment-section-action-btn danger"
+                    onClick={(e) => { e.stopPropagation(); setClearingEnv(env); }}
+                    title="Clear Environment (delete all variables, keep the environment)"
+                  >
+                    <Eraser size={16} />
+                  </button>
                   <button
                     className="environment-section-action-btn danger"
                     onClick={(e) => handleDeleteClick(env, e)}
@@ -189,7 +251,7 @@ export const EnvironmentSection: React.FC<EnvironmentSectionProps> = ({ projectI
           </div>
           {selectedEnv && (
             <VariableList 
-              key={selectedEnv.id} // Add key to force re-mount on env change
+              key={`${selectedEnv.id}-${clearCount}`} // Add key to force re-mount on env change or clear
               environmentId={selectedEnv.id!} 
               environmentName={selectedEnv.name}
             />
@@ -232,6 +294,15 @@ export const EnvironmentSection: React.FC<EnvironmentSectionProps> = ({ projectI
         message={`Are you sure you want to delete the "${deletingEnv?.name || ''}" environment? All associated variables will be permanently removed.`}
         itemName={deletingEnv?.name || ''}
       />
+
+      <DeleteConfirmModal
+        isOpen={!!clearingEnv}
+        onClose={() => setClearingEnv(null)}
+        onConfirm={confirmClear}
+        title="Clear Environment"
+        message={`Delete every variable in the "${clearingEnv?.name || ''}" environment? The environment itself is kept. Protected variables must be unprotected first.`}
+        itemName={clearingEnv?.name || ''}
+      />
     </div>
   );
 };
\ No newline at end of file
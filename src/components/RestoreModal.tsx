@@ -88,8 +88,8 @@ export const RestoreModal: React.FC<RestoreModalProps> = ({
       setProgress('Restoring backup...');
       setErrorMessage('');
 
-      // Restore backup
-      await restoreBackup(backupData);
+      // Restore backup, locking the open vault first
+      await restoreBackup(backupData, true);
 
       setStatus('success');
       setProgress('');
@@ -160,6 +160,7 @@ export const RestoreModal: React.FC<RestoreModalProps> = ({
                 <div>
                   <p><strong>Warning:</strong> Restoring will replace your current vault data.</p>
                   <p>Your existing vault and database will be backed up automatically before restore.</p>
+                  <p>The vault is locked during restore; unlock it again with the backup's password.</p>
                 </div>
               </div>
 
@@ -2,10 +2,11 @@ import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
 import { useToast } from './Toast';
 import { useTheme, ThemeColor } from '../contexts/ThemeContext';
-import { Settings, X, Lock, Info, Moon, Sun, Palette, Keyboard, Shield, Download, Upload, Terminal, CheckCircle, AlertCircle, BookOpen, ChevronDown, ChevronUp } from 'lucide-react';
+import { Settings, X, Lock, Info, Moon, Sun, Palette, Keyboard, Shield, Download, Upload, Database, Terminal, CheckCircle, AlertCircle, BookOpen, ChevronDown, ChevronUp } from 'lucide-react';
 import { BackupModal } from './BackupModal';
 import { RestoreModal } from './RestoreModal';
 import { UpdateChecker } from './UpdateChecker';
+import type { CompactReport, RotatePasswordResponse } from '../types/database';
 import './SettingsModal.css';
 
 interface SettingsModalProps {
@@ -23,12 +24,47 @@ const TIMEOUT_OPTIONS = [
   { value: 60, label: '1 hour' },
 ];
 
+const EXIT_LOCK_OPTIONS = [
+  { value: 'soft', label: 'Keep Remember Me' },
+  { value: 'hard', label: 'Forget stored key' },
+];
+
+const DESCRIPTION_OPTIONS = [
+  { value: false, label: 'Plaintext' },
+  { value: true, label: 'Encrypted' },
+];
+
+const VALUE_KEY_OPTIONS = [
+  { value: false, label: 'Vault key' },
+  { value: true, label: 'Key per variable' },
+];
+
+const AUDIT_OPTIONS = [
+  { value: true, label: 'Record operations' },
+  { value: false, label: 'Off' },
+];
+
+const AUTO_UNLOCK_OPTIONS = [
+  { value: false, label: 'Allow auto-unlock' },
+  { value: true, label: 'Always ask for password' },
+];
+
 export const SettingsModal: React.FC<SettingsModalProps> = ({ 
   isOpen, 
   onClose,
   onRestoreSuccess,
 }) => {
   const [lockTimeout, setLockTimeout] = useState(0);
+  const [exitLockPolicy, setExitLockPolicy] = useState('soft');
+  const [autoUnlockDisabled, setAutoUnlockDisabled] = useState(false);
+  const [encryptDescriptions, setEncryptDescriptions] = useState(false);
+  const [savedEncryptDescriptions, setSavedEncryptDescriptions] = useState(false);
+  const [perValueKeys, setPerValueKeys] = useState(false);
+  const [savedPerValueKeys, setSavedPerValueKeys] = useState(false);
+  const [auditEnabled, setAuditEnabled] = useState(true);
+  const [passwordHint, setPasswordHint] = useState('');
+  const [savedPasswordHint, setSavedPasswordHint] = useState('');
+  const [savedAuditEnabled, setSavedAuditEnabled] = useState(true);
   const [isSaving, setIsSaving] = useState(false);
   const [isLoading, setIsLoading] = useState(true);
   const [isBackupModalOpen, setIsBackupModalOpen] = useState(false);
@@ -36,6 +72,11 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({
   const [isPathAdded, setIsPathAdded] = useState(false);
   const [isCheckingPath, setIsCheckingPath] = useState(false);
   const [isAddingPath, setIsAddingPath] = useState(false);
+  const [isCompacting, setIsCompacting] = useState(false);
+  const [currentPassword, setCurrentPassword] = useState('');
+  const [newPassword, setNewPassword] = useState('');
+  const [confirmPassword, setConfirmPassword] = useState('');
+  const [isRotatingPassword, setIsRotatingPassword] = useState(false);
   const [isCliGuideExpanded, setIsCliGuideExpanded] = useState(false);
   const toast = useToast();
   const { isDarkMode, themeColor, toggleDarkMode, setThemeColor } = useTheme();
@@ -97,11 +138,70 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({
     }
   };
 
+  const handleCompact = async () => {
+    try {
+      setIsCompacting(true);
+      const report = await invoke<CompactReport>('compact_database');
+      const kib = (bytes: number) => `${(bytes / 1024).toFixed(1)} KiB`;
+      toast.success(`Database compacted: ${kib(report.size_before)} → ${kib(report.size_after)}`);
+    } catch (err) {
+      console.error('Failed to compact database:', err);
+      const errorMessage = err instanceof Error ? err.message : 'Failed to compact database';
+      toast.error(errorMessage);
+    } finally {
+      setIsCompacting(false);
+    }
+  };
+
+  const handleRotatePassword = async () => {
+    if (newPassword.length < 8) {
+      toast.error('Password must be at least 8 characters long');
+      return;
+    }
+    if (newPassword !== confirmPassword) {
+      toast.error('Passwords do not match');
+      return;
+    }
+
+    try {
+      setIsRotatingPassword(true);
+      const response = await invoke<RotatePasswordResponse>('rotate_password', {
+        oldPassword: currentPassword,
+        newPassword,
+      });
+      toast.success(response.message);
+      setCurrentPassword('');
+      setNewPassword('');
+      setConfirmPassword('');
+    } catch (err) {
+      console.error('Failed to change master password:', err);
+      toast.error(typeof err === 'string' ? err : 'Failed to change master password');
+    } finally {
+      setIsRotatingPassword(false);
+    }
+  };
+
   const loadSettings = async () => {
     try {
       setIsLoading(true);
       const timeout = await invoke<number>('get_lock_timeout');
       setLockTimeout(timeout);
+      const policy = await invoke<string>('get_exit_lock_policy');
+      setExitLockPolicy(policy);
+      const disabled = await invoke<boolean>('is_auto_unlock_disabled');
+      setAutoUnlockDisabled(disabled);
+      const encrypted = await invoke<boolean>('get_description_encryption');
+      setEncryptDescriptions(encrypted);
+      setSavedEncryptDescriptions(encrypted);
+      const valueKeys = await invoke<boolean>('get_per_value_keys');
+      setPerValueKeys(valueKeys);
+      setSavedPerValueKeys(valueKeys);
+      const audit = await invoke<boolean>('get_audit_enabled');
+      setAuditEnabled(audit);
+      setSavedAuditEnabled(audit);
+      const hint = (await invoke<string | null>('get_password_hint')) ?? '';
+      setPasswordHint(hint);
+      setSavedPasswordHint(hint);
     } catch (err) {
       console.error('Failed to load settings:', err);
       toast.error('Failed to load settings');
@@ -114,6 +214,27 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({
     try {
       setIsSaving(true);
       await invoke('set_lock_timeout', { timeoutMinutes: lockTimeout });
+      await invoke('set_exit_lock_policy', { policy: exitLockPolicy });
+      await invoke('disable_auto_unlock', { disabled: autoUnlockDisabled });
+      // Only when changed: switching rewrites every existing description
+      if (encryptDescriptions !== savedEncryptDescriptions) {
+        await invoke<number>('set_description_encryption', { enabled: encryptDescriptions });
+        setSavedEncryptDescriptions(encryptDescriptions);
+      }
+      // Only when changed: switching re-seals every existing value
+      if (perValueKeys !== savedPerValueKeys) {
+        await invoke<number>('set_per_value_keys', { enabled: perValueKeys });
+        setSavedPerValueKeys(perValueKeys);
+      }
+      // Only when changed: each switch is itself recorded in the audit log
+      if (auditEnabled !== savedAuditEnabled) {
+        await invoke('set_audit_enabled', { enabled: auditEnabled });
+        setSavedAuditEnabled(auditEnabled);
+      }
+      if (passwordHint.trim() !== savedPasswordHint) {
+        await invoke('set_password_hint', { hint: passwordHint.trim() || null });
+        setSavedPasswordHint(passwordHint.trim());
+      }
       toast.success('Settings saved successfully');
       setTimeout(() => {
         onClose();
@@ -225,6 +346,168 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({
                   <p>The vault will lock after {TIMEOUT_OPTIONS.find(o => o.value === lockTimeout)?.label} of inactivity (mouse movement, clicks, or typing).</p>
                 </div>
               )}
+
+              <div className="settings-modal-item">
+                <label>When the app closes</label>
+                <p className="settings-modal-description">
+                  The vault is always locked on exit. Choose whether a key saved with "Remember Me" is kept for the next launch.
+                </p>
+                <div className="settings-modal-button-group">
+                  {EXIT_LOCK_OPTIONS.map((option) => (
+                    <button
+                      key={option.value}
+                      type="button"
+                      className={`settings-modal-option-button ${exitLockPolicy === option.value ? 'active' : ''}`}
+                      onClick={() => setExitLockPolicy(option.value)}
+                      disabled={isSaving}
+                    >
+                      {option.label}
+                    </button>
+                  ))}
+                </div>
+              </div>
+
+              <div className="settings-modal-item">
+                <label>Variable descriptions</label>
+                <p className="settings-modal-description">
+                  Encrypt descriptions like values, so notes such as "EU cluster admin password" are not readable from the database file. Plaintext descriptions also appear in audit entries.
+                </p>
+                <div className="settings-modal-button-group">
+                  {DESCRIPTION_OPTIONS.map((option) => (
+                    <button
+                      key={option.label}
+                      type="button"
+                      className={`settings-modal-option-button ${encryptDescriptions === option.value ? 'active' : ''}`}
+                      onClick={() => setEncryptDescriptions(option.value)}
+                      disabled={isSaving}
+                    >
+                      {option.label}
+                    </button>
+                  ))}
+                </div>
+              </div>
+
+              <div className="settings-modal-item">
+                <label>Value encryption keys</label>
+                <p className="settings-modal-description">
+                  Seal each variable under its own key, derived from the vault key and a random salt stored with the variable. Adds a key derivation to every read and write.
+                </p>
+                <div className="settings-modal-button-group">
+                  {VALUE_KEY_OPTIONS.map((option) => (
+                    <button
+                      key={option.label}
+                      type="button"
+                      className={`settings-modal-option-button ${perValueKeys === option.value ? 'active' : ''}`}
+                      onClick={() => setPerValueKeys(option.value)}
+                      disabled={isSaving}
+                    >
+                      {option.label}
+                    </button>
+                  ))}
+                </div>
+              </div>
+
+              <div className="settings-modal-item">
+                <label htmlFor="password-hint">Password hint</label>
+                <p className="settings-modal-description">
+                  Shown on the unlock screen to anyone who opens the app, and stored unencrypted in vault.clerk. Never use the password or anything that gives it away.
+                </p>
+                <input
+                  id="password-hint"
+                  type="text"
+                  className="settings-modal-text-input"
+                  value={passwordHint}
+                  onChange={(e) => setPasswordHint(e.target.value)}
+                  maxLength={120}
+                  placeholder="No hint"
+                  disabled={isSaving}
+                />
+              </div>
+
+              <div className="settings-modal-item">
+                <label htmlFor="current-password">Master password</label>
+                <p className="settings-modal-description">
+                  Change the password and re-encrypt every variable under the new key. CLI sessions are ended; Remember Me is updated.
+                </p>
+                <input
+                  id="current-password"
+                  type="password"
+                  className="settings-modal-text-input"
+                  value={currentPassword}
+                  onChange={(e) => setCurrentPassword(e.target.value)}
+                  placeholder="Current password"
+                  autoComplete="current-password"
+                  disabled={isRotatingPassword}
+                />
+                <input
+                  type="password"
+                  className="settings-modal-text-input"
+                  value={newPassword}
+                  onChange={(e) => setNewPassword(e.target.value)}
+                  placeholder="New password"
+                  autoComplete="new-password"
+                  disabled={isRotatingPassword}
+                />
+                <input
+                  type="password"
+                  className="settings-modal-text-input"
+                  value={confirmPassword}
+                  onChange={(e) => setConfirmPassword(e.target.value)}
+                  placeholder="Confirm new password"
+                  autoComplete="new-password"
+                  disabled={isRotatingPassword}
+                />
+                <div className="settings-modal-button-group">
+                  <button
+                    type="button"
+                    className="settings-modal-option-button"
+                    onClick={handleRotatePassword}
+                    disabled={isRotatingPassword || !currentPassword || !newPassword}
+                  >
+                    {isRotatingPassword ? 'Changing...' : 'Change password'}
+                  </button>
+                </div>
+              </div>
+
+              <div className="settings-modal-item">
+                <label>Audit log</label>
+                <p className="settings-modal-description">
+                  Turn off to stop recording operations. Entries already in the log are kept.
+                </p>
+                <div className="settings-modal-button-group">
+                  {AUDIT_OPTIONS.map((option) => (
+                    <button
+                      key={option.label}
+                      type="button"
+                      className={`settings-modal-option-button ${auditEnabled === option.value ? 'active' : ''}`}
+                      onClick={() => setAuditEnabled(option.value)}
+                      disabled={isSaving}
+                    >
+                      {option.label}
+                    </button>
+                  ))}
+                </div>
+              </div>
+
+              <div className="settings-modal-item">
+                <label>Auto-unlock this session</label>
+                <p className="settings-modal-description">
+                  Ignore any key saved with "Remember Me" until the app is restarted. Start the app with --no-keychain to apply this from launch.
+                </p>
+                <div className="settings-modal-button-group">
+                  {AUTO_UNLOCK_OPTIONS.map((option) => (
+                    <button
+                      key={option.label}
+                      type="button"
+                      className={`settings-modal-option-button ${autoUnlockDisabled === option.value ? 'active' : ''}`}
+                      onClick={() => setAutoUnlockDisabled(option.value)}
+                      disabled={isSaving}
+                    >
+                      {option.label}
+                    </button>
+                  ))}
+                </div>
+              </div>
               </div>
 
               <div className="settings-modal-section">
@@ -444,6 +727,23 @@ export const SettingsModal: React.FC<SettingsModalProps> = ({
                       </div>
                     </div>
                   </button>
+
+                  <button
+                    type="button"
+                    className="settings-modal-backup-btn"
+                    onClick={handleCompact}
+                    disabled={isCompacting}
+                  >
+                    <Database size={18} />
+                    <div>
+                      <div className="settings-modal-backup-btn-title">
+                        {isCompacting ? 'Compacting...' : 'Compact Database'}
+                      </div>
+                      <div className="settings-modal-backup-btn-subtitle">
+                        Reclaim space after large deletions
+                      </div>
+                    </div>
+                  </button>
                 </div>
 
                 <div className="settings-modal-info-box">
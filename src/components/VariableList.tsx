@@ -7,6 +7,8 @@ import type {
   GetVariablesResponse,
   DeleteVariableRequest,
   DeleteVariableResponse,
+  SetVariableProtectedRequest,
+  SetVariableProtectedResponse,
   ImportEnvRequest,
   ImportEnvResponse
 } from '../types/database';
@@ -19,7 +21,7 @@ import { useDebounce } from '../hooks/useDebounce';
 import { 
   Plus, Upload, Download, Search, Eye, EyeOff, Copy, 
   FilePenLine, Trash2, AlertTriangle, Inbox, X, CheckCircle2, XCircle,
-  CheckSquare, Square, Trash, FileDown 
+  CheckSquare, Square, Trash, FileDown, Lock, LockOpen
 } from 'lucide-react';
 import { VariableTableSkeleton } from './Skeleton';
 import { validateVariable } from '../utils/variableValidation';
@@ -39,6 +41,8 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
   const [editingVar, setEditingVar] = useState<Variable | null>(null);
   const [deletingVar, setDeletingVar] = useState<Variable | null>(null);
   const [showValues, setShowValues] = useState<Record<number, boolean>>({});
+  // Plaintext of the values currently shown; the list itself only holds masked values
+  const [revealedValues, setRevealedValues] = useState<Record<number, string>>({});
   const [searchQuery, setSearchQuery] = useState('');
   const [showExportModal, setShowExportModal] = useState(false);
   const [selectedVariables, setSelectedVariables] = useState<Set<number>>(new Set());
@@ -52,9 +56,9 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
   const filteredVariables = useMemo(() => {
     return variables.filter(variable =>
       variable.key.toLowerCase().includes(debouncedSearchQuery.toLowerCase()) ||
-      (showValues[variable.id!] && variable.value.toLowerCase().includes(debouncedSearchQuery.toLowerCase()))
+      (showValues[variable.id!] && (revealedValues[variable.id!] ?? '').toLowerCase().includes(debouncedSearchQuery.toLowerCase()))
     );
-  }, [variables, debouncedSearchQuery, showValues]);
+  }, [variables, debouncedSearchQuery, showValues, revealedValues]);
 
   const loadVariables = useCallback(async () => {
     try {
@@ -64,6 +68,8 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
       const response = await invoke<GetVariablesResponse>('get_variables', { request });
       if (response.success) {
         setVariables(response.variables);
+        setShowValues({});
+        setRevealedValues({});
       } else {
         setError(response.message);
       }
@@ -79,8 +85,32 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
     loadVariables();
   }, [loadVariables]);
 
+  // Fetch the plaintext of the given variables, dropping every other value in the response
+  const fetchValues = useCallback(async (ids: number[]) => {
+    const request: GetVariablesRequest = { environment_id: environmentId, reveal: true };
+    const response = await invoke<GetVariablesResponse>('get_variables', { request });
+    if (!response.success) {
+      throw new Error(response.message);
+    }
+    const wanted = new Set(ids);
+    const values: Record<number, string> = {};
+    for (const variable of response.variables) {
+      if (wanted.has(variable.id!)) {
+        values[variable.id!] = variable.value;
+      }
+    }
+    return values;
+  }, [environmentId]);
+
+  const revealValue = useCallback(async (variable: Variable) => {
+    const revealed = revealedValues[variable.id!];
+    if (revealed !== undefined) return revealed;
+    const values = await fetchValues([variable.id!]);
+    return values[variable.id!] ?? '';
+  }, [revealedValues, fetchValues]);
+
   const handleDeleteClick = useCallback((variable: Variable, event: React.MouseEvent) => {
-    if (event.shiftKey) {
+    if (event.shiftKey && !variable.is_protected) {
       // Shift tuşuna basılıysa direkt sil
       deleteVariable(variable);
     } else {
@@ -91,7 +121,8 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
 
   const deleteVariable = useCallback(async (variable: Variable) => {
     try {
-        const request: DeleteVariableRequest = { id: variable.id! };
+        // Protected variables only get here through the confirmation modal
+        const request: DeleteVariableRequest = { id: variable.id!, force: variable.is_protected };
         const response = await invoke<DeleteVariableResponse>('delete_variable', { request });
         if (response.success) {
             toast.success(`Variable "${variable.key}" deleted`);
@@ -109,9 +140,39 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
     await deleteVariable(deletingVar);
   }, [deletingVar, deleteVariable]);
 
-  const toggleShowValue = useCallback((varId: number) => {
-    setShowValues(prev => ({ ...prev, [varId]: !prev[varId] }));
-  }, []);
+  const toggleProtected = useCallback(async (variable: Variable) => {
+    try {
+      const request: SetVariableProtectedRequest = { id: variable.id!, protected: !variable.is_protected };
+      const response = await invoke<SetVariableProtectedResponse>('set_variable_protected', { request });
+      if (response.success) {
+        toast.success(`"${variable.key}" ${request.protected ? 'protected' : 'unprotected'}`);
+        await loadVariables();
+      } else {
+        throw new Error(response.message);
+      }
+    } catch (err) {
+      toast.error(err instanceof Error ? err.message : 'Failed to update protection');
+    }
+  }, [toast, loadVariables]);
+
+  const toggleShowValue = useCallback(async (varId: number) => {
+    if (showValues[varId]) {
+      setShowValues(prev => ({ ...prev, [varId]: false }));
+      setRevealedValues(prev => {
+        const rest = { ...prev };
+        delete rest[varId];
+        return rest;
+      });
+      return;
+    }
+    try {
+      const values = await fetchValues([varId]);
+      setRevealedValues(prev => ({ ...prev, [varId]: values[varId] ?? '' }));
+      setShowValues(prev => ({ ...prev, [varId]: true }));
+    } catch (err) {
+      toast.error(err instanceof Error ? err.message : 'Failed to show value');
+    }
+  }, [showValues, fetchValues, toast]);
 
   const copyToClipboard = useCallback(async (text: string, customMessage?: string) => {
     try {
@@ -122,6 +183,22 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
     }
   }, [toast]);
 
+  const copyValue = useCallback(async (variable: Variable) => {
+    try {
+      await copyToClipboard(await revealValue(variable));
+    } catch (err) {
+      toast.error(err instanceof Error ? err.message : 'Failed to copy');
+    }
+  }, [copyToClipboard, revealValue, toast]);
+
+  const startEditing = useCallback(async (variable: Variable) => {
+    try {
+      setEditingVar({ ...variable, value: await revealValue(variable) });
+    } catch (err) {
+      toast.error(err instanceof Error ? err.message : 'Failed to load value');
+    }
+  }, [revealValue, toast]);
+
   // Bulk operations
   const toggleSelectVariable = (varId: number) => {
     setSelectedVariables(prev => {
@@ -175,9 +252,11 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
   const handleExport = async (format: ExportFormat, options: { includeComments?: boolean; sortKeys?: boolean }) => {
     try {
       // Use selected variables if in bulk mode, otherwise all variables
-      const varsToExport = selectedVariables.size > 0 
+      const chosen = selectedVariables.size > 0 
         ? variables.filter(v => selectedVariables.has(v.id!))
         : variables;
+      const values = await fetchValues(chosen.map(v => v.id!));
+      const varsToExport = chosen.map(v => ({ ...v, value: values[v.id!] ?? '' }));
       
       let content: string;
       let defaultExtension: string;
@@ -412,7 +491,8 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
             </thead>
             <tbody>
               {filteredVariables.map((variable) => {
-                const validation = validateVariable(variable.key, variable.value);
+                const revealedValue = revealedValues[variable.id!] ?? '';
+                const validation = validateVariable(variable.key, revealedValue);
                 const showValidation = validation.type !== 'none' && showValues[variable.id!];
                 const isSelected = selectedVariables.has(variable.id!);
                 
@@ -437,6 +517,24 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
                     >
                       {variable.key}
                     </code>
+                    {variable.is_protected && (
+                      <span className="variable-list-protected" title="Protected: changes need explicit confirmation">
+                        <Lock size={14} />
+                      </span>
+                    )}
+                    {variable.lossy && (
+                      <span className="variable-list-lossy" title="Stored value is not valid UTF-8; invalid bytes are shown as �. Saving it will store the replaced text.">
+                        <AlertTriangle size={14} />
+                      </span>
+                    )}
+                    {variable.expires_at != null && variable.expires_at * 1000 <= Date.now() && (
+                      <span
+                        className="variable-list-expired"
+                        title={`Expired ${new Date(variable.expires_at * 1000).toLocaleString()}; the CLI skips it unless --include-expired is given`}
+                      >
+                        Expired
+                      </span>
+                    )}
                   </td>
                   <td className="variable-list-value">
                     <div className="variable-list-value-container">
@@ -444,10 +542,10 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
                         <>
                           <code 
                             className="variable-list-value-text clickable" 
-                            onClick={() => copyToClipboard(variable.value, `"${variable.key}" copied!`)}
+                            onClick={() => copyToClipboard(revealedValue, `"${variable.key}" copied!`)}
                             title="Click to copy"
                           >
-                            {variable.value}
+                            {revealedValue}
                           </code>
                           {showValidation && (
                             <div className={`variable-validation-indicator ${validation.isValid ? 'valid' : 'invalid'}`} title={validation.isValid ? `Valid ${validation.type}` : validation.message}>
@@ -460,7 +558,12 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
                           )}
                         </>
                       ) : (
-                        <span className="variable-list-value-hidden">••••••••••••••</span>
+                        <span
+                          className="variable-list-value-hidden"
+                          title={variable.value_length != null ? `${variable.value_length} characters` : undefined}
+                        >
+                          ••••••••••••••
+                        </span>
                       )}
                       <div className="variable-list-value-actions">
                         <button
@@ -472,7 +575,7 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
                         </button>
                         <button
                           className="variable-list-action-btn"
-                          onClick={() => copyToClipboard(variable.value)}
+                          onClick={() => copyValue(variable)}
                           title="Copy to clipboard"
                         >
                           <Copy size={16} />
@@ -481,7 +584,14 @@ export const VariableList: React.FC<VariableListProps> = ({ environmentId, envir
                     </div>
                   </td>
                   <td className="variable-list-actions">
-                    <button className="variable-list-action-btn" onClick={() => setEditingVar(variable)} title="Edit">
+                    <button
+                      className="variable-list-action-btn"
+                      onClick={() => toggleProtected(variable)}
+                      title={variable.is_protected ? 'Unprotect' : 'Protect'}
+                    >
+                      {variable.is_protected ? <LockOpen size={16} /> : <Lock size={16} />}
+                    </button>
+                    <button className="variable-list-action-btn" onClick={() => startEditing(variable)} title="Edit">
                       <FilePenLine size={16} />
                     </button>
                     <button className="variable-list-action-btn danger" onClick={(e) => handleDeleteClick(variable, e)} title="Delete (Shift+Click to skip confirmation)">
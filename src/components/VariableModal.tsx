@@ -1,11 +1,19 @@
 import React, { useState, useEffect } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import type { Variable, CreateVariableRequest, CreateVariableResponse, UpdateVariableRequest, UpdateVariableResponse } from '../types/database';
+import type { Variable, ValueType, CreateVariableRequest, CreateVariableResponse, UpdateVariableRequest, UpdateVariableResponse } from '../types/database';
 import { useToast } from './Toast';
 import { validateVariable } from '../utils/variableValidation';
 import { KeyRound, X, AlertTriangle, Lock, CheckCircle2, XCircle } from 'lucide-react';
 import './VariableModal.css';
 
+const VALUE_TYPES: { value: ValueType; label: string }[] = [
+  { value: 'text', label: 'Text' },
+  { value: 'url', label: 'URL' },
+  { value: 'json', label: 'JSON' },
+  { value: 'bool', label: 'Boolean' },
+  { value: 'number', label: 'Number' },
+];
+
 interface VariableModalProps {
   isOpen: boolean;
   onClose: () => void;
@@ -23,21 +31,27 @@ export const VariableModal: React.FC<VariableModalProps> = ({
 }) => {
   const [key, setKey] = useState('');
   const [value, setValue] = useState('');
+  const [valueType, setValueType] = useState<ValueType>('text');
+  const [confirmProtected, setConfirmProtected] = useState(false);
   const [isSubmitting, setIsSubmitting] = useState(false);
   const [error, setError] = useState('');
   const toast = useToast();
 
   const isEditMode = !!variable;
+  const isProtected = !!variable?.is_protected;
 
   useEffect(() => {
     if (isOpen) {
         if (variable) {
             setKey(variable.key);
             setValue(variable.value);
+            setValueType(variable.value_type ?? 'text');
         } else {
             setKey('');
             setValue('');
+            setValueType('text');
         }
+        setConfirmProtected(false);
         setError('');
     }
   }, [variable, isOpen]);
@@ -64,6 +78,8 @@ export const VariableModal: React.FC<VariableModalProps> = ({
           id: variable.id!,
           key: key.trim(),
           value: value.trim(),
+          value_type: valueType,
+          force: isProtected && confirmProtected,
         };
         const response = await invoke<UpdateVariableResponse>('update_variable', { request });
         if (response.success) {
@@ -78,6 +94,7 @@ export const VariableModal: React.FC<VariableModalProps> = ({
           environment_id: environmentId,
           key: key.trim(),
           value: value.trim(),
+          value_type: valueType,
         };
         const response = await invoke<CreateVariableResponse>('create_variable', { request });
         if (response.success) {
@@ -141,19 +158,52 @@ export const VariableModal: React.FC<VariableModalProps> = ({
             </p>
           </div>
 
+          <div className="variable-modal-form-group">
+            <label htmlFor="var-type">Type</label>
+            <select
+              id="var-type"
+              value={valueType}
+              onChange={(e) => setValueType(e.target.value as ValueType)}
+              disabled={isSubmitting}
+            >
+              {VALUE_TYPES.map(({ value, label }) => (
+                <option key={value} value={value}>{label}</option>
+              ))}
+            </select>
+          </div>
+
           <div className="variable-modal-form-group">
             <label htmlFor="var-value">
               Value <span className="variable-modal-required">*</span>
             </label>
-            <textarea
-              id="var-value"
-              value={value}
-              onChange={(e) => setValue(e.target.value)}
-              placeholder="Enter the value for this variable..."
-              rows={4}
-              disabled={isSubmitting}
-              required
-            />
+            {valueType === 'bool' ? (
+              <select
+                id="var-value"
+                value={value}
+                onChange={(e) => setValue(e.target.value)}
+                disabled={isSubmitting}
+                required
+              >
+                <option value="" disabled>Select a value...</option>
+                <option value="true">true</option>
+                <option value="false">false</option>
+              </select>
+            ) : (
+              <textarea
+                id="var-value"
+                value={value}
+                onChange={(e) => setValue(e.target.value)}
+                placeholder={
+                  valueType === 'url' ? 'https://example.com' :
+                  valueType === 'json' ? '{"key": "value"}' :
+                  valueType === 'number' ? '42' :
+                  'Enter the value for this variable...'
+                }
+                rows={valueType === 'json' ? 6 : 4}
+                disabled={isSubmitting}
+                required
+              />
+            )}
             <div className="variable-modal-hints">
               <p className="variable-modal-hint encrypted">
                 <Lock size={12} /> This value will be encrypted at rest.
@@ -182,6 +232,19 @@ export const VariableModal: React.FC<VariableModalProps> = ({
             </div>
           </div>
 
+          {isProtected && (
+            <label className="variable-modal-protected">
+              <input
+                type="checkbox"
+                checked={confirmProtected}
+                onChange={(e) => setConfirmProtected(e.target.checked)}
+                disabled={isSubmitting}
+              />
+              <Lock size={14} />
+              This variable is protected. I want to change it anyway.
+            </label>
+          )}
+
           {error && (
             <div className="variable-modal-error-message">
               <AlertTriangle size={16} />
@@ -201,7 +264,7 @@ export const VariableModal: React.FC<VariableModalProps> = ({
             <button
               type="submit"
               className="variable-modal-btn-submit"
-              disabled={isSubmitting || !key.trim() || !value.trim()}
+              disabled={isSubmitting || !key.trim() || !value.trim() || (isProtected && !confirmProtected)}
             >
               {isSubmitting ? (isEditMode ? 'Updating...' : 'Adding...') : (isEditMode ? 'Update Variable' : 'Add Variable')}
             </button>
@@ -12,6 +12,10 @@ interface CreateVaultResponse {
   message: string;
 }
 
+// Bounds of the backend's KdfParams; empty inputs keep its defaults
+const KDF_MEMORY_RANGE = { min: 8192, max: 1048576, default: 65536 };
+const KDF_ITERATIONS_RANGE = { min: 1, max: 64, default: 3 };
+
 interface PasswordCriteria {
   length: boolean;
   uppercase: boolean;
@@ -29,6 +33,8 @@ export const VaultCreation: React.FC<VaultCreationProps> = ({ onVaultCreated })
   const [showConfirmPassword, setShowConfirmPassword] = useState(false);
   const [passwordStrength, setPasswordStrength] = useState(0);
   const [acknowledgeRisk, setAcknowledgeRisk] = useState(false);
+  const [kdfMemory, setKdfMemory] = useState('');
+  const [kdfIterations, setKdfIterations] = useState('');
   const [passwordCriteria, setPasswordCriteria] = useState<PasswordCriteria>({
     length: false,
     uppercase: false,
@@ -77,9 +83,23 @@ export const VaultCreation: React.FC<VaultCreationProps> = ({ onVaultCreated })
         setError('Please ensure all password criteria are met and passwords match.');
         return;
     }
+    const memory = kdfMemory.trim() ? Number(kdfMemory) : undefined;
+    const iterations = kdfIterations.trim() ? Number(kdfIterations) : undefined;
+    if (memory !== undefined && !(Number.isInteger(memory) && memory >= KDF_MEMORY_RANGE.min && memory <= KDF_MEMORY_RANGE.max)) {
+      setError(`KDF memory must be between ${KDF_MEMORY_RANGE.min} and ${KDF_MEMORY_RANGE.max} KiB`);
+      return;
+    }
+    if (iterations !== undefined && !(Number.isInteger(iterations) && iterations >= KDF_ITERATIONS_RANGE.min && iterations <= KDF_ITERATIONS_RANGE.max)) {
+      setError(`KDF iterations must be between ${KDF_ITERATIONS_RANGE.min} and ${KDF_ITERATIONS_RANGE.max}`);
+      return;
+    }
     try {
       setIsCreating(true);
-      const response = await invoke<CreateVaultResponse>('create_vault', { password });
+      const response = await invoke<CreateVaultResponse>('create_vault', {
+        password,
+        kdfMemory: memory,
+        kdfIterations: iterations,
+      });
       if (response.success) {
         onVaultCreated();
       } else {
@@ -206,6 +226,45 @@ export const VaultCreation: React.FC<VaultCreationProps> = ({ onVaultCreated })
             </ul>
           </div>
           
+          <details className="vault-creation-advanced">
+            <summary>Advanced: key derivation</summary>
+            <p>
+              Argon2id cost for unlocking. Lower it on weak hardware; run <code>clerk benchmark-kdf</code> for a recommendation.
+            </p>
+            <div className="vault-creation-advanced-fields">
+              <label>
+                <span>Memory (KiB)</span>
+                <input
+                  type="number"
+                  value={kdfMemory}
+                  onChange={(e) => {
+                    setKdfMemory(e.target.value);
+                    setError('');
+                  }}
+                  placeholder={String(KDF_MEMORY_RANGE.default)}
+                  min={KDF_MEMORY_RANGE.min}
+                  max={KDF_MEMORY_RANGE.max}
+                  disabled={isCreating}
+                />
+              </label>
+              <label>
+                <span>Iterations</span>
+                <input
+                  type="number"
+                  value={kdfIterations}
+                  onChange={(e) => {
+                    setKdfIterations(e.target.value);
+                    setError('');
+                  }}
+                  placeholder={String(KDF_ITERATIONS_RANGE.default)}
+                  min={KDF_ITERATIONS_RANGE.min}
+                  max={KDF_ITERATIONS_RANGE.max}
+                  disabled={isCreating}
+                />
+              </label>
+            </div>
+          </details>
+
           {error && <div className="vault-creation-error-message">{error}</div>}
 
           <div className="vault-creation-footer">
@@ -1,6 +1,7 @@
 import React, { useState, useEffect, useCallback, useMemo } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import type { Project, GetProjectsResponse, DeleteProjectRequest, DeleteProjectResponse } from '../types/database';
+import { listen } from '@tauri-apps/api/event';
+import type { Project, GetProjectsResponse, DeleteProjectRequest, DeleteProjectResponse, KeychainUnavailablePayload, RepairKeychainResponse } from '../types/database';
 import { ProjectModal } from './ProjectModal';
 import { DeleteConfirmModal } from './DeleteConfirmModal';
 import { EnvironmentSection } from './EnvironmentSection';
@@ -18,6 +19,9 @@ import AppIcon from './AppIcon';
 import { ProjectListSkeleton } from './Skeleton';
 import './VaultDashboard.css';
 
+// Emitted by the backend keychain monitor (KEYCHAIN_UNAVAILABLE_EVENT)
+const KEYCHAIN_UNAVAILABLE_EVENT = 'keychain-unavailable';
+
 interface VaultDashboardProps {
   onLock: () => void;
 }
@@ -34,6 +38,7 @@ export const VaultDashboard: React.FC<VaultDashboardProps> = ({ onLock }) => {
   const [lockTimeout, setLockTimeout] = useState(0);
   const [showSettings, setShowSettings] = useState(false);
   const [showAuditLog, setShowAuditLog] = useState(false);
+  const [keychainWarning, setKeychainWarning] = useState<string | null>(null);
   const toast = useToast();
 
   const handleInactivityTimeout = () => {
@@ -68,6 +73,27 @@ export const VaultDashboard: React.FC<VaultDashboardProps> = ({ onLock }) => {
     loadLockTimeout();
   }, []);
 
+  // The backend re-checks the "Remember Me" keychain entry while unlocked
+  useEffect(() => {
+    const unlisten = listen<KeychainUnavailablePayload>(KEYCHAIN_UNAVAILABLE_EVENT, (event) => {
+      setKeychainWarning(event.payload.message);
+      toast.warning('Remember Me is no longer available');
+    });
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, [toast]);
+
+  const handleRepairKeychain = useCallback(async () => {
+    try {
+      const response = await invoke<RepairKeychainResponse>('repair_keychain');
+      setKeychainWarning(null);
+      toast.success(response.message);
+    } catch (err) {
+      toast.error(typeof err === 'string' ? err : 'Failed to repair keychain');
+    }
+  }, [toast]);
+
   // Debounce search query to avoid excessive filtering
   const debouncedSearchQuery = useDebounce(searchQuery, 300);
 
@@ -261,6 +287,21 @@ export const VaultDashboard: React.FC<VaultDashboardProps> = ({ onLock }) => {
         </div>
       </header>
 
+      {keychainWarning && (
+        <div className="vault-dashboard-keychain-banner">
+          <AlertTriangle size={18} />
+          <span>
+            {keychainWarning}. This session stays unlocked, but the next launch will ask for your master password.
+          </span>
+          <button className="vault-dashboard-btn-primary" onClick={handleRepairKeychain}>
+            Repair Remember Me
+          </button>
+          <button className="vault-dashboard-btn-icon" onClick={() => setKeychainWarning(null)} title="Dismiss">
+            <X size={16} />
+          </button>
+        </div>
+      )}
+
       <div className="vault-dashboard-body">
         <aside className="vault-dashboard-sidebar">
           <div className="vault-dashboard-sidebar-header">
@@ -415,9 +456,9 @@ export const VaultDashboard: React.FC<VaultDashboardProps> = ({ onLock }) => {
         isOpen={showSettings} 
         onClose={handleSettingsClose}
         onRestoreSuccess={() => {
-          // Reload all projects after restore
-          loadProjects();
-          toast.success('Backup restored successfully! Data refreshed.');
+          // The restore locked the vault; unlock the restored one to continue
+          toast.success('Backup restored successfully! Unlock the vault to continue.');
+          onLock();
         }}
       />
 
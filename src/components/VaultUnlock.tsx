@@ -1,23 +1,40 @@
-import React, { useState } from 'react';
+import React, { useEffect, useState } from 'react';
 import { invoke } from '@tauri-apps/api/core';
-import { LockKeyholeOpen, Eye, EyeOff, KeyRound, ShieldCheck } from 'lucide-react';
+import { ask } from '@tauri-apps/plugin-dialog';
+import { LockKeyholeOpen, Eye, EyeOff, KeyRound, Lightbulb, ShieldCheck } from 'lucide-react';
+import { useToast } from './Toast';
 import './VaultUnlock.css';
 
 interface VaultUnlockProps {
   onVaultUnlocked: () => void;
+  /** Shown until the first unlock attempt, e.g. why auto-unlock failed */
+  notice?: string;
 }
 
 interface UnlockVaultResponse {
   success: boolean;
   message: string;
+  remember_failed: boolean;
 }
 
-export const VaultUnlock: React.FC<VaultUnlockProps> = ({ onVaultUnlocked }) => {
+// Prefix of the unlock_vault error for a vault whose vault.db is gone
+const DATABASE_MISSING_ERROR = 'Vault database is missing';
+
+export const VaultUnlock: React.FC<VaultUnlockProps> = ({ onVaultUnlocked, notice }) => {
   const [password, setPassword] = useState('');
   const [rememberMe, setRememberMe] = useState(false);
   const [isUnlocking, setIsUnlocking] = useState(false);
-  const [error, setError] = useState('');
+  const [error, setError] = useState(notice ?? '');
   const [showPassword, setShowPassword] = useState(false);
+  const [hint, setHint] = useState<string | null>(null);
+  const [showHint, setShowHint] = useState(false);
+  const toast = useToast();
+
+  useEffect(() => {
+    invoke<string | null>('get_password_hint')
+      .then(setHint)
+      .catch((err) => console.error('Failed to load password hint:', err));
+  }, []);
 
   // Handle form submission
   const handleSubmit = async (e: React.FormEvent) => {
@@ -31,12 +48,35 @@ export const VaultUnlock: React.FC<VaultUnlockProps> = ({ onVaultUnlocked }) =>
 
     try {
       setIsUnlocking(true);
-      const response = await invoke<UnlockVaultResponse>('unlock_vault', {
-        password,
-        rememberMe,
-      });
+      let response: UnlockVaultResponse;
+      try {
+        response = await invoke<UnlockVaultResponse>('unlock_vault', {
+          password,
+          rememberMe,
+        });
+      } catch (err) {
+        if (typeof err !== 'string' || !err.startsWith(DATABASE_MISSING_ERROR)) {
+          throw err;
+        }
+        const recreate = await ask(
+          `${err}\n\nCreate a new, empty database for this vault? Restore from a backup instead if you have one.`,
+          { title: 'Vault database missing', kind: 'warning' },
+        );
+        if (!recreate) {
+          setError(err);
+          return;
+        }
+        response = await invoke<UnlockVaultResponse>('unlock_vault', {
+          password,
+          rememberMe,
+          recreateDatabase: true,
+        });
+      }
 
       if (response.success) {
+        if (response.remember_failed) {
+          toast.warning(response.message);
+        }
         onVaultUnlocked();
       } else {
         setError(response.message || 'Invalid password');
@@ -109,6 +149,21 @@ export const VaultUnlock: React.FC<VaultUnlockProps> = ({ onVaultUnlocked }) =>
 
           {error && <div className="vault-unlock-error-message">{error}</div>}
 
+          {hint && (showHint ? (
+            <div className="vault-unlock-info-box">
+              <Lightbulb size={16} />
+              <p>Hint: {hint}</p>
+            </div>
+          ) : (
+            <button
+              type="button"
+              className="vault-unlock-hint-toggle"
+              onClick={() => setShowHint(true)}
+            >
+              Show password hint
+            </button>
+          ))}
+
           <button
             type="submit"
             className="vault-unlock-button"
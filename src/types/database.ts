@@ -17,11 +17,20 @@ export interface Environment {
   updated_at: number;
 }
 
+export type ValueType = 'text' | 'url' | 'json' | 'bool' | 'number';
+
 export interface Variable {
   id: number;
   environment_id: number;
   key: string;
-  value: string; // Decrypted value
+  value: string; // Decrypted value; the "********" placeholder when masked
+  value_length?: number | null; // Set instead of the value when get_variables masked it
+  value_type: ValueType;
+  is_protected?: boolean;
+  lossy?: boolean; // Value was not valid UTF-8; invalid bytes shown as U+FFFD
+  expires_at?: number | null; // Unix seconds; expired variables are skipped by CLI reads
+  tags?: string[]; // Sorted labels, e.g. ["db"]
+  has_pending?: boolean; // A next value is staged for rotation (clerk set --slot pending)
   description?: string;
   created_at: number;
   updated_at: number;
@@ -111,12 +120,35 @@ export interface DeleteEnvironmentResponse {
   message: string;
 }
 
+export interface ClearEnvironmentRequest {
+  environment_id: number;
+  force?: boolean;
+}
+
+export interface ClearEnvironmentResponse {
+  success: boolean;
+  deleted: number;
+  message: string;
+}
+
+export interface ReorderEnvironmentsRequest {
+  project_id: number;
+  environment_ids: number[]; // Every environment of the project, first to last
+}
+
+export interface ReorderEnvironmentsResponse {
+  success: boolean;
+  message: string;
+}
+
 // Variables
 export interface CreateVariableRequest {
   environment_id: number;
   key: string;
   value: string;
   description?: string;
+  value_type?: ValueType;
+  upsert?: boolean; // Update instead of failing when the key exists (default false)
 }
 
 export interface CreateVariableResponse {
@@ -127,6 +159,7 @@ export interface CreateVariableResponse {
 
 export interface GetVariablesRequest {
   environment_id: number;
+  reveal?: boolean; // Return plaintext values; only for an explicit user action
 }
 
 export interface GetVariablesResponse {
@@ -140,6 +173,8 @@ export interface UpdateVariableRequest {
   key: string;
   value: string;
   description?: string;
+  value_type?: ValueType;
+  force?: boolean; // Required for protected variables
 }
 
 export interface UpdateVariableResponse {
@@ -149,6 +184,7 @@ export interface UpdateVariableResponse {
 
 export interface DeleteVariableRequest {
   id: number;
+  force?: boolean; // Required for protected variables
 }
 
 export interface DeleteVariableResponse {
@@ -156,6 +192,16 @@ export interface DeleteVariableResponse {
   message: string;
 }
 
+export interface SetVariableProtectedRequest {
+  id: number;
+  protected: boolean;
+}
+
+export interface SetVariableProtectedResponse {
+  success: boolean;
+  message: string;
+}
+
 // Export/Import
 export interface ExportEnvRequest {
   environment_id: number;
@@ -172,9 +218,64 @@ export interface ImportEnvRequest {
   content: string;
 }
 
+export interface EnvironmentJsonRequest {
+  environment_id: number;
+  reveal?: boolean; // Values are masked unless set
+}
+
+export interface EnvironmentJsonResponse {
+  success: boolean;
+  variables?: Record<string, string>;
+  message: string;
+}
+
 export interface ImportEnvResponse {
   success: boolean;
   imported_count?: number;
   errors?: string[];
   message: string;
 }
+
+// Dashboard stats
+export interface LargestValue {
+  project: string;
+  environment: string;
+  key: string;
+  encrypted_bytes: number;
+}
+
+export interface StorageStats {
+  total_encrypted_bytes: number;
+  average_encrypted_bytes: number;
+  largest_value?: LargestValue | null;
+  value_types: Partial<Record<ValueType, number>>;
+}
+
+export interface DashboardStats {
+  project_count: number;
+  environment_count: number;
+  variable_count: number;
+  storage: StorageStats;
+}
+
+// Result of compact_database, in bytes
+export interface CompactReport {
+  size_before: number;
+  size_after: number;
+}
+
+// Keychain
+export interface KeychainUnavailablePayload {
+  message: string;
+}
+
+export interface RepairKeychainResponse {
+  success: boolean;
+  message: string;
+}
+
+export interface RotatePasswordResponse {
+  success: boolean;
+  message: string;
+  reencrypted: number;
+}